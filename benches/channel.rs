@@ -0,0 +1,41 @@
+use std::{hint::black_box, thread, time::Instant};
+
+use vte_input::{
+    channel::Channel, key::KeyType, sequence::KeyboardModifiers, synthesize::SyntheticKeyEvent,
+};
+
+fn main() {
+    let events = 2_000_000;
+    let mut channel = Channel::<SyntheticKeyEvent<'static>, 256>::new();
+    let (mut producer, mut consumer) = channel.split();
+
+    let start = Instant::now();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for i in 0..events {
+                let mut event = SyntheticKeyEvent {
+                    modifiers: KeyboardModifiers::from_bits_retain(i as u8),
+                    ..SyntheticKeyEvent::from(KeyType::Unicode('a'))
+                };
+                while let Err(back) = producer.push(event) {
+                    event = back;
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut received = 0;
+        while received < events {
+            match consumer.pop() {
+                Some(event) => {
+                    black_box(event);
+                    received += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+    });
+    let elapsed = start.elapsed();
+    let rate = events as f64 / elapsed.as_secs_f64() / 1e6;
+    println!("key events: {events} events, {rate:.1} M events/s");
+}
@@ -0,0 +1,28 @@
+use std::{hint::black_box, time::Instant};
+
+use vte_input::parse::{Events, TerminalEvent};
+
+fn bench(name: &str, input: &[u8]) {
+    let rounds = 200;
+    let start = Instant::now();
+    let mut events = 0;
+    for _ in 0..rounds {
+        events += Events::new(black_box(input))
+            .filter(|event| !matches!(event, TerminalEvent::Malformed(_)))
+            .count();
+    }
+    let elapsed = start.elapsed();
+    let throughput = (input.len() * rounds) as f64 / elapsed.as_secs_f64() / 1e6;
+    println!("{name}: {events} events, {throughput:.0} MB/s");
+}
+
+fn main() {
+    let text = "The quick brown fox jumps over the lazy dog. ".repeat(20_000);
+    bench("text", text.as_bytes());
+
+    let keys = "\x1b[97;5u\x1b[1;3Aab\x1b[3~".repeat(20_000);
+    bench("keys", keys.as_bytes());
+
+    let mixed = "ls -la /usr/share/doc\x1b[A".repeat(20_000);
+    bench("mixed", mixed.as_bytes());
+}
@@ -0,0 +1,158 @@
+use core::{
+    fmt::{self, Write},
+    time::Duration,
+};
+
+use alloc::{format, string::String};
+
+use crate::{
+    generate_sequence_with, legacy::LegacyOptions, EventResponse, KeyEvent, ReportingMode,
+};
+
+// An asciicast v2 recording that only holds input ("i") events. Times are
+// taken relative to `start`, on the same clock as the event timestamps.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    out: String,
+    start: Duration,
+    last: Duration,
+}
+
+impl Recording {
+    pub fn new(width: u16, height: u16, start: Duration) -> Self {
+        Self {
+            out: format_header(width, height),
+            start,
+            last: Duration::ZERO,
+        }
+    }
+
+    // Bytes read from or written to the pty, invalid UTF-8 is replaced since
+    // the format only carries strings
+    pub fn input(&mut self, at: Duration, bytes: &[u8]) {
+        self.event(at, |out| out.write_str(&String::from_utf8_lossy(bytes)));
+    }
+
+    pub fn key(&mut self, mode: ReportingMode, event: &impl KeyEvent) {
+        self.key_with(mode, &LegacyOptions::default(), event);
+    }
+
+    // Events without a timestamp are recorded at the time of the previous one
+    pub fn key_with(
+        &mut self,
+        mode: ReportingMode,
+        options: &LegacyOptions,
+        event: &impl KeyEvent,
+    ) {
+        let response = generate_sequence_with(mode, options, event);
+        if matches!(response, EventResponse::Nothing) {
+            return;
+        }
+
+        let at = event
+            .timestamp()
+            .unwrap_or(self.start.saturating_add(self.last));
+        self.event(at, |out| write!(out, "{response}"));
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.out
+    }
+
+    pub fn into_string(self) -> String {
+        self.out
+    }
+
+    fn event(&mut self, at: Duration, data: impl FnOnce(&mut JsonString<'_>) -> fmt::Result) {
+        // Recordings have to be ordered, late events keep the previous time
+        let time = at.saturating_sub(self.start).max(self.last);
+        self.last = time;
+
+        let _ = write!(
+            self.out,
+            "[{}.{:06}, \"i\", \"",
+            time.as_secs(),
+            time.subsec_micros()
+        );
+        let _ = data(&mut JsonString(&mut self.out));
+        self.out.push_str("\"]\n");
+    }
+}
+
+fn format_header(width: u16, height: u16) -> String {
+    format!("{{\"version\": 2, \"width\": {width}, \"height\": {height}}}\n")
+}
+
+// Escapes everything written into the body of a JSON string
+struct JsonString<'a>(&'a mut String);
+
+impl Write for JsonString<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for ch in s.chars() {
+            match ch {
+                '"' => self.0.push_str("\\\""),
+                '\\' => self.0.push_str("\\\\"),
+                '\n' => self.0.push_str("\\n"),
+                '\r' => self.0.push_str("\\r"),
+                '\t' => self.0.push_str("\\t"),
+                '\0'..='\x1f' | '\x7f' => write!(self.0, "\\u{:04x}", ch as u32)?,
+                _ => self.0.push(ch),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        key::{FunctionalKey, KeyType},
+        sequence::KeyboardModifiers,
+        synthesize::{SyntheticKeyEvent, TimedEvent},
+    };
+
+    #[test]
+    fn input_events() {
+        let start = Duration::from_secs(10);
+        let mut recording = Recording::new(80, 24, start);
+
+        let up = TimedEvent {
+            timestamp: Duration::from_millis(10_500),
+            event: SyntheticKeyEvent::from(KeyType::Functional(FunctionalKey::Up)),
+        };
+        recording.key(ReportingMode::empty(), &up);
+        // No timestamp, shares the one before
+        recording.key(
+            ReportingMode::DISAMBIGUATE_ESC_CODES,
+            &(KeyType::Unicode('"'), KeyboardModifiers::CTRL),
+        );
+        recording.input(Duration::from_millis(11_250), b"a\\b\r\xff");
+        // Before the previous event, keeps its time
+        recording.input(Duration::from_secs(11), b"\x7f");
+
+        assert_eq!(
+            recording.as_str(),
+            concat!(
+                "{\"version\": 2, \"width\": 80, \"height\": 24}\n",
+                "[0.500000, \"i\", \"\\u001b[A\"]\n",
+                "[0.500000, \"i\", \"\\u001b[34;5u\"]\n",
+                "[1.250000, \"i\", \"a\\\\b\\r\u{fffd}\"]\n",
+                "[1.250000, \"i\", \"\\u007f\"]\n",
+            )
+        );
+    }
+
+    #[test]
+    fn nothing_is_not_recorded() {
+        let mut recording = Recording::new(80, 24, Duration::ZERO);
+        let henkan = (
+            KeyType::Functional(FunctionalKey::Henkan),
+            KeyboardModifiers::empty(),
+        );
+        recording.key(ReportingMode::empty(), &henkan);
+
+        assert_eq!(recording.into_string().lines().count(), 1);
+    }
+}
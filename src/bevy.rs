@@ -0,0 +1,306 @@
+use crate::{
+    key::{FunctionalKey, KeyType},
+    layouts::PhysicalKey,
+    sequence::{EventType, KeyboardModifiers},
+    synthesize::SyntheticKeyEvent,
+};
+
+// The writing system keys of bevy's `KeyCode`, named like the W3C `code`
+// values the layouts use
+pub fn physical_key(key_code: &str) -> Option<PhysicalKey> {
+    const KEYS: [(&str, PhysicalKey); 51] = [
+        ("Backquote", PhysicalKey::Backquote),
+        ("Digit1", PhysicalKey::Digit1),
+        ("Digit2", PhysicalKey::Digit2),
+        ("Digit3", PhysicalKey::Digit3),
+        ("Digit4", PhysicalKey::Digit4),
+        ("Digit5", PhysicalKey::Digit5),
+        ("Digit6", PhysicalKey::Digit6),
+        ("Digit7", PhysicalKey::Digit7),
+        ("Digit8", PhysicalKey::Digit8),
+        ("Digit9", PhysicalKey::Digit9),
+        ("Digit0", PhysicalKey::Digit0),
+        ("Minus", PhysicalKey::Minus),
+        ("Equal", PhysicalKey::Equal),
+        ("KeyQ", PhysicalKey::KeyQ),
+        ("KeyW", PhysicalKey::KeyW),
+        ("KeyE", PhysicalKey::KeyE),
+        ("KeyR", PhysicalKey::KeyR),
+        ("KeyT", PhysicalKey::KeyT),
+        ("KeyY", PhysicalKey::KeyY),
+        ("KeyU", PhysicalKey::KeyU),
+        ("KeyI", PhysicalKey::KeyI),
+        ("KeyO", PhysicalKey::KeyO),
+        ("KeyP", PhysicalKey::KeyP),
+        ("BracketLeft", PhysicalKey::BracketLeft),
+        ("BracketRight", PhysicalKey::BracketRight),
+        ("Backslash", PhysicalKey::Backslash),
+        ("KeyA", PhysicalKey::KeyA),
+        ("KeyS", PhysicalKey::KeyS),
+        ("KeyD", PhysicalKey::KeyD),
+        ("KeyF", PhysicalKey::KeyF),
+        ("KeyG", PhysicalKey::KeyG),
+        ("KeyH", PhysicalKey::KeyH),
+        ("KeyJ", PhysicalKey::KeyJ),
+        ("KeyK", PhysicalKey::KeyK),
+        ("KeyL", PhysicalKey::KeyL),
+        ("Semicolon", PhysicalKey::Semicolon),
+        ("Quote", PhysicalKey::Quote),
+        ("IntlBackslash", PhysicalKey::IntlBackslash),
+        ("KeyZ", PhysicalKey::KeyZ),
+        ("KeyX", PhysicalKey::KeyX),
+        ("KeyC", PhysicalKey::KeyC),
+        ("KeyV", PhysicalKey::KeyV),
+        ("KeyB", PhysicalKey::KeyB),
+        ("KeyN", PhysicalKey::KeyN),
+        ("KeyM", PhysicalKey::KeyM),
+        ("Comma", PhysicalKey::Comma),
+        ("Period", PhysicalKey::Period),
+        ("Slash", PhysicalKey::Slash),
+        ("IntlRo", PhysicalKey::IntlRo),
+        ("IntlYen", PhysicalKey::IntlYen),
+        ("Space", PhysicalKey::Space),
+    ];
+
+    KEYS.iter()
+        .find(|(name, _)| *name == key_code)
+        .map(|(_, key)| *key)
+}
+
+// bevy's `KeyCode` for the keys that are not characters. Modifiers and the
+// keypad are told apart here, the logical key does not say which side or
+// whether it was the keypad.
+pub fn functional_key(key_code: &str) -> Option<FunctionalKey> {
+    let func = match key_code {
+        "ShiftLeft" => FunctionalKey::LeftShift,
+        "ShiftRight" => FunctionalKey::RightShift,
+        "ControlLeft" => FunctionalKey::LeftControl,
+        "ControlRight" => FunctionalKey::RightControl,
+        "AltLeft" => FunctionalKey::LeftAlt,
+        "AltRight" => FunctionalKey::RightAlt,
+        "SuperLeft" => FunctionalKey::LeftSuper,
+        "SuperRight" => FunctionalKey::RightSuper,
+        "Numpad0" => FunctionalKey::NumPad0,
+        "Numpad1" => FunctionalKey::NumPad1,
+        "Numpad2" => FunctionalKey::NumPad2,
+        "Numpad3" => FunctionalKey::NumPad3,
+        "Numpad4" => FunctionalKey::NumPad4,
+        "Numpad5" => FunctionalKey::NumPad5,
+        "Numpad6" => FunctionalKey::NumPad6,
+        "Numpad7" => FunctionalKey::NumPad7,
+        "Numpad8" => FunctionalKey::NumPad8,
+        "Numpad9" => FunctionalKey::NumPad9,
+        "NumpadDecimal" => FunctionalKey::NumPadDecimal,
+        "NumpadDivide" => FunctionalKey::NumPadDivide,
+        "NumpadMultiply" => FunctionalKey::NumPadMultply,
+        "NumpadSubtract" => FunctionalKey::NumPadSubtract,
+        "NumpadAdd" => FunctionalKey::NumPadAdd,
+        "NumpadEnter" => FunctionalKey::NumPadEnter,
+        "NumpadEqual" => FunctionalKey::NumPadEqual,
+        "NumpadComma" => FunctionalKey::NumPadSeparator,
+        "Lang1" => FunctionalKey::Hangul,
+        "Lang2" => FunctionalKey::Hanja,
+        "IntlBackslash" | "IntlRo" | "IntlYen" => return None,
+        _ => return named_key(key_code),
+    };
+
+    Some(func)
+}
+
+// The named keys of bevy's logical `Key`, the W3C `key` values. Most are
+// also the name of the `KeyCode`.
+pub fn named_key(name: &str) -> Option<FunctionalKey> {
+    let func = match name {
+        "Escape" => FunctionalKey::Escape,
+        "Enter" => FunctionalKey::Enter,
+        "Tab" => FunctionalKey::Tab,
+        "Backspace" => FunctionalKey::Backspace,
+        "Insert" => FunctionalKey::Insert,
+        "Delete" => FunctionalKey::Delete,
+        "ArrowLeft" => FunctionalKey::Left,
+        "ArrowRight" => FunctionalKey::Right,
+        "ArrowUp" => FunctionalKey::Up,
+        "ArrowDown" => FunctionalKey::Down,
+        "PageUp" => FunctionalKey::PageUp,
+        "PageDown" => FunctionalKey::PageDown,
+        "Home" => FunctionalKey::Home,
+        "End" => FunctionalKey::End,
+        "CapsLock" => FunctionalKey::CapsLock,
+        "ScrollLock" => FunctionalKey::ScrollLock,
+        "NumLock" => FunctionalKey::NumLock,
+        "PrintScreen" => FunctionalKey::PrintScreen,
+        "Pause" => FunctionalKey::Pause,
+        "ContextMenu" => FunctionalKey::Menu,
+        "MediaPlay" => FunctionalKey::MediaPlay,
+        "MediaPause" => FunctionalKey::MediaPause,
+        "MediaPlayPause" => FunctionalKey::MediaPlayPause,
+        "MediaStop" => FunctionalKey::MediaStop,
+        "MediaFastForward" => FunctionalKey::MediaFastForward,
+        "MediaRewind" => FunctionalKey::MediaRewind,
+        "MediaTrackNext" => FunctionalKey::MediaTrackNext,
+        "MediaTrackPrevious" => FunctionalKey::MediaTrackPrevious,
+        "MediaRecord" => FunctionalKey::MediaRecord,
+        "AudioVolumeDown" => FunctionalKey::LowerVolume,
+        "AudioVolumeUp" => FunctionalKey::RaiseVolume,
+        "AudioVolumeMute" => FunctionalKey::MuteVolume,
+        // Logical modifiers, without a side
+        "Shift" => FunctionalKey::LeftShift,
+        "Control" => FunctionalKey::LeftControl,
+        "Alt" => FunctionalKey::LeftAlt,
+        "Super" => FunctionalKey::LeftSuper,
+        "Hyper" => FunctionalKey::LeftHyper,
+        "Meta" => FunctionalKey::LeftMeta,
+        "AltGraph" => FunctionalKey::IsoLevel3Shift,
+        "Convert" => FunctionalKey::Henkan,
+        "NonConvert" => FunctionalKey::Muhenkan,
+        "KanaMode" | "HiraganaKatakana" => FunctionalKey::KatakanaHiragana,
+        "ZenkakuHankaku" => FunctionalKey::ZenkakuHankaku,
+        "Eisu" => FunctionalKey::Eisu,
+        "HangulMode" => FunctionalKey::Hangul,
+        "HanjaMode" => FunctionalKey::Hanja,
+        // F1 to F35
+        _ => {
+            let number: usize = name.strip_prefix('F')?.parse().ok()?;
+            let index = FunctionalKey::F1 as usize + number.checked_sub(1)?;
+            return FunctionalKey::ALL
+                .get(index)
+                .copied()
+                .filter(|_| number <= 35);
+        }
+    };
+
+    Some(func)
+}
+
+// `ButtonState` and the `repeat` flag of a `KeyboardInput`
+pub fn event_type(pressed: bool, repeat: bool) -> EventType {
+    match (pressed, repeat) {
+        (false, _) => EventType::Release,
+        (true, false) => EventType::Press,
+        (true, true) => EventType::Repeat,
+    }
+}
+
+// Whether either key of each modifier is held in `ButtonInput<KeyCode>`
+pub fn modifiers(shift: bool, control: bool, alt: bool, super_key: bool) -> KeyboardModifiers {
+    let mut modifiers = KeyboardModifiers::empty();
+    modifiers.set(KeyboardModifiers::SHIFT, shift);
+    modifiers.set(KeyboardModifiers::CTRL, control);
+    modifiers.set(KeyboardModifiers::ALT, alt);
+    modifiers.set(KeyboardModifiers::SUPER, super_key);
+    modifiers
+}
+
+// A `KeyboardInput`: the `KeyCode` name, the logical key as the text of
+// `Key::Character` or the name of a named key, and the typed text. The
+// unshifted key is the lowercase form of the logical key, the base layout
+// key comes from the physical key.
+pub fn key_event<'a>(
+    key_code: &str,
+    logical_key: &str,
+    text: Option<&'a str>,
+    modifiers: KeyboardModifiers,
+    event_type: EventType,
+) -> SyntheticKeyEvent<'a> {
+    // Control characters are the key's doing, not text
+    let text = text.filter(|text| {
+        event_type != EventType::Release && !text.is_empty() && !text.chars().any(char::is_control)
+    });
+
+    if let Some(func) = functional_key(key_code).or_else(|| named_key(logical_key)) {
+        return SyntheticKeyEvent {
+            modifiers,
+            event_type,
+            associated_text: text,
+            ..SyntheticKeyEvent::from(KeyType::Functional(func))
+        };
+    }
+
+    let mut chars = logical_key.chars();
+    let key = match (logical_key, chars.next(), chars.next()) {
+        ("Space", ..) => KeyType::Unicode(' '),
+        (_, Some(ch), None) => KeyType::Unicode(ch),
+        // Dead keys and unidentified ones
+        _ => KeyType::Unknown,
+    };
+    SyntheticKeyEvent {
+        key_with_modifiers: key,
+        key_without_modifiers: key.lowercase(),
+        key_base_layout: physical_key(key_code)
+            .map_or(KeyType::Unknown, PhysicalKey::base_layout_key),
+        modifiers,
+        event_type,
+        associated_text: text,
+        timestamp: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_sequence, ReportingMode};
+
+    extern crate std;
+    use std::format;
+
+    fn encode(mode: ReportingMode, event: &SyntheticKeyEvent<'_>) -> std::string::String {
+        format!("{}", generate_sequence(mode, event))
+    }
+
+    #[test]
+    fn key_codes() {
+        assert_eq!(physical_key("KeyQ"), Some(PhysicalKey::KeyQ));
+        assert_eq!(physical_key("Enter"), None);
+        assert_eq!(functional_key("ArrowUp"), Some(FunctionalKey::Up));
+        assert_eq!(
+            functional_key("ControlRight"),
+            Some(FunctionalKey::RightControl)
+        );
+        assert_eq!(
+            functional_key("NumpadEnter"),
+            Some(FunctionalKey::NumPadEnter)
+        );
+        assert_eq!(functional_key("F24"), Some(FunctionalKey::F24));
+        assert_eq!(functional_key("F36"), None);
+        assert_eq!(functional_key("KeyA"), None);
+        assert_eq!(named_key("Control"), Some(FunctionalKey::LeftControl));
+        assert_eq!(named_key("Fn"), None);
+
+        assert_eq!(event_type(true, false), EventType::Press);
+        assert_eq!(event_type(true, true), EventType::Repeat);
+        assert_eq!(event_type(false, true), EventType::Release);
+    }
+
+    #[test]
+    fn key_events() {
+        let none = KeyboardModifiers::empty();
+        let event = key_event("PageDown", "PageDown", None, none, EventType::Press);
+        assert_eq!(encode(ReportingMode::empty(), &event), "\x1b[6~");
+        let event = key_event("Enter", "Enter", Some("\r"), none, EventType::Press);
+        assert_eq!(event.associated_text, None);
+        assert_eq!(encode(ReportingMode::empty(), &event), "\r");
+
+        // The keypad keeps its identity and its text
+        let event = key_event("Numpad5", "5", Some("5"), none, EventType::Press);
+        assert_eq!(
+            event.key_with_modifiers,
+            KeyType::Functional(FunctionalKey::NumPad5)
+        );
+        assert_eq!(event.associated_text, Some("5"));
+
+        // A French layout: the key at Q types 'a'
+        let shift = modifiers(true, false, false, false);
+        let event = key_event("KeyQ", "A", Some("A"), shift, EventType::Press);
+        assert_eq!(event.key_with_modifiers, KeyType::Unicode('A'));
+        assert_eq!(event.key_without_modifiers, KeyType::Unicode('a'));
+        assert_eq!(event.key_base_layout, KeyType::Unicode('q'));
+        assert_eq!(event.associated_text, Some("A"));
+        assert_eq!(encode(ReportingMode::all(), &event), "\x1b[97:65:113;2;65u");
+
+        let event = key_event("Space", "Space", Some(" "), none, EventType::Release);
+        assert_eq!(event.key_with_modifiers, KeyType::Unicode(' '));
+        assert_eq!(event.associated_text, None);
+        let event = key_event("Quote", "Dead", None, none, EventType::Press);
+        assert_eq!(event.key_with_modifiers, KeyType::Unknown);
+    }
+}
@@ -0,0 +1,1014 @@
+use core::{
+    fmt::{Display, Write},
+    str::FromStr,
+};
+
+use crate::{
+    key::{FunctionalKey, KeyType},
+    sequence::{EventType, KeyboardModifiers},
+    KeyEvent,
+};
+
+pub const MAX_SEQUENCE_LEN: usize = 4;
+
+// Lock states are never part of a chord
+const IGNORED_MODIFIERS: KeyboardModifiers =
+    KeyboardModifiers::CAPS_LOCK.union(KeyboardModifiers::NUM_LOCK);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Chord {
+    pub key: KeyType,
+    pub modifiers: KeyboardModifiers,
+}
+
+impl Chord {
+    pub const fn new(key: KeyType, modifiers: KeyboardModifiers) -> Self {
+        Self { key, modifiers }
+    }
+
+    pub fn normalized(self) -> Self {
+        let key = match self.key {
+            KeyType::Unicode(ch) => KeyType::Unicode(lowercase(ch)),
+            key => key,
+        };
+
+        Self {
+            key,
+            modifiers: self.modifiers.difference(IGNORED_MODIFIERS),
+        }
+    }
+
+    pub fn matches(&self, event: &impl KeyEvent) -> bool {
+        Stroke::from_event(event).matches(self)
+    }
+
+    pub fn display(&self, style: ChordStyle) -> ChordDisplay {
+        ChordDisplay {
+            chord: *self,
+            style,
+        }
+    }
+}
+
+impl Display for Chord {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.display(ChordStyle::Canonical).fmt(f)
+    }
+}
+
+impl From<KeyType> for Chord {
+    fn from(key: KeyType) -> Self {
+        Self::new(key, KeyboardModifiers::empty())
+    }
+}
+
+impl FromStr for Chord {
+    type Err = ChordParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ChordParseError::Empty);
+        }
+
+        let mut modifiers = KeyboardModifiers::empty();
+        let mut rest = s;
+
+        // A trailing '+' is the plus key itself, e.g. "ctrl++", and so is the
+        // one in a code point, e.g. "ctrl+U+0009"
+        while let Some((name, after)) = rest
+            .split_once('+')
+            .filter(|(_, after)| !after.is_empty() && code_point(rest).is_none())
+        {
+            if name.is_empty() {
+                return Err(ChordParseError::UnknownModifier);
+            }
+            modifiers |= parse_modifier(name).ok_or(ChordParseError::UnknownModifier)?;
+            rest = after;
+        }
+
+        let key = parse_key(rest).ok_or(ChordParseError::UnknownKey)?;
+
+        Ok(Self::new(key, modifiers).normalized())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KeySequence {
+    chords: [Chord; MAX_SEQUENCE_LEN],
+    len: usize,
+}
+
+impl KeySequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn as_slice(&self) -> &[Chord] {
+        self.chords.get(..self.len).unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn display(&self, style: ChordStyle) -> KeySequenceDisplay {
+        KeySequenceDisplay {
+            sequence: *self,
+            style,
+        }
+    }
+
+    pub fn push(&mut self, chord: Chord) -> Result<(), ChordParseError> {
+        let slot = self
+            .chords
+            .get_mut(self.len)
+            .ok_or(ChordParseError::TooLong)?;
+        *slot = chord;
+        self.len += 1;
+        Ok(())
+    }
+}
+
+impl From<Chord> for KeySequence {
+    fn from(chord: Chord) -> Self {
+        let mut sequence = Self::new();
+        sequence.chords[0] = chord;
+        sequence.len = 1;
+        sequence
+    }
+}
+
+impl Display for KeySequence {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.display(ChordStyle::Canonical).fmt(f)
+    }
+}
+
+impl FromStr for KeySequence {
+    type Err = ChordParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sequence = Self::new();
+        for chord in s.split_whitespace() {
+            sequence.push(chord.parse()?)?;
+        }
+
+        if sequence.is_empty() {
+            return Err(ChordParseError::Empty);
+        }
+        Ok(sequence)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChordStyle {
+    // "Ctrl+Shift+F5", accepted back by the parser
+    #[default]
+    Canonical,
+    // "⌃⇧F5", for display only
+    Mac,
+}
+
+impl ChordStyle {
+    pub fn native() -> Self {
+        if cfg!(target_os = "macos") {
+            ChordStyle::Mac
+        } else {
+            ChordStyle::Canonical
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChordDisplay {
+    chord: Chord,
+    style: ChordStyle,
+}
+
+impl Display for ChordDisplay {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let modifiers = self.chord.modifiers;
+
+        for (modifier, canonical, mac) in MODIFIER_NAMES {
+            if !modifiers.contains(*modifier) {
+                continue;
+            }
+            match self.style {
+                ChordStyle::Canonical => write!(f, "{canonical}+")?,
+                ChordStyle::Mac => f.write_str(mac)?,
+            }
+        }
+
+        if self.style == ChordStyle::Mac {
+            if let KeyType::Functional(key) = self.chord.key {
+                if let Some((_, symbol)) = MAC_KEY_SYMBOLS.iter().find(|(k, _)| *k == key) {
+                    return f.write_str(symbol);
+                }
+            }
+        }
+
+        match self.chord.key {
+            KeyType::Unicode(' ') => f.write_str("Space"),
+            KeyType::Unicode('+') if self.style == ChordStyle::Canonical => f.write_str("Plus"),
+            KeyType::Unicode(ch) => {
+                let mut upper = ch.to_uppercase();
+                let shown = match (upper.next(), upper.next()) {
+                    (Some(upper), None) if lowercase(upper) == lowercase(ch) => upper,
+                    _ => ch,
+                };
+                // The parser trims whitespace and cannot tell control characters apart
+                if shown.is_whitespace() || shown.is_control() {
+                    write!(f, "U+{:04X}", u32::from(ch))
+                } else {
+                    f.write_char(shown)
+                }
+            }
+            KeyType::Functional(key) => f.write_str(key_name(key)),
+            KeyType::Unknown => f.write_str("Unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeySequenceDisplay {
+    sequence: KeySequence,
+    style: ChordStyle,
+}
+
+impl Display for KeySequenceDisplay {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut chords = self.sequence.as_slice().iter();
+        if let Some(chord) = chords.next() {
+            chord.display(self.style).fmt(f)?;
+        }
+        for chord in chords {
+            write!(f, " {}", chord.display(self.style))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChordParseError {
+    Empty,
+    UnknownModifier,
+    UnknownKey,
+    TooLong,
+}
+
+impl Display for ChordParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ChordParseError::Empty => f.write_str("empty key binding"),
+            ChordParseError::UnknownModifier => f.write_str("unknown modifier"),
+            ChordParseError::UnknownKey => f.write_str("unknown key"),
+            ChordParseError::TooLong => {
+                write!(f, "key sequences are limited to {MAX_SEQUENCE_LEN} chords")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatPolicy {
+    #[default]
+    Trigger,
+    Ignore,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding<A> {
+    pub sequence: KeySequence,
+    pub action: A,
+    pub repeat: RepeatPolicy,
+}
+
+impl<A> Binding<A> {
+    pub fn new(sequence: impl Into<KeySequence>, action: A) -> Self {
+        Self {
+            sequence: sequence.into(),
+            action,
+            repeat: RepeatPolicy::default(),
+        }
+    }
+
+    pub fn parse(sequence: &str, action: A) -> Result<Self, ChordParseError> {
+        Ok(Self::new(sequence.parse::<KeySequence>()?, action))
+    }
+
+    pub fn with_repeat(mut self, repeat: RepeatPolicy) -> Self {
+        self.repeat = repeat;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchResult<'b, A> {
+    Matched(&'b A),
+    Pending,
+    NoMatch,
+    Ignored,
+}
+
+// Both views of a key press: the unshifted key with all modifiers, and the
+// shifted key with SHIFT consumed, so that "ctrl+shift+/" and "ctrl+?" match
+// the same event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Stroke {
+    unshifted: Chord,
+    shifted: Chord,
+}
+
+impl Stroke {
+    fn from_event(event: &impl KeyEvent) -> Self {
+        let modifiers = event.modifiers();
+        // Caps Lock alone only changes the case, with SHIFT held the case is
+        // part of the shifted key
+        let shifted = match event.key_with_modifiers() {
+            KeyType::Unicode(ch)
+                if modifiers.intersects(KeyboardModifiers::CAPS_LOCK)
+                    && !modifiers.intersects(KeyboardModifiers::SHIFT) =>
+            {
+                KeyType::Unicode(lowercase(ch))
+            }
+            key => key,
+        };
+
+        Self {
+            unshifted: Chord::new(event.key_without_modifiers(), modifiers).normalized(),
+            shifted: Chord::new(
+                shifted,
+                modifiers.difference(KeyboardModifiers::SHIFT.union(IGNORED_MODIFIERS)),
+            ),
+        }
+    }
+
+    fn matches(&self, chord: &Chord) -> bool {
+        self.unshifted == *chord || self.shifted == *chord
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Matcher<'b, A> {
+    bindings: &'b [Binding<A>],
+    pending: [Stroke; MAX_SEQUENCE_LEN],
+    pending_len: usize,
+}
+
+impl<'b, A> Matcher<'b, A> {
+    pub fn new(bindings: &'b [Binding<A>]) -> Self {
+        Self {
+            bindings,
+            pending: Default::default(),
+            pending_len: 0,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending_len > 0
+    }
+
+    pub fn reset(&mut self) {
+        self.pending_len = 0;
+    }
+
+    pub fn feed(&mut self, event: &impl KeyEvent) -> MatchResult<'b, A> {
+        let event_type = event.event_type();
+        if event_type == EventType::Release || is_ignored_key(event.key_without_modifiers()) {
+            return MatchResult::Ignored;
+        }
+
+        let stroke = Stroke::from_event(event);
+
+        if self.pending_len == MAX_SEQUENCE_LEN {
+            self.reset();
+        }
+        if let Some(slot) = self.pending.get_mut(self.pending_len) {
+            *slot = stroke;
+            self.pending_len += 1;
+        }
+
+        match self.lookup(event_type) {
+            MatchResult::NoMatch if self.pending_len > 1 => {
+                // Abandon the unfinished sequence but give the key itself a chance
+                self.pending[0] = stroke;
+                self.pending_len = 1;
+                let result = self.lookup(event_type);
+                if !matches!(result, MatchResult::Pending) {
+                    self.reset();
+                }
+                result
+            }
+            MatchResult::Pending => MatchResult::Pending,
+            result => {
+                self.reset();
+                result
+            }
+        }
+    }
+
+    fn lookup(&self, event_type: EventType) -> MatchResult<'b, A> {
+        let pending = self.pending.get(..self.pending_len).unwrap_or_default();
+        let mut prefix = false;
+
+        for binding in self.bindings {
+            if event_type == EventType::Repeat && binding.repeat == RepeatPolicy::Ignore {
+                continue;
+            }
+
+            let chords = binding.sequence.as_slice();
+            if chords.len() < pending.len()
+                || !pending
+                    .iter()
+                    .zip(chords)
+                    .all(|(stroke, chord)| stroke.matches(chord))
+            {
+                continue;
+            }
+
+            if chords.len() == pending.len() {
+                return MatchResult::Matched(&binding.action);
+            }
+            prefix = true;
+        }
+
+        if prefix {
+            MatchResult::Pending
+        } else {
+            MatchResult::NoMatch
+        }
+    }
+}
+
+// Modifier keys are reported on their own when all keys are reported as escape
+// codes; they are part of the chord, not a key of the sequence.
+fn is_ignored_key(key: KeyType) -> bool {
+    matches!(
+        key,
+        KeyType::Unknown
+            | KeyType::Functional(
+                FunctionalKey::LeftShift
+                    | FunctionalKey::LeftControl
+                    | FunctionalKey::LeftAlt
+                    | FunctionalKey::LeftSuper
+                    | FunctionalKey::LeftHyper
+                    | FunctionalKey::LeftMeta
+                    | FunctionalKey::RightShift
+                    | FunctionalKey::RightControl
+                    | FunctionalKey::RightAlt
+                    | FunctionalKey::RightSuper
+                    | FunctionalKey::RightHyper
+                    | FunctionalKey::RightMeta
+                    | FunctionalKey::IsoLevel3Shift
+                    | FunctionalKey::IsoLevel5Shift
+            )
+    )
+}
+
+fn lowercase(ch: char) -> char {
+    let mut lower = ch.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => ch,
+    }
+}
+
+// In display order, following the platform conventions for each style
+const MODIFIER_NAMES: &[(KeyboardModifiers, &str, &str)] = &[
+    (KeyboardModifiers::CTRL, "Ctrl", "⌃"),
+    (KeyboardModifiers::ALT, "Alt", "⌥"),
+    (KeyboardModifiers::SHIFT, "Shift", "⇧"),
+    (KeyboardModifiers::SUPER, "Super", "⌘"),
+    (KeyboardModifiers::HYPER, "Hyper", "Hyper+"),
+    (KeyboardModifiers::META, "Meta", "Meta+"),
+];
+
+const MAC_KEY_SYMBOLS: &[(FunctionalKey, &str)] = &[
+    (FunctionalKey::Escape, "⎋"),
+    (FunctionalKey::Enter, "↩"),
+    (FunctionalKey::Tab, "⇥"),
+    (FunctionalKey::Backspace, "⌫"),
+    (FunctionalKey::Delete, "⌦"),
+    (FunctionalKey::Left, "←"),
+    (FunctionalKey::Right, "→"),
+    (FunctionalKey::Up, "↑"),
+    (FunctionalKey::Down, "↓"),
+    (FunctionalKey::PageUp, "⇞"),
+    (FunctionalKey::PageDown, "⇟"),
+    (FunctionalKey::Home, "↖"),
+    (FunctionalKey::End, "↘"),
+];
+
+fn parse_modifier(name: &str) -> Option<KeyboardModifiers> {
+    const NAMES: &[(&str, KeyboardModifiers)] = &[
+        ("shift", KeyboardModifiers::SHIFT),
+        ("alt", KeyboardModifiers::ALT),
+        ("opt", KeyboardModifiers::ALT),
+        ("option", KeyboardModifiers::ALT),
+        ("ctrl", KeyboardModifiers::CTRL),
+        ("control", KeyboardModifiers::CTRL),
+        ("super", KeyboardModifiers::SUPER),
+        ("cmd", KeyboardModifiers::SUPER),
+        ("command", KeyboardModifiers::SUPER),
+        ("win", KeyboardModifiers::SUPER),
+        ("hyper", KeyboardModifiers::HYPER),
+        ("meta", KeyboardModifiers::META),
+    ];
+
+    NAMES
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, modifier)| *modifier)
+}
+
+fn parse_key(name: &str) -> Option<KeyType> {
+    let mut chars = name.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Some(KeyType::Unicode(ch));
+    }
+
+    if let Some(ch) = code_point(name) {
+        return Some(KeyType::Unicode(ch));
+    }
+
+    const ALIASES: &[(&str, KeyType)] = &[
+        ("unknown", KeyType::Unknown),
+        ("space", KeyType::Unicode(' ')),
+        ("plus", KeyType::Unicode('+')),
+        ("minus", KeyType::Unicode('-')),
+        ("esc", KeyType::Functional(FunctionalKey::Escape)),
+        ("return", KeyType::Functional(FunctionalKey::Enter)),
+        ("ins", KeyType::Functional(FunctionalKey::Insert)),
+        ("del", KeyType::Functional(FunctionalKey::Delete)),
+        ("pgup", KeyType::Functional(FunctionalKey::PageUp)),
+        ("pgdn", KeyType::Functional(FunctionalKey::PageDown)),
+    ];
+
+    ALIASES
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, key)| *key)
+        .or_else(|| {
+            KEY_NAMES
+                .iter()
+                .find(|(_, n)| n.eq_ignore_ascii_case(name))
+                .map(|(key, _)| KeyType::Functional(*key))
+        })
+}
+
+// "U+0009", for characters without a name of their own
+fn code_point(name: &str) -> Option<char> {
+    let hex = name
+        .strip_prefix("U+")
+        .or_else(|| name.strip_prefix("u+"))?;
+    if !(4..=6).contains(&hex.len()) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+// The name chords use for `key`, like "PageUp"
+pub fn key_name(key: FunctionalKey) -> &'static str {
+    // The table has an entry for every key, the fallback is never used
+    KEY_NAMES
+        .get(key as usize)
+        .map_or("Unknown", |(_, name)| *name)
+}
+
+// Indexed by discriminant, like the sequence table
+const KEY_NAMES: [(FunctionalKey, &str); FunctionalKey::ALL.len()] = [
+    (FunctionalKey::Escape, "Escape"),
+    (FunctionalKey::Enter, "Enter"),
+    (FunctionalKey::Tab, "Tab"),
+    (FunctionalKey::Backspace, "Backspace"),
+    (FunctionalKey::Insert, "Insert"),
+    (FunctionalKey::Delete, "Delete"),
+    (FunctionalKey::Left, "Left"),
+    (FunctionalKey::Right, "Right"),
+    (FunctionalKey::Up, "Up"),
+    (FunctionalKey::Down, "Down"),
+    (FunctionalKey::PageUp, "PageUp"),
+    (FunctionalKey::PageDown, "PageDown"),
+    (FunctionalKey::Home, "Home"),
+    (FunctionalKey::End, "End"),
+    (FunctionalKey::CapsLock, "CapsLock"),
+    (FunctionalKey::ScrollLock, "ScrollLock"),
+    (FunctionalKey::NumLock, "NumLock"),
+    (FunctionalKey::PrintScreen, "PrintScreen"),
+    (FunctionalKey::Pause, "Pause"),
+    (FunctionalKey::Menu, "Menu"),
+    (FunctionalKey::F1, "F1"),
+    (FunctionalKey::F2, "F2"),
+    (FunctionalKey::F3, "F3"),
+    (FunctionalKey::F4, "F4"),
+    (FunctionalKey::F5, "F5"),
+    (FunctionalKey::F6, "F6"),
+    (FunctionalKey::F7, "F7"),
+    (FunctionalKey::F8, "F8"),
+    (FunctionalKey::F9, "F9"),
+    (FunctionalKey::F10, "F10"),
+    (FunctionalKey::F11, "F11"),
+    (FunctionalKey::F12, "F12"),
+    (FunctionalKey::F13, "F13"),
+    (FunctionalKey::F14, "F14"),
+    (FunctionalKey::F15, "F15"),
+    (FunctionalKey::F16, "F16"),
+    (FunctionalKey::F17, "F17"),
+    (FunctionalKey::F18, "F18"),
+    (FunctionalKey::F19, "F19"),
+    (FunctionalKey::F20, "F20"),
+    (FunctionalKey::F21, "F21"),
+    (FunctionalKey::F22, "F22"),
+    (FunctionalKey::F23, "F23"),
+    (FunctionalKey::F24, "F24"),
+    (FunctionalKey::F25, "F25"),
+    (FunctionalKey::F26, "F26"),
+    (FunctionalKey::F27, "F27"),
+    (FunctionalKey::F28, "F28"),
+    (FunctionalKey::F29, "F29"),
+    (FunctionalKey::F30, "F30"),
+    (FunctionalKey::F31, "F31"),
+    (FunctionalKey::F32, "F32"),
+    (FunctionalKey::F33, "F33"),
+    (FunctionalKey::F34, "F34"),
+    (FunctionalKey::F35, "F35"),
+    (FunctionalKey::NumPad0, "NumPad0"),
+    (FunctionalKey::NumPad1, "NumPad1"),
+    (FunctionalKey::NumPad2, "NumPad2"),
+    (FunctionalKey::NumPad3, "NumPad3"),
+    (FunctionalKey::NumPad4, "NumPad4"),
+    (FunctionalKey::NumPad5, "NumPad5"),
+    (FunctionalKey::NumPad6, "NumPad6"),
+    (FunctionalKey::NumPad7, "NumPad7"),
+    (FunctionalKey::NumPad8, "NumPad8"),
+    (FunctionalKey::NumPad9, "NumPad9"),
+    (FunctionalKey::NumPadDecimal, "NumPadDecimal"),
+    (FunctionalKey::NumPadDivide, "NumPadDivide"),
+    (FunctionalKey::NumPadMultply, "NumPadMultiply"),
+    (FunctionalKey::NumPadSubtract, "NumPadSubtract"),
+    (FunctionalKey::NumPadAdd, "NumPadAdd"),
+    (FunctionalKey::NumPadEnter, "NumPadEnter"),
+    (FunctionalKey::NumPadEqual, "NumPadEqual"),
+    (FunctionalKey::NumPadSeparator, "NumPadSeparator"),
+    (FunctionalKey::NumPadLeft, "NumPadLeft"),
+    (FunctionalKey::NumPadRight, "NumPadRight"),
+    (FunctionalKey::NumPadUp, "NumPadUp"),
+    (FunctionalKey::NumPadDown, "NumPadDown"),
+    (FunctionalKey::NumPadPageUp, "NumPadPageUp"),
+    (FunctionalKey::NumPadPageDown, "NumPadPageDown"),
+    (FunctionalKey::NumPadHome, "NumPadHome"),
+    (FunctionalKey::NumPadEnd, "NumPadEnd"),
+    (FunctionalKey::NumPadInsert, "NumPadInsert"),
+    (FunctionalKey::NumPadDelete, "NumPadDelete"),
+    (FunctionalKey::NumPadBegin, "NumPadBegin"),
+    (FunctionalKey::MediaPlay, "MediaPlay"),
+    (FunctionalKey::MediaPause, "MediaPause"),
+    (FunctionalKey::MediaPlayPause, "MediaPlayPause"),
+    (FunctionalKey::MediaReverse, "MediaReverse"),
+    (FunctionalKey::MediaStop, "MediaStop"),
+    (FunctionalKey::MediaFastForward, "MediaFastForward"),
+    (FunctionalKey::MediaRewind, "MediaRewind"),
+    (FunctionalKey::MediaTrackNext, "MediaTrackNext"),
+    (FunctionalKey::MediaTrackPrevious, "MediaTrackPrevious"),
+    (FunctionalKey::MediaRecord, "MediaRecord"),
+    (FunctionalKey::LowerVolume, "LowerVolume"),
+    (FunctionalKey::RaiseVolume, "RaiseVolume"),
+    (FunctionalKey::MuteVolume, "MuteVolume"),
+    (FunctionalKey::LeftShift, "LeftShift"),
+    (FunctionalKey::LeftControl, "LeftControl"),
+    (FunctionalKey::LeftAlt, "LeftAlt"),
+    (FunctionalKey::LeftSuper, "LeftSuper"),
+    (FunctionalKey::LeftHyper, "LeftHyper"),
+    (FunctionalKey::LeftMeta, "LeftMeta"),
+    (FunctionalKey::RightShift, "RightShift"),
+    (FunctionalKey::RightControl, "RightControl"),
+    (FunctionalKey::RightAlt, "RightAlt"),
+    (FunctionalKey::RightSuper, "RightSuper"),
+    (FunctionalKey::RightHyper, "RightHyper"),
+    (FunctionalKey::RightMeta, "RightMeta"),
+    (FunctionalKey::IsoLevel3Shift, "IsoLevel3Shift"),
+    (FunctionalKey::IsoLevel5Shift, "IsoLevel5Shift"),
+    (FunctionalKey::Muhenkan, "Muhenkan"),
+    (FunctionalKey::Henkan, "Henkan"),
+    (FunctionalKey::KatakanaHiragana, "KatakanaHiragana"),
+    (FunctionalKey::ZenkakuHankaku, "ZenkakuHankaku"),
+    (FunctionalKey::Eisu, "Eisu"),
+    (FunctionalKey::Hangul, "Hangul"),
+    (FunctionalKey::Hanja, "Hanja"),
+    (FunctionalKey::IntlBackslash, "IntlBackslash"),
+    (FunctionalKey::IntlRo, "IntlRo"),
+    (FunctionalKey::IntlYen, "IntlYen"),
+];
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{KeyInfo, ModifierInfo, TextInfo};
+
+    extern crate std;
+    use std::format;
+
+    #[derive(Debug, Clone, Default)]
+    struct Press {
+        key_with_modifiers: KeyType,
+        key_without_modifiers: KeyType,
+        modifiers: KeyboardModifiers,
+        event_type: EventType,
+    }
+
+    impl Press {
+        fn new(ch: char, modifiers: KeyboardModifiers) -> Self {
+            Self {
+                key_with_modifiers: KeyType::Unicode(ch),
+                key_without_modifiers: KeyType::Unicode(ch),
+                modifiers,
+                ..Default::default()
+            }
+        }
+    }
+
+    impl KeyInfo for Press {
+        fn key_with_modifiers(&self) -> KeyType {
+            self.key_with_modifiers
+        }
+
+        fn key_without_modifiers(&self) -> KeyType {
+            self.key_without_modifiers
+        }
+
+        fn key_base_layout(&self) -> KeyType {
+            self.key_without_modifiers
+        }
+
+        fn event_type(&self) -> EventType {
+            self.event_type
+        }
+    }
+
+    impl ModifierInfo for Press {
+        fn modifiers(&self) -> KeyboardModifiers {
+            self.modifiers
+        }
+    }
+
+    impl TextInfo for Press {
+        fn associated_text(&self) -> Option<crate::sequence::AssociatedText<'_>> {
+            None
+        }
+    }
+
+    #[test]
+    fn parse_chord() {
+        assert_eq!(
+            "Ctrl+Shift+F5".parse(),
+            Ok(Chord::new(
+                KeyType::Functional(FunctionalKey::F5),
+                KeyboardModifiers::CTRL | KeyboardModifiers::SHIFT
+            ))
+        );
+        assert_eq!(
+            "ctrl+A".parse(),
+            Ok(Chord::new(KeyType::Unicode('a'), KeyboardModifiers::CTRL))
+        );
+        assert_eq!(
+            "alt++".parse(),
+            Ok(Chord::new(KeyType::Unicode('+'), KeyboardModifiers::ALT))
+        );
+        assert_eq!(
+            "esc".parse(),
+            Ok(Chord::from(KeyType::Functional(FunctionalKey::Escape)))
+        );
+        assert_eq!("".parse::<Chord>(), Err(ChordParseError::Empty));
+        assert_eq!(
+            "hyperctrl+a".parse::<Chord>(),
+            Err(ChordParseError::UnknownModifier)
+        );
+        assert_eq!(
+            "ctrl+foo".parse::<Chord>(),
+            Err(ChordParseError::UnknownKey)
+        );
+    }
+
+    #[test]
+    fn parse_sequence() {
+        let sequence: KeySequence = "ctrl+x ctrl+s".parse().unwrap();
+        assert_eq!(
+            sequence.as_slice(),
+            &[
+                Chord::new(KeyType::Unicode('x'), KeyboardModifiers::CTRL),
+                Chord::new(KeyType::Unicode('s'), KeyboardModifiers::CTRL)
+            ]
+        );
+        assert_eq!(
+            "a b c d e".parse::<KeySequence>(),
+            Err(ChordParseError::TooLong)
+        );
+    }
+
+    #[test]
+    fn display_chord() {
+        let chord = Chord::new(
+            KeyType::Functional(FunctionalKey::F5),
+            KeyboardModifiers::SHIFT | KeyboardModifiers::CTRL,
+        );
+        assert_eq!(format!("{chord}"), "Ctrl+Shift+F5");
+        assert_eq!(format!("{}", chord.display(ChordStyle::Mac)), "⌃⇧F5");
+
+        let chord = Chord::new(KeyType::Unicode('k'), KeyboardModifiers::SUPER);
+        assert_eq!(format!("{}", chord.display(ChordStyle::Mac)), "⌘K");
+        assert_eq!(format!("{chord}"), "Super+K");
+
+        let chord = Chord::new(KeyType::Unicode('+'), KeyboardModifiers::ALT);
+        assert_eq!(format!("{chord}"), "Alt+Plus");
+        assert_eq!(format!("{}", chord.display(ChordStyle::Mac)), "⌥+");
+
+        let chord = Chord::new(
+            KeyType::Functional(FunctionalKey::Up),
+            KeyboardModifiers::ALT,
+        );
+        assert_eq!(format!("{}", chord.display(ChordStyle::Mac)), "⌥↑");
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for input in [
+            "Ctrl+Alt+Shift+Super+Hyper+Meta+Delete",
+            "Ctrl+X Ctrl+S",
+            "Space",
+            "Ctrl+Plus",
+            "NumPadMultiply",
+            "Shift+Tab",
+        ] {
+            let sequence: KeySequence = input.parse().unwrap();
+            assert_eq!(format!("{sequence}"), input);
+        }
+
+        let sequence: KeySequence = "shift+alt+a  ctrl+esc".parse().unwrap();
+        assert_eq!(format!("{sequence}"), "Alt+Shift+A Ctrl+Escape");
+    }
+
+    #[test]
+    fn display_parse_all_keys() {
+        for (index, (key, _)) in KEY_NAMES.iter().enumerate() {
+            assert_eq!(*key as usize, index, "{key:?} is out of order in KEY_NAMES");
+        }
+
+        let functional = FunctionalKey::ALL
+            .iter()
+            .map(|key| KeyType::Functional(*key));
+        let characters = [
+            ' ', '+', '-', 'a', 'A', '?', '\t', '\n', '\x03', '\x7f', '\u{a0}', 'ß', 'ı', 'é',
+            '\u{212a}', '\u{e000}',
+        ]
+        .map(KeyType::Unicode);
+        for key in functional.chain(characters).chain([KeyType::Unknown]) {
+            for modifiers in [KeyboardModifiers::empty(), KeyboardModifiers::all()] {
+                let chord = Chord::new(key, modifiers);
+                let shown = format!("{chord}");
+                assert_eq!(
+                    shown.parse(),
+                    Ok(chord.normalized()),
+                    "{key:?} shown as {shown:?}"
+                );
+            }
+        }
+
+        let tab: Chord = "ctrl+U+0009".parse().unwrap();
+        assert_eq!(format!("{tab}"), "Ctrl+U+0009");
+        assert!("U+D800".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn modifier_normalization() {
+        let chord: Chord = "ctrl+?".parse().unwrap();
+        let shifted = Press {
+            key_with_modifiers: KeyType::Unicode('?'),
+            key_without_modifiers: KeyType::Unicode('/'),
+            modifiers: KeyboardModifiers::CTRL
+                | KeyboardModifiers::SHIFT
+                | KeyboardModifiers::NUM_LOCK,
+            ..Default::default()
+        };
+
+        assert!(chord.matches(&shifted));
+        assert!("ctrl+shift+/".parse::<Chord>().unwrap().matches(&shifted));
+        assert!(!"ctrl+/".parse::<Chord>().unwrap().matches(&shifted));
+        let ctrl_shift_a = Press {
+            key_with_modifiers: KeyType::Unicode('A'),
+            key_without_modifiers: KeyType::Unicode('a'),
+            modifiers: KeyboardModifiers::CTRL | KeyboardModifiers::SHIFT,
+            ..Default::default()
+        };
+        assert!(!"ctrl+a".parse::<Chord>().unwrap().matches(&ctrl_shift_a));
+        assert!("ctrl+shift+a"
+            .parse::<Chord>()
+            .unwrap()
+            .matches(&ctrl_shift_a));
+        let bindings = [
+            Binding::parse("ctrl+a", 1).unwrap(),
+            Binding::parse("ctrl+shift+a", 2).unwrap(),
+        ];
+        assert_eq!(
+            Matcher::new(&bindings).feed(&ctrl_shift_a),
+            MatchResult::Matched(&2)
+        );
+        assert!("ctrl+a".parse::<Chord>().unwrap().matches(&Press::new(
+            'A',
+            KeyboardModifiers::CTRL | KeyboardModifiers::CAPS_LOCK
+        )));
+    }
+
+    #[test]
+    fn leader_sequences() {
+        let bindings = [
+            Binding::parse("ctrl+x ctrl+s", 1).unwrap(),
+            Binding::parse("ctrl+x k", 2).unwrap(),
+            Binding::parse("q", 3).unwrap(),
+        ];
+        let mut matcher = Matcher::new(&bindings);
+
+        let ctrl_x = Press::new('x', KeyboardModifiers::CTRL);
+        assert_eq!(matcher.feed(&ctrl_x), MatchResult::Pending);
+        assert_eq!(
+            matcher.feed(&Press::new('s', KeyboardModifiers::CTRL)),
+            MatchResult::Matched(&1)
+        );
+        assert!(!matcher.is_pending());
+
+        assert_eq!(matcher.feed(&ctrl_x), MatchResult::Pending);
+        let release = Press {
+            event_type: EventType::Release,
+            ..ctrl_x.clone()
+        };
+        assert_eq!(matcher.feed(&release), MatchResult::Ignored);
+        assert_eq!(
+            matcher.feed(&Press::new('k', KeyboardModifiers::empty())),
+            MatchResult::Matched(&2)
+        );
+
+        // An unknown continuation drops the prefix and retries the key alone
+        assert_eq!(matcher.feed(&ctrl_x), MatchResult::Pending);
+        assert_eq!(
+            matcher.feed(&Press::new('q', KeyboardModifiers::empty())),
+            MatchResult::Matched(&3)
+        );
+        assert_eq!(
+            matcher.feed(&Press::new('z', KeyboardModifiers::empty())),
+            MatchResult::NoMatch
+        );
+    }
+
+    #[test]
+    fn repeat_policy() {
+        let bindings = [
+            Binding::parse("j", 1).unwrap(),
+            Binding::parse("d", 2)
+                .unwrap()
+                .with_repeat(RepeatPolicy::Ignore),
+        ];
+        let mut matcher = Matcher::new(&bindings);
+
+        let repeat = |ch| Press {
+            event_type: EventType::Repeat,
+            ..Press::new(ch, KeyboardModifiers::empty())
+        };
+
+        assert_eq!(matcher.feed(&repeat('j')), MatchResult::Matched(&1));
+        assert_eq!(matcher.feed(&repeat('d')), MatchResult::NoMatch);
+        assert_eq!(
+            matcher.feed(&Press::new('d', KeyboardModifiers::empty())),
+            MatchResult::Matched(&2)
+        );
+    }
+
+    #[test]
+    fn modifier_keys_ignored() {
+        let bindings = [Binding::parse("ctrl+x ctrl+s", ()).unwrap()];
+        let mut matcher = Matcher::new(&bindings);
+
+        let control = Press {
+            key_with_modifiers: KeyType::Functional(FunctionalKey::LeftControl),
+            key_without_modifiers: KeyType::Functional(FunctionalKey::LeftControl),
+            modifiers: KeyboardModifiers::CTRL,
+            ..Default::default()
+        };
+
+        assert_eq!(matcher.feed(&control), MatchResult::Ignored);
+        assert_eq!(
+            matcher.feed(&Press::new('x', KeyboardModifiers::CTRL)),
+            MatchResult::Pending
+        );
+        assert_eq!(matcher.feed(&control), MatchResult::Ignored);
+        assert_eq!(
+            matcher.feed(&Press::new('s', KeyboardModifiers::CTRL)),
+            MatchResult::Matched(&())
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// A fixed size queue from one reader thread to one emulator thread, e.g. of
+// `SyntheticKeyEvent`s or `MouseEvent`s, without allocating per event. Both
+// ends borrow the channel, which outlives the two threads, e.g. with
+// `std::thread::scope`.
+pub struct Channel<T, const N: usize = 64> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    // Counters of the events read and written so far, wrapping around
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// Each slot is only accessed by one end at a time, handed over by the counters
+unsafe impl<T: Send, const N: usize> Sync for Channel<T, N> {}
+
+impl<T, const N: usize> Channel<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (Producer { channel: self }, Consumer { channel: self })
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        self.tail.load(Ordering::Acquire).wrapping_sub(head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn slot(&self, counter: usize) -> Option<&UnsafeCell<MaybeUninit<T>>> {
+        self.slots.get(counter.checked_rem(N)?)
+    }
+}
+
+impl<T, const N: usize> Default for Channel<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Channel<T, N> {
+    fn drop(&mut self) {
+        let (_, mut consumer) = self.split();
+        while consumer.pop().is_some() {}
+    }
+}
+
+pub struct Producer<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+}
+
+impl<T, const N: usize> Producer<'_, T, N> {
+    // Hands the event back if the consumer has fallen behind
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let channel = self.channel;
+        let tail = channel.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(channel.head.load(Ordering::Acquire)) >= N {
+            return Err(value);
+        }
+        let Some(slot) = channel.slot(tail) else {
+            return Err(value);
+        };
+
+        // The consumer is done with the slot, it read it before moving the head
+        unsafe { (*slot.get()).write(value) };
+        channel.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.channel.len() >= N
+    }
+}
+
+pub struct Consumer<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+}
+
+impl<T, const N: usize> Consumer<'_, T, N> {
+    pub fn pop(&mut self) -> Option<T> {
+        let channel = self.channel;
+        let head = channel.head.load(Ordering::Relaxed);
+        if head == channel.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let slot = channel.slot(head)?;
+
+        // The producer wrote the slot before moving the tail past it
+        let value = unsafe { (*slot.get()).assume_init_read() };
+        channel.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channel.is_empty()
+    }
+}
+
+impl<T, const N: usize> Iterator for Consumer<'_, T, N> {
+    type Item = T;
+
+    // Ends at the first empty poll, the producer may still add more
+    fn next(&mut self) -> Option<T> {
+        self.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{key::KeyType, synthesize::SyntheticKeyEvent};
+    use core::cell::Cell;
+
+    extern crate std;
+    use std::{thread, vec::Vec};
+
+    #[test]
+    fn fixed_capacity() {
+        let mut channel = Channel::<u32, 3>::new();
+        let (mut producer, mut consumer) = channel.split();
+
+        for round in 0..4 {
+            assert_eq!(producer.push(round), Ok(()));
+            assert_eq!(producer.push(round + 1), Ok(()));
+            assert_eq!(producer.push(round + 2), Ok(()));
+            assert!(producer.is_full());
+            assert_eq!(producer.push(99), Err(99));
+
+            assert_eq!(consumer.pop(), Some(round));
+            assert_eq!(
+                consumer.by_ref().collect::<Vec<_>>(),
+                [round + 1, round + 2]
+            );
+            assert!(consumer.is_empty());
+        }
+        assert_eq!(Channel::<u32, 0>::new().split().0.push(1), Err(1));
+    }
+
+    #[test]
+    fn drops_unread_events() {
+        struct Counted<'a>(&'a Cell<u32>);
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        let mut channel = Channel::<Counted, 4>::new();
+        let (mut producer, mut consumer) = channel.split();
+        for _ in 0..3 {
+            let _ = producer.push(Counted(&drops));
+        }
+        drop(consumer.pop());
+        assert_eq!(drops.get(), 1);
+
+        drop(channel);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn between_threads() {
+        let mut channel = Channel::<SyntheticKeyEvent<'static>, 8>::new();
+        let (mut producer, mut consumer) = channel.split();
+        let keys: Vec<_> = ('a'..='z').cycle().take(1000).collect();
+
+        let received = thread::scope(|scope| {
+            scope.spawn(|| {
+                for ch in &keys {
+                    let mut event = SyntheticKeyEvent::from(KeyType::Unicode(*ch));
+                    while let Err(back) = producer.push(event) {
+                        event = back;
+                        thread::yield_now();
+                    }
+                }
+            });
+
+            let mut received = Vec::new();
+            while received.len() < keys.len() {
+                match consumer.pop() {
+                    Some(event) => received.push(event.key_with_modifiers),
+                    None => thread::yield_now(),
+                }
+            }
+            received
+        });
+
+        let sent: Vec<_> = keys.iter().map(|ch| KeyType::Unicode(*ch)).collect();
+        assert_eq!(received, sent);
+    }
+}
@@ -0,0 +1,264 @@
+use core::fmt::{Display, Write};
+
+use crate::{
+    encoder::InputEncoder,
+    generate_resolved_sequence,
+    key::{FunctionalKey, KeyType, EXTENSION_KEY_BASE},
+    legacy::LegacyOptions,
+    sequence::EventType,
+    EventResponse, KeyEvent, ReportingMode, ResolvedKeyEvent,
+};
+
+// A key kitty has no code for, e.g. a microphone mute or a macro key. Its code
+// is a character of Supplementary Private Use Area-A, above the keys starting
+// at `EXTENSION_KEY_BASE`, reported like kitty's own functional keys. The
+// legacy form is sent as it is while no flags are set, without one the key is
+// not reported there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CustomKey {
+    pub name: &'static str,
+    pub code: char,
+    pub legacy: Option<&'static str>,
+}
+
+impl CustomKey {
+    pub const fn new(name: &'static str, code: char) -> Self {
+        Self {
+            name,
+            code,
+            legacy: None,
+        }
+    }
+
+    pub const fn with_legacy(mut self, legacy: &'static str) -> Self {
+        self.legacy = Some(legacy);
+        self
+    }
+
+    pub fn key(&self) -> KeyType {
+        KeyType::Unicode(self.code)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterError {
+    NotPrivateUse,
+    // Taken by one of the functional keys
+    Reserved,
+    Duplicate,
+    Full,
+}
+
+impl Display for RegisterError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RegisterError::NotPrivateUse => {
+                f.write_str("key code is not in supplementary private use area A")
+            }
+            RegisterError::Reserved => f.write_str("key code is used by a functional key"),
+            RegisterError::Duplicate => f.write_str("key code or name is already registered"),
+            RegisterError::Full => f.write_str("no room left for another key"),
+        }
+    }
+}
+
+// The custom keys of an emulator, registered one by one or from a const table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CustomKeys<const N: usize = 16> {
+    keys: [Option<CustomKey>; N],
+}
+
+impl<const N: usize> Default for CustomKeys<N> {
+    fn default() -> Self {
+        Self { keys: [None; N] }
+    }
+}
+
+impl<const N: usize> CustomKeys<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_table(table: &[CustomKey]) -> Result<Self, RegisterError> {
+        let mut keys = Self::new();
+        for key in table {
+            keys.register(*key)?;
+        }
+        Ok(keys)
+    }
+
+    pub fn register(&mut self, key: CustomKey) -> Result<(), RegisterError> {
+        if !(EXTENSION_KEY_BASE..=0xffffd).contains(&u32::from(key.code)) {
+            return Err(RegisterError::NotPrivateUse);
+        }
+        if FunctionalKey::is_assigned_code(key.code.into()) {
+            return Err(RegisterError::Reserved);
+        }
+        if self.get(key.key()).is_some() || self.by_name(key.name).is_some() {
+            return Err(RegisterError::Duplicate);
+        }
+
+        let slot = self.keys.iter_mut().find(|slot| slot.is_none());
+        *slot.ok_or(RegisterError::Full)? = Some(key);
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CustomKey> {
+        self.keys.iter().flatten()
+    }
+
+    pub fn get(&self, key: KeyType) -> Option<&CustomKey> {
+        self.iter().find(|custom| custom.key() == key)
+    }
+
+    // Names are matched ignoring ASCII case, like the chord names
+    pub fn by_name(&self, name: &str) -> Option<&CustomKey> {
+        self.iter()
+            .find(|custom| custom.name.eq_ignore_ascii_case(name))
+    }
+
+    // The key a legacy form read back from the terminal stands for
+    pub fn from_legacy(&self, bytes: &[u8]) -> Option<&CustomKey> {
+        self.iter().find(|custom| {
+            custom
+                .legacy
+                .is_some_and(|legacy| legacy.as_bytes() == bytes)
+        })
+    }
+
+    // The name of a parsed key, if it is one of these
+    pub fn name(&self, key: KeyType) -> Option<&'static str> {
+        self.get(key).map(|custom| custom.name)
+    }
+
+    // `None` for events that are not for a custom key, the generator handles
+    // those. Custom keys are escape codes whenever a flag is set, since no
+    // legacy encoding could be mistaken for them.
+    pub fn generate<'a>(
+        &self,
+        mode: ReportingMode,
+        options: &LegacyOptions,
+        event: &'a impl KeyEvent,
+    ) -> Option<EventResponse<'a>> {
+        let event = ResolvedKeyEvent::new(event);
+        let custom = self.get(event.key_without_modifiers)?;
+
+        if mode.intersects(
+            ReportingMode::DISAMBIGUATE_ESC_CODES | ReportingMode::REPORT_ALL_KEYS_AS_ESC,
+        ) {
+            let mode = mode | ReportingMode::REPORT_ALL_KEYS_AS_ESC;
+            return Some(generate_resolved_sequence(mode, options, event));
+        }
+
+        Some(match custom.legacy {
+            Some(text) if event.event_type != EventType::Release => EventResponse::Text {
+                text,
+                alt_pressed: options.sends_escape(event.modifiers),
+            },
+            _ => EventResponse::Nothing,
+        })
+    }
+
+    // `InputEncoder::encode_key`, with the custom keys encoded as well
+    pub fn encode(
+        &self,
+        encoder: &InputEncoder,
+        event: &impl KeyEvent,
+        out: &mut impl Write,
+    ) -> core::fmt::Result {
+        let mode = encoder.effective_mode(event);
+        match self.generate(mode, &encoder.legacy_options(), event) {
+            Some(response) => write!(out, "{response}"),
+            None => encoder.encode_key(event, out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parse::{ParserConfig, TerminalEvent},
+        sequence::KeyboardModifiers,
+        synthesize::SyntheticKeyEvent,
+        KeyInfo,
+    };
+
+    extern crate std;
+    use std::string::String;
+
+    const KEYS: &[CustomKey] = &[
+        CustomKey::new("mic_mute", '\u{f0100}').with_legacy("\x1b[57;5~"),
+        CustomKey::new("macro_1", '\u{f0101}'),
+    ];
+
+    #[test]
+    fn registration() {
+        let mut keys = CustomKeys::<3>::from_table(KEYS).unwrap();
+
+        assert_eq!(
+            keys.register(CustomKey::new("latin", 'a')),
+            Err(RegisterError::NotPrivateUse)
+        );
+        assert_eq!(
+            keys.register(CustomKey::new("caps", '\u{e00e}')),
+            Err(RegisterError::NotPrivateUse)
+        );
+        assert_eq!(
+            keys.register(CustomKey::new("hangul", '\u{f0005}')),
+            Err(RegisterError::Reserved)
+        );
+        assert_eq!(
+            keys.register(CustomKey::new("MIC_MUTE", '\u{f0102}')),
+            Err(RegisterError::Duplicate)
+        );
+        assert_eq!(
+            keys.register(CustomKey::new("macro_2", '\u{f0102}')),
+            Ok(())
+        );
+        assert_eq!(
+            keys.register(CustomKey::new("macro_3", '\u{f0103}')),
+            Err(RegisterError::Full)
+        );
+
+        assert_eq!(
+            keys.by_name("Macro_1").map(CustomKey::key),
+            Some(KeyType::Unicode('\u{f0101}'))
+        );
+        assert_eq!(keys.from_legacy(b"\x1b[57;5~"), keys.by_name("mic_mute"));
+    }
+
+    #[test]
+    fn encoding() {
+        let keys = CustomKeys::<16>::from_table(KEYS).unwrap();
+        let mut encoder = InputEncoder::new();
+        let mut out = String::new();
+
+        let mic_mute = SyntheticKeyEvent::from(KeyType::Unicode('\u{f0100}'));
+        let macro_1 = SyntheticKeyEvent {
+            modifiers: KeyboardModifiers::CTRL,
+            ..SyntheticKeyEvent::from(KeyType::Unicode('\u{f0101}'))
+        };
+        for event in [&mic_mute, &macro_1] {
+            keys.encode(&encoder, event, &mut out).unwrap();
+        }
+        assert_eq!(out, "\x1b[57;5~");
+
+        out.clear();
+        encoder.set_mode(ReportingMode::DISAMBIGUATE_ESC_CODES);
+        for event in [
+            &mic_mute,
+            &macro_1,
+            &SyntheticKeyEvent::from(KeyType::Unicode('a')),
+        ] {
+            keys.encode(&encoder, event, &mut out).unwrap();
+        }
+        assert_eq!(out, "\x1b[983296u\x1b[983297;5ua");
+
+        let Ok((TerminalEvent::Key(key), _)) = ParserConfig::kitty().parse(b"\x1b[983297;5u")
+        else {
+            panic!("not a key");
+        };
+        assert_eq!(keys.name(key.key_without_modifiers()), Some("macro_1"));
+    }
+}
@@ -0,0 +1,118 @@
+use core::fmt::Display;
+
+use crate::{legacy::LegacyOptions, sequence::EventType, EventResponse, KeyEvent, ReportingMode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingPath {
+    // Characters and fixed strings, e.g. `\r` for Enter
+    LegacyText,
+    // The xterm sequences sent while no flags are set
+    LegacySequence,
+    // `CSI u` and the letter and tilde forms with kitty's fields
+    KittySequence,
+    Suppressed,
+}
+
+// How the generator arrived at a response, for debug overlays and bug reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diagnostics {
+    pub path: EncodingPath,
+    // The flags that decided the response, set or not
+    pub consulted: ReportingMode,
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self.path {
+            EncodingPath::LegacyText => "legacy text",
+            EncodingPath::LegacySequence => "legacy sequence",
+            EncodingPath::KittySequence => "kitty sequence",
+            EncodingPath::Suppressed => "suppressed",
+        })?;
+        f.write_str(", consulted ")?;
+        bitflags::parser::to_writer(&self.consulted, f)
+    }
+}
+
+// `ReportingMode::encode_with`, along with how the response came about
+pub fn encode_with_diagnostics<'a>(
+    mode: ReportingMode,
+    options: &LegacyOptions,
+    key_event: &'a impl KeyEvent,
+) -> (EventResponse<'a>, Diagnostics) {
+    let response = mode.encode_with(options, key_event);
+
+    let mut consulted = ReportingMode::empty();
+    if key_event.event_type() == EventType::Release {
+        consulted |= ReportingMode::REPORT_EVENT_TYPES;
+    }
+    consulted |= ReportingMode::REPORT_ALL_KEYS_AS_ESC;
+    if !mode.intersects(ReportingMode::REPORT_ALL_KEYS_AS_ESC) {
+        consulted |= ReportingMode::DISAMBIGUATE_ESC_CODES;
+    }
+    let kitty = mode
+        .intersects(ReportingMode::DISAMBIGUATE_ESC_CODES | ReportingMode::REPORT_ALL_KEYS_AS_ESC);
+
+    let path = match response {
+        EventResponse::Nothing => EncodingPath::Suppressed,
+        EventResponse::Text { .. } | EventResponse::Character { .. } => EncodingPath::LegacyText,
+        EventResponse::Sequence(_) | EventResponse::PrefixedSequence(_) => {
+            // Every field a sequence can carry is behind a flag
+            consulted |= ReportingMode::REPORT_EVENT_TYPES
+                | ReportingMode::REPORT_ALTERNATE_KEYS
+                | ReportingMode::REPORT_ASSOCIATED_TEXT;
+            if kitty {
+                EncodingPath::KittySequence
+            } else {
+                EncodingPath::LegacySequence
+            }
+        }
+    };
+
+    (response, Diagnostics { path, consulted })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        key::{FunctionalKey, KeyType},
+        sequence::KeyboardModifiers,
+        synthesize::SyntheticKeyEvent,
+    };
+
+    extern crate std;
+    use std::format;
+
+    fn diagnose(mode: ReportingMode, event: &impl KeyEvent) -> Diagnostics {
+        encode_with_diagnostics(mode, &LegacyOptions::default(), event).1
+    }
+
+    #[test]
+    fn encoding_paths() {
+        let up = (
+            KeyType::Functional(FunctionalKey::Up),
+            KeyboardModifiers::CTRL,
+        );
+        let legacy = diagnose(ReportingMode::empty(), &up);
+        assert_eq!(legacy.path, EncodingPath::LegacySequence);
+
+        let a = (KeyType::Unicode('a'), KeyboardModifiers::empty());
+        let text = diagnose(ReportingMode::DISAMBIGUATE_ESC_CODES, &a);
+        assert_eq!(text.path, EncodingPath::LegacyText);
+        assert_eq!(
+            format!("{text}"),
+            "legacy text, consulted DISAMBIGUATE_ESC_CODES | REPORT_ALL_KEYS_AS_ESC"
+        );
+
+        let all_keys = ReportingMode::REPORT_ALL_KEYS_AS_ESC;
+        assert_eq!(diagnose(all_keys, &a).path, EncodingPath::KittySequence);
+
+        let release = SyntheticKeyEvent::from(KeyType::Unicode('a')).released();
+        let suppressed = diagnose(all_keys, &release);
+        assert_eq!(suppressed.path, EncodingPath::Suppressed);
+        assert!(suppressed
+            .consulted
+            .contains(ReportingMode::REPORT_EVENT_TYPES));
+    }
+}
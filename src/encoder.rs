@@ -0,0 +1,763 @@
+use core::fmt::Write;
+
+use crate::{
+    binding::{Binding, Chord, MatchResult, Matcher},
+    filter::Coalesced,
+    key::{FunctionalKey, KeyType},
+    legacy::{FunctionKeys, LegacyOptions},
+    mouse::{
+        encode_mouse, Locator, MouseButton, MouseEncoding, MouseEvent, MouseEventKind,
+        MouseTracking,
+    },
+    paste::write_bracketed_paste,
+    sequence::{EventType, SequenceIntroducer, SequenceTerminator},
+    synthesize::{type_text, SyntheticKeyEvent},
+    EventResponse, KeyEvent, KeyInfo, ReportingMode,
+};
+
+pub const MODE_OVERRIDE_CAPACITY: usize = 8;
+
+// Chords encoded under a reporting mode of their own instead of the one the
+// application set, e.g. Ctrl+I told apart from Tab for applications that never
+// asked for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ModeOverrides {
+    overrides: [Option<(Chord, ReportingMode)>; MODE_OVERRIDE_CAPACITY],
+}
+
+impl ModeOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns false if there is no room left
+    pub fn set(&mut self, chord: Chord, mode: ReportingMode) -> bool {
+        let chord = chord.normalized();
+        let slot = self
+            .overrides
+            .iter()
+            .position(|entry| entry.is_some_and(|(c, _)| c == chord))
+            .or_else(|| self.overrides.iter().position(Option::is_none))
+            .and_then(|slot| self.overrides.get_mut(slot));
+
+        match slot {
+            Some(slot) => {
+                *slot = Some((chord, mode));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, chord: Chord) {
+        let chord = chord.normalized();
+        for entry in &mut self.overrides {
+            if entry.is_some_and(|(c, _)| c == chord) {
+                *entry = None;
+            }
+        }
+    }
+
+    // The mode to encode `event` under, if it is overridden
+    pub fn get(&self, event: &impl KeyEvent) -> Option<ReportingMode> {
+        self.overrides
+            .iter()
+            .flatten()
+            .find(|(chord, _)| chord.matches(event))
+            .map(|(_, mode)| *mode)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intercepted<'b, A> {
+    Action(&'b A),
+    // Part of a shortcut, e.g. its release or the start of a sequence
+    Swallowed,
+    // Not a shortcut, goes to the pty
+    Forward,
+}
+
+// Shortcuts the emulator handles itself, like Ctrl+Shift+C. Once a press is
+// taken, the repeats and the release of that key are kept from the pty too.
+// At most `N` taken keys can be held at once.
+#[derive(Debug, Clone)]
+pub struct Shortcuts<'b, A, const N: usize = 8> {
+    matcher: Matcher<'b, A>,
+    held: [Option<KeyType>; N],
+}
+
+impl<'b, A, const N: usize> Shortcuts<'b, A, N> {
+    pub fn new(bindings: &'b [Binding<A>]) -> Self {
+        Self {
+            matcher: Matcher::new(bindings),
+            held: [None; N],
+        }
+    }
+
+    pub fn feed(&mut self, event: &impl KeyEvent) -> Intercepted<'b, A> {
+        let key = event.key_without_modifiers();
+        let held = self.held.iter().position(|held| *held == Some(key));
+
+        if event.event_type() == EventType::Release {
+            return match held.and_then(|index| self.held.get_mut(index)) {
+                Some(slot) => {
+                    *slot = None;
+                    Intercepted::Swallowed
+                }
+                None => Intercepted::Forward,
+            };
+        }
+
+        let intercepted = match self.matcher.feed(event) {
+            MatchResult::Matched(action) => Intercepted::Action(action),
+            MatchResult::Pending => Intercepted::Swallowed,
+            MatchResult::NoMatch | MatchResult::Ignored if held.is_some() => {
+                return Intercepted::Swallowed;
+            }
+            MatchResult::NoMatch | MatchResult::Ignored => return Intercepted::Forward,
+        };
+
+        if held.is_none() {
+            if let Some(slot) = self.held.iter_mut().find(|held| held.is_none()) {
+                *slot = Some(key);
+            }
+        }
+        intercepted
+    }
+}
+
+// The input side of a terminal emulator: holds the modes the application set
+// and turns frontend events into the bytes written to the pty.
+//
+// It is plain data, `Send` and `Sync`. Encoding keys only reads it, so the UI
+// thread and the pty writer can share it behind an `RwLock`: the writer takes
+// the write lock for the modes it parses out of the output, the UI thread a
+// read lock per key. Mouse and focus events update state and need the write
+// lock as well.
+#[derive(Debug, Clone)]
+pub struct InputEncoder {
+    mode: ReportingMode,
+    legacy: LegacyOptions,
+    mode_overrides: ModeOverrides,
+
+    cursor_keys_application: bool,
+    alternate_screen: bool,
+    alternate_scroll: bool,
+    wheel_lines: u8,
+    max_repeats: usize,
+    verbose: bool,
+    focus_events: bool,
+    // Last focus change seen, whether it was reported or not
+    focused: Option<bool>,
+    bracketed_paste: bool,
+
+    mouse_tracking: MouseTracking,
+    mouse_encoding: MouseEncoding,
+    locator: Locator,
+}
+
+// Fails to build if a field ever stops the encoder from being shared
+const _: () = {
+    const fn thread_safe<T: Send + Sync>() {}
+    thread_safe::<InputEncoder>();
+    thread_safe::<ModeOverrides>();
+    thread_safe::<LegacyOptions>();
+};
+
+impl InputEncoder {
+    pub const DEFAULT_WHEEL_LINES: u8 = 3;
+    pub const DEFAULT_MAX_REPEATS: usize = 1;
+
+    pub fn new() -> Self {
+        Self {
+            mode: ReportingMode::empty(),
+            legacy: LegacyOptions::default(),
+            mode_overrides: ModeOverrides::new(),
+            cursor_keys_application: false,
+            alternate_screen: false,
+            alternate_scroll: true,
+            wheel_lines: Self::DEFAULT_WHEEL_LINES,
+            max_repeats: Self::DEFAULT_MAX_REPEATS,
+            verbose: false,
+            focus_events: false,
+            focused: None,
+            bracketed_paste: false,
+            mouse_tracking: MouseTracking::None,
+            mouse_encoding: MouseEncoding::Default,
+            locator: Locator::new(),
+        }
+    }
+
+    pub fn mode(&self) -> ReportingMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ReportingMode) {
+        self.mode = mode;
+    }
+
+    pub fn legacy_options(&self) -> LegacyOptions {
+        self.legacy
+    }
+
+    pub fn set_legacy_options(&mut self, options: impl Into<LegacyOptions>) {
+        self.legacy = options.into();
+    }
+
+    pub fn mode_overrides(&mut self) -> &mut ModeOverrides {
+        &mut self.mode_overrides
+    }
+
+    // The mode `event` is encoded under, the application's unless overridden
+    pub fn effective_mode(&self, event: &impl KeyEvent) -> ReportingMode {
+        self.mode_overrides.get(event).unwrap_or(self.mode)
+    }
+
+    // Arrow sequences sent for each wheel tick on the alternate screen
+    pub fn set_wheel_lines(&mut self, lines: u8) {
+        self.wheel_lines = lines;
+    }
+
+    // Repeats sent for a run merged by `RepeatCoalescer`, the rest are dropped
+    pub fn set_max_repeats(&mut self, repeats: usize) {
+        self.max_repeats = repeats;
+    }
+
+    // Spell out default fields in kitty sequences, see `Sequence::verbose`
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    pub fn mouse_tracking(&self) -> MouseTracking {
+        self.mouse_tracking
+    }
+
+    pub fn mouse_encoding(&self) -> MouseEncoding {
+        self.mouse_encoding
+    }
+
+    pub fn is_alternate_screen(&self) -> bool {
+        self.alternate_screen
+    }
+
+    pub fn is_bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    // DECELR and friends are not DECSET modes, they are forwarded here
+    pub fn locator(&mut self) -> &mut Locator {
+        &mut self.locator
+    }
+
+    // DECSET (`enabled`) and DECRST, returns whether the mode is known
+    pub fn set_private_mode(&mut self, mode: u16, enabled: bool) -> bool {
+        match mode {
+            1 => self.cursor_keys_application = enabled,
+            9 | 1000 | 1002 | 1003 if !enabled => self.mouse_tracking = MouseTracking::None,
+            9 => self.mouse_tracking = MouseTracking::X10,
+            1000 => self.mouse_tracking = MouseTracking::Normal,
+            1002 => self.mouse_tracking = MouseTracking::ButtonEvent,
+            1003 => self.mouse_tracking = MouseTracking::AnyEvent,
+            1004 => self.focus_events = enabled,
+            1005 | 1006 | 1015 | 1016 if !enabled => self.mouse_encoding = MouseEncoding::Default,
+            1005 => self.mouse_encoding = MouseEncoding::Utf8,
+            1006 => self.mouse_encoding = MouseEncoding::Sgr,
+            1007 => self.alternate_scroll = enabled,
+            1015 => self.mouse_encoding = MouseEncoding::Urxvt,
+            1016 => self.mouse_encoding = MouseEncoding::SgrPixels,
+            1034 => self.legacy.eight_bit_input = enabled,
+            1035 => self.legacy.num_lock = enabled,
+            1036 => self.legacy.meta_sends_escape = enabled,
+            1037 => self.legacy.delete_is_del = enabled,
+            1039 => self.legacy.alt_sends_escape = enabled,
+            1051..=1053 if !enabled => self.legacy.function_keys = FunctionKeys::Vt220,
+            1051 => self.legacy.function_keys = FunctionKeys::Sun,
+            1052 => self.legacy.function_keys = FunctionKeys::Hp,
+            1053 => self.legacy.function_keys = FunctionKeys::Sco,
+            47 | 1047 | 1049 => self.alternate_screen = enabled,
+            2004 => self.bracketed_paste = enabled,
+            _ => return false,
+        }
+
+        true
+    }
+
+    pub fn encode_key(&self, event: &impl KeyEvent, out: &mut impl Write) -> core::fmt::Result {
+        let mode = self.effective_mode(event);
+        match mode.encode_with(&self.legacy, event) {
+            // DECCKM only changes cursor keys without parameters
+            EventResponse::Sequence(mut seq)
+                if self.cursor_keys_application
+                    && seq.modifier.is_empty()
+                    && seq.event_type == EventType::Press
+                    && seq.associated_text.is_none()
+                    && matches!(
+                        seq.terminator,
+                        SequenceTerminator::Other('A'..='D' | 'H' | 'F')
+                    ) =>
+            {
+                seq.introducer = SequenceIntroducer::SS3;
+                write!(out, "{seq}")
+            }
+            // Legacy parsers do not know the event type field
+            EventResponse::Sequence(seq) if self.verbose && !mode.is_empty() => {
+                write!(out, "{}", seq.verbose())
+            }
+            response => write!(out, "{response}"),
+        }
+    }
+
+    // Sends a merged run of repeats at most `max_repeats` times, any other
+    // event once
+    pub fn encode_coalesced<E: KeyEvent>(
+        &self,
+        event: &Coalesced<E>,
+        out: &mut impl Write,
+    ) -> core::fmt::Result {
+        let times = match event.event_type() {
+            EventType::Repeat => event.count.min(self.max_repeats),
+            _ => 1,
+        };
+        for _ in 0..times {
+            self.encode_key(event, out)?;
+        }
+        Ok(())
+    }
+
+    // The paste action: a bracketed paste block while the application asked
+    // for one (DECSET 2004), otherwise the text typed key by key on a US layout
+    pub fn encode_paste(&self, text: &str, out: &mut impl Write) -> core::fmt::Result {
+        if self.bracketed_paste {
+            return write_bracketed_paste(text, out);
+        }
+
+        for event in type_text(self.mode, text) {
+            self.encode_key(&event, out)?;
+        }
+        Ok(())
+    }
+
+    pub fn encode_mouse(&mut self, event: &MouseEvent, out: &mut impl Write) -> core::fmt::Result {
+        self.locator.mouse_event(event, out)?;
+
+        if self.mouse_tracking != MouseTracking::None {
+            return encode_mouse(event, self.mouse_tracking, self.mouse_encoding, out);
+        }
+
+        // Applications on the alternate screen have no scrollback, scroll
+        // them with the cursor keys instead
+        let arrow = match event.button {
+            Some(MouseButton::WheelUp) => FunctionalKey::Up,
+            Some(MouseButton::WheelDown) => FunctionalKey::Down,
+            Some(MouseButton::WheelLeft) => FunctionalKey::Left,
+            Some(MouseButton::WheelRight) => FunctionalKey::Right,
+            _ => return Ok(()),
+        };
+        if !self.alternate_screen || !self.alternate_scroll || event.kind != MouseEventKind::Press {
+            return Ok(());
+        }
+
+        let key = SyntheticKeyEvent::from(KeyType::Functional(arrow));
+        for _ in 0..self.wheel_lines {
+            self.encode_key(&key, out)?;
+        }
+
+        Ok(())
+    }
+
+    // Encodes `event` unless it belongs to one of `shortcuts`, returning the
+    // action of a shortcut that was triggered
+    pub fn encode_key_with_shortcuts<'b, A, const N: usize>(
+        &self,
+        shortcuts: &mut Shortcuts<'b, A, N>,
+        event: &impl KeyEvent,
+        out: &mut impl Write,
+    ) -> Result<Option<&'b A>, core::fmt::Error> {
+        match shortcuts.feed(event) {
+            Intercepted::Action(action) => Ok(Some(action)),
+            Intercepted::Swallowed => Ok(None),
+            Intercepted::Forward => self.encode_key(event, out).map(|()| None),
+        }
+    }
+
+    // Windowing systems repeat focus changes, only actual changes are reported
+    pub fn encode_focus(&mut self, focused: bool, out: &mut impl Write) -> core::fmt::Result {
+        let changed = self.focused != Some(focused);
+        self.focused = Some(focused);
+
+        match (self.focus_events && changed, focused) {
+            (false, _) => Ok(()),
+            (true, true) => out.write_str("\x1b[I"),
+            (true, false) => out.write_str("\x1b[O"),
+        }
+    }
+
+    // Taken as focused until the first focus change
+    pub fn is_focused(&self) -> bool {
+        self.focused != Some(false)
+    }
+}
+
+impl Default for InputEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence::KeyboardModifiers;
+
+    extern crate std;
+    use std::{
+        string::String,
+        sync::{Arc, RwLock},
+        thread,
+    };
+
+    fn wheel(button: MouseButton) -> MouseEvent {
+        MouseEvent {
+            button: Some(button),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cursor_key_mode() {
+        let mut encoder = InputEncoder::new();
+        let up = SyntheticKeyEvent::from(KeyType::Functional(FunctionalKey::Up));
+        let mut out = String::new();
+
+        encoder.encode_key(&up, &mut out).unwrap();
+        assert!(encoder.set_private_mode(1, true));
+        encoder.encode_key(&up, &mut out).unwrap();
+        assert_eq!(out, "\x1b[A\x1bOA");
+    }
+
+    #[test]
+    fn verbose_sequences() {
+        let mut encoder = InputEncoder::new();
+        let up = SyntheticKeyEvent::from(KeyType::Functional(FunctionalKey::Up));
+        let mut out = String::new();
+        encoder.set_verbose(true);
+
+        encoder.encode_key(&up, &mut out).unwrap();
+        encoder.set_mode(ReportingMode::DISAMBIGUATE_ESC_CODES);
+        encoder.encode_key(&up, &mut out).unwrap();
+        assert_eq!(out, "\x1b[A\x1b[1;1:1A");
+    }
+
+    #[test]
+    fn mode_overrides() {
+        let mut encoder = InputEncoder::new();
+        let ctrl_i = SyntheticKeyEvent::with_modifiers('i', KeyboardModifiers::CTRL);
+        let ctrl_j = SyntheticKeyEvent::with_modifiers('j', KeyboardModifiers::CTRL);
+        let mut out = String::new();
+
+        let chord = Chord::new(KeyType::Unicode('I'), KeyboardModifiers::CTRL);
+        assert!(encoder
+            .mode_overrides()
+            .set(chord, ReportingMode::DISAMBIGUATE_ESC_CODES));
+        encoder.encode_key(&ctrl_i, &mut out).unwrap();
+        encoder.encode_key(&ctrl_j, &mut out).unwrap();
+        assert_eq!(out, "\x1b[105;5u\n");
+
+        out.clear();
+        encoder.mode_overrides().remove(chord);
+        encoder.encode_key(&ctrl_i, &mut out).unwrap();
+        assert_eq!(out, "\t");
+    }
+
+    #[test]
+    fn shortcuts() {
+        #[derive(Debug, PartialEq)]
+        enum Action {
+            Copy,
+            Bigger,
+        }
+
+        let bindings = [
+            Binding::parse("ctrl+shift+c", Action::Copy).unwrap(),
+            Binding::parse("ctrl+=", Action::Bigger).unwrap(),
+        ];
+        let mut shortcuts = Shortcuts::<_, 2>::new(&bindings);
+        let mut encoder = InputEncoder::new();
+        encoder.set_mode(ReportingMode::REPORT_ALL_KEYS_AS_ESC | ReportingMode::REPORT_EVENT_TYPES);
+        let mut out = String::new();
+
+        let copy = SyntheticKeyEvent::with_modifiers(
+            'C',
+            KeyboardModifiers::CTRL | KeyboardModifiers::SHIFT,
+        );
+        let bigger = SyntheticKeyEvent::with_modifiers('=', KeyboardModifiers::CTRL);
+        let mut encode = |event: &SyntheticKeyEvent, event_type| {
+            let event = SyntheticKeyEvent {
+                event_type,
+                ..*event
+            };
+            encoder
+                .encode_key_with_shortcuts(&mut shortcuts, &event, &mut out)
+                .unwrap()
+        };
+
+        assert_eq!(encode(&copy, EventType::Press), Some(&Action::Copy));
+        // Shift let go first, the release no longer has the modifiers
+        let c = SyntheticKeyEvent::from(KeyType::Unicode('c'));
+        assert_eq!(encode(&c, EventType::Release), None);
+        assert_eq!(encode(&bigger, EventType::Press), Some(&Action::Bigger));
+        assert_eq!(encode(&bigger, EventType::Repeat), Some(&Action::Bigger));
+        assert_eq!(encode(&bigger, EventType::Release), None);
+        assert_eq!(encode(&c, EventType::Press), None);
+        assert_eq!(encode(&c, EventType::Release), None);
+        assert_eq!(out, "\x1b[99u\x1b[99;1:3u");
+    }
+
+    #[test]
+    fn coalesced_repeats() {
+        use crate::{filter::RepeatCoalescer, parse::ParsedKey};
+        use std::vec::Vec;
+
+        let mut encoder = InputEncoder::new();
+        encoder.set_mode(ReportingMode::REPORT_EVENT_TYPES | ReportingMode::REPORT_ALL_KEYS_AS_ESC);
+        let j = SyntheticKeyEvent::from(KeyType::Unicode('j'));
+        let repeat = SyntheticKeyEvent {
+            event_type: EventType::Repeat,
+            ..j
+        };
+        let held = [j].into_iter().chain([repeat; 100]).chain([j.released()]);
+
+        let encode = |encoder: &InputEncoder| {
+            let mut coalescer = RepeatCoalescer::new();
+            let mut out = String::new();
+            for event in held.clone() {
+                for run in coalescer.push(event) {
+                    encoder.encode_coalesced(&run, &mut out).unwrap();
+                }
+            }
+            out
+        };
+        assert_eq!(encode(&encoder), "\x1b[106u\x1b[106;1:2u\x1b[106;1:3u");
+        encoder.set_max_repeats(3);
+        let out = encode(&encoder);
+        assert_eq!(out.matches("\x1b[106;1:2u").count(), 3);
+
+        // The application merges them again while it cannot keep up
+        let mut coalescer = RepeatCoalescer::new();
+        let mut input = out.as_bytes();
+        let mut runs = Vec::new();
+        while let Ok((key, len)) = ParsedKey::parse(input) {
+            input = input.get(len..).unwrap();
+            runs.extend(coalescer.push(key).map(|run| (run.event_type(), run.count)));
+        }
+        assert_eq!(
+            runs,
+            [
+                (EventType::Press, 1),
+                (EventType::Repeat, 3),
+                (EventType::Release, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn paste_action() {
+        let mut encoder = InputEncoder::new();
+        let mut out = String::new();
+
+        encoder.encode_paste("a\x1b\n", &mut out).unwrap();
+        assert_eq!(out, "a\x1b\r");
+
+        out.clear();
+        encoder.set_mode(ReportingMode::DISAMBIGUATE_ESC_CODES);
+        encoder.encode_paste("A\x1b", &mut out).unwrap();
+        assert_eq!(out, "A\x1b[27u");
+
+        out.clear();
+        encoder.set_private_mode(2004, true);
+        encoder.encode_paste("a\x1b[201~\n", &mut out).unwrap();
+        assert_eq!(out, "\x1b[200~a[201~\n\x1b[201~");
+    }
+
+    #[test]
+    fn wheel_on_alternate_screen() {
+        let mut encoder = InputEncoder::new();
+        let mut out = String::new();
+
+        encoder
+            .encode_mouse(&wheel(MouseButton::WheelUp), &mut out)
+            .unwrap();
+        assert_eq!(out, "");
+
+        encoder.set_private_mode(1049, true);
+        encoder.set_wheel_lines(2);
+        encoder
+            .encode_mouse(&wheel(MouseButton::WheelUp), &mut out)
+            .unwrap();
+        assert_eq!(out, "\x1b[A\x1b[A");
+
+        out.clear();
+        encoder.set_private_mode(1, true);
+        encoder
+            .encode_mouse(&wheel(MouseButton::WheelDown), &mut out)
+            .unwrap();
+        assert_eq!(out, "\x1bOB\x1bOB");
+
+        out.clear();
+        encoder.set_private_mode(1000, true);
+        encoder.set_private_mode(1006, true);
+        encoder
+            .encode_mouse(&wheel(MouseButton::WheelDown), &mut out)
+            .unwrap();
+        assert_eq!(out, "\x1b[<65;1;1M");
+
+        out.clear();
+        encoder.set_private_mode(1000, false);
+        encoder.set_private_mode(1007, false);
+        encoder
+            .encode_mouse(&wheel(MouseButton::WheelDown), &mut out)
+            .unwrap();
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    fn focus_reports() {
+        let mut encoder = InputEncoder::new();
+        let mut out = String::new();
+
+        encoder.encode_focus(true, &mut out).unwrap();
+        encoder.set_private_mode(1004, true);
+        encoder.encode_focus(false, &mut out).unwrap();
+        assert_eq!(out, "\x1b[O");
+        assert!(!encoder.set_private_mode(12345, true));
+    }
+
+    #[test]
+    fn duplicate_focus_reports() {
+        let mut encoder = InputEncoder::new();
+        let mut out = String::new();
+        encoder.set_private_mode(1004, true);
+        assert!(encoder.is_focused());
+
+        for focused in [false, false, true, true, false] {
+            encoder.encode_focus(focused, &mut out).unwrap();
+        }
+        assert_eq!(out, "\x1b[O\x1b[I\x1b[O");
+        assert!(!encoder.is_focused());
+    }
+
+    #[test]
+    fn meta_and_alt_modes() {
+        let mut encoder = InputEncoder::new();
+        let key = |modifiers| SyntheticKeyEvent {
+            modifiers,
+            ..SyntheticKeyEvent::from(KeyType::Unicode('x'))
+        };
+        let encode = |encoder: &InputEncoder, modifiers| {
+            let mut out = String::new();
+            encoder.encode_key(&key(modifiers), &mut out).unwrap();
+            out
+        };
+
+        assert_eq!(encode(&encoder, KeyboardModifiers::ALT), "\x1bx");
+        assert_eq!(encode(&encoder, KeyboardModifiers::META), "x");
+
+        encoder.set_private_mode(1036, true);
+        assert_eq!(encode(&encoder, KeyboardModifiers::META), "\x1bx");
+
+        encoder.set_private_mode(1039, false);
+        assert_eq!(encode(&encoder, KeyboardModifiers::ALT), "x");
+        encoder.set_private_mode(1034, true);
+        assert_eq!(encode(&encoder, KeyboardModifiers::ALT), "\u{f8}");
+    }
+
+    #[test]
+    fn num_lock_mode() {
+        let mut encoder = InputEncoder::new();
+        let key = SyntheticKeyEvent {
+            modifiers: KeyboardModifiers::NUM_LOCK,
+            associated_text: Some("8"),
+            ..SyntheticKeyEvent::from(KeyType::Functional(FunctionalKey::NumPad8))
+        };
+        let without_lock = SyntheticKeyEvent {
+            modifiers: KeyboardModifiers::empty(),
+            ..key
+        };
+        let mut out = String::new();
+
+        encoder.encode_key(&key, &mut out).unwrap();
+        encoder.set_private_mode(1035, false);
+        encoder.encode_key(&without_lock, &mut out).unwrap();
+        assert_eq!(out, "8\x1b[A");
+    }
+
+    #[test]
+    fn function_key_modes() {
+        let mut encoder = InputEncoder::new();
+        let keys = [FunctionalKey::F1, FunctionalKey::F12, FunctionalKey::PageUp];
+        let encode = |encoder: &InputEncoder| {
+            let mut out = String::new();
+            for key in keys {
+                let event = SyntheticKeyEvent::from(KeyType::Functional(key));
+                encoder.encode_key(&event, &mut out).unwrap();
+            }
+            out
+        };
+
+        assert_eq!(encode(&encoder), "\x1b[P\x1b[24~\x1b[5~");
+        encoder.set_private_mode(1051, true);
+        assert_eq!(encode(&encoder), "\x1b[224z\x1b[193z\x1b[216z");
+        encoder.set_private_mode(1052, true);
+        assert_eq!(encode(&encoder), "\x1bp\x1b[24~\x1bT");
+        encoder.set_private_mode(1053, true);
+        assert_eq!(encode(&encoder), "\x1b[M\x1b[X\x1b[I");
+        encoder.set_private_mode(1053, false);
+        assert_eq!(encoder.legacy_options().function_keys, FunctionKeys::Vt220);
+    }
+
+    #[test]
+    fn delete_is_del() {
+        let mut encoder = InputEncoder::new();
+        let delete = SyntheticKeyEvent::from(KeyType::Functional(FunctionalKey::Delete));
+        let mut out = String::new();
+
+        encoder.encode_key(&delete, &mut out).unwrap();
+        encoder.set_private_mode(1037, true);
+        encoder.encode_key(&delete, &mut out).unwrap();
+        assert_eq!(out, "\x1b[3~\x7f");
+
+        out.clear();
+        encoder.set_mode(ReportingMode::DISAMBIGUATE_ESC_CODES);
+        encoder.encode_key(&delete, &mut out).unwrap();
+        assert_eq!(out, "\x1b[3~");
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let encoder = Arc::new(RwLock::new(InputEncoder::new()));
+
+        let writer = Arc::clone(&encoder);
+        thread::spawn(move || {
+            let mut encoder = writer.write().unwrap();
+            encoder.set_mode(ReportingMode::DISAMBIGUATE_ESC_CODES);
+            encoder.set_private_mode(1004, true);
+        })
+        .join()
+        .unwrap();
+
+        let mut out = String::new();
+        let ctrl_c = (KeyType::Unicode('c'), KeyboardModifiers::CTRL);
+        encoder
+            .read()
+            .unwrap()
+            .encode_key(&ctrl_c, &mut out)
+            .unwrap();
+        encoder
+            .write()
+            .unwrap()
+            .encode_focus(false, &mut out)
+            .unwrap();
+        assert_eq!(out, "\x1b[99;5u\x1b[O");
+    }
+}
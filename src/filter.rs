@@ -0,0 +1,1058 @@
+use core::{iter::Chain as IterChain, option, time::Duration};
+
+use bitflags::bitflags;
+
+use crate::{
+    binding::Chord,
+    key::KeyType,
+    sequence::{AssociatedText, EventType, KeyboardModifiers},
+    synthesize::{Script, ScriptEvent, ScriptEvents, Step, SyntheticKeyEvent},
+    KeyEvent, KeyInfo, ModifierInfo, ReportingMode, TextInfo,
+};
+
+pub trait Clock {
+    // Monotonic time since an arbitrary epoch
+    fn now(&self) -> Duration;
+}
+
+impl<F: Fn() -> Duration> Clock for F {
+    fn now(&self) -> Duration {
+        self()
+    }
+}
+
+pub trait Filter<E: KeyEvent> {
+    type Output: KeyEvent;
+
+    fn filter(&mut self, event: E) -> Option<Self::Output>;
+
+    // Returns events the filter held back that are ready now; call it
+    // regularly, e.g. from the embedder's timer
+    fn poll(&mut self) -> Option<Self::Output> {
+        None
+    }
+
+    fn chain<F: Filter<Self::Output>>(self, next: F) -> Chain<Self, F>
+    where
+        Self: Sized,
+    {
+        Chain {
+            first: self,
+            second: next,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<E: KeyEvent, A: Filter<E>, B: Filter<A::Output>> Filter<E> for Chain<A, B> {
+    type Output = B::Output;
+
+    fn filter(&mut self, event: E) -> Option<Self::Output> {
+        let event = self.first.filter(event)?;
+        self.second.filter(event)
+    }
+
+    fn poll(&mut self) -> Option<Self::Output> {
+        while let Some(event) = self.first.poll() {
+            if let Some(event) = self.second.filter(event) {
+                return Some(event);
+            }
+        }
+        self.second.poll()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Latched<E> {
+    pub event: E,
+    pub latched: KeyboardModifiers,
+}
+
+impl<E: KeyEvent> KeyInfo for Latched<E> {
+    fn key_with_modifiers(&self) -> KeyType {
+        let key = self.event.key_with_modifiers();
+
+        // Without layout knowledge, shifting letters is the best we can do
+        match key {
+            KeyType::Unicode(ch)
+                if self.latched.intersects(KeyboardModifiers::SHIFT)
+                    && key == self.event.key_without_modifiers() =>
+            {
+                let mut upper = ch.to_uppercase();
+                match (upper.next(), upper.next()) {
+                    (Some(upper), None) => KeyType::Unicode(upper),
+                    _ => key,
+                }
+            }
+            _ => key,
+        }
+    }
+
+    fn key_without_modifiers(&self) -> KeyType {
+        self.event.key_without_modifiers()
+    }
+
+    fn key_base_layout(&self) -> KeyType {
+        self.event.key_base_layout()
+    }
+
+    fn event_type(&self) -> EventType {
+        self.event.event_type()
+    }
+
+    fn timestamp(&self) -> Option<Duration> {
+        self.event.timestamp()
+    }
+}
+
+impl<E: KeyEvent> ModifierInfo for Latched<E> {
+    fn modifiers(&self) -> KeyboardModifiers {
+        self.event.modifiers() | self.latched
+    }
+}
+
+impl<E: KeyEvent> TextInfo for Latched<E> {
+    fn associated_text(&self) -> Option<AssociatedText<'_>> {
+        self.event.associated_text()
+    }
+}
+
+// Tapping a modifier latches it for the next key, tapping it twice locks it
+// until it is tapped again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StickyKeys {
+    latched: KeyboardModifiers,
+    locked: KeyboardModifiers,
+    tapping: Option<KeyboardModifiers>,
+}
+
+impl StickyKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn latched(&self) -> KeyboardModifiers {
+        self.latched
+    }
+
+    pub fn locked(&self) -> KeyboardModifiers {
+        self.locked
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+impl<E: KeyEvent> Filter<E> for StickyKeys {
+    type Output = Latched<E>;
+
+    fn filter(&mut self, event: E) -> Option<Self::Output> {
+        let active = self.latched | self.locked;
+
+        if let Some(modifier) = modifier_bit(event.key_without_modifiers()) {
+            match event.event_type() {
+                EventType::Press => self.tapping = Some(modifier),
+                EventType::Repeat => {}
+                EventType::Release if self.tapping == Some(modifier) => {
+                    self.tapping = None;
+                    if self.locked.intersects(modifier) {
+                        self.locked.remove(modifier);
+                    } else if self.latched.intersects(modifier) {
+                        self.latched.remove(modifier);
+                        self.locked.insert(modifier);
+                    } else {
+                        self.latched.insert(modifier);
+                    }
+                }
+                EventType::Release => {}
+            }
+
+            return Some(Latched {
+                event,
+                latched: active,
+            });
+        }
+
+        // Holding a modifier while pressing a key is a regular chord, not a tap
+        self.tapping = None;
+        if event.event_type() == EventType::Press {
+            self.latched = KeyboardModifiers::empty();
+        }
+
+        Some(Latched {
+            event,
+            latched: active,
+        })
+    }
+}
+
+// Windows and other platforms without an AltGr modifier deliver it as Ctrl +
+// Alt, which applications would take for a chord
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AltGrPolicy {
+    // Report Ctrl + Alt as the platform sent them
+    Keep,
+    // Drop them from keys that typed a character through AltGr
+    #[default]
+    Characters,
+    // Drop them from every key pressed with AltGr, e.g. AltGr+F1 too
+    All,
+}
+
+impl AltGrPolicy {
+    // `altgr` comes from the frontend, e.g. the right Alt key being held on a
+    // layout that has AltGr
+    pub fn apply<E: KeyEvent>(self, event: E, altgr: bool) -> AltGrEvent<E> {
+        let altgr_modifiers = KeyboardModifiers::CTRL | KeyboardModifiers::ALT;
+        let typed = matches!(
+            event.key_with_modifiers(),
+            KeyType::Unicode(ch) if !ch.is_control()
+        );
+        let strip = altgr
+            && event.modifiers().contains(altgr_modifiers)
+            && match self {
+                AltGrPolicy::Keep => false,
+                AltGrPolicy::Characters => typed,
+                AltGrPolicy::All => true,
+            };
+
+        AltGrEvent {
+            event,
+            stripped: if strip {
+                altgr_modifiers
+            } else {
+                KeyboardModifiers::empty()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AltGrEvent<E> {
+    pub event: E,
+    pub stripped: KeyboardModifiers,
+}
+
+impl<E: KeyEvent> KeyInfo for AltGrEvent<E> {
+    fn key_with_modifiers(&self) -> KeyType {
+        self.event.key_with_modifiers()
+    }
+
+    fn key_without_modifiers(&self) -> KeyType {
+        self.event.key_without_modifiers()
+    }
+
+    fn key_base_layout(&self) -> KeyType {
+        self.event.key_base_layout()
+    }
+
+    fn event_type(&self) -> EventType {
+        self.event.event_type()
+    }
+
+    fn timestamp(&self) -> Option<Duration> {
+        self.event.timestamp()
+    }
+}
+
+impl<E: KeyEvent> ModifierInfo for AltGrEvent<E> {
+    fn modifiers(&self) -> KeyboardModifiers {
+        self.event.modifiers() - self.stripped
+    }
+}
+
+impl<E: KeyEvent> TextInfo for AltGrEvent<E> {
+    fn associated_text(&self) -> Option<AssociatedText<'_>> {
+        self.event.associated_text()
+    }
+}
+
+// Keys only count as pressed once they are held for `delay`. A key pressed
+// before the one held so far took long enough replaces it, the replaced key's
+// repeats and release are dropped.
+#[derive(Debug, Clone)]
+pub struct SlowKeys<E, C> {
+    clock: C,
+    delay: Duration,
+    pending: Option<(E, Duration)>,
+    // A release to send after the press it completes, only while nothing is
+    // pending
+    queued: Option<E>,
+    // Keys replaced before their press was accepted, at most
+    // `MAX_DISCARDED` of them
+    discarded: [Option<KeyType>; MAX_DISCARDED],
+}
+
+const MAX_DISCARDED: usize = 8;
+
+impl<E: KeyEvent, C: Clock> SlowKeys<E, C> {
+    pub fn new(clock: C, delay: Duration) -> Self {
+        Self {
+            clock,
+            delay,
+            pending: None,
+            queued: None,
+            discarded: [None; MAX_DISCARDED],
+        }
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    // The pending press, once it was held for `delay`
+    fn accepted(&mut self) -> Option<E> {
+        let (_, pressed_at) = self.pending.as_ref()?;
+        if self.clock.now().saturating_sub(*pressed_at) < self.delay {
+            return None;
+        }
+
+        self.pending.take().map(|(event, _)| event)
+    }
+
+    fn is_discarded(&self, key: KeyType) -> bool {
+        self.discarded.contains(&Some(key))
+    }
+
+    fn discard(&mut self, key: KeyType) {
+        if let Some(slot) = self.discarded.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(key);
+        }
+    }
+
+    fn forget(&mut self, key: KeyType) {
+        if let Some(slot) = self.discarded.iter_mut().find(|slot| **slot == Some(key)) {
+            *slot = None;
+        }
+    }
+}
+
+impl<E: KeyEvent, C: Clock> Filter<E> for SlowKeys<E, C> {
+    type Output = E;
+
+    fn filter(&mut self, event: E) -> Option<Self::Output> {
+        let key = event.key_without_modifiers();
+        let pending_key = self
+            .pending
+            .as_ref()
+            .map(|(pending, _)| pending.key_without_modifiers());
+
+        match event.event_type() {
+            EventType::Press => {
+                self.forget(key);
+                // A press that was held long enough still counts, even if
+                // nobody polled for it
+                let earlier = self.queued.take().or_else(|| self.accepted());
+                if let Some((replaced, _)) = self.pending.take() {
+                    self.discard(replaced.key_without_modifiers());
+                }
+                self.pending = Some((event, self.clock.now()));
+                earlier.or_else(|| self.poll())
+            }
+            EventType::Repeat if pending_key == Some(key) => self.poll(),
+            // Released too early, the press never happened
+            EventType::Release if pending_key == Some(key) => {
+                let press = self.poll();
+                if press.is_some() {
+                    self.queued = Some(event);
+                } else {
+                    self.pending = None;
+                }
+                press
+            }
+            EventType::Repeat if self.is_discarded(key) => self.poll(),
+            EventType::Release if self.is_discarded(key) => {
+                self.forget(key);
+                self.poll()
+            }
+            _ => Some(event),
+        }
+    }
+
+    fn poll(&mut self) -> Option<Self::Output> {
+        self.queued.take().or_else(|| self.accepted())
+    }
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct KeyClasses: u8 {
+        const TEXT       = 0b001;
+        // Functional keys other than the modifiers
+        const FUNCTIONAL = 0b010;
+        const MODIFIERS  = 0b100;
+    }
+}
+
+impl KeyClasses {
+    pub fn of(key: KeyType) -> Self {
+        match key {
+            KeyType::Unicode(_) => KeyClasses::TEXT,
+            _ if modifier_bit(key).is_some() => KeyClasses::MODIFIERS,
+            KeyType::Functional(_) => KeyClasses::FUNCTIONAL,
+            KeyType::Unknown => KeyClasses::empty(),
+        }
+    }
+}
+
+// Drops a press that exactly repeats the one before it while the key is still
+// held, as some IME and compositor paths deliver them. Only keys in `classes`
+// are checked, at most `N` held keys are tracked.
+#[derive(Debug, Clone)]
+pub struct Deduplicate<const N: usize = 16> {
+    classes: KeyClasses,
+    held: [Option<(KeyType, KeyType, KeyboardModifiers)>; N],
+}
+
+impl<const N: usize> Deduplicate<N> {
+    pub fn new(classes: KeyClasses) -> Self {
+        Self {
+            classes,
+            held: [None; N],
+        }
+    }
+
+    fn slot(&mut self, key: KeyType) -> Option<&mut Option<(KeyType, KeyType, KeyboardModifiers)>> {
+        let index = self
+            .held
+            .iter()
+            .position(|held| held.is_some_and(|(_, without, _)| without == key))
+            .or_else(|| self.held.iter().position(Option::is_none))?;
+        self.held.get_mut(index)
+    }
+}
+
+impl<const N: usize> Default for Deduplicate<N> {
+    fn default() -> Self {
+        Self::new(KeyClasses::all())
+    }
+}
+
+impl<E: KeyEvent, const N: usize> Filter<E> for Deduplicate<N> {
+    type Output = E;
+
+    fn filter(&mut self, event: E) -> Option<Self::Output> {
+        let key = event.key_without_modifiers();
+        if !self.classes.intersects(KeyClasses::of(key)) {
+            return Some(event);
+        }
+
+        let press = (event.key_with_modifiers(), key, event.modifiers());
+        match event.event_type() {
+            EventType::Press => match self.slot(key) {
+                Some(slot) if *slot == Some(press) => return None,
+                Some(slot) => *slot = Some(press),
+                // Without room to track the key, nothing is dropped
+                None => {}
+            },
+            EventType::Release => {
+                for held in &mut self.held {
+                    if held.is_some_and(|(_, without, _)| without == key) {
+                        *held = None;
+                    }
+                }
+            }
+            EventType::Repeat => {}
+        }
+
+        Some(event)
+    }
+}
+
+// A user defined keyboard macro, e.g. `ctrl+alt+g` typing `git status` and
+// pressing Enter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Macro<'a> {
+    pub trigger: Chord,
+    pub steps: &'a [Step<'a>],
+}
+
+impl<'a> Macro<'a> {
+    pub const fn new(trigger: Chord, steps: &'a [Step<'a>]) -> Self {
+        Self { trigger, steps }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expanded<'a, E> {
+    Event(E),
+    Macro(SyntheticKeyEvent<'a>),
+}
+
+impl<E: KeyEvent> KeyInfo for Expanded<'_, E> {
+    fn key_with_modifiers(&self) -> KeyType {
+        match self {
+            Expanded::Event(event) => event.key_with_modifiers(),
+            Expanded::Macro(event) => event.key_with_modifiers(),
+        }
+    }
+
+    fn key_without_modifiers(&self) -> KeyType {
+        match self {
+            Expanded::Event(event) => event.key_without_modifiers(),
+            Expanded::Macro(event) => event.key_without_modifiers(),
+        }
+    }
+
+    fn key_base_layout(&self) -> KeyType {
+        match self {
+            Expanded::Event(event) => event.key_base_layout(),
+            Expanded::Macro(event) => event.key_base_layout(),
+        }
+    }
+
+    fn event_type(&self) -> EventType {
+        match self {
+            Expanded::Event(event) => event.event_type(),
+            Expanded::Macro(event) => event.event_type(),
+        }
+    }
+
+    fn timestamp(&self) -> Option<Duration> {
+        match self {
+            Expanded::Event(event) => event.timestamp(),
+            Expanded::Macro(event) => event.timestamp(),
+        }
+    }
+}
+
+impl<E: KeyEvent> ModifierInfo for Expanded<'_, E> {
+    fn modifiers(&self) -> KeyboardModifiers {
+        match self {
+            Expanded::Event(event) => event.modifiers(),
+            Expanded::Macro(event) => event.modifiers(),
+        }
+    }
+}
+
+impl<E: KeyEvent> TextInfo for Expanded<'_, E> {
+    fn associated_text(&self) -> Option<AssociatedText<'_>> {
+        match self {
+            Expanded::Event(event) => event.associated_text(),
+            Expanded::Macro(event) => event.associated_text(),
+        }
+    }
+}
+
+// Replaces the press of a trigger chord with the key events of its macro. The
+// first one comes out of `filter`, the rest out of `poll`, all at once: waits
+// and markers are left out and the events carry no timestamps. Repeats and the
+// release of the trigger are dropped.
+#[derive(Debug, Clone)]
+pub struct Macros<'a> {
+    macros: &'a [Macro<'a>],
+    mode: ReportingMode,
+    expanding: Option<ScriptEvents<'a>>,
+    trigger: Option<KeyType>,
+}
+
+impl<'a> Macros<'a> {
+    // The events are typed for `mode`, it decides whether releases are sent
+    pub fn new(mode: ReportingMode, macros: &'a [Macro<'a>]) -> Self {
+        Self {
+            macros,
+            mode,
+            expanding: None,
+            trigger: None,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: ReportingMode) {
+        self.mode = mode;
+    }
+
+    pub fn is_expanding(&self) -> bool {
+        self.expanding.is_some()
+    }
+
+    fn next_key(&mut self) -> Option<SyntheticKeyEvent<'a>> {
+        let expanding = self.expanding.as_mut()?;
+        let key = expanding.find_map(|timed| match timed.event {
+            ScriptEvent::Key(event) => Some(SyntheticKeyEvent {
+                timestamp: None,
+                ..event
+            }),
+            ScriptEvent::Marker(_) => None,
+        });
+        if key.is_none() {
+            self.expanding = None;
+        }
+        key
+    }
+}
+
+impl<'a, E: KeyEvent> Filter<E> for Macros<'a> {
+    type Output = Expanded<'a, E>;
+
+    fn filter(&mut self, event: E) -> Option<Self::Output> {
+        let key = event.key_without_modifiers();
+        if self.trigger == Some(key) {
+            if event.event_type() == EventType::Release {
+                self.trigger = None;
+            }
+            return None;
+        }
+
+        let triggered = self
+            .macros
+            .iter()
+            .find(|m| event.event_type() == EventType::Press && m.trigger.matches(&event));
+        let Some(triggered) = triggered else {
+            return Some(Expanded::Event(event));
+        };
+
+        self.trigger = Some(key);
+        self.expanding = Some(Script::new(self.mode, triggered.steps).events());
+        self.next_key().map(Expanded::Macro)
+    }
+
+    fn poll(&mut self) -> Option<Self::Output> {
+        self.next_key().map(Expanded::Macro)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Coalesced<E> {
+    pub event: E,
+    pub count: usize,
+}
+
+// The first event of the run stands for all of them
+impl<E: KeyEvent> KeyInfo for Coalesced<E> {
+    fn key_with_modifiers(&self) -> KeyType {
+        self.event.key_with_modifiers()
+    }
+
+    fn key_without_modifiers(&self) -> KeyType {
+        self.event.key_without_modifiers()
+    }
+
+    fn key_base_layout(&self) -> KeyType {
+        self.event.key_base_layout()
+    }
+
+    fn event_type(&self) -> EventType {
+        self.event.event_type()
+    }
+
+    fn timestamp(&self) -> Option<Duration> {
+        self.event.timestamp()
+    }
+}
+
+impl<E: KeyEvent> ModifierInfo for Coalesced<E> {
+    fn modifiers(&self) -> KeyboardModifiers {
+        self.event.modifiers()
+    }
+}
+
+impl<E: KeyEvent> TextInfo for Coalesced<E> {
+    fn associated_text(&self) -> Option<AssociatedText<'_>> {
+        self.event.associated_text()
+    }
+}
+
+pub type Flushed<E> = IterChain<option::IntoIter<Coalesced<E>>, option::IntoIter<Coalesced<E>>>;
+
+// Merges runs of identical repeat events while the consumer is busy; anything
+// else passes through immediately, after the run it interrupted. The runs go
+// to `InputEncoder::encode_coalesced`, or to an application reading them.
+#[derive(Debug, Clone)]
+pub struct RepeatCoalescer<E> {
+    pending: Option<Coalesced<E>>,
+}
+
+impl<E: KeyEvent> RepeatCoalescer<E> {
+    pub fn new() -> Self {
+        Self { pending: None }
+    }
+
+    pub fn push(&mut self, event: E) -> Flushed<E> {
+        if event.event_type() == EventType::Repeat {
+            match &mut self.pending {
+                Some(pending) if same_key(&pending.event, &event) => {
+                    pending.count += 1;
+                    None.into_iter().chain(None)
+                }
+                pending => pending
+                    .replace(Coalesced { event, count: 1 })
+                    .into_iter()
+                    .chain(None),
+            }
+        } else {
+            self.pending
+                .take()
+                .into_iter()
+                .chain(Some(Coalesced { event, count: 1 }))
+        }
+    }
+
+    // Call once the consumer can accept input again
+    pub fn flush(&mut self) -> Option<Coalesced<E>> {
+        self.pending.take()
+    }
+}
+
+impl<E: KeyEvent> Default for RepeatCoalescer<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn same_key(a: &impl KeyEvent, b: &impl KeyEvent) -> bool {
+    a.key_with_modifiers() == b.key_with_modifiers()
+        && a.key_without_modifiers() == b.key_without_modifiers()
+        && a.modifiers() == b.modifiers()
+}
+
+pub fn modifier_bit(key: KeyType) -> Option<KeyboardModifiers> {
+    match key {
+        KeyType::Functional(func) => func.modifier().map(|(bit, _)| bit),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use core::cell::Cell;
+
+    use super::*;
+    use crate::{key::FunctionalKey, synthesize::SyntheticKeyEvent};
+
+    extern crate std;
+    use std::vec::Vec;
+
+    fn key(key: KeyType, event_type: EventType) -> SyntheticKeyEvent<'static> {
+        SyntheticKeyEvent {
+            event_type,
+            ..SyntheticKeyEvent::from(key)
+        }
+    }
+
+    fn tap(filter: &mut StickyKeys, modifier: FunctionalKey) {
+        let modifier = KeyType::Functional(modifier);
+        filter.filter(key(modifier, EventType::Press)).unwrap();
+        filter.filter(key(modifier, EventType::Release)).unwrap();
+    }
+
+    #[test]
+    fn sticky_latch() {
+        let mut sticky = StickyKeys::new();
+        tap(&mut sticky, FunctionalKey::LeftShift);
+        assert_eq!(sticky.latched(), KeyboardModifiers::SHIFT);
+
+        let a = KeyType::Unicode('a');
+        let press = sticky.filter(key(a, EventType::Press)).unwrap();
+        assert_eq!(press.modifiers(), KeyboardModifiers::SHIFT);
+        assert_eq!(press.key_with_modifiers(), KeyType::Unicode('A'));
+
+        let release = sticky.filter(key(a, EventType::Release)).unwrap();
+        assert_eq!(release.modifiers(), KeyboardModifiers::empty());
+        assert_eq!(sticky.latched(), KeyboardModifiers::empty());
+    }
+
+    #[test]
+    fn sticky_lock() {
+        let mut sticky = StickyKeys::new();
+        tap(&mut sticky, FunctionalKey::LeftControl);
+        tap(&mut sticky, FunctionalKey::RightControl);
+        assert_eq!(sticky.locked(), KeyboardModifiers::CTRL);
+
+        for _ in 0..2 {
+            let event = sticky
+                .filter(key(KeyType::Unicode('c'), EventType::Press))
+                .unwrap();
+            assert_eq!(event.modifiers(), KeyboardModifiers::CTRL);
+        }
+
+        tap(&mut sticky, FunctionalKey::LeftControl);
+        assert_eq!(
+            sticky.locked() | sticky.latched(),
+            KeyboardModifiers::empty()
+        );
+    }
+
+    #[test]
+    fn sticky_chord_does_not_latch() {
+        let mut sticky = StickyKeys::new();
+        let shift = KeyType::Functional(FunctionalKey::LeftShift);
+
+        sticky.filter(key(shift, EventType::Press));
+        sticky.filter(SyntheticKeyEvent {
+            modifiers: KeyboardModifiers::SHIFT,
+            ..key(KeyType::Unicode('a'), EventType::Press)
+        });
+        sticky.filter(key(shift, EventType::Release));
+
+        assert_eq!(sticky.latched(), KeyboardModifiers::empty());
+    }
+
+    #[test]
+    fn duplicate_presses() {
+        let mut dedup = Deduplicate::<2>::new(KeyClasses::TEXT | KeyClasses::MODIFIERS);
+        let a = KeyType::Unicode('a');
+        let up = KeyType::Functional(FunctionalKey::Up);
+
+        assert!(dedup.filter(key(a, EventType::Press)).is_some());
+        assert_eq!(dedup.filter(key(a, EventType::Press)), None);
+        assert!(dedup.filter(key(a, EventType::Repeat)).is_some());
+        // Same key, different modifiers
+        let ctrl_a = SyntheticKeyEvent {
+            modifiers: KeyboardModifiers::CTRL,
+            ..key(a, EventType::Press)
+        };
+        assert!(dedup.filter(ctrl_a).is_some());
+        assert!(dedup.filter(key(a, EventType::Release)).is_some());
+        assert!(dedup.filter(key(a, EventType::Press)).is_some());
+
+        // Not a checked class
+        assert!(dedup.filter(key(up, EventType::Press)).is_some());
+        assert!(dedup.filter(key(up, EventType::Press)).is_some());
+
+        // Untracked once full
+        let b = KeyType::Unicode('b');
+        let c = KeyType::Unicode('c');
+        dedup.filter(key(b, EventType::Press));
+        assert!(dedup.filter(key(c, EventType::Press)).is_some());
+        assert!(dedup.filter(key(c, EventType::Press)).is_some());
+    }
+
+    #[test]
+    fn altgr_policies() {
+        use crate::generate_sequence;
+        use std::format;
+
+        let altgr = KeyboardModifiers::CTRL | KeyboardModifiers::ALT;
+        // AltGr+q on a German layout
+        let at = SyntheticKeyEvent {
+            key_with_modifiers: KeyType::Unicode('@'),
+            key_without_modifiers: KeyType::Unicode('q'),
+            modifiers: altgr,
+            associated_text: Some("@"),
+            ..Default::default()
+        };
+        let f1 = SyntheticKeyEvent {
+            modifiers: altgr,
+            ..key(KeyType::Functional(FunctionalKey::F1), EventType::Press)
+        };
+        let mode = ReportingMode::DISAMBIGUATE_ESC_CODES;
+        let encode = |event: &AltGrEvent<SyntheticKeyEvent<'_>>| {
+            format!("{}", generate_sequence(mode, event))
+        };
+
+        assert_eq!(encode(&AltGrPolicy::Characters.apply(at, true)), "@");
+        assert_eq!(
+            encode(&AltGrPolicy::Characters.apply(at, false)),
+            "\x1b[113;7u"
+        );
+        assert_eq!(encode(&AltGrPolicy::Keep.apply(at, true)), "\x1b[113;7u");
+        assert_eq!(encode(&AltGrPolicy::Characters.apply(f1, true)), "\x1b[;7P");
+        assert_eq!(encode(&AltGrPolicy::All.apply(f1, true)), "\x1b[P");
+
+        let shifted = AltGrPolicy::All.apply(
+            SyntheticKeyEvent {
+                modifiers: altgr | KeyboardModifiers::SHIFT,
+                ..f1
+            },
+            true,
+        );
+        assert_eq!(shifted.modifiers(), KeyboardModifiers::SHIFT);
+    }
+
+    #[test]
+    fn slow_keys() {
+        let now = Cell::new(Duration::ZERO);
+        let clock = || now.get();
+        let mut slow = SlowKeys::new(clock, Duration::from_millis(300));
+        let a = KeyType::Unicode('a');
+
+        // Released too early
+        assert_eq!(slow.filter(key(a, EventType::Press)), None);
+        now.set(Duration::from_millis(100));
+        assert_eq!(slow.filter(key(a, EventType::Release)), None);
+        assert!(!slow.is_pending());
+
+        // Held long enough
+        assert_eq!(slow.filter(key(a, EventType::Press)), None);
+        now.set(Duration::from_millis(250));
+        assert_eq!(slow.poll(), None);
+        now.set(Duration::from_millis(400));
+        assert_eq!(slow.poll(), Some(key(a, EventType::Press)));
+        assert_eq!(
+            slow.filter(key(a, EventType::Release)),
+            Some(key(a, EventType::Release))
+        );
+
+        // Released after the delay without polling in between
+        assert_eq!(slow.filter(key(a, EventType::Press)), None);
+        now.set(Duration::from_millis(800));
+        assert_eq!(
+            slow.filter(key(a, EventType::Release)),
+            Some(key(a, EventType::Press))
+        );
+        assert_eq!(slow.poll(), Some(key(a, EventType::Release)));
+    }
+
+    #[test]
+    fn slow_keys_overlapping() {
+        let now = Cell::new(Duration::ZERO);
+        let clock = || now.get();
+        let mut slow = SlowKeys::new(clock, Duration::from_millis(300));
+        let (a, b, c) = (
+            KeyType::Unicode('a'),
+            KeyType::Unicode('b'),
+            KeyType::Unicode('c'),
+        );
+
+        // `a` is replaced before it was held long enough
+        assert_eq!(slow.filter(key(a, EventType::Press)), None);
+        now.set(Duration::from_millis(100));
+        assert_eq!(slow.filter(key(b, EventType::Press)), None);
+        now.set(Duration::from_millis(200));
+        assert_eq!(slow.filter(key(a, EventType::Repeat)), None);
+        assert_eq!(slow.filter(key(a, EventType::Release)), None);
+
+        // `b` was held long enough when `c` came, without a poll in between
+        now.set(Duration::from_millis(500));
+        assert_eq!(
+            slow.filter(key(c, EventType::Press)),
+            Some(key(b, EventType::Press))
+        );
+        assert_eq!(
+            slow.filter(key(b, EventType::Release)),
+            Some(key(b, EventType::Release))
+        );
+        now.set(Duration::from_millis(900));
+        assert_eq!(
+            slow.filter(key(c, EventType::Release)),
+            Some(key(c, EventType::Press))
+        );
+        assert_eq!(slow.poll(), Some(key(c, EventType::Release)));
+
+        // A replaced key pressed again counts as usual
+        assert_eq!(slow.filter(key(a, EventType::Press)), None);
+        assert_eq!(slow.filter(key(b, EventType::Press)), None);
+        assert_eq!(slow.filter(key(a, EventType::Press)), None);
+        now.set(Duration::from_millis(1300));
+        assert_eq!(slow.poll(), Some(key(a, EventType::Press)));
+        assert_eq!(slow.filter(key(b, EventType::Release)), None);
+        assert_eq!(
+            slow.filter(key(a, EventType::Release)),
+            Some(key(a, EventType::Release))
+        );
+    }
+
+    #[test]
+    fn coalesce_repeats() {
+        let mut coalescer = RepeatCoalescer::new();
+        let j = KeyType::Unicode('j');
+        let k = KeyType::Unicode('k');
+
+        let counts = |flushed: Flushed<SyntheticKeyEvent<'static>>| {
+            flushed
+                .map(|c| (c.event.key_with_modifiers, c.event.event_type, c.count))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            counts(coalescer.push(key(j, EventType::Press))),
+            [(j, EventType::Press, 1)]
+        );
+        for _ in 0..100 {
+            assert_eq!(counts(coalescer.push(key(j, EventType::Repeat))), []);
+        }
+        assert_eq!(
+            counts(coalescer.push(key(k, EventType::Repeat))),
+            [(j, EventType::Repeat, 100)]
+        );
+        assert_eq!(
+            counts(coalescer.push(key(j, EventType::Release))),
+            [(k, EventType::Repeat, 1), (j, EventType::Release, 1)]
+        );
+        assert_eq!(coalescer.flush(), None);
+
+        for _ in 0..2 {
+            assert_eq!(counts(coalescer.push(key(j, EventType::Repeat))), []);
+        }
+        assert_eq!(coalescer.flush().map(|c| c.count), Some(2));
+    }
+
+    #[test]
+    fn keyboard_macros() {
+        use crate::generate_sequence;
+        use std::{format, string::String};
+
+        let steps = [
+            Step::Type("ls"),
+            Step::Marker("typed"),
+            Step::Press(Chord::from(KeyType::Functional(FunctionalKey::Enter))),
+        ];
+        let macros = [Macro::new("ctrl+alt+l".parse().unwrap(), &steps)];
+        let mut filter = Macros::new(ReportingMode::empty(), &macros);
+        let mut out = String::new();
+        let mut encode = |event: Expanded<'_, SyntheticKeyEvent<'_>>| {
+            out += &format!("{}", generate_sequence(ReportingMode::empty(), &event));
+        };
+
+        let trigger = SyntheticKeyEvent::with_modifiers(
+            'l',
+            KeyboardModifiers::CTRL | KeyboardModifiers::ALT,
+        );
+        encode(filter.filter(trigger).unwrap());
+        assert!(filter.is_expanding());
+        while let Some(event) = Filter::<SyntheticKeyEvent>::poll(&mut filter) {
+            encode(event);
+        }
+        assert!(!filter.is_expanding());
+
+        let repeat = SyntheticKeyEvent {
+            event_type: EventType::Repeat,
+            ..trigger
+        };
+        assert_eq!(filter.filter(repeat), None);
+        assert_eq!(filter.filter(trigger.released()), None);
+        encode(
+            filter
+                .filter(SyntheticKeyEvent::from_char('l', "l"))
+                .unwrap(),
+        );
+
+        assert_eq!(out, "ls\rl");
+    }
+
+    #[test]
+    fn chained_filters() {
+        let now = Cell::new(Duration::ZERO);
+        let clock = || now.get();
+        let mut pipeline =
+            SlowKeys::new(clock, Duration::from_millis(100)).chain(StickyKeys::new());
+        let shift = KeyType::Functional(FunctionalKey::LeftShift);
+
+        assert!(pipeline.filter(key(shift, EventType::Press)).is_none());
+        now.set(Duration::from_millis(200));
+        // The release hands out the accepted press first
+        assert!(pipeline.filter(key(shift, EventType::Release)).is_some());
+        assert!(pipeline.poll().is_some());
+
+        let x = SyntheticKeyEvent {
+            timestamp: Some(Duration::from_millis(250)),
+            ..key(KeyType::Unicode('x'), EventType::Press)
+        };
+        assert!(pipeline.filter(x).is_none());
+        now.set(Duration::from_millis(400));
+        let event = pipeline.poll().unwrap();
+        assert_eq!(event.modifiers(), KeyboardModifiers::SHIFT);
+        assert_eq!(event.timestamp(), Some(Duration::from_millis(250)));
+    }
+}
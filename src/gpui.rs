@@ -0,0 +1,206 @@
+use crate::{
+    key::{FunctionalKey, KeyType},
+    sequence::{EventType, KeyboardModifiers},
+    synthesize::SyntheticKeyEvent,
+};
+
+// The names GPUI gives `Keystroke::key` for keys that are not characters
+pub fn named_key(name: &str) -> Option<FunctionalKey> {
+    let func = match name {
+        "backspace" => FunctionalKey::Backspace,
+        "tab" => FunctionalKey::Tab,
+        "enter" => FunctionalKey::Enter,
+        "escape" => FunctionalKey::Escape,
+        "delete" => FunctionalKey::Delete,
+        "insert" => FunctionalKey::Insert,
+        "home" => FunctionalKey::Home,
+        "end" => FunctionalKey::End,
+        "pageup" => FunctionalKey::PageUp,
+        "pagedown" => FunctionalKey::PageDown,
+        "up" => FunctionalKey::Up,
+        "down" => FunctionalKey::Down,
+        "left" => FunctionalKey::Left,
+        "right" => FunctionalKey::Right,
+        "menu" => FunctionalKey::Menu,
+        "capslock" => FunctionalKey::CapsLock,
+        // Modifier changes, GPUI does not tell left and right apart
+        "shift" => FunctionalKey::LeftShift,
+        "control" => FunctionalKey::LeftControl,
+        "alt" => FunctionalKey::LeftAlt,
+        "platform" => FunctionalKey::LeftSuper,
+        // F1 to F35
+        _ => {
+            let number: usize = name.strip_prefix('f')?.parse().ok()?;
+            let index = FunctionalKey::F1 as usize + number.checked_sub(1)?;
+            return FunctionalKey::ALL
+                .get(index)
+                .copied()
+                .filter(|_| number <= 35);
+        }
+    };
+
+    Some(func)
+}
+
+// `Keystroke::key`, characters are unshifted
+pub fn key(name: &str) -> KeyType {
+    if let Some(func) = named_key(name) {
+        return KeyType::Functional(func);
+    }
+
+    let mut chars = name.chars();
+    match (name, chars.next(), chars.next()) {
+        ("space", ..) => KeyType::Unicode(' '),
+        (_, Some(ch), None) => KeyType::Unicode(ch),
+        _ => KeyType::Unknown,
+    }
+}
+
+// GPUI's `Modifiers`, `platform` is the Command or Windows key. The Fn key
+// has no kitty modifier and is left out.
+pub fn modifiers(control: bool, alt: bool, shift: bool, platform: bool) -> KeyboardModifiers {
+    let mut modifiers = KeyboardModifiers::empty();
+    modifiers.set(KeyboardModifiers::CTRL, control);
+    modifiers.set(KeyboardModifiers::ALT, alt);
+    modifiers.set(KeyboardModifiers::SHIFT, shift);
+    modifiers.set(KeyboardModifiers::SUPER, platform);
+    modifiers
+}
+
+// A `KeyDownEvent` or `KeyUpEvent`. `key_char` is what GPUI would type, the
+// shifted form of the key comes from it as GPUI has no layout information.
+pub fn key_event<'a>(
+    name: &str,
+    key_char: Option<&'a str>,
+    modifiers: KeyboardModifiers,
+    event_type: EventType,
+) -> SyntheticKeyEvent<'a> {
+    let key = key(name);
+    if let KeyType::Functional(_) = key {
+        return SyntheticKeyEvent {
+            modifiers,
+            event_type,
+            ..SyntheticKeyEvent::from(key)
+        };
+    }
+
+    let typed = key_char.and_then(|text| text.chars().next());
+    SyntheticKeyEvent {
+        key_with_modifiers: typed.map_or(key, KeyType::Unicode),
+        key_without_modifiers: key,
+        key_base_layout: KeyType::Unknown,
+        modifiers,
+        event_type,
+        associated_text: key_char.filter(|_| event_type != EventType::Release),
+        timestamp: None,
+    }
+}
+
+// The keystrokes of Zed's keymaps, e.g. `ctrl-shift-a` or `cmd--`
+pub fn parse_keystroke(source: &str) -> Option<(KeyType, KeyboardModifiers)> {
+    let (prefix, name) = match source.strip_suffix("--") {
+        Some(prefix) => (prefix, "-"),
+        None if source == "-" => ("", "-"),
+        None => source.rsplit_once('-').unwrap_or(("", source)),
+    };
+
+    let mut modifiers = KeyboardModifiers::empty();
+    for modifier in prefix.split('-').filter(|name| !name.is_empty()) {
+        modifiers |= match modifier {
+            "ctrl" => KeyboardModifiers::CTRL,
+            "alt" => KeyboardModifiers::ALT,
+            "shift" => KeyboardModifiers::SHIFT,
+            "cmd" | "super" | "win" => KeyboardModifiers::SUPER,
+            "fn" => KeyboardModifiers::empty(),
+            _ => return None,
+        };
+    }
+
+    match key(name) {
+        KeyType::Unknown => None,
+        key => Some((key, modifiers)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_sequence, ReportingMode};
+
+    extern crate std;
+    use std::format;
+
+    fn encode(mode: ReportingMode, event: &SyntheticKeyEvent<'_>) -> std::string::String {
+        format!("{}", generate_sequence(mode, event))
+    }
+
+    #[test]
+    fn named_keys() {
+        assert_eq!(named_key("pageup"), Some(FunctionalKey::PageUp));
+        assert_eq!(named_key("f3"), Some(FunctionalKey::F3));
+        assert_eq!(named_key("f0"), None);
+        assert_eq!(named_key("f36"), None);
+        assert_eq!(named_key("fn"), None);
+        assert_eq!(key("space"), KeyType::Unicode(' '));
+        assert_eq!(key("spaces"), KeyType::Unknown);
+
+        let none = KeyboardModifiers::empty();
+        let event = key_event("pagedown", None, none, EventType::Press);
+        assert_eq!(encode(ReportingMode::empty(), &event), "\x1b[6~");
+        let event = key_event("escape", None, none, EventType::Press);
+        assert_eq!(
+            encode(ReportingMode::DISAMBIGUATE_ESC_CODES, &event),
+            "\x1b[27u"
+        );
+    }
+
+    #[test]
+    fn characters() {
+        let shift = modifiers(false, false, true, false);
+        let event = key_event("a", Some("A"), shift, EventType::Press);
+        assert_eq!(event.key_with_modifiers, KeyType::Unicode('A'));
+        assert_eq!(event.key_without_modifiers, KeyType::Unicode('a'));
+        assert_eq!(event.associated_text, Some("A"));
+        assert_eq!(encode(ReportingMode::empty(), &event), "A");
+
+        let ctrl = modifiers(true, false, false, false);
+        let event = key_event("c", None, ctrl, EventType::Press);
+        assert_eq!(
+            encode(ReportingMode::DISAMBIGUATE_ESC_CODES, &event),
+            "\x1b[99;5u"
+        );
+        let event = key_event(
+            "c",
+            Some("c"),
+            KeyboardModifiers::empty(),
+            EventType::Release,
+        );
+        assert_eq!(event.associated_text, None);
+    }
+
+    #[test]
+    fn keystrokes() {
+        let ctrl_shift = KeyboardModifiers::CTRL | KeyboardModifiers::SHIFT;
+        assert_eq!(
+            parse_keystroke("ctrl-shift-a"),
+            Some((KeyType::Unicode('a'), ctrl_shift))
+        );
+        assert_eq!(
+            parse_keystroke("cmd--"),
+            Some((KeyType::Unicode('-'), KeyboardModifiers::SUPER))
+        );
+        assert_eq!(
+            parse_keystroke("-"),
+            Some((KeyType::Unicode('-'), KeyboardModifiers::empty()))
+        );
+        assert_eq!(
+            parse_keystroke("fn-f12"),
+            Some((
+                KeyType::Functional(FunctionalKey::F12),
+                KeyboardModifiers::empty()
+            ))
+        );
+        assert_eq!(parse_keystroke("hyper-a"), None);
+        assert_eq!(parse_keystroke("ctrl-nothing"), None);
+    }
+}
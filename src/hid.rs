@@ -0,0 +1,382 @@
+use crate::{
+    key::{FunctionalKey, KeyType},
+    layouts::{Layout, PhysicalKey, US},
+    sequence::{EventType, KeyboardModifiers},
+    synthesize::SyntheticKeyEvent,
+};
+
+// Usage reported in every key slot when more keys are held than fit
+const ERROR_ROLL_OVER: u8 = 0x01;
+
+// Bits of the modifier byte, in order
+const MODIFIER_KEYS: [FunctionalKey; 8] = [
+    FunctionalKey::LeftControl,
+    FunctionalKey::LeftShift,
+    FunctionalKey::LeftAlt,
+    FunctionalKey::LeftSuper,
+    FunctionalKey::RightControl,
+    FunctionalKey::RightShift,
+    FunctionalKey::RightAlt,
+    FunctionalKey::RightSuper,
+];
+
+// Turns successive 8 byte boot protocol reports (modifiers, reserved, six key
+// usages) into press and release events. Lock keys are toggled here, the host
+// is still responsible for the LEDs.
+#[derive(Debug, Clone)]
+pub struct BootKeyboard {
+    layout: Layout,
+    report: [u8; 8],
+    locks: KeyboardModifiers,
+}
+
+impl BootKeyboard {
+    pub fn new() -> Self {
+        Self::with_layout(US)
+    }
+
+    pub fn with_layout(layout: Layout) -> Self {
+        Self {
+            layout,
+            report: [0; 8],
+            locks: KeyboardModifiers::empty(),
+        }
+    }
+
+    pub fn modifiers(&self) -> KeyboardModifiers {
+        modifier_byte(self.report[0]) | self.locks
+    }
+
+    pub fn update(&mut self, report: &[u8; 8], mut emit: impl FnMut(SyntheticKeyEvent<'static>)) {
+        // Too many keys, which ones are held is unknown until the next report
+        if report[2..].iter().all(|usage| *usage == ERROR_ROLL_OVER) {
+            return;
+        }
+
+        let previous = self.report;
+        for (bit, key) in MODIFIER_KEYS.iter().enumerate() {
+            let mask = 1 << bit;
+            if (previous[0] ^ report[0]) & mask != 0 {
+                self.report[0] ^= mask;
+                let pressed = report[0] & mask != 0;
+                emit(self.event(KeyType::Functional(*key), None, pressed));
+            }
+        }
+
+        for usage in keys(&previous) {
+            if !keys(report).any(|held| held == usage) {
+                if let Some(event) = self.key(usage, false) {
+                    emit(event);
+                }
+            }
+        }
+        for usage in keys(report) {
+            if !keys(&previous).any(|held| held == usage) {
+                if let Some(event) = self.key(usage, true) {
+                    emit(event);
+                }
+            }
+        }
+
+        self.report = *report;
+    }
+
+    fn key(&mut self, usage: u8, pressed: bool) -> Option<SyntheticKeyEvent<'static>> {
+        let (key, physical) = match physical_key(usage) {
+            Some(physical) => (
+                KeyType::Unicode(self.layout.base(physical)?),
+                Some(physical),
+            ),
+            None => (KeyType::Functional(functional_key(usage)?), None),
+        };
+
+        if pressed {
+            self.locks.toggle(match key {
+                KeyType::Functional(FunctionalKey::CapsLock) => KeyboardModifiers::CAPS_LOCK,
+                KeyType::Functional(FunctionalKey::NumLock) => KeyboardModifiers::NUM_LOCK,
+                _ => KeyboardModifiers::empty(),
+            });
+        }
+
+        Some(self.event(key, physical, pressed))
+    }
+
+    fn event(
+        &self,
+        key: KeyType,
+        physical: Option<PhysicalKey>,
+        pressed: bool,
+    ) -> SyntheticKeyEvent<'static> {
+        let modifiers = self.modifiers();
+        SyntheticKeyEvent {
+            key_with_modifiers: physical.map_or(key, |physical| {
+                self.layout.key_with_modifiers(physical, modifiers)
+            }),
+            key_without_modifiers: key,
+            key_base_layout: physical.map_or(key, PhysicalKey::base_layout_key),
+            modifiers,
+            event_type: if pressed {
+                EventType::Press
+            } else {
+                EventType::Release
+            },
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for BootKeyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn keys(report: &[u8; 8]) -> impl Iterator<Item = u8> + '_ {
+    report[2..]
+        .iter()
+        .copied()
+        .filter(|usage| *usage > ERROR_ROLL_OVER)
+}
+
+fn modifier_byte(byte: u8) -> KeyboardModifiers {
+    let mut modifiers = KeyboardModifiers::empty();
+    for (bit, modifier) in [
+        KeyboardModifiers::CTRL,
+        KeyboardModifiers::SHIFT,
+        KeyboardModifiers::ALT,
+        KeyboardModifiers::SUPER,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        // Left and right modifiers are four bits apart
+        if byte & (0x11 << bit) != 0 {
+            modifiers |= modifier;
+        }
+    }
+    modifiers
+}
+
+// Keyboard/Keypad page usages of the writing system keys
+pub fn physical_key(usage: u8) -> Option<PhysicalKey> {
+    const LETTERS: [PhysicalKey; 26] = [
+        PhysicalKey::KeyA,
+        PhysicalKey::KeyB,
+        PhysicalKey::KeyC,
+        PhysicalKey::KeyD,
+        PhysicalKey::KeyE,
+        PhysicalKey::KeyF,
+        PhysicalKey::KeyG,
+        PhysicalKey::KeyH,
+        PhysicalKey::KeyI,
+        PhysicalKey::KeyJ,
+        PhysicalKey::KeyK,
+        PhysicalKey::KeyL,
+        PhysicalKey::KeyM,
+        PhysicalKey::KeyN,
+        PhysicalKey::KeyO,
+        PhysicalKey::KeyP,
+        PhysicalKey::KeyQ,
+        PhysicalKey::KeyR,
+        PhysicalKey::KeyS,
+        PhysicalKey::KeyT,
+        PhysicalKey::KeyU,
+        PhysicalKey::KeyV,
+        PhysicalKey::KeyW,
+        PhysicalKey::KeyX,
+        PhysicalKey::KeyY,
+        PhysicalKey::KeyZ,
+    ];
+    const DIGITS: [PhysicalKey; 10] = [
+        PhysicalKey::Digit1,
+        PhysicalKey::Digit2,
+        PhysicalKey::Digit3,
+        PhysicalKey::Digit4,
+        PhysicalKey::Digit5,
+        PhysicalKey::Digit6,
+        PhysicalKey::Digit7,
+        PhysicalKey::Digit8,
+        PhysicalKey::Digit9,
+        PhysicalKey::Digit0,
+    ];
+
+    Some(match usage {
+        0x04..=0x1d => return LETTERS.get(usize::from(usage - 0x04)).copied(),
+        0x1e..=0x27 => return DIGITS.get(usize::from(usage - 0x1e)).copied(),
+        0x2c => PhysicalKey::Space,
+        0x2d => PhysicalKey::Minus,
+        0x2e => PhysicalKey::Equal,
+        0x2f => PhysicalKey::BracketLeft,
+        0x30 => PhysicalKey::BracketRight,
+        // Non-US # sits where Backslash is on ISO keyboards
+        0x31 | 0x32 => PhysicalKey::Backslash,
+        0x33 => PhysicalKey::Semicolon,
+        0x34 => PhysicalKey::Quote,
+        0x35 => PhysicalKey::Backquote,
+        0x36 => PhysicalKey::Comma,
+        0x37 => PhysicalKey::Period,
+        0x38 => PhysicalKey::Slash,
+        0x64 => PhysicalKey::IntlBackslash,
+        0x87 => PhysicalKey::IntlRo,
+        0x89 => PhysicalKey::IntlYen,
+        _ => return None,
+    })
+}
+
+pub fn functional_key(usage: u8) -> Option<FunctionalKey> {
+    const F1_TO_F12: [FunctionalKey; 12] = [
+        FunctionalKey::F1,
+        FunctionalKey::F2,
+        FunctionalKey::F3,
+        FunctionalKey::F4,
+        FunctionalKey::F5,
+        FunctionalKey::F6,
+        FunctionalKey::F7,
+        FunctionalKey::F8,
+        FunctionalKey::F9,
+        FunctionalKey::F10,
+        FunctionalKey::F11,
+        FunctionalKey::F12,
+    ];
+    const F13_TO_F24: [FunctionalKey; 12] = [
+        FunctionalKey::F13,
+        FunctionalKey::F14,
+        FunctionalKey::F15,
+        FunctionalKey::F16,
+        FunctionalKey::F17,
+        FunctionalKey::F18,
+        FunctionalKey::F19,
+        FunctionalKey::F20,
+        FunctionalKey::F21,
+        FunctionalKey::F22,
+        FunctionalKey::F23,
+        FunctionalKey::F24,
+    ];
+    const NUMPAD_DIGITS: [FunctionalKey; 10] = [
+        FunctionalKey::NumPad1,
+        FunctionalKey::NumPad2,
+        FunctionalKey::NumPad3,
+        FunctionalKey::NumPad4,
+        FunctionalKey::NumPad5,
+        FunctionalKey::NumPad6,
+        FunctionalKey::NumPad7,
+        FunctionalKey::NumPad8,
+        FunctionalKey::NumPad9,
+        FunctionalKey::NumPad0,
+    ];
+
+    Some(match usage {
+        0x28 => FunctionalKey::Enter,
+        0x29 => FunctionalKey::Escape,
+        0x2a => FunctionalKey::Backspace,
+        0x2b => FunctionalKey::Tab,
+        0x39 => FunctionalKey::CapsLock,
+        0x3a..=0x45 => return F1_TO_F12.get(usize::from(usage - 0x3a)).copied(),
+        0x46 => FunctionalKey::PrintScreen,
+        0x47 => FunctionalKey::ScrollLock,
+        0x48 => FunctionalKey::Pause,
+        0x49 => FunctionalKey::Insert,
+        0x4a => FunctionalKey::Home,
+        0x4b => FunctionalKey::PageUp,
+        0x4c => FunctionalKey::Delete,
+        0x4d => FunctionalKey::End,
+        0x4e => FunctionalKey::PageDown,
+        0x4f => FunctionalKey::Right,
+        0x50 => FunctionalKey::Left,
+        0x51 => FunctionalKey::Down,
+        0x52 => FunctionalKey::Up,
+        0x53 => FunctionalKey::NumLock,
+        0x54 => FunctionalKey::NumPadDivide,
+        0x55 => FunctionalKey::NumPadMultply,
+        0x56 => FunctionalKey::NumPadSubtract,
+        0x57 => FunctionalKey::NumPadAdd,
+        0x58 => FunctionalKey::NumPadEnter,
+        0x59..=0x62 => return NUMPAD_DIGITS.get(usize::from(usage - 0x59)).copied(),
+        0x63 => FunctionalKey::NumPadDecimal,
+        0x65 => FunctionalKey::Menu,
+        0x67 => FunctionalKey::NumPadEqual,
+        0x68..=0x73 => return F13_TO_F24.get(usize::from(usage - 0x68)).copied(),
+        0x7f => FunctionalKey::MuteVolume,
+        0x80 => FunctionalKey::RaiseVolume,
+        0x81 => FunctionalKey::LowerVolume,
+        0x85 => FunctionalKey::NumPadSeparator,
+        0x88 => FunctionalKey::KatakanaHiragana,
+        0x8a => FunctionalKey::Henkan,
+        0x8b => FunctionalKey::Muhenkan,
+        0x90 => FunctionalKey::Hangul,
+        0x91 => FunctionalKey::Hanja,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::vec::Vec;
+
+    fn update(keyboard: &mut BootKeyboard, report: [u8; 8]) -> Vec<(KeyType, EventType)> {
+        let mut events = Vec::new();
+        keyboard.update(&report, |event| {
+            events.push((event.key_with_modifiers, event.event_type))
+        });
+        events
+    }
+
+    #[test]
+    fn report_diffs() {
+        let mut keyboard = BootKeyboard::new();
+        let shift = KeyType::Functional(FunctionalKey::LeftShift);
+
+        assert_eq!(
+            update(&mut keyboard, [0x02, 0, 0x04, 0, 0, 0, 0, 0]),
+            [
+                (shift, EventType::Press),
+                (KeyType::Unicode('A'), EventType::Press)
+            ]
+        );
+        assert_eq!(keyboard.modifiers(), KeyboardModifiers::SHIFT);
+
+        // The slot a key is reported in does not matter
+        assert_eq!(
+            update(&mut keyboard, [0x02, 0, 0x05, 0x04, 0, 0, 0, 0]),
+            [(KeyType::Unicode('B'), EventType::Press)]
+        );
+        assert_eq!(
+            update(&mut keyboard, [0, 0, 0x05, 0, 0, 0, 0, 0]),
+            [
+                (shift, EventType::Release),
+                (KeyType::Unicode('a'), EventType::Release)
+            ]
+        );
+    }
+
+    #[test]
+    fn rollover_keeps_state() {
+        let mut keyboard = BootKeyboard::new();
+        update(&mut keyboard, [0, 0, 0x52, 0, 0, 0, 0, 0]);
+
+        assert!(update(&mut keyboard, [0, 0, 1, 1, 1, 1, 1, 1]).is_empty());
+        assert_eq!(
+            update(&mut keyboard, [0; 8]),
+            [(KeyType::Functional(FunctionalKey::Up), EventType::Release)]
+        );
+    }
+
+    #[test]
+    fn right_modifiers_and_locks() {
+        let mut keyboard = BootKeyboard::new();
+        update(&mut keyboard, [0x10, 0, 0x39, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            keyboard.modifiers(),
+            KeyboardModifiers::CTRL | KeyboardModifiers::CAPS_LOCK
+        );
+
+        update(&mut keyboard, [0; 8]);
+        assert_eq!(
+            update(&mut keyboard, [0, 0, 0x14, 0, 0, 0, 0, 0]),
+            [(KeyType::Unicode('Q'), EventType::Press)]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::sequence::{KeyCode, Sequence, SequenceTerminator};
+use crate::sequence::{KeyCode, KeyboardModifiers, Sequence, SequenceTerminator};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum KeyType {
@@ -18,8 +18,31 @@ impl<'a> KeyType {
                 },
                 ..Default::default()
             }),
-            KeyType::Functional(func) => Some(func.to_sequence()),
-            KeyType::Unknown => None,
+            KeyType::Functional(func) if func.is_enabled() => Some(func.to_sequence()),
+            KeyType::Functional(_) | KeyType::Unknown => None,
+        }
+    }
+
+    // Keys left out by the code size features become `Unknown`
+    pub fn enabled(self) -> KeyType {
+        match self {
+            KeyType::Functional(func) if !func.is_enabled() => KeyType::Unknown,
+            key => key,
+        }
+    }
+
+    // The spec wants the unshifted key in its lowercase form. Characters that
+    // lowercase to more than one are left alone.
+    pub fn lowercase(self) -> KeyType {
+        match self {
+            KeyType::Unicode(ch) => {
+                let mut lower = ch.to_lowercase();
+                match (lower.next(), lower.next()) {
+                    (Some(lower), None) => KeyType::Unicode(lower),
+                    _ => self,
+                }
+            }
+            key => key,
         }
     }
 
@@ -29,6 +52,16 @@ impl<'a> KeyType {
             _ => None,
         }
     }
+
+    pub fn from_kitty_code(code: u32, terminator: SequenceTerminator) -> KeyType {
+        match FunctionalKey::from_kitty_code(code, terminator) {
+            Some(func) => KeyType::Functional(func),
+            None if terminator == SequenceTerminator::Kitty => {
+                char::from_u32(code).map_or(KeyType::Unknown, KeyType::Unicode)
+            }
+            None => KeyType::Unknown,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -152,154 +185,101 @@ pub enum FunctionalKey {
 
     IsoLevel3Shift,
     IsoLevel5Shift,
+
+    Muhenkan,
+    Henkan,
+    KatakanaHiragana,
+    ZenkakuHankaku,
+    Eisu,
+    Hangul,
+    Hanja,
+    IntlBackslash,
+    IntlRo,
+    IntlYen,
 }
 
 impl<'a> FunctionalKey {
     pub fn to_sequence(self) -> Sequence<'a> {
-        macro_rules! seq {
-            ($num:expr) => {
-                Sequence {
-                    key_code: KeyCode {
-                        key_code: $num,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                }
-            };
-
-            ($num:expr, $ch:literal) => {
-                Sequence {
-                    key_code: KeyCode {
-                        key_code: $num,
-                        ..Default::default()
-                    },
-                    terminator: SequenceTerminator::Other($ch),
-                    ..Default::default()
-                }
-            };
+        // The table has an entry for every key, the fallback is never used
+        let (key_code, terminator) = SEQUENCES
+            .get(self as usize)
+            .copied()
+            .unwrap_or((0, SequenceTerminator::Kitty));
+        Sequence {
+            key_code: KeyCode {
+                key_code,
+                ..Default::default()
+            },
+            terminator,
+            ..Default::default()
         }
+    }
 
-        match self {
-            FunctionalKey::Escape => seq!(27),
-            FunctionalKey::Enter => seq!(13),
-            FunctionalKey::Tab => seq!(9),
-            FunctionalKey::Backspace => seq!(127),
-            FunctionalKey::Insert => seq!(2, '~'),
-            FunctionalKey::Delete => seq!(3, '~'),
-            FunctionalKey::Left => seq!(1, 'D'),
-            FunctionalKey::Right => seq!(1, 'C'),
-            FunctionalKey::Up => seq!(1, 'A'),
-            FunctionalKey::Down => seq!(1, 'B'),
-            FunctionalKey::PageUp => seq!(5, '~'),
-            FunctionalKey::PageDown => seq!(6, '~'),
-            FunctionalKey::Home => seq!(1, 'H'),
-            FunctionalKey::End => seq!(1, 'F'),
-            FunctionalKey::CapsLock => seq!(57358),
-            FunctionalKey::ScrollLock => seq!(57359),
-            FunctionalKey::NumLock => seq!(57360),
-            FunctionalKey::PrintScreen => seq!(57361),
-            FunctionalKey::Pause => seq!(57362),
-            FunctionalKey::Menu => seq!(57363),
-
-            FunctionalKey::F1 => seq!(1, 'P'),
-            FunctionalKey::F2 => seq!(1, 'Q'),
-            FunctionalKey::F3 => seq!(13, '~'),
-            FunctionalKey::F4 => seq!(1, 'S'),
-            FunctionalKey::F5 => seq!(15, '~'),
-            FunctionalKey::F6 => seq!(17, '~'),
-            FunctionalKey::F7 => seq!(18, '~'),
-            FunctionalKey::F8 => seq!(19, '~'),
-            FunctionalKey::F9 => seq!(20, '~'),
-            FunctionalKey::F10 => seq!(21, '~'),
-            FunctionalKey::F11 => seq!(23, '~'),
-            FunctionalKey::F12 => seq!(24, '~'),
-            FunctionalKey::F13 => seq!(57376),
-            FunctionalKey::F14 => seq!(57377),
-            FunctionalKey::F15 => seq!(57378),
-            FunctionalKey::F16 => seq!(57379),
-            FunctionalKey::F17 => seq!(57380),
-            FunctionalKey::F18 => seq!(57381),
-            FunctionalKey::F19 => seq!(57382),
-            FunctionalKey::F20 => seq!(57383),
-            FunctionalKey::F21 => seq!(57384),
-            FunctionalKey::F22 => seq!(57385),
-            FunctionalKey::F23 => seq!(57386),
-            FunctionalKey::F24 => seq!(57387),
-            FunctionalKey::F25 => seq!(57388),
-            FunctionalKey::F26 => seq!(57389),
-            FunctionalKey::F27 => seq!(57390),
-            FunctionalKey::F28 => seq!(57391),
-            FunctionalKey::F29 => seq!(57392),
-            FunctionalKey::F30 => seq!(57393),
-            FunctionalKey::F31 => seq!(57394),
-            FunctionalKey::F32 => seq!(57395),
-            FunctionalKey::F33 => seq!(57396),
-            FunctionalKey::F34 => seq!(57397),
-            FunctionalKey::F35 => seq!(57398),
-
-            FunctionalKey::NumPad0 => seq!(57399),
-            FunctionalKey::NumPad1 => seq!(57400),
-            FunctionalKey::NumPad2 => seq!(57401),
-            FunctionalKey::NumPad3 => seq!(57402),
-            FunctionalKey::NumPad4 => seq!(57403),
-            FunctionalKey::NumPad5 => seq!(57404),
-            FunctionalKey::NumPad6 => seq!(57405),
-            FunctionalKey::NumPad7 => seq!(57406),
-            FunctionalKey::NumPad8 => seq!(57407),
-            FunctionalKey::NumPad9 => seq!(57408),
-
-            FunctionalKey::NumPadDecimal => seq!(57409),
-            FunctionalKey::NumPadDivide => seq!(57410),
-            FunctionalKey::NumPadMultply => seq!(57411),
-            FunctionalKey::NumPadSubtract => seq!(57412),
-            FunctionalKey::NumPadAdd => seq!(57413),
-            FunctionalKey::NumPadEnter => seq!(57414),
-            FunctionalKey::NumPadEqual => seq!(57415),
-            FunctionalKey::NumPadSeparator => seq!(57416),
-            FunctionalKey::NumPadLeft => seq!(57417),
-            FunctionalKey::NumPadRight => seq!(57418),
-            FunctionalKey::NumPadUp => seq!(57419),
-            FunctionalKey::NumPadDown => seq!(57420),
-            FunctionalKey::NumPadPageUp => seq!(57421),
-            FunctionalKey::NumPadPageDown => seq!(57422),
-            FunctionalKey::NumPadHome => seq!(57423),
-            FunctionalKey::NumPadEnd => seq!(57424),
-            FunctionalKey::NumPadInsert => seq!(57425),
-            FunctionalKey::NumPadDelete => seq!(57426),
-            FunctionalKey::NumPadBegin => seq!(1, 'E'),
-
-            FunctionalKey::MediaPlay => seq!(57428),
-            FunctionalKey::MediaPause => seq!(57429),
-            FunctionalKey::MediaPlayPause => seq!(57430),
-            FunctionalKey::MediaReverse => seq!(57431),
-            FunctionalKey::MediaStop => seq!(57432),
-            FunctionalKey::MediaFastForward => seq!(57433),
-            FunctionalKey::MediaRewind => seq!(57434),
-            FunctionalKey::MediaTrackNext => seq!(57435),
-            FunctionalKey::MediaTrackPrevious => seq!(57436),
-            FunctionalKey::MediaRecord => seq!(57437),
-
-            FunctionalKey::LowerVolume => seq!(57438),
-            FunctionalKey::RaiseVolume => seq!(57439),
-            FunctionalKey::MuteVolume => seq!(57440),
-
-            FunctionalKey::LeftShift => seq!(57441),
-            FunctionalKey::LeftControl => seq!(57442),
-            FunctionalKey::LeftAlt => seq!(57443),
-            FunctionalKey::LeftSuper => seq!(57444),
-            FunctionalKey::LeftHyper => seq!(57445),
-            FunctionalKey::LeftMeta => seq!(57446),
-
-            FunctionalKey::RightShift => seq!(57447),
-            FunctionalKey::RightControl => seq!(57448),
-            FunctionalKey::RightAlt => seq!(57449),
-            FunctionalKey::RightSuper => seq!(57450),
-            FunctionalKey::RightHyper => seq!(57451),
-            FunctionalKey::RightMeta => seq!(57452),
-
-            FunctionalKey::IsoLevel3Shift => seq!(57453),
-            FunctionalKey::IsoLevel5Shift => seq!(57454),
-        }
+    // Whether the code size features kept this key's table
+    pub fn is_enabled(&self) -> bool {
+        let extended_function_key = matches!(
+            self,
+            FunctionalKey::F13
+                | FunctionalKey::F14
+                | FunctionalKey::F15
+                | FunctionalKey::F16
+                | FunctionalKey::F17
+                | FunctionalKey::F18
+                | FunctionalKey::F19
+                | FunctionalKey::F20
+                | FunctionalKey::F21
+                | FunctionalKey::F22
+                | FunctionalKey::F23
+                | FunctionalKey::F24
+                | FunctionalKey::F25
+                | FunctionalKey::F26
+                | FunctionalKey::F27
+                | FunctionalKey::F28
+                | FunctionalKey::F29
+                | FunctionalKey::F30
+                | FunctionalKey::F31
+                | FunctionalKey::F32
+                | FunctionalKey::F33
+                | FunctionalKey::F34
+                | FunctionalKey::F35
+        );
+        let media_key = matches!(
+            self,
+            FunctionalKey::MediaPlay
+                | FunctionalKey::MediaPause
+                | FunctionalKey::MediaPlayPause
+                | FunctionalKey::MediaReverse
+                | FunctionalKey::MediaStop
+                | FunctionalKey::MediaFastForward
+                | FunctionalKey::MediaRewind
+                | FunctionalKey::MediaTrackNext
+                | FunctionalKey::MediaTrackPrevious
+                | FunctionalKey::MediaRecord
+                | FunctionalKey::LowerVolume
+                | FunctionalKey::RaiseVolume
+                | FunctionalKey::MuteVolume
+        );
+        let modifier_key = matches!(
+            self,
+            FunctionalKey::LeftShift
+                | FunctionalKey::LeftControl
+                | FunctionalKey::LeftAlt
+                | FunctionalKey::LeftSuper
+                | FunctionalKey::LeftHyper
+                | FunctionalKey::LeftMeta
+                | FunctionalKey::RightShift
+                | FunctionalKey::RightControl
+                | FunctionalKey::RightAlt
+                | FunctionalKey::RightSuper
+                | FunctionalKey::RightHyper
+                | FunctionalKey::RightMeta
+                | FunctionalKey::IsoLevel3Shift
+                | FunctionalKey::IsoLevel5Shift
+        );
+
+        (cfg!(feature = "extended-function-keys") || !extended_function_key)
+            && (cfg!(feature = "media-keys") || !media_key)
+            && (cfg!(feature = "modifier-keys") || !modifier_key)
     }
 
     pub fn is_numpad(&self) -> bool {
@@ -346,4 +326,437 @@ impl<'a> FunctionalKey {
             _ => return None,
         })
     }
+
+    // The character a key types on the layouts it is found on, for the keys
+    // whose character depends on the layout, like the yen sign on JIS
+    pub fn typed_text(&self) -> Option<&'static str> {
+        Some(match self {
+            FunctionalKey::IntlBackslash | FunctionalKey::IntlRo => "\\",
+            FunctionalKey::IntlYen => "\u{a5}",
+            _ => return None,
+        })
+    }
+
+    // The text a keypad key types while Num Lock is on
+    pub fn numpad_text(&self) -> Option<&'static str> {
+        Some(match self {
+            FunctionalKey::NumPad0 => "0",
+            FunctionalKey::NumPad1 => "1",
+            FunctionalKey::NumPad2 => "2",
+            FunctionalKey::NumPad3 => "3",
+            FunctionalKey::NumPad4 => "4",
+            FunctionalKey::NumPad5 => "5",
+            FunctionalKey::NumPad6 => "6",
+            FunctionalKey::NumPad7 => "7",
+            FunctionalKey::NumPad8 => "8",
+            FunctionalKey::NumPad9 => "9",
+            FunctionalKey::NumPadDecimal => ".",
+            _ => return None,
+        })
+    }
+
+    // The key outside the keypad doing the same, `Left` for `NumPadLeft`.
+    // Digits and operators type characters and have none.
+    pub fn base_equivalent(&self) -> Option<FunctionalKey> {
+        Some(match self {
+            FunctionalKey::NumPadEnter => FunctionalKey::Enter,
+            FunctionalKey::NumPadLeft => FunctionalKey::Left,
+            FunctionalKey::NumPadRight => FunctionalKey::Right,
+            FunctionalKey::NumPadUp => FunctionalKey::Up,
+            FunctionalKey::NumPadDown => FunctionalKey::Down,
+            FunctionalKey::NumPadPageUp => FunctionalKey::PageUp,
+            FunctionalKey::NumPadPageDown => FunctionalKey::PageDown,
+            FunctionalKey::NumPadHome => FunctionalKey::Home,
+            FunctionalKey::NumPadEnd => FunctionalKey::End,
+            FunctionalKey::NumPadInsert => FunctionalKey::Insert,
+            FunctionalKey::NumPadDelete => FunctionalKey::Delete,
+            _ => return None,
+        })
+    }
+
+    // The other way around, `NumPadEnter` for `Enter`
+    pub fn numpad_equivalent(&self) -> Option<FunctionalKey> {
+        Some(match self {
+            FunctionalKey::Enter => FunctionalKey::NumPadEnter,
+            FunctionalKey::Left => FunctionalKey::NumPadLeft,
+            FunctionalKey::Right => FunctionalKey::NumPadRight,
+            FunctionalKey::Up => FunctionalKey::NumPadUp,
+            FunctionalKey::Down => FunctionalKey::NumPadDown,
+            FunctionalKey::PageUp => FunctionalKey::NumPadPageUp,
+            FunctionalKey::PageDown => FunctionalKey::NumPadPageDown,
+            FunctionalKey::Home => FunctionalKey::NumPadHome,
+            FunctionalKey::End => FunctionalKey::NumPadEnd,
+            FunctionalKey::Insert => FunctionalKey::NumPadInsert,
+            FunctionalKey::Delete => FunctionalKey::NumPadDelete,
+            _ => return None,
+        })
+    }
+
+    // The bit a modifier key sets while held, and which of the two keys it
+    // is. The ISO level shifts have no bit of their own.
+    pub fn modifier(&self) -> Option<(KeyboardModifiers, Side)> {
+        Some(match self {
+            FunctionalKey::LeftShift => (KeyboardModifiers::SHIFT, Side::Left),
+            FunctionalKey::LeftControl => (KeyboardModifiers::CTRL, Side::Left),
+            FunctionalKey::LeftAlt => (KeyboardModifiers::ALT, Side::Left),
+            FunctionalKey::LeftSuper => (KeyboardModifiers::SUPER, Side::Left),
+            FunctionalKey::LeftHyper => (KeyboardModifiers::HYPER, Side::Left),
+            FunctionalKey::LeftMeta => (KeyboardModifiers::META, Side::Left),
+            FunctionalKey::RightShift => (KeyboardModifiers::SHIFT, Side::Right),
+            FunctionalKey::RightControl => (KeyboardModifiers::CTRL, Side::Right),
+            FunctionalKey::RightAlt => (KeyboardModifiers::ALT, Side::Right),
+            FunctionalKey::RightSuper => (KeyboardModifiers::SUPER, Side::Right),
+            FunctionalKey::RightHyper => (KeyboardModifiers::HYPER, Side::Right),
+            FunctionalKey::RightMeta => (KeyboardModifiers::META, Side::Right),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl FunctionalKey {
+    pub const ALL: &'static [FunctionalKey] = &[
+        FunctionalKey::Escape,
+        FunctionalKey::Enter,
+        FunctionalKey::Tab,
+        FunctionalKey::Backspace,
+        FunctionalKey::Insert,
+        FunctionalKey::Delete,
+        FunctionalKey::Left,
+        FunctionalKey::Right,
+        FunctionalKey::Up,
+        FunctionalKey::Down,
+        FunctionalKey::PageUp,
+        FunctionalKey::PageDown,
+        FunctionalKey::Home,
+        FunctionalKey::End,
+        FunctionalKey::CapsLock,
+        FunctionalKey::ScrollLock,
+        FunctionalKey::NumLock,
+        FunctionalKey::PrintScreen,
+        FunctionalKey::Pause,
+        FunctionalKey::Menu,
+        FunctionalKey::F1,
+        FunctionalKey::F2,
+        FunctionalKey::F3,
+        FunctionalKey::F4,
+        FunctionalKey::F5,
+        FunctionalKey::F6,
+        FunctionalKey::F7,
+        FunctionalKey::F8,
+        FunctionalKey::F9,
+        FunctionalKey::F10,
+        FunctionalKey::F11,
+        FunctionalKey::F12,
+        FunctionalKey::F13,
+        FunctionalKey::F14,
+        FunctionalKey::F15,
+        FunctionalKey::F16,
+        FunctionalKey::F17,
+        FunctionalKey::F18,
+        FunctionalKey::F19,
+        FunctionalKey::F20,
+        FunctionalKey::F21,
+        FunctionalKey::F22,
+        FunctionalKey::F23,
+        FunctionalKey::F24,
+        FunctionalKey::F25,
+        FunctionalKey::F26,
+        FunctionalKey::F27,
+        FunctionalKey::F28,
+        FunctionalKey::F29,
+        FunctionalKey::F30,
+        FunctionalKey::F31,
+        FunctionalKey::F32,
+        FunctionalKey::F33,
+        FunctionalKey::F34,
+        FunctionalKey::F35,
+        FunctionalKey::NumPad0,
+        FunctionalKey::NumPad1,
+        FunctionalKey::NumPad2,
+        FunctionalKey::NumPad3,
+        FunctionalKey::NumPad4,
+        FunctionalKey::NumPad5,
+        FunctionalKey::NumPad6,
+        FunctionalKey::NumPad7,
+        FunctionalKey::NumPad8,
+        FunctionalKey::NumPad9,
+        FunctionalKey::NumPadDecimal,
+        FunctionalKey::NumPadDivide,
+        FunctionalKey::NumPadMultply,
+        FunctionalKey::NumPadSubtract,
+        FunctionalKey::NumPadAdd,
+        FunctionalKey::NumPadEnter,
+        FunctionalKey::NumPadEqual,
+        FunctionalKey::NumPadSeparator,
+        FunctionalKey::NumPadLeft,
+        FunctionalKey::NumPadRight,
+        FunctionalKey::NumPadUp,
+        FunctionalKey::NumPadDown,
+        FunctionalKey::NumPadPageUp,
+        FunctionalKey::NumPadPageDown,
+        FunctionalKey::NumPadHome,
+        FunctionalKey::NumPadEnd,
+        FunctionalKey::NumPadInsert,
+        FunctionalKey::NumPadDelete,
+        FunctionalKey::NumPadBegin,
+        FunctionalKey::MediaPlay,
+        FunctionalKey::MediaPause,
+        FunctionalKey::MediaPlayPause,
+        FunctionalKey::MediaReverse,
+        FunctionalKey::MediaStop,
+        FunctionalKey::MediaFastForward,
+        FunctionalKey::MediaRewind,
+        FunctionalKey::MediaTrackNext,
+        FunctionalKey::MediaTrackPrevious,
+        FunctionalKey::MediaRecord,
+        FunctionalKey::LowerVolume,
+        FunctionalKey::RaiseVolume,
+        FunctionalKey::MuteVolume,
+        FunctionalKey::LeftShift,
+        FunctionalKey::LeftControl,
+        FunctionalKey::LeftAlt,
+        FunctionalKey::LeftSuper,
+        FunctionalKey::LeftHyper,
+        FunctionalKey::LeftMeta,
+        FunctionalKey::RightShift,
+        FunctionalKey::RightControl,
+        FunctionalKey::RightAlt,
+        FunctionalKey::RightSuper,
+        FunctionalKey::RightHyper,
+        FunctionalKey::RightMeta,
+        FunctionalKey::IsoLevel3Shift,
+        FunctionalKey::IsoLevel5Shift,
+        FunctionalKey::Muhenkan,
+        FunctionalKey::Henkan,
+        FunctionalKey::KatakanaHiragana,
+        FunctionalKey::ZenkakuHankaku,
+        FunctionalKey::Eisu,
+        FunctionalKey::Hangul,
+        FunctionalKey::Hanja,
+        FunctionalKey::IntlBackslash,
+        FunctionalKey::IntlRo,
+        FunctionalKey::IntlYen,
+    ];
+
+    // The inverse of `to_sequence`, also accepting the older tilde forms
+    pub fn from_kitty_code(code: u32, terminator: SequenceTerminator) -> Option<Self> {
+        let mut sequence = Sequence {
+            key_code: KeyCode {
+                key_code: code,
+                ..Default::default()
+            },
+            terminator,
+            ..Default::default()
+        };
+        sequence.normalize();
+
+        let wanted = (sequence.key_code.key_code, sequence.terminator);
+        SEQUENCES
+            .iter()
+            .position(|entry| *entry == wanted)
+            .and_then(|index| Self::ALL.get(index).copied())
+            .filter(Self::is_enabled)
+    }
+
+    // Whether one of the keys has `code`, including keys of disabled features
+    pub fn is_assigned_code(code: u32) -> bool {
+        SEQUENCES.contains(&(code, SequenceTerminator::Kitty))
+    }
+}
+
+// The codes of keys kitty has none for start at Supplementary Private Use
+// Area-A, so they cannot clash with a code kitty assigns later in its own
+// range, U+E000 to U+F8FF
+pub const EXTENSION_KEY_BASE: u32 = 0xf0000;
+
+// Key code and terminator of each key, indexed by discriminant
+const SEQUENCES: [(u32, SequenceTerminator); FunctionalKey::ALL.len()] = [
+    (27, SequenceTerminator::Kitty),      // Escape
+    (13, SequenceTerminator::Kitty),      // Enter
+    (9, SequenceTerminator::Kitty),       // Tab
+    (127, SequenceTerminator::Kitty),     // Backspace
+    (2, SequenceTerminator::Other('~')),  // Insert
+    (3, SequenceTerminator::Other('~')),  // Delete
+    (1, SequenceTerminator::Other('D')),  // Left
+    (1, SequenceTerminator::Other('C')),  // Right
+    (1, SequenceTerminator::Other('A')),  // Up
+    (1, SequenceTerminator::Other('B')),  // Down
+    (5, SequenceTerminator::Other('~')),  // PageUp
+    (6, SequenceTerminator::Other('~')),  // PageDown
+    (1, SequenceTerminator::Other('H')),  // Home
+    (1, SequenceTerminator::Other('F')),  // End
+    (57358, SequenceTerminator::Kitty),   // CapsLock
+    (57359, SequenceTerminator::Kitty),   // ScrollLock
+    (57360, SequenceTerminator::Kitty),   // NumLock
+    (57361, SequenceTerminator::Kitty),   // PrintScreen
+    (57362, SequenceTerminator::Kitty),   // Pause
+    (57363, SequenceTerminator::Kitty),   // Menu
+    (1, SequenceTerminator::Other('P')),  // F1
+    (1, SequenceTerminator::Other('Q')),  // F2
+    (13, SequenceTerminator::Other('~')), // F3
+    (1, SequenceTerminator::Other('S')),  // F4
+    (15, SequenceTerminator::Other('~')), // F5
+    (17, SequenceTerminator::Other('~')), // F6
+    (18, SequenceTerminator::Other('~')), // F7
+    (19, SequenceTerminator::Other('~')), // F8
+    (20, SequenceTerminator::Other('~')), // F9
+    (21, SequenceTerminator::Other('~')), // F10
+    (23, SequenceTerminator::Other('~')), // F11
+    (24, SequenceTerminator::Other('~')), // F12
+    (57376, SequenceTerminator::Kitty),   // F13
+    (57377, SequenceTerminator::Kitty),   // F14
+    (57378, SequenceTerminator::Kitty),   // F15
+    (57379, SequenceTerminator::Kitty),   // F16
+    (57380, SequenceTerminator::Kitty),   // F17
+    (57381, SequenceTerminator::Kitty),   // F18
+    (57382, SequenceTerminator::Kitty),   // F19
+    (57383, SequenceTerminator::Kitty),   // F20
+    (57384, SequenceTerminator::Kitty),   // F21
+    (57385, SequenceTerminator::Kitty),   // F22
+    (57386, SequenceTerminator::Kitty),   // F23
+    (57387, SequenceTerminator::Kitty),   // F24
+    (57388, SequenceTerminator::Kitty),   // F25
+    (57389, SequenceTerminator::Kitty),   // F26
+    (57390, SequenceTerminator::Kitty),   // F27
+    (57391, SequenceTerminator::Kitty),   // F28
+    (57392, SequenceTerminator::Kitty),   // F29
+    (57393, SequenceTerminator::Kitty),   // F30
+    (57394, SequenceTerminator::Kitty),   // F31
+    (57395, SequenceTerminator::Kitty),   // F32
+    (57396, SequenceTerminator::Kitty),   // F33
+    (57397, SequenceTerminator::Kitty),   // F34
+    (57398, SequenceTerminator::Kitty),   // F35
+    (57399, SequenceTerminator::Kitty),   // NumPad0
+    (57400, SequenceTerminator::Kitty),   // NumPad1
+    (57401, SequenceTerminator::Kitty),   // NumPad2
+    (57402, SequenceTerminator::Kitty),   // NumPad3
+    (57403, SequenceTerminator::Kitty),   // NumPad4
+    (57404, SequenceTerminator::Kitty),   // NumPad5
+    (57405, SequenceTerminator::Kitty),   // NumPad6
+    (57406, SequenceTerminator::Kitty),   // NumPad7
+    (57407, SequenceTerminator::Kitty),   // NumPad8
+    (57408, SequenceTerminator::Kitty),   // NumPad9
+    (57409, SequenceTerminator::Kitty),   // NumPadDecimal
+    (57410, SequenceTerminator::Kitty),   // NumPadDivide
+    (57411, SequenceTerminator::Kitty),   // NumPadMultply
+    (57412, SequenceTerminator::Kitty),   // NumPadSubtract
+    (57413, SequenceTerminator::Kitty),   // NumPadAdd
+    (57414, SequenceTerminator::Kitty),   // NumPadEnter
+    (57415, SequenceTerminator::Kitty),   // NumPadEqual
+    (57416, SequenceTerminator::Kitty),   // NumPadSeparator
+    (57417, SequenceTerminator::Kitty),   // NumPadLeft
+    (57418, SequenceTerminator::Kitty),   // NumPadRight
+    (57419, SequenceTerminator::Kitty),   // NumPadUp
+    (57420, SequenceTerminator::Kitty),   // NumPadDown
+    (57421, SequenceTerminator::Kitty),   // NumPadPageUp
+    (57422, SequenceTerminator::Kitty),   // NumPadPageDown
+    (57423, SequenceTerminator::Kitty),   // NumPadHome
+    (57424, SequenceTerminator::Kitty),   // NumPadEnd
+    (57425, SequenceTerminator::Kitty),   // NumPadInsert
+    (57426, SequenceTerminator::Kitty),   // NumPadDelete
+    (1, SequenceTerminator::Other('E')),  // NumPadBegin
+    (57428, SequenceTerminator::Kitty),   // MediaPlay
+    (57429, SequenceTerminator::Kitty),   // MediaPause
+    (57430, SequenceTerminator::Kitty),   // MediaPlayPause
+    (57431, SequenceTerminator::Kitty),   // MediaReverse
+    (57432, SequenceTerminator::Kitty),   // MediaStop
+    (57433, SequenceTerminator::Kitty),   // MediaFastForward
+    (57434, SequenceTerminator::Kitty),   // MediaRewind
+    (57435, SequenceTerminator::Kitty),   // MediaTrackNext
+    (57436, SequenceTerminator::Kitty),   // MediaTrackPrevious
+    (57437, SequenceTerminator::Kitty),   // MediaRecord
+    (57438, SequenceTerminator::Kitty),   // LowerVolume
+    (57439, SequenceTerminator::Kitty),   // RaiseVolume
+    (57440, SequenceTerminator::Kitty),   // MuteVolume
+    (57441, SequenceTerminator::Kitty),   // LeftShift
+    (57442, SequenceTerminator::Kitty),   // LeftControl
+    (57443, SequenceTerminator::Kitty),   // LeftAlt
+    (57444, SequenceTerminator::Kitty),   // LeftSuper
+    (57445, SequenceTerminator::Kitty),   // LeftHyper
+    (57446, SequenceTerminator::Kitty),   // LeftMeta
+    (57447, SequenceTerminator::Kitty),   // RightShift
+    (57448, SequenceTerminator::Kitty),   // RightControl
+    (57449, SequenceTerminator::Kitty),   // RightAlt
+    (57450, SequenceTerminator::Kitty),   // RightSuper
+    (57451, SequenceTerminator::Kitty),   // RightHyper
+    (57452, SequenceTerminator::Kitty),   // RightMeta
+    (57453, SequenceTerminator::Kitty),   // IsoLevel3Shift
+    (57454, SequenceTerminator::Kitty),   // IsoLevel5Shift
+    // Not assigned by kitty, numbered from EXTENSION_KEY_BASE
+    (EXTENSION_KEY_BASE, SequenceTerminator::Kitty), // Muhenkan
+    (EXTENSION_KEY_BASE + 1, SequenceTerminator::Kitty), // Henkan
+    (EXTENSION_KEY_BASE + 2, SequenceTerminator::Kitty), // KatakanaHiragana
+    (EXTENSION_KEY_BASE + 3, SequenceTerminator::Kitty), // ZenkakuHankaku
+    (EXTENSION_KEY_BASE + 4, SequenceTerminator::Kitty), // Eisu
+    (EXTENSION_KEY_BASE + 5, SequenceTerminator::Kitty), // Hangul
+    (EXTENSION_KEY_BASE + 6, SequenceTerminator::Kitty), // Hanja
+    (EXTENSION_KEY_BASE + 7, SequenceTerminator::Kitty), // IntlBackslash
+    (EXTENSION_KEY_BASE + 8, SequenceTerminator::Kitty), // IntlRo
+    (EXTENSION_KEY_BASE + 9, SequenceTerminator::Kitty), // IntlYen
+];
+
+// The character a terminal sends for Ctrl + `ch` in legacy mode
+pub fn control_character(ch: char) -> Option<char> {
+    match ch {
+        ' ' | '@' => Some('\0'),
+        'a'..='z' => Some((ch as u8 - b'a' + 1) as char),
+        'A'..='Z' => Some((ch as u8 - b'A' + 1) as char),
+        '[' | '\\' | ']' | '^' | '_' => Some((ch as u8 - b'@') as char),
+        '?' => Some('\x7f'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_table() {
+        for (index, key) in FunctionalKey::ALL.iter().enumerate() {
+            assert_eq!(*key as usize, index, "{key:?} is out of order in ALL");
+
+            let sequence = key.to_sequence();
+            assert_eq!(
+                FunctionalKey::from_kitty_code(sequence.key_code.key_code, sequence.terminator),
+                Some(*key).filter(FunctionalKey::is_enabled)
+            );
+        }
+    }
+
+    #[test]
+    fn numpad_equivalents() {
+        for key in FunctionalKey::ALL {
+            if let Some(base) = key.base_equivalent() {
+                assert!(key.is_numpad() && !base.is_numpad(), "{key:?}");
+                assert_eq!(base.numpad_equivalent(), Some(*key));
+            }
+            if let Some(numpad) = key.numpad_equivalent() {
+                assert_eq!(numpad.base_equivalent(), Some(*key));
+            }
+        }
+        assert_eq!(FunctionalKey::NumPad4.base_equivalent(), None);
+        assert_eq!(FunctionalKey::NumPadBegin.base_equivalent(), None);
+    }
+
+    #[test]
+    fn modifier_bits() {
+        assert_eq!(
+            FunctionalKey::RightControl.modifier(),
+            Some((KeyboardModifiers::CTRL, Side::Right))
+        );
+        assert_eq!(FunctionalKey::IsoLevel3Shift.modifier(), None);
+        assert_eq!(FunctionalKey::CapsLock.modifier(), None);
+
+        let modifier_keys = FunctionalKey::ALL
+            .iter()
+            .filter(|key| key.modifier().is_some());
+        assert_eq!(modifier_keys.count(), 12);
+    }
 }
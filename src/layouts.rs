@@ -0,0 +1,433 @@
+use crate::{key::KeyType, sequence::KeyboardModifiers};
+
+// Positions of the writing system keys, named after the key they carry on a
+// US keyboard (as in the W3C UI Events `code` values)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysicalKey {
+    Backquote,
+    Digit1,
+    Digit2,
+    Digit3,
+    Digit4,
+    Digit5,
+    Digit6,
+    Digit7,
+    Digit8,
+    Digit9,
+    Digit0,
+    Minus,
+    Equal,
+    KeyQ,
+    KeyW,
+    KeyE,
+    KeyR,
+    KeyT,
+    KeyY,
+    KeyU,
+    KeyI,
+    KeyO,
+    KeyP,
+    BracketLeft,
+    BracketRight,
+    Backslash,
+    KeyA,
+    KeyS,
+    KeyD,
+    KeyF,
+    KeyG,
+    KeyH,
+    KeyJ,
+    KeyK,
+    KeyL,
+    Semicolon,
+    Quote,
+    IntlBackslash,
+    KeyZ,
+    KeyX,
+    KeyC,
+    KeyV,
+    KeyB,
+    KeyN,
+    KeyM,
+    Comma,
+    Period,
+    Slash,
+    IntlRo,
+    IntlYen,
+    Space,
+}
+
+impl PhysicalKey {
+    // The spec defines the base layout key as the key on a PC-101 US layout
+    pub fn base_layout_key(self) -> KeyType {
+        US.base(self).map_or(KeyType::Unknown, KeyType::Unicode)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub name: &'static str,
+    keys: &'static [(PhysicalKey, char, Option<char>)],
+}
+
+impl Layout {
+    pub const fn new(
+        name: &'static str,
+        keys: &'static [(PhysicalKey, char, Option<char>)],
+    ) -> Self {
+        Self { name, keys }
+    }
+
+    pub fn base(&self, key: PhysicalKey) -> Option<char> {
+        self.entry(key).map(|(_, base, _)| *base)
+    }
+
+    pub fn shifted(&self, key: PhysicalKey) -> Option<char> {
+        self.entry(key).and_then(|(_, _, shifted)| *shifted)
+    }
+
+    pub fn key(&self, key: PhysicalKey, shift: bool) -> KeyType {
+        let ch = if shift {
+            self.shifted(key).or_else(|| self.base(key))
+        } else {
+            self.base(key)
+        };
+        ch.map_or(KeyType::Unknown, KeyType::Unicode)
+    }
+
+    // What a key types with `modifiers` held, see `shifted_key`. Ctrl is left
+    // to the encoder, it sends the control character in legacy mode only.
+    pub fn key_with_modifiers(&self, key: PhysicalKey, modifiers: KeyboardModifiers) -> KeyType {
+        let Some(base) = self.base(key) else {
+            return KeyType::Unknown;
+        };
+
+        KeyType::Unicode(shifted_key(base, self.shifted(key), modifiers))
+    }
+
+    // Finds the key producing `ch`, and whether shift is needed for it
+    pub fn find(&self, ch: char) -> Option<(PhysicalKey, bool)> {
+        self.keys.iter().find_map(|(key, base, shifted)| {
+            if *base == ch {
+                Some((*key, false))
+            } else if *shifted == Some(ch) {
+                Some((*key, true))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn entry(&self, key: PhysicalKey) -> Option<&(PhysicalKey, char, Option<char>)> {
+        self.keys.iter().find(|(k, _, _)| *k == key)
+    }
+}
+
+pub const US: Layout = Layout::new("us", US_KEYS);
+pub const UK: Layout = Layout::new("gb", UK_KEYS);
+pub const DE: Layout = Layout::new("de", DE_KEYS);
+pub const FR: Layout = Layout::new("fr", FR_KEYS);
+
+pub const LAYOUTS: &[Layout] = &[US, UK, DE, FR];
+
+// The character a key with `base` on it types, for frontends without a layout
+// table. Caps Lock inverts Shift for letters only, and letters without a known
+// `shifted` character are uppercased. Other keys need `shifted` to change.
+pub fn shifted_key(base: char, shifted: Option<char>, modifiers: KeyboardModifiers) -> char {
+    let mut shift = modifiers.intersects(KeyboardModifiers::SHIFT);
+    let letter = base.is_alphabetic();
+    if letter && modifiers.intersects(KeyboardModifiers::CAPS_LOCK) {
+        shift = !shift;
+    }
+
+    if !shift {
+        return base;
+    }
+    shifted
+        .or_else(|| letter.then(|| uppercase(base)).flatten())
+        .unwrap_or(base)
+}
+
+fn uppercase(ch: char) -> Option<char> {
+    let mut upper = ch.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(upper), None) => Some(upper),
+        _ => None,
+    }
+}
+
+const US_KEYS: &[(PhysicalKey, char, Option<char>)] = &[
+    (PhysicalKey::Backquote, '`', Some('~')),
+    (PhysicalKey::Digit1, '1', Some('!')),
+    (PhysicalKey::Digit2, '2', Some('@')),
+    (PhysicalKey::Digit3, '3', Some('#')),
+    (PhysicalKey::Digit4, '4', Some('$')),
+    (PhysicalKey::Digit5, '5', Some('%')),
+    (PhysicalKey::Digit6, '6', Some('^')),
+    (PhysicalKey::Digit7, '7', Some('&')),
+    (PhysicalKey::Digit8, '8', Some('*')),
+    (PhysicalKey::Digit9, '9', Some('(')),
+    (PhysicalKey::Digit0, '0', Some(')')),
+    (PhysicalKey::Minus, '-', Some('_')),
+    (PhysicalKey::Equal, '=', Some('+')),
+    (PhysicalKey::KeyQ, 'q', Some('Q')),
+    (PhysicalKey::KeyW, 'w', Some('W')),
+    (PhysicalKey::KeyE, 'e', Some('E')),
+    (PhysicalKey::KeyR, 'r', Some('R')),
+    (PhysicalKey::KeyT, 't', Some('T')),
+    (PhysicalKey::KeyY, 'y', Some('Y')),
+    (PhysicalKey::KeyU, 'u', Some('U')),
+    (PhysicalKey::KeyI, 'i', Some('I')),
+    (PhysicalKey::KeyO, 'o', Some('O')),
+    (PhysicalKey::KeyP, 'p', Some('P')),
+    (PhysicalKey::BracketLeft, '[', Some('{')),
+    (PhysicalKey::BracketRight, ']', Some('}')),
+    (PhysicalKey::Backslash, '\\', Some('|')),
+    (PhysicalKey::KeyA, 'a', Some('A')),
+    (PhysicalKey::KeyS, 's', Some('S')),
+    (PhysicalKey::KeyD, 'd', Some('D')),
+    (PhysicalKey::KeyF, 'f', Some('F')),
+    (PhysicalKey::KeyG, 'g', Some('G')),
+    (PhysicalKey::KeyH, 'h', Some('H')),
+    (PhysicalKey::KeyJ, 'j', Some('J')),
+    (PhysicalKey::KeyK, 'k', Some('K')),
+    (PhysicalKey::KeyL, 'l', Some('L')),
+    (PhysicalKey::Semicolon, ';', Some(':')),
+    (PhysicalKey::Quote, '\'', Some('"')),
+    (PhysicalKey::KeyZ, 'z', Some('Z')),
+    (PhysicalKey::KeyX, 'x', Some('X')),
+    (PhysicalKey::KeyC, 'c', Some('C')),
+    (PhysicalKey::KeyV, 'v', Some('V')),
+    (PhysicalKey::KeyB, 'b', Some('B')),
+    (PhysicalKey::KeyN, 'n', Some('N')),
+    (PhysicalKey::KeyM, 'm', Some('M')),
+    (PhysicalKey::Comma, ',', Some('<')),
+    (PhysicalKey::Period, '.', Some('>')),
+    (PhysicalKey::Slash, '/', Some('?')),
+    (PhysicalKey::Space, ' ', Some(' ')),
+];
+
+const UK_KEYS: &[(PhysicalKey, char, Option<char>)] = &[
+    (PhysicalKey::Backquote, '`', Some('¬')),
+    (PhysicalKey::Digit1, '1', Some('!')),
+    (PhysicalKey::Digit2, '2', Some('"')),
+    (PhysicalKey::Digit3, '3', Some('£')),
+    (PhysicalKey::Digit4, '4', Some('$')),
+    (PhysicalKey::Digit5, '5', Some('%')),
+    (PhysicalKey::Digit6, '6', Some('^')),
+    (PhysicalKey::Digit7, '7', Some('&')),
+    (PhysicalKey::Digit8, '8', Some('*')),
+    (PhysicalKey::Digit9, '9', Some('(')),
+    (PhysicalKey::Digit0, '0', Some(')')),
+    (PhysicalKey::Minus, '-', Some('_')),
+    (PhysicalKey::Equal, '=', Some('+')),
+    (PhysicalKey::KeyQ, 'q', Some('Q')),
+    (PhysicalKey::KeyW, 'w', Some('W')),
+    (PhysicalKey::KeyE, 'e', Some('E')),
+    (PhysicalKey::KeyR, 'r', Some('R')),
+    (PhysicalKey::KeyT, 't', Some('T')),
+    (PhysicalKey::KeyY, 'y', Some('Y')),
+    (PhysicalKey::KeyU, 'u', Some('U')),
+    (PhysicalKey::KeyI, 'i', Some('I')),
+    (PhysicalKey::KeyO, 'o', Some('O')),
+    (PhysicalKey::KeyP, 'p', Some('P')),
+    (PhysicalKey::BracketLeft, '[', Some('{')),
+    (PhysicalKey::BracketRight, ']', Some('}')),
+    (PhysicalKey::Backslash, '#', Some('~')),
+    (PhysicalKey::KeyA, 'a', Some('A')),
+    (PhysicalKey::KeyS, 's', Some('S')),
+    (PhysicalKey::KeyD, 'd', Some('D')),
+    (PhysicalKey::KeyF, 'f', Some('F')),
+    (PhysicalKey::KeyG, 'g', Some('G')),
+    (PhysicalKey::KeyH, 'h', Some('H')),
+    (PhysicalKey::KeyJ, 'j', Some('J')),
+    (PhysicalKey::KeyK, 'k', Some('K')),
+    (PhysicalKey::KeyL, 'l', Some('L')),
+    (PhysicalKey::Semicolon, ';', Some(':')),
+    (PhysicalKey::Quote, '\'', Some('@')),
+    (PhysicalKey::IntlBackslash, '\\', Some('|')),
+    (PhysicalKey::KeyZ, 'z', Some('Z')),
+    (PhysicalKey::KeyX, 'x', Some('X')),
+    (PhysicalKey::KeyC, 'c', Some('C')),
+    (PhysicalKey::KeyV, 'v', Some('V')),
+    (PhysicalKey::KeyB, 'b', Some('B')),
+    (PhysicalKey::KeyN, 'n', Some('N')),
+    (PhysicalKey::KeyM, 'm', Some('M')),
+    (PhysicalKey::Comma, ',', Some('<')),
+    (PhysicalKey::Period, '.', Some('>')),
+    (PhysicalKey::Slash, '/', Some('?')),
+    (PhysicalKey::Space, ' ', Some(' ')),
+];
+
+const DE_KEYS: &[(PhysicalKey, char, Option<char>)] = &[
+    (PhysicalKey::Backquote, '^', Some('°')),
+    (PhysicalKey::Digit1, '1', Some('!')),
+    (PhysicalKey::Digit2, '2', Some('"')),
+    (PhysicalKey::Digit3, '3', Some('§')),
+    (PhysicalKey::Digit4, '4', Some('$')),
+    (PhysicalKey::Digit5, '5', Some('%')),
+    (PhysicalKey::Digit6, '6', Some('&')),
+    (PhysicalKey::Digit7, '7', Some('/')),
+    (PhysicalKey::Digit8, '8', Some('(')),
+    (PhysicalKey::Digit9, '9', Some(')')),
+    (PhysicalKey::Digit0, '0', Some('=')),
+    (PhysicalKey::Minus, 'ß', Some('?')),
+    (PhysicalKey::Equal, '´', Some('`')),
+    (PhysicalKey::KeyQ, 'q', Some('Q')),
+    (PhysicalKey::KeyW, 'w', Some('W')),
+    (PhysicalKey::KeyE, 'e', Some('E')),
+    (PhysicalKey::KeyR, 'r', Some('R')),
+    (PhysicalKey::KeyT, 't', Some('T')),
+    (PhysicalKey::KeyY, 'z', Some('Z')),
+    (PhysicalKey::KeyU, 'u', Some('U')),
+    (PhysicalKey::KeyI, 'i', Some('I')),
+    (PhysicalKey::KeyO, 'o', Some('O')),
+    (PhysicalKey::KeyP, 'p', Some('P')),
+    (PhysicalKey::BracketLeft, 'ü', Some('Ü')),
+    (PhysicalKey::BracketRight, '+', Some('*')),
+    (PhysicalKey::Backslash, '#', Some('\'')),
+    (PhysicalKey::KeyA, 'a', Some('A')),
+    (PhysicalKey::KeyS, 's', Some('S')),
+    (PhysicalKey::KeyD, 'd', Some('D')),
+    (PhysicalKey::KeyF, 'f', Some('F')),
+    (PhysicalKey::KeyG, 'g', Some('G')),
+    (PhysicalKey::KeyH, 'h', Some('H')),
+    (PhysicalKey::KeyJ, 'j', Some('J')),
+    (PhysicalKey::KeyK, 'k', Some('K')),
+    (PhysicalKey::KeyL, 'l', Some('L')),
+    (PhysicalKey::Semicolon, 'ö', Some('Ö')),
+    (PhysicalKey::Quote, 'ä', Some('Ä')),
+    (PhysicalKey::IntlBackslash, '<', Some('>')),
+    (PhysicalKey::KeyZ, 'y', Some('Y')),
+    (PhysicalKey::KeyX, 'x', Some('X')),
+    (PhysicalKey::KeyC, 'c', Some('C')),
+    (PhysicalKey::KeyV, 'v', Some('V')),
+    (PhysicalKey::KeyB, 'b', Some('B')),
+    (PhysicalKey::KeyN, 'n', Some('N')),
+    (PhysicalKey::KeyM, 'm', Some('M')),
+    (PhysicalKey::Comma, ',', Some(';')),
+    (PhysicalKey::Period, '.', Some(':')),
+    (PhysicalKey::Slash, '-', Some('_')),
+    (PhysicalKey::Space, ' ', Some(' ')),
+];
+
+const FR_KEYS: &[(PhysicalKey, char, Option<char>)] = &[
+    (PhysicalKey::Backquote, '²', None),
+    (PhysicalKey::Digit1, '&', Some('1')),
+    (PhysicalKey::Digit2, 'é', Some('2')),
+    (PhysicalKey::Digit3, '"', Some('3')),
+    (PhysicalKey::Digit4, '\'', Some('4')),
+    (PhysicalKey::Digit5, '(', Some('5')),
+    (PhysicalKey::Digit6, '-', Some('6')),
+    (PhysicalKey::Digit7, 'è', Some('7')),
+    (PhysicalKey::Digit8, '_', Some('8')),
+    (PhysicalKey::Digit9, 'ç', Some('9')),
+    (PhysicalKey::Digit0, 'à', Some('0')),
+    (PhysicalKey::Minus, ')', Some('°')),
+    (PhysicalKey::Equal, '=', Some('+')),
+    (PhysicalKey::KeyQ, 'a', Some('A')),
+    (PhysicalKey::KeyW, 'z', Some('Z')),
+    (PhysicalKey::KeyE, 'e', Some('E')),
+    (PhysicalKey::KeyR, 'r', Some('R')),
+    (PhysicalKey::KeyT, 't', Some('T')),
+    (PhysicalKey::KeyY, 'y', Some('Y')),
+    (PhysicalKey::KeyU, 'u', Some('U')),
+    (PhysicalKey::KeyI, 'i', Some('I')),
+    (PhysicalKey::KeyO, 'o', Some('O')),
+    (PhysicalKey::KeyP, 'p', Some('P')),
+    (PhysicalKey::BracketLeft, '^', Some('¨')),
+    (PhysicalKey::BracketRight, '$', Some('£')),
+    (PhysicalKey::Backslash, '*', Some('µ')),
+    (PhysicalKey::KeyA, 'q', Some('Q')),
+    (PhysicalKey::KeyS, 's', Some('S')),
+    (PhysicalKey::KeyD, 'd', Some('D')),
+    (PhysicalKey::KeyF, 'f', Some('F')),
+    (PhysicalKey::KeyG, 'g', Some('G')),
+    (PhysicalKey::KeyH, 'h', Some('H')),
+    (PhysicalKey::KeyJ, 'j', Some('J')),
+    (PhysicalKey::KeyK, 'k', Some('K')),
+    (PhysicalKey::KeyL, 'l', Some('L')),
+    (PhysicalKey::Semicolon, 'm', Some('M')),
+    (PhysicalKey::Quote, 'ù', Some('%')),
+    (PhysicalKey::IntlBackslash, '<', Some('>')),
+    (PhysicalKey::KeyZ, 'w', Some('W')),
+    (PhysicalKey::KeyX, 'x', Some('X')),
+    (PhysicalKey::KeyC, 'c', Some('C')),
+    (PhysicalKey::KeyV, 'v', Some('V')),
+    (PhysicalKey::KeyB, 'b', Some('B')),
+    (PhysicalKey::KeyN, 'n', Some('N')),
+    (PhysicalKey::KeyM, ',', Some('?')),
+    (PhysicalKey::Comma, ';', Some('.')),
+    (PhysicalKey::Period, ':', Some('/')),
+    (PhysicalKey::Slash, '!', Some('§')),
+    (PhysicalKey::Space, ' ', Some(' ')),
+];
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn base_layout_is_us() {
+        assert_eq!(PhysicalKey::KeyQ.base_layout_key(), KeyType::Unicode('q'));
+        assert_eq!(
+            PhysicalKey::IntlBackslash.base_layout_key(),
+            KeyType::Unknown
+        );
+        assert_eq!(FR.base(PhysicalKey::KeyQ), Some('a'));
+        assert_eq!(DE.base(PhysicalKey::KeyY), Some('z'));
+    }
+
+    #[test]
+    fn shifted_keys() {
+        assert_eq!(US.key(PhysicalKey::Digit2, true), KeyType::Unicode('@'));
+        assert_eq!(UK.key(PhysicalKey::Digit2, true), KeyType::Unicode('"'));
+        assert_eq!(DE.key(PhysicalKey::Semicolon, true), KeyType::Unicode('Ö'));
+        assert_eq!(FR.key(PhysicalKey::Digit1, true), KeyType::Unicode('1'));
+        // No shifted symbol, the key keeps producing its base character
+        assert_eq!(FR.key(PhysicalKey::Backquote, true), KeyType::Unicode('²'));
+    }
+
+    #[test]
+    fn caps_lock() {
+        let shift = KeyboardModifiers::SHIFT;
+        let caps = KeyboardModifiers::CAPS_LOCK;
+
+        assert_eq!(shifted_key('a', None, shift), 'A');
+        assert_eq!(shifted_key('a', None, caps), 'A');
+        assert_eq!(shifted_key('a', None, shift | caps), 'a');
+        assert_eq!(shifted_key('1', Some('!'), shift), '!');
+        assert_eq!(shifted_key('1', Some('!'), caps), '1');
+        assert_eq!(shifted_key('1', None, shift), '1');
+        // No single character uppercase form
+        assert_eq!(shifted_key('ß', None, caps), 'ß');
+
+        assert_eq!(
+            DE.key_with_modifiers(PhysicalKey::Semicolon, caps),
+            KeyType::Unicode('Ö')
+        );
+        assert_eq!(
+            US.key_with_modifiers(PhysicalKey::Digit2, shift | caps),
+            KeyType::Unicode('@')
+        );
+        assert_eq!(
+            US.key_with_modifiers(PhysicalKey::KeyA, shift | KeyboardModifiers::CTRL),
+            KeyType::Unicode('A')
+        );
+    }
+
+    #[test]
+    fn reverse_lookup() {
+        assert_eq!(US.find('?'), Some((PhysicalKey::Slash, true)));
+        assert_eq!(DE.find('z'), Some((PhysicalKey::KeyY, false)));
+        assert_eq!(FR.find('é'), Some((PhysicalKey::Digit2, false)));
+        assert_eq!(US.find('é'), None);
+
+        for layout in LAYOUTS {
+            for (key, base, _) in layout.keys {
+                assert_eq!(layout.find(*base), Some((*key, false)), "{}", layout.name);
+            }
+        }
+    }
+}
@@ -0,0 +1,484 @@
+use crate::{
+    key::{FunctionalKey, KeyType},
+    sequence::{KeyCode, KeyboardModifiers, Sequence, SequenceIntroducer, SequenceTerminator},
+    EventResponse,
+};
+
+// How Alt is encoded for functional keys that have a legacy sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AltEncoding {
+    // `CSI 1;3A`, xterm style
+    #[default]
+    ModifierParameter,
+    // `ESC CSI A`, rxvt style
+    EscPrefix,
+}
+
+// How F3 is sent while disambiguating. Kitty sends `CSI 13~`, which some
+// parsers mix up with Enter (13), and still accepts the xterm style `CSI R`,
+// which in turn looks like a cursor position report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum F3Encoding {
+    #[default]
+    Tilde,
+    Letter,
+}
+
+// The kitty release whose encoding to match, for applications tested against
+// an older kitty. Each level leaves out the parts of the spec that came after
+// it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum CompatLevel {
+    // No associated text yet and the ISO level shift keys are not reported
+    Kitty0_23,
+    // Associated text
+    Kitty0_26,
+    // The current spec
+    #[default]
+    Latest,
+}
+
+impl CompatLevel {
+    pub fn supports(self, key: FunctionalKey) -> bool {
+        let level_shift = matches!(
+            key,
+            FunctionalKey::IsoLevel3Shift | FunctionalKey::IsoLevel5Shift
+        );
+        self >= CompatLevel::Kitty0_26 || !level_shift
+    }
+
+    // Keys the level does not know of are sent as if they had no code
+    pub fn key(self, key: KeyType) -> KeyType {
+        match key {
+            KeyType::Functional(func) if !self.supports(func) => KeyType::Unknown,
+            key => key,
+        }
+    }
+}
+
+// The function key sets of xterm's keyboard types, DECSET 1051 to 1053, and
+// of the Linux console
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FunctionKeys {
+    #[default]
+    Vt220,
+    Sun,
+    Hp,
+    Sco,
+    Linux,
+}
+
+impl FunctionKeys {
+    // The response for keys this set encodes differently from the default
+    pub fn response(self, key: FunctionalKey, alt_pressed: bool) -> Option<EventResponse<'static>> {
+        let csi = |code, terminator| {
+            EventResponse::Sequence(Sequence {
+                key_code: KeyCode {
+                    key_code: code,
+                    ..Default::default()
+                },
+                terminator: SequenceTerminator::Other(terminator),
+                ..Default::default()
+            })
+        };
+        let esc = |text| EventResponse::Text { text, alt_pressed };
+
+        Some(match self {
+            FunctionKeys::Vt220 => return None,
+            FunctionKeys::Sun => match key {
+                FunctionalKey::Insert => csi(2, 'z'),
+                FunctionalKey::Delete => csi(3, 'z'),
+                FunctionalKey::Home => csi(214, 'z'),
+                FunctionalKey::PageUp => csi(216, 'z'),
+                FunctionalKey::End => csi(220, 'z'),
+                FunctionalKey::PageDown => csi(222, 'z'),
+                FunctionalKey::F11 => csi(192, 'z'),
+                FunctionalKey::F12 => csi(193, 'z'),
+                _ => csi(
+                    224 + function_key_index(key).filter(|index| *index < 10)?,
+                    'z',
+                ),
+            },
+            FunctionKeys::Hp => esc(match key {
+                FunctionalKey::Up => "\x1bA",
+                FunctionalKey::Down => "\x1bB",
+                FunctionalKey::Right => "\x1bC",
+                FunctionalKey::Left => "\x1bD",
+                FunctionalKey::Home => "\x1bh",
+                FunctionalKey::End => "\x1bF",
+                FunctionalKey::Insert => "\x1bQ",
+                FunctionalKey::Delete => "\x1bP",
+                FunctionalKey::PageUp => "\x1bT",
+                FunctionalKey::PageDown => "\x1bS",
+                FunctionalKey::F1 => "\x1bp",
+                FunctionalKey::F2 => "\x1bq",
+                FunctionalKey::F3 => "\x1br",
+                FunctionalKey::F4 => "\x1bs",
+                FunctionalKey::F5 => "\x1bt",
+                FunctionalKey::F6 => "\x1bu",
+                FunctionalKey::F7 => "\x1bv",
+                FunctionalKey::F8 => "\x1bw",
+                _ => return None,
+            }),
+            FunctionKeys::Sco => match key {
+                FunctionalKey::Insert => csi(1, 'L'),
+                FunctionalKey::PageUp => csi(1, 'I'),
+                FunctionalKey::PageDown => csi(1, 'G'),
+                _ => {
+                    let index = function_key_index(key).filter(|index| *index < 12)?;
+                    csi(1, char::from(b'M' + index as u8))
+                }
+            },
+            FunctionKeys::Linux => esc(match key {
+                FunctionalKey::Home => "\x1b[1~",
+                FunctionalKey::End => "\x1b[4~",
+                FunctionalKey::F1 => "\x1b[[A",
+                FunctionalKey::F2 => "\x1b[[B",
+                FunctionalKey::F3 => "\x1b[[C",
+                FunctionalKey::F4 => "\x1b[[D",
+                FunctionalKey::F5 => "\x1b[[E",
+                _ => return None,
+            }),
+        })
+    }
+}
+
+// F1 is 0
+fn function_key_index(key: FunctionalKey) -> Option<u32> {
+    let index = FunctionalKey::ALL.iter().position(|func| *func == key)?;
+    let f1 = FunctionalKey::ALL
+        .iter()
+        .position(|func| *func == FunctionalKey::F1)?;
+    let index = index.checked_sub(f1).filter(|index| *index < 35)?;
+    Some(index as u32)
+}
+
+// A legacy sequence without its modifiers, they are filled in when encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LegacySequence {
+    pub introducer: SequenceIntroducer,
+    pub code: u32,
+    pub terminator: char,
+}
+
+impl LegacySequence {
+    // The sequence xterm sends for `key`, if it has one
+    pub fn of(key: FunctionalKey) -> Option<Self> {
+        let seq = key.to_sequence();
+        match seq.terminator {
+            SequenceTerminator::Other(terminator) => Some(Self {
+                introducer: seq.introducer,
+                code: seq.key_code.key_code,
+                terminator,
+            }),
+            SequenceTerminator::Kitty => None,
+        }
+    }
+
+    pub fn to_sequence(self) -> Sequence<'static> {
+        Sequence {
+            introducer: self.introducer,
+            key_code: KeyCode {
+                key_code: self.code,
+                ..Default::default()
+            },
+            terminator: SequenceTerminator::Other(self.terminator),
+            ..Default::default()
+        }
+    }
+}
+
+pub const KEYMAP_CAPACITY: usize = 16;
+
+// Per key overrides of the legacy sequences, they win over the function key
+// set and the other options
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LegacyKeymap {
+    overrides: [Option<(FunctionalKey, LegacySequence)>; KEYMAP_CAPACITY],
+}
+
+impl LegacyKeymap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns false if the keymap is full
+    pub fn set(&mut self, key: FunctionalKey, sequence: LegacySequence) -> bool {
+        let slot = self
+            .overrides
+            .iter()
+            .position(|entry| entry.is_some_and(|(func, _)| func == key))
+            .or_else(|| self.overrides.iter().position(Option::is_none))
+            .and_then(|slot| self.overrides.get_mut(slot));
+
+        match slot {
+            Some(slot) => {
+                *slot = Some((key, sequence));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, key: FunctionalKey) {
+        for entry in &mut self.overrides {
+            if entry.is_some_and(|(func, _)| func == key) {
+                *entry = None;
+            }
+        }
+    }
+
+    // The override for `key`
+    pub fn get(&self, key: FunctionalKey) -> Option<LegacySequence> {
+        self.overrides
+            .iter()
+            .flatten()
+            .find(|(func, _)| *func == key)
+            .map(|(_, sequence)| *sequence)
+    }
+
+    // The override or the built in sequence
+    pub fn sequence(&self, key: FunctionalKey) -> Option<LegacySequence> {
+        self.get(key).or_else(|| LegacySequence::of(key))
+    }
+}
+
+// Knobs for the legacy (non kitty) encoding, where terminals disagree. The
+// booleans mirror xterm's resources of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LegacyOptions {
+    pub alt_functional: AltEncoding,
+    pub function_keys: FunctionKeys,
+    pub keymap: LegacyKeymap,
+    // DECSET 1039
+    pub alt_sends_escape: bool,
+    // DECSET 1036
+    pub meta_sends_escape: bool,
+    // DECSET 1037, the editing keypad's Delete sends DEL instead of `CSI 3~`
+    pub delete_is_del: bool,
+    // DECSET 1034, Alt or Meta set the eighth bit when not sending ESC
+    pub eight_bit_input: bool,
+    // DECSET 1035, the keypad sends digits while Num Lock is on. The frontend
+    // decides that by reporting digits and text.
+    pub num_lock: bool,
+    // Not legacy knobs, only the kitty encodings are changed
+    pub f3: F3Encoding,
+    pub compat: CompatLevel,
+}
+
+impl Default for LegacyOptions {
+    fn default() -> Self {
+        Self {
+            alt_functional: AltEncoding::default(),
+            function_keys: FunctionKeys::default(),
+            keymap: LegacyKeymap::new(),
+            alt_sends_escape: true,
+            meta_sends_escape: false,
+            delete_is_del: false,
+            eight_bit_input: false,
+            num_lock: true,
+            f3: F3Encoding::default(),
+            compat: CompatLevel::default(),
+        }
+    }
+}
+
+impl LegacyOptions {
+    // The response for functional keys these options change
+    pub fn functional_response(
+        &self,
+        key: FunctionalKey,
+        alt_pressed: bool,
+    ) -> Option<EventResponse<'static>> {
+        if let Some(sequence) = self.keymap.get(key) {
+            return Some(EventResponse::Sequence(sequence.to_sequence()));
+        }
+        if self.delete_is_del && key == FunctionalKey::Delete {
+            return Some(EventResponse::Text {
+                text: "\x7f",
+                alt_pressed,
+            });
+        }
+
+        self.function_keys.response(key, alt_pressed)
+    }
+
+    pub fn sends_escape(&self, modifiers: KeyboardModifiers) -> bool {
+        (self.alt_sends_escape && modifiers.intersects(KeyboardModifiers::ALT))
+            || (self.meta_sends_escape && modifiers.intersects(KeyboardModifiers::META))
+    }
+
+    // The character sent for `ch` when no ESC is sent for Alt/Meta
+    pub fn eight_bit(&self, ch: char, modifiers: KeyboardModifiers) -> char {
+        let meta = modifiers.intersects(KeyboardModifiers::ALT | KeyboardModifiers::META);
+        if self.eight_bit_input && meta && !self.sends_escape(modifiers) && ch.is_ascii() {
+            char::from(ch as u8 | 0x80)
+        } else {
+            ch
+        }
+    }
+
+    // Without the Num Lock handling, keypad keys are always the editing keys
+    // printed on them
+    pub fn keypad_key(&self, key: KeyType) -> KeyType {
+        if self.num_lock {
+            return key;
+        }
+
+        let KeyType::Functional(func) = key else {
+            return key;
+        };
+        let navigation = match func {
+            FunctionalKey::NumPad0 => FunctionalKey::NumPadInsert,
+            FunctionalKey::NumPad1 => FunctionalKey::NumPadEnd,
+            FunctionalKey::NumPad2 => FunctionalKey::NumPadDown,
+            FunctionalKey::NumPad3 => FunctionalKey::NumPadPageDown,
+            FunctionalKey::NumPad4 => FunctionalKey::NumPadLeft,
+            FunctionalKey::NumPad5 => FunctionalKey::NumPadBegin,
+            FunctionalKey::NumPad6 => FunctionalKey::NumPadRight,
+            FunctionalKey::NumPad7 => FunctionalKey::NumPadHome,
+            FunctionalKey::NumPad8 => FunctionalKey::NumPadUp,
+            FunctionalKey::NumPad9 => FunctionalKey::NumPadPageUp,
+            FunctionalKey::NumPadDecimal => FunctionalKey::NumPadDelete,
+            _ => func,
+        };
+
+        KeyType::Functional(navigation.base_equivalent().unwrap_or(navigation))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Personality {
+    #[default]
+    Xterm,
+    Rxvt,
+    Sun,
+    Hp,
+    Sco,
+    Linux,
+}
+
+impl Personality {
+    pub fn options(self) -> LegacyOptions {
+        match self {
+            Personality::Xterm => LegacyOptions::default(),
+            Personality::Rxvt => LegacyOptions {
+                alt_functional: AltEncoding::EscPrefix,
+                ..Default::default()
+            },
+            Personality::Sun => LegacyOptions {
+                function_keys: FunctionKeys::Sun,
+                ..Default::default()
+            },
+            Personality::Hp => LegacyOptions {
+                function_keys: FunctionKeys::Hp,
+                ..Default::default()
+            },
+            Personality::Sco => LegacyOptions {
+                function_keys: FunctionKeys::Sco,
+                ..Default::default()
+            },
+            Personality::Linux => LegacyOptions {
+                function_keys: FunctionKeys::Linux,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl From<Personality> for LegacyOptions {
+    fn from(personality: Personality) -> Self {
+        personality.options()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{generate_sequence_with, synthesize::SyntheticKeyEvent, ReportingMode};
+
+    extern crate std;
+    use std::format;
+
+    #[test]
+    fn linux_console() {
+        let linux = Personality::Linux.options().function_keys;
+        let encode = |key| format!("{}", linux.response(key, false).unwrap_or_default());
+
+        assert_eq!(encode(FunctionalKey::F1), "\x1b[[A");
+        assert_eq!(encode(FunctionalKey::F5), "\x1b[[E");
+        assert_eq!(encode(FunctionalKey::Home), "\x1b[1~");
+        assert_eq!(encode(FunctionalKey::End), "\x1b[4~");
+        assert!(linux.response(FunctionalKey::F6, false).is_none());
+    }
+
+    #[test]
+    fn keymap_overrides() {
+        let mut options = LegacyOptions::default();
+        // rxvt's Home
+        let home = LegacySequence {
+            introducer: SequenceIntroducer::CSI,
+            code: 7,
+            terminator: '~',
+        };
+        assert!(options.keymap.set(FunctionalKey::Home, home));
+        assert_eq!(options.keymap.sequence(FunctionalKey::Home), Some(home));
+        assert_eq!(
+            options.keymap.sequence(FunctionalKey::Up),
+            LegacySequence::of(FunctionalKey::Up)
+        );
+        assert_eq!(LegacySequence::of(FunctionalKey::CapsLock), None);
+
+        let response = options.functional_response(FunctionalKey::Home, false);
+        assert_eq!(format!("{}", response.unwrap_or_default()), "\x1b[7~");
+
+        options.keymap.remove(FunctionalKey::Home);
+        assert!(options
+            .functional_response(FunctionalKey::Home, false)
+            .is_none());
+
+        for key in &FunctionalKey::ALL[..KEYMAP_CAPACITY] {
+            assert!(options.keymap.set(*key, home));
+        }
+        assert!(!options.keymap.set(FunctionalKey::F1, home));
+    }
+
+    #[test]
+    fn compat_levels() {
+        let mode = ReportingMode::all();
+        let encode = |compat, event: &SyntheticKeyEvent| {
+            let options = LegacyOptions {
+                compat,
+                ..LegacyOptions::default()
+            };
+            format!("{}", generate_sequence_with(mode, &options, event))
+        };
+
+        let a = SyntheticKeyEvent::from_char('a', "a");
+        assert_eq!(encode(CompatLevel::Latest, &a), "\x1b[97;;97u");
+        assert_eq!(encode(CompatLevel::Kitty0_26, &a), "\x1b[97;;97u");
+        assert_eq!(encode(CompatLevel::Kitty0_23, &a), "\x1b[97u");
+
+        let keypad = SyntheticKeyEvent {
+            modifiers: KeyboardModifiers::NUM_LOCK,
+            ..SyntheticKeyEvent::from(KeyType::Functional(FunctionalKey::NumPad1))
+        };
+        assert_eq!(
+            encode(CompatLevel::Kitty0_26, &keypad),
+            "\x1b[57400;129;49u"
+        );
+        assert_eq!(encode(CompatLevel::Kitty0_23, &keypad), "\x1b[57400;129u");
+
+        #[cfg(feature = "modifier-keys")]
+        {
+            let level3 =
+                SyntheticKeyEvent::from(KeyType::Functional(FunctionalKey::IsoLevel3Shift));
+            assert_eq!(encode(CompatLevel::Kitty0_26, &level3), "\x1b[57453u");
+            assert_eq!(encode(CompatLevel::Kitty0_23, &level3), "");
+        }
+        let henkan = SyntheticKeyEvent::from(KeyType::Functional(FunctionalKey::Henkan));
+        assert_eq!(encode(CompatLevel::Latest, &henkan), "\x1b[983041u");
+        assert_eq!(encode(CompatLevel::Kitty0_23, &henkan), "\x1b[983041u");
+    }
+}
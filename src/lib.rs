@@ -1,17 +1,89 @@
 #![no_std]
-
-use core::fmt::{Display, Write};
+// Nothing outside of tests may panic, checked by `cargo clippy --features no-panic`
+#![cfg_attr(
+    all(feature = "no-panic", not(test)),
+    deny(
+        clippy::panic,
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::indexing_slicing,
+        clippy::string_slice
+    )
+)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::{
+    fmt::{Display, Write},
+    time::Duration,
+};
 
 use bitflags::bitflags;
-use key::{FunctionalKey, KeyType};
-use sequence::{AssociatedText, EventType, KeyboardModifiers, Sequence};
-
+use key::{control_character, FunctionalKey, KeyType};
+use legacy::{AltEncoding, CompatLevel, F3Encoding, LegacyOptions};
+use sequence::{
+    AssociatedText, EventType, KeyboardModifiers, Sequence, SequenceError, SequenceTerminator,
+};
+
+#[cfg(feature = "alloc")]
+pub mod asciicast;
+#[cfg(feature = "bevy")]
+pub mod bevy;
+pub mod binding;
+pub mod channel;
+pub mod custom_keys;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+pub mod encoder;
+pub mod filter;
+#[cfg(feature = "gpui")]
+pub mod gpui;
+pub mod hid;
 pub mod key;
+pub mod layouts;
+pub mod legacy;
+pub mod matrix;
+pub mod mouse;
+pub mod parse;
+pub mod paste;
+#[cfg(feature = "perform")]
+pub mod perform;
+pub mod playback;
+#[cfg(feature = "ps2")]
+pub mod ps2;
+pub mod queue;
 pub mod sequence;
+pub mod show_key;
+#[cfg(feature = "std")]
+pub mod simulation;
+#[cfg(feature = "slint")]
+pub mod slint;
+pub mod state;
+pub mod synthesize;
+#[cfg(feature = "termion")]
+pub mod termion;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+pub mod transcode;
+#[cfg(feature = "std")]
+pub mod vectored;
+pub mod virtual_keyboard;
+#[cfg(feature = "wire")]
+pub mod wire;
+pub mod xkb;
+pub mod xkb_keymap;
 
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-    pub struct ReportingMode: u8 {
+    // Wider than the flags defined so far, later additions to the protocol fit
+    // without breaking changes
+    pub struct ReportingMode: u32 {
         const DISAMBIGUATE_ESC_CODES  = 0b0000_0001;
         const REPORT_EVENT_TYPES      = 0b0000_0010;
         const REPORT_ALTERNATE_KEYS   = 0b0000_0100;
@@ -20,6 +92,75 @@ bitflags! {
     }
 }
 
+impl ReportingMode {
+    // The flags an application asked for with `CSI > flags u` or
+    // `CSI = flags ; mode u`. Flags this crate does not implement are dropped,
+    // so queries never claim support for them.
+    pub fn from_request(flags: u32) -> ReportingMode {
+        ReportingMode::from_bits_truncate(flags)
+    }
+
+    // `CSI ? flags u`, the reply to a query
+    pub fn write_query_response(self, out: &mut impl Write) -> core::fmt::Result {
+        write!(out, "\x1b[?{}u", (self & ReportingMode::all()).bits())
+    }
+
+    // The flags the flags in `self` need to have an effect on all keys, e.g.
+    // associated text is only sent with keys reported as escape codes
+    pub fn dependencies(self) -> ReportingMode {
+        let mut dependencies = ReportingMode::empty();
+        if self.intersects(ReportingMode::REPORT_ASSOCIATED_TEXT) {
+            dependencies |= ReportingMode::REPORT_ALL_KEYS_AS_ESC;
+        }
+        let escape_codes_only =
+            ReportingMode::REPORT_EVENT_TYPES | ReportingMode::REPORT_ALTERNATE_KEYS;
+        if self.intersects(escape_codes_only) {
+            dependencies |= ReportingMode::DISAMBIGUATE_ESC_CODES;
+        }
+        dependencies
+    }
+
+    // The mode that behaves the same, reporting all keys as escape codes
+    // disambiguates them as well
+    pub fn effective(self) -> ReportingMode {
+        if self.intersects(ReportingMode::REPORT_ALL_KEYS_AS_ESC) {
+            self | ReportingMode::DISAMBIGUATE_ESC_CODES
+        } else {
+            self
+        }
+    }
+
+    pub fn is_coherent(self) -> bool {
+        self.effective().contains(self.dependencies())
+    }
+
+    // `self` with everything its flags depend on
+    pub fn closure(self) -> ReportingMode {
+        let mut mode = self;
+        loop {
+            let closed = (mode | mode.dependencies()).effective();
+            if closed == mode {
+                return mode;
+            }
+            mode = closed;
+        }
+    }
+
+    // The response to `key_event` under these flags, with the default legacy
+    // options
+    pub fn encode(self, key_event: &impl KeyEvent) -> EventResponse<'_> {
+        self.encode_with(&LegacyOptions::default(), key_event)
+    }
+
+    pub fn encode_with<'a>(
+        self,
+        options: &LegacyOptions,
+        key_event: &'a impl KeyEvent,
+    ) -> EventResponse<'a> {
+        generate_resolved_sequence(self, options, ResolvedKeyEvent::new(key_event))
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub enum EventResponse<'a> {
     Text {
@@ -31,6 +172,8 @@ pub enum EventResponse<'a> {
         alt_pressed: bool,
     },
     Sequence(Sequence<'a>),
+    // A sequence sent after an ESC for Alt
+    PrefixedSequence(Sequence<'a>),
     #[default]
     Nothing,
 }
@@ -59,28 +202,259 @@ impl<'a> Display for EventResponse<'a> {
                 write!(f, "\x1b{character}")
             }
             EventResponse::Sequence(seq) => seq.fmt(f),
+            EventResponse::PrefixedSequence(seq) => write!(f, "\x1b{seq}"),
             EventResponse::Nothing => Ok(()),
         }
     }
 }
 
-pub trait KeyEvent {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseKind {
+    Text,
+    Character,
+    Sequence,
+    Nothing,
+}
+
+impl<'a> EventResponse<'a> {
+    pub fn kind(&self) -> ResponseKind {
+        match self {
+            EventResponse::Text { .. } => ResponseKind::Text,
+            EventResponse::Character { .. } => ResponseKind::Character,
+            EventResponse::Sequence(_) | EventResponse::PrefixedSequence(_) => {
+                ResponseKind::Sequence
+            }
+            EventResponse::Nothing => ResponseKind::Nothing,
+        }
+    }
+
+    pub fn as_sequence(&self) -> Option<&Sequence<'a>> {
+        match self {
+            EventResponse::Sequence(seq) | EventResponse::PrefixedSequence(seq) => Some(seq),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&'a str> {
+        match self {
+            EventResponse::Text { text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_character(&self) -> Option<char> {
+        match self {
+            EventResponse::Character { character, .. } => Some(*character),
+            _ => None,
+        }
+    }
+
+    pub fn alt_pressed(&self) -> bool {
+        matches!(
+            self,
+            EventResponse::Text {
+                alt_pressed: true,
+                ..
+            } | EventResponse::Character {
+                alt_pressed: true,
+                ..
+            } | EventResponse::PrefixedSequence(_)
+        )
+    }
+
+    pub fn is_nothing(&self) -> bool {
+        matches!(self, EventResponse::Nothing)
+    }
+}
+
+// A key event is split up so frontends only implement what they know, the
+// defaults fill in the rest
+pub trait KeyInfo {
     fn key_with_modifiers(&self) -> KeyType;
-    fn key_without_modifiers(&self) -> KeyType;
-    fn key_base_layout(&self) -> KeyType;
 
-    fn modifiers(&self) -> KeyboardModifiers;
-    fn event_type(&self) -> EventType;
-    fn associated_text(&self) -> Option<AssociatedText>;
+    // Without layout knowledge the keys are all the same
+    fn key_without_modifiers(&self) -> KeyType {
+        self.key_with_modifiers()
+    }
+
+    fn key_base_layout(&self) -> KeyType {
+        self.key_without_modifiers()
+    }
+
+    fn event_type(&self) -> EventType {
+        EventType::Press
+    }
+
+    // When the event happened, on whatever clock the source uses
+    fn timestamp(&self) -> Option<Duration> {
+        None
+    }
 }
 
-pub fn generate_sequence(mode: ReportingMode, key_event: &impl KeyEvent) -> EventResponse {
-    let shifted_key = key_event.key_with_modifiers();
-    let unshifted_key = key_event.key_without_modifiers();
-    let modifiers = key_event.modifiers();
+pub trait ModifierInfo {
+    fn modifiers(&self) -> KeyboardModifiers {
+        KeyboardModifiers::empty()
+    }
+}
+
+pub trait TextInfo {
+    fn associated_text(&self) -> Option<AssociatedText<'_>> {
+        None
+    }
+}
+
+pub trait KeyEvent: KeyInfo + ModifierInfo + TextInfo {}
+
+impl<T: KeyInfo + ModifierInfo + TextInfo + ?Sized> KeyEvent for T {}
+
+// Quick events for scripts and tests, all key views are the same key
+impl KeyInfo for (KeyType, KeyboardModifiers) {
+    fn key_with_modifiers(&self) -> KeyType {
+        self.0
+    }
+}
+
+impl ModifierInfo for (KeyType, KeyboardModifiers) {
+    fn modifiers(&self) -> KeyboardModifiers {
+        self.1
+    }
+}
+
+impl TextInfo for (KeyType, KeyboardModifiers) {}
+
+impl KeyInfo for (KeyType, KeyboardModifiers, EventType) {
+    fn key_with_modifiers(&self) -> KeyType {
+        self.0
+    }
+
+    fn event_type(&self) -> EventType {
+        self.2
+    }
+}
+
+impl ModifierInfo for (KeyType, KeyboardModifiers, EventType) {
+    fn modifiers(&self) -> KeyboardModifiers {
+        self.1
+    }
+}
+
+impl TextInfo for (KeyType, KeyboardModifiers, EventType) {}
+
+// Events behind references and smart pointers, so they can sit in queues or be
+// passed as trait objects
+macro_rules! forward_key_event {
+    ($($wrapper:ty),*) => {$(
+        impl<T: KeyInfo + ?Sized> KeyInfo for $wrapper {
+            fn key_with_modifiers(&self) -> KeyType {
+                (**self).key_with_modifiers()
+            }
+
+            fn key_without_modifiers(&self) -> KeyType {
+                (**self).key_without_modifiers()
+            }
+
+            fn key_base_layout(&self) -> KeyType {
+                (**self).key_base_layout()
+            }
+
+            fn event_type(&self) -> EventType {
+                (**self).event_type()
+            }
+
+            fn timestamp(&self) -> Option<Duration> {
+                (**self).timestamp()
+            }
+        }
+
+        impl<T: ModifierInfo + ?Sized> ModifierInfo for $wrapper {
+            fn modifiers(&self) -> KeyboardModifiers {
+                (**self).modifiers()
+            }
+        }
+
+        impl<T: TextInfo + ?Sized> TextInfo for $wrapper {
+            fn associated_text(&self) -> Option<AssociatedText<'_>> {
+                (**self).associated_text()
+            }
+        }
+    )*};
+}
+
+forward_key_event!(&T);
+
+#[cfg(feature = "alloc")]
+forward_key_event!(alloc::boxed::Box<T>, alloc::rc::Rc<T>);
+
+pub fn generate_sequence(mode: ReportingMode, key_event: &impl KeyEvent) -> EventResponse<'_> {
+    mode.encode(key_event)
+}
+
+// The views of a key event the generator uses, read out once
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedKeyEvent<'a> {
+    pub key_with_modifiers: KeyType,
+    pub key_without_modifiers: KeyType,
+    pub key_base_layout: KeyType,
+    pub modifiers: KeyboardModifiers,
+    pub event_type: EventType,
+    pub associated_text: Option<AssociatedText<'a>>,
+}
+
+impl<'a> ResolvedKeyEvent<'a> {
+    pub fn new(key_event: &'a (impl KeyEvent + ?Sized)) -> Self {
+        Self {
+            key_with_modifiers: key_event.key_with_modifiers(),
+            key_without_modifiers: key_event.key_without_modifiers(),
+            key_base_layout: key_event.key_base_layout(),
+            modifiers: key_event.modifiers(),
+            event_type: key_event.event_type(),
+            associated_text: key_event.associated_text(),
+        }
+    }
+
+    // For frontends that pass the shifted character as the unshifted key
+    pub fn with_lowercase_key(mut self) -> Self {
+        self.key_without_modifiers = self.key_without_modifiers.lowercase();
+        self
+    }
+}
+
+pub fn generate_sequence_with<'a>(
+    mode: ReportingMode,
+    options: &LegacyOptions,
+    key_event: &'a impl KeyEvent,
+) -> EventResponse<'a> {
+    mode.encode_with(options, key_event)
+}
+
+// Rejects events whose unshifted key is not in lowercase, e.g. 'A' for Shift+a,
+// instead of sending a key code the application does not expect
+pub fn generate_sequence_checked<'a>(
+    mode: ReportingMode,
+    options: &LegacyOptions,
+    key_event: &'a impl KeyEvent,
+) -> Result<EventResponse<'a>, SequenceError> {
+    let key_event = ResolvedKeyEvent::new(key_event);
+    if key_event.key_without_modifiers != key_event.key_without_modifiers.lowercase() {
+        return Err(SequenceError::ShiftedKeyCode);
+    }
+
+    Ok(generate_resolved_sequence(mode, options, key_event))
+}
+
+// Not generic over the event type, every frontend shares one copy
+pub fn generate_resolved_sequence<'a>(
+    mode: ReportingMode,
+    options: &LegacyOptions,
+    key_event: ResolvedKeyEvent<'a>,
+) -> EventResponse<'a> {
+    let compat = options.compat;
+    let shifted_key = compat.key(key_event.key_with_modifiers.enabled());
+    let unshifted_key = compat.key(key_event.key_without_modifiers.enabled());
+    let modifiers = key_event.modifiers;
 
     // Don't report release event when event types are not reported
-    if key_event.event_type() == EventType::Release
+    if key_event.event_type == EventType::Release
         && !mode.intersects(ReportingMode::REPORT_EVENT_TYPES)
     {
         return EventResponse::Nothing;
@@ -108,7 +482,10 @@ pub fn generate_sequence(mode: ReportingMode, key_event: &impl KeyEvent) -> Even
                 KeyType::Functional(func) if func.is_numpad() => {
                     EventResponse::Sequence(func.to_sequence())
                 }
-                KeyType::Functional(func) => match func.legacy_representation() {
+                KeyType::Functional(func) => match func
+                    .legacy_representation()
+                    .or_else(|| typed_text(func, &key_event))
+                {
                     Some(repr) => EventResponse::Text {
                         text: repr,
                         alt_pressed: false,
@@ -127,28 +504,40 @@ pub fn generate_sequence(mode: ReportingMode, key_event: &impl KeyEvent) -> Even
             EventResponse::Nothing
         }
     } else { // Legacy mode
-        match shifted_key {
-            KeyType::Unicode(character) => EventResponse::Character {
-                character,
-                alt_pressed: modifiers.intersects(KeyboardModifiers::ALT),
-            },
+        let alt_pressed = options.sends_escape(modifiers);
+        let key = options.keypad_key(shifted_key);
+        // The text belongs to the digit, not the editing key
+        let text = if key == shifted_key {
+            key_event.associated_text.as_ref().map(|at| at.0)
+        } else {
+            None
+        };
+        match key {
+            KeyType::Unicode(mut character) => {
+                // Ctrl and a key the terminal has a C0 control for
+                if modifiers.intersects(KeyboardModifiers::CTRL) {
+                    character = control_character(character).unwrap_or(character);
+                }
+                EventResponse::Character {
+                    character: options.eight_bit(character, modifiers),
+                    alt_pressed,
+                }
+            }
             KeyType::Functional(func) => {
-                if let Some(text) = func
+                if let Some(response) = options.functional_response(func, alt_pressed) {
+                    response
+                } else if let Some(text) = func
                     .legacy_representation()
-                    .or_else(|| key_event.associated_text().map(|at| at.0))
+                    .or(text)
+                    .or_else(|| typed_text(func, &key_event))
                 {
-                    EventResponse::Text {
-                        text,
-                        alt_pressed: modifiers.intersects(KeyboardModifiers::ALT),
-                    }
+                    EventResponse::Text { text, alt_pressed }
                 } else {
-                    let seq = func.to_sequence();
-                    match seq.terminator {
-                        // Only send legacy sequences, not kitty's
-                        sequence::SequenceTerminator::Kitty => EventResponse::Nothing,
-                        sequence::SequenceTerminator::Other(_) => EventResponse::Sequence(seq),
+                    // Only send legacy sequences, not kitty's
+                    match options.keymap.sequence(func) {
+                        Some(seq) => EventResponse::Sequence(seq.to_sequence()),
+                        None => EventResponse::Nothing,
                     }
-                    
                 }
             }
             KeyType::Unknown => EventResponse::Nothing,
@@ -159,28 +548,68 @@ pub fn generate_sequence(mode: ReportingMode, key_event: &impl KeyEvent) -> Even
         EventResponse::Sequence(mut sequence) => {
             sequence.modifier = modifiers;
 
+            let legacy = !mode.intersects(
+                ReportingMode::REPORT_ALL_KEYS_AS_ESC | ReportingMode::DISAMBIGUATE_ESC_CODES,
+            );
+            if !legacy
+                && options.f3 == F3Encoding::Letter
+                && unshifted_key == KeyType::Functional(FunctionalKey::F3)
+            {
+                sequence.key_code.key_code = 1;
+                sequence.terminator = SequenceTerminator::Other('R');
+            }
+
             if mode.intersects(ReportingMode::REPORT_EVENT_TYPES) {
-                sequence.event_type = key_event.event_type();
+                sequence.event_type = key_event.event_type;
             }
 
             if mode.intersects(ReportingMode::REPORT_ALTERNATE_KEYS) {
                 if modifiers.intersects(KeyboardModifiers::SHIFT) {
                     sequence.key_code.shifted_key_code = shifted_key.to_key_code();
                 }
-                sequence.key_code.base_layout_key_code = key_event.key_base_layout().to_key_code();
+                let base_layout = key_event.key_base_layout;
+                if base_layout != unshifted_key {
+                    sequence.key_code.base_layout_key_code = base_layout.to_key_code();
+                }
             }
 
-            if mode.intersects(ReportingMode::REPORT_ASSOCIATED_TEXT) {
-                sequence.associated_text = key_event.associated_text();
+            if mode.intersects(ReportingMode::REPORT_ASSOCIATED_TEXT)
+                && compat >= CompatLevel::Kitty0_26
+            {
+                // Frontends often leave out the text of keypad digits
+                let num_lock = modifiers.intersects(KeyboardModifiers::NUM_LOCK);
+                let numpad_text = match unshifted_key {
+                    KeyType::Functional(func) if num_lock => func.numpad_text().map(AssociatedText),
+                    _ => None,
+                };
+                sequence.associated_text = key_event.associated_text.clone().or(numpad_text);
+            }
+
+            if legacy
+                && options.alt_functional == AltEncoding::EscPrefix
+                && modifiers.intersects(KeyboardModifiers::ALT)
+            {
+                sequence.modifier.remove(KeyboardModifiers::ALT);
+                return EventResponse::PrefixedSequence(sequence);
             }
 
             EventResponse::Sequence(sequence)
         }
-        _ if key_event.event_type() == EventType::Release => EventResponse::Nothing,
+        _ if key_event.event_type == EventType::Release => EventResponse::Nothing,
         a => a,
     }
 }
 
+// The frontend's text over the key's usual character, which is unshifted
+fn typed_text<'a>(func: FunctionalKey, key_event: &ResolvedKeyEvent<'a>) -> Option<&'a str> {
+    let usual = func.typed_text()?;
+    match &key_event.associated_text {
+        Some(text) => Some(text.0),
+        None if key_event.modifiers.intersects(KeyboardModifiers::SHIFT) => None,
+        None => Some(usual),
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -229,6 +658,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn response_accessors() {
+        let text = EventResponse::Text {
+            text: "\r",
+            alt_pressed: true,
+        };
+        assert_eq!(text.kind(), ResponseKind::Text);
+        assert_eq!(text.as_text(), Some("\r"));
+        assert!(text.alt_pressed());
+        assert!(text.as_sequence().is_none());
+
+        let character = EventResponse::Character {
+            character: 'x',
+            alt_pressed: false,
+        };
+        assert_eq!(character.kind(), ResponseKind::Character);
+        assert_eq!(character.as_character(), Some('x'));
+        assert_eq!(character.as_text(), None);
+        assert!(!character.alt_pressed());
+
+        let sequence = EventResponse::Sequence(FunctionalKey::Up.to_sequence());
+        assert_eq!(sequence.kind(), ResponseKind::Sequence);
+        assert_eq!(
+            sequence.as_sequence().map(|seq| seq.terminator),
+            Some(SequenceTerminator::Other('A'))
+        );
+
+        assert!(EventResponse::Nothing.is_nothing());
+        assert_eq!(EventResponse::Nothing.kind(), ResponseKind::Nothing);
+    }
+
     #[derive(Debug, Clone, Default)]
     struct DummyKeyEvent {
         key_with_modifiers: KeyType,
@@ -240,7 +700,7 @@ mod tests {
         associated_text: Option<String>,
     }
 
-    impl KeyEvent for DummyKeyEvent {
+    impl KeyInfo for DummyKeyEvent {
         fn key_with_modifiers(&self) -> KeyType {
             self.key_with_modifiers
         }
@@ -253,16 +713,20 @@ mod tests {
             self.key_base_layout
         }
 
-        fn modifiers(&self) -> KeyboardModifiers {
-            self.modifiers
-        }
-
         fn event_type(&self) -> EventType {
             self.event_type
         }
+    }
+
+    impl ModifierInfo for DummyKeyEvent {
+        fn modifiers(&self) -> KeyboardModifiers {
+            self.modifiers
+        }
+    }
 
-        fn associated_text(&self) -> Option<AssociatedText> {
-            self.associated_text.as_ref().map(|t| AssociatedText(&t))
+    impl TextInfo for DummyKeyEvent {
+        fn associated_text(&self) -> Option<AssociatedText<'_>> {
+            self.associated_text.as_ref().map(|t| AssociatedText(t))
         }
     }
 
@@ -358,7 +822,13 @@ mod tests {
 
                 let response = generate_sequence(mode, &long_keycode_event);
 
-                assert_eq!(format!("{response}"), $long_keycode, "Left control");
+                // Modifier keys are Unknown without their table
+                let long_keycode = if cfg!(feature = "modifier-keys") {
+                    $long_keycode
+                } else {
+                    ""
+                };
+                assert_eq!(format!("{response}"), long_keycode, "Left control");
             }
         };
     }
@@ -445,4 +915,377 @@ mod tests {
         "\x1b[98;1:3u",
         "\x1b[57442u"
     );
+
+    #[test]
+    fn unknown_mode_bits() {
+        let requested = ReportingMode::from_request(0b1_0000_0001);
+        assert_eq!(requested, ReportingMode::DISAMBIGUATE_ESC_CODES);
+
+        let mut out = String::new();
+        ReportingMode::from_bits_retain(0b1_0000_1000)
+            .write_query_response(&mut out)
+            .unwrap();
+        assert_eq!(out, "\x1b[?8u");
+    }
+
+    #[test]
+    fn mode_encode() {
+        let ctrl_a = (KeyType::Unicode('a'), KeyboardModifiers::CTRL);
+        let mode = ReportingMode::DISAMBIGUATE_ESC_CODES;
+        assert_eq!(format!("{}", mode.encode(&ctrl_a)), "\x1b[97;5u");
+        assert_eq!(
+            format!("{}", ReportingMode::empty().encode(&ctrl_a)),
+            format!("{}", generate_sequence(ReportingMode::empty(), &ctrl_a))
+        );
+
+        let f3 = (
+            KeyType::Functional(FunctionalKey::F3),
+            KeyboardModifiers::CTRL,
+        );
+        let options = LegacyOptions {
+            f3: F3Encoding::Letter,
+            ..Default::default()
+        };
+        assert_eq!(format!("{}", mode.encode_with(&options, &f3)), "\x1b[;5R");
+    }
+
+    #[test]
+    fn mode_dependencies() {
+        let text = ReportingMode::REPORT_ASSOCIATED_TEXT;
+        assert!(!text.is_coherent());
+        assert_eq!(text.dependencies(), ReportingMode::REPORT_ALL_KEYS_AS_ESC);
+        assert_eq!(
+            text.closure(),
+            ReportingMode::REPORT_ASSOCIATED_TEXT
+                | ReportingMode::REPORT_ALL_KEYS_AS_ESC
+                | ReportingMode::DISAMBIGUATE_ESC_CODES
+        );
+
+        let all_keys = ReportingMode::REPORT_ALL_KEYS_AS_ESC | ReportingMode::REPORT_EVENT_TYPES;
+        assert!(all_keys.is_coherent());
+        assert_eq!(
+            all_keys.effective(),
+            all_keys | ReportingMode::DISAMBIGUATE_ESC_CODES
+        );
+        assert!(!ReportingMode::REPORT_ALTERNATE_KEYS.is_coherent());
+        assert!(ReportingMode::empty().is_coherent());
+        assert!(ReportingMode::all().closure() == ReportingMode::all());
+    }
+
+    #[test]
+    fn numpad_associated_text() {
+        let mode = ReportingMode::REPORT_ALL_KEYS_AS_ESC | ReportingMode::REPORT_ASSOCIATED_TEXT;
+        let mut five = DummyKeyEvent {
+            key_with_modifiers: KeyType::Functional(FunctionalKey::NumPad5),
+            key_without_modifiers: KeyType::Functional(FunctionalKey::NumPad5),
+            ..Default::default()
+        };
+
+        assert_eq!(format!("{}", generate_sequence(mode, &five)), "\x1b[57404u");
+        five.modifiers = KeyboardModifiers::NUM_LOCK;
+        assert_eq!(
+            format!("{}", generate_sequence(mode, &five)),
+            "\x1b[57404;129;53u"
+        );
+        five.associated_text = Some("٥".into());
+        assert_eq!(
+            format!("{}", generate_sequence(mode, &five)),
+            "\x1b[57404;129;1637u"
+        );
+    }
+
+    #[test]
+    fn partial_frontend() {
+        struct Bare(KeyType);
+
+        impl KeyInfo for Bare {
+            fn key_with_modifiers(&self) -> KeyType {
+                self.0
+            }
+        }
+
+        impl ModifierInfo for Bare {}
+
+        impl TextInfo for Bare {}
+
+        let key = Bare(KeyType::Unicode('a'));
+        assert_eq!(key.key_base_layout(), KeyType::Unicode('a'));
+        assert_eq!(key.modifiers(), KeyboardModifiers::empty());
+        assert_eq!(key.event_type(), EventType::Press);
+        assert_eq!(
+            format!(
+                "{}",
+                generate_sequence(ReportingMode::REPORT_ALL_KEYS_AS_ESC, &key)
+            ),
+            "\x1b[97u"
+        );
+    }
+
+    #[test]
+    fn tuple_events() {
+        let ctrl_a = (KeyType::Unicode('a'), KeyboardModifiers::CTRL);
+        assert_eq!(
+            format!(
+                "{}",
+                generate_sequence(ReportingMode::DISAMBIGUATE_ESC_CODES, &ctrl_a)
+            ),
+            "\x1b[97;5u"
+        );
+
+        let release = (
+            KeyType::Functional(FunctionalKey::PageUp),
+            KeyboardModifiers::empty(),
+            EventType::Release,
+        );
+        let mode = ReportingMode::DISAMBIGUATE_ESC_CODES | ReportingMode::REPORT_EVENT_TYPES;
+        assert_eq!(
+            format!("{}", generate_sequence(mode, &release)),
+            "\x1b[5;1:3~"
+        );
+    }
+
+    #[test]
+    fn forwarded_events() {
+        let ctrl_a = (KeyType::Unicode('a'), KeyboardModifiers::CTRL);
+        let mode = ReportingMode::DISAMBIGUATE_ESC_CODES;
+        let by_ref: &dyn KeyEvent = &ctrl_a;
+        assert_eq!(
+            format!("{}", generate_sequence(mode, &by_ref)),
+            "\x1b[97;5u"
+        );
+
+        #[cfg(feature = "alloc")]
+        {
+            use std::{boxed::Box, rc::Rc, vec::Vec};
+
+            let queue: Vec<Box<dyn KeyEvent>> = Vec::from([
+                Box::new(ctrl_a) as Box<dyn KeyEvent>,
+                Box::new(Rc::new((KeyType::Unicode('b'), KeyboardModifiers::ALT))),
+            ]);
+            let out: Vec<_> = queue
+                .iter()
+                .map(|event| format!("{}", generate_sequence(mode, event)))
+                .collect();
+            assert_eq!(out, ["\x1b[97;5u", "\x1b[98;3u"]);
+        }
+    }
+
+    #[test]
+    fn code_size_features() {
+        let f20 = (
+            KeyType::Functional(FunctionalKey::F20),
+            KeyboardModifiers::empty(),
+        );
+        let out = format!(
+            "{}",
+            generate_sequence(ReportingMode::DISAMBIGUATE_ESC_CODES, &f20)
+        );
+        let parsed = KeyType::from_kitty_code(57383, SequenceTerminator::Kitty);
+
+        if cfg!(feature = "extended-function-keys") {
+            assert_eq!(out, "\x1b[57383u");
+            assert_eq!(parsed, KeyType::Functional(FunctionalKey::F20));
+        } else {
+            assert_eq!(out, "");
+            assert_eq!(f20.0.enabled(), KeyType::Unknown);
+        }
+    }
+
+    #[test]
+    fn resolved_events() {
+        let event = DummyKeyEvent {
+            key_with_modifiers: KeyType::Unicode('A'),
+            key_without_modifiers: KeyType::Unicode('a'),
+            modifiers: KeyboardModifiers::SHIFT,
+            associated_text: Some("A".into()),
+            ..Default::default()
+        };
+        let mode = ReportingMode::REPORT_ALL_KEYS_AS_ESC
+            | ReportingMode::REPORT_ALTERNATE_KEYS
+            | ReportingMode::REPORT_ASSOCIATED_TEXT;
+
+        let resolved = ResolvedKeyEvent::new(&event);
+        assert_eq!(resolved.key_with_modifiers, KeyType::Unicode('A'));
+        assert_eq!(
+            format!(
+                "{}",
+                generate_resolved_sequence(mode, &LegacyOptions::default(), resolved)
+            ),
+            format!("{}", generate_sequence(mode, &event))
+        );
+    }
+
+    #[test]
+    fn f3_encoding() {
+        let f3 = DummyKeyEvent {
+            key_with_modifiers: KeyType::Functional(FunctionalKey::F3),
+            key_without_modifiers: KeyType::Functional(FunctionalKey::F3),
+            modifiers: KeyboardModifiers::CTRL,
+            ..Default::default()
+        };
+        let options = LegacyOptions {
+            f3: F3Encoding::Letter,
+            ..Default::default()
+        };
+        let encode = |mode, options: &LegacyOptions| {
+            format!("{}", generate_sequence_with(mode, options, &f3))
+        };
+
+        let mode = ReportingMode::DISAMBIGUATE_ESC_CODES;
+        assert_eq!(encode(mode, &LegacyOptions::default()), "\x1b[13;5~");
+        assert_eq!(encode(mode, &options), "\x1b[;5R");
+        // The legacy encoding is left alone
+        assert_eq!(encode(ReportingMode::empty(), &options), "\x1b[13;5~");
+    }
+
+    #[test]
+    fn lowercase_key_code() {
+        let shifted = DummyKeyEvent {
+            key_with_modifiers: KeyType::Unicode('A'),
+            key_without_modifiers: KeyType::Unicode('A'),
+            modifiers: KeyboardModifiers::SHIFT,
+            ..Default::default()
+        };
+        let mode = ReportingMode::REPORT_ALL_KEYS_AS_ESC | ReportingMode::REPORT_ALTERNATE_KEYS;
+        let options = LegacyOptions::default();
+
+        assert!(matches!(
+            generate_sequence_checked(mode, &options, &shifted),
+            Err(SequenceError::ShiftedKeyCode)
+        ));
+        assert_eq!(
+            format!(
+                "{}",
+                generate_resolved_sequence(
+                    mode,
+                    &options,
+                    ResolvedKeyEvent::new(&shifted).with_lowercase_key()
+                )
+            ),
+            "\x1b[97:65;2u"
+        );
+
+        let lowercase = DummyKeyEvent {
+            key_without_modifiers: KeyType::Unicode('a'),
+            ..shifted
+        };
+        assert_eq!(
+            format!(
+                "{}",
+                generate_sequence_checked(mode, &options, &lowercase).unwrap()
+            ),
+            "\x1b[97:65;2u"
+        );
+        // No single character lowercase form
+        assert_eq!(
+            KeyType::Unicode('\u{130}').lowercase(),
+            KeyType::Unicode('\u{130}')
+        );
+    }
+
+    #[test]
+    fn international_keys() {
+        let henkan = DummyKeyEvent {
+            key_with_modifiers: KeyType::Functional(FunctionalKey::Henkan),
+            key_without_modifiers: KeyType::Functional(FunctionalKey::Henkan),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            format!("{}", generate_sequence(ReportingMode::empty(), &henkan)),
+            ""
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                generate_sequence(ReportingMode::DISAMBIGUATE_ESC_CODES, &henkan)
+            ),
+            "\x1b[983041u"
+        );
+        assert_eq!(
+            parse::ParsedKey::parse(b"\x1b[983041u")
+                .unwrap()
+                .0
+                .key_with_modifiers(),
+            KeyType::Functional(FunctionalKey::Henkan)
+        );
+
+        let yen = DummyKeyEvent {
+            key_with_modifiers: KeyType::Functional(FunctionalKey::IntlYen),
+            key_without_modifiers: KeyType::Functional(FunctionalKey::IntlYen),
+            ..Default::default()
+        };
+        let disambiguate = ReportingMode::DISAMBIGUATE_ESC_CODES;
+        for mode in [ReportingMode::empty(), disambiguate] {
+            assert_eq!(format!("{}", generate_sequence(mode, &yen)), "¥");
+        }
+        assert_eq!(
+            format!(
+                "{}",
+                generate_sequence(ReportingMode::REPORT_ALL_KEYS_AS_ESC, &yen)
+            ),
+            "\x1b[983049u"
+        );
+
+        // The layout's text wins, without it shifted keys are sequences
+        let ro = DummyKeyEvent {
+            key_with_modifiers: KeyType::Functional(FunctionalKey::IntlRo),
+            key_without_modifiers: KeyType::Functional(FunctionalKey::IntlRo),
+            modifiers: KeyboardModifiers::SHIFT,
+            ..Default::default()
+        };
+        assert_eq!(
+            format!("{}", generate_sequence(disambiguate, &ro)),
+            "\x1b[983048;2u"
+        );
+        let ro = DummyKeyEvent {
+            associated_text: Some("_".into()),
+            ..ro
+        };
+        for mode in [ReportingMode::empty(), disambiguate] {
+            assert_eq!(format!("{}", generate_sequence(mode, &ro)), "_");
+        }
+    }
+
+    #[test]
+    fn rxvt_alt_prefix() {
+        let alt_up = DummyKeyEvent {
+            key_with_modifiers: KeyType::Functional(FunctionalKey::Up),
+            key_without_modifiers: KeyType::Functional(FunctionalKey::Up),
+            modifiers: KeyboardModifiers::ALT,
+            ..Default::default()
+        };
+        let rxvt = legacy::Personality::Rxvt.options();
+
+        assert_eq!(
+            format!("{}", generate_sequence(ReportingMode::empty(), &alt_up)),
+            "\x1b[;3A"
+        );
+        let response = generate_sequence_with(ReportingMode::empty(), &rxvt, &alt_up);
+        assert!(response.alt_pressed());
+        assert_eq!(format!("{response}"), "\x1b\x1b[A");
+
+        let ctrl_alt_delete = DummyKeyEvent {
+            key_with_modifiers: KeyType::Functional(FunctionalKey::Delete),
+            key_without_modifiers: KeyType::Functional(FunctionalKey::Delete),
+            modifiers: KeyboardModifiers::ALT | KeyboardModifiers::CTRL,
+            ..Default::default()
+        };
+        assert_eq!(
+            format!(
+                "{}",
+                generate_sequence_with(ReportingMode::empty(), &rxvt, &ctrl_alt_delete)
+            ),
+            "\x1b\x1b[3;5~"
+        );
+
+        // The kitty protocol is unaffected
+        assert_eq!(
+            format!(
+                "{}",
+                generate_sequence_with(ReportingMode::DISAMBIGUATE_ESC_CODES, &rxvt, &alt_up)
+            ),
+            "\x1b[;3A"
+        );
+    }
 }
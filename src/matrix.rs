@@ -0,0 +1,232 @@
+use crate::{
+    filter::modifier_bit,
+    key::KeyType,
+    sequence::{EventType, KeyboardModifiers},
+    synthesize::SyntheticKeyEvent,
+};
+
+// Which switches are closed in a scan. Debouncing happens before this, every
+// change is reported as a key event.
+pub trait MatrixScan {
+    fn is_pressed(&self, row: usize, column: usize) -> bool;
+}
+
+impl<F: Fn(usize, usize) -> bool> MatrixScan for F {
+    fn is_pressed(&self, row: usize, column: usize) -> bool {
+        self(row, column)
+    }
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> MatrixScan for [[bool; COLUMNS]; ROWS] {
+    fn is_pressed(&self, row: usize, column: usize) -> bool {
+        position(self, row, column).is_some_and(|pressed| *pressed)
+    }
+}
+
+// What the key at each position produces, `KeyType::Unknown` for positions
+// without a switch
+pub trait MatrixKeymap {
+    fn key(&self, row: usize, column: usize) -> KeyType;
+
+    fn shifted_key(&self, row: usize, column: usize) -> KeyType {
+        self.key(row, column)
+    }
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> MatrixKeymap for [[KeyType; COLUMNS]; ROWS] {
+    fn key(&self, row: usize, column: usize) -> KeyType {
+        position(self, row, column).map_or(KeyType::Unknown, |key| *key)
+    }
+}
+
+// Unshifted and shifted key for each position
+impl<const ROWS: usize, const COLUMNS: usize> MatrixKeymap
+    for [[(KeyType, KeyType); COLUMNS]; ROWS]
+{
+    fn key(&self, row: usize, column: usize) -> KeyType {
+        position(self, row, column).map_or(KeyType::Unknown, |(key, _)| *key)
+    }
+
+    fn shifted_key(&self, row: usize, column: usize) -> KeyType {
+        position(self, row, column).map_or(KeyType::Unknown, |(_, key)| *key)
+    }
+}
+
+fn position<T, const COLUMNS: usize>(
+    matrix: &[[T; COLUMNS]],
+    row: usize,
+    column: usize,
+) -> Option<&T> {
+    matrix.get(row)?.get(column)
+}
+
+// Turns successive scans of a key matrix into press and release events
+#[derive(Debug, Clone)]
+pub struct Matrix<K, const ROWS: usize, const COLUMNS: usize> {
+    keymap: K,
+    pressed: [[bool; COLUMNS]; ROWS],
+    modifiers: KeyboardModifiers,
+}
+
+impl<K: MatrixKeymap, const ROWS: usize, const COLUMNS: usize> Matrix<K, ROWS, COLUMNS> {
+    pub fn new(keymap: K) -> Self {
+        Self {
+            keymap,
+            pressed: [[false; COLUMNS]; ROWS],
+            modifiers: KeyboardModifiers::empty(),
+        }
+    }
+
+    pub fn modifiers(&self) -> KeyboardModifiers {
+        self.modifiers
+    }
+
+    pub fn scan(
+        &mut self,
+        state: &impl MatrixScan,
+        mut emit: impl FnMut(SyntheticKeyEvent<'static>),
+    ) {
+        // Modifiers first, Shift and a letter in the same scan is a capital
+        for modifiers in [true, false] {
+            for row in 0..ROWS {
+                for column in 0..COLUMNS {
+                    let pressed = state.is_pressed(row, column);
+                    let key = self.keymap.key(row, column);
+                    if key == KeyType::Unknown || modifier_bit(key).is_some() != modifiers {
+                        continue;
+                    }
+
+                    let Some(held) = self
+                        .pressed
+                        .get_mut(row)
+                        .and_then(|row| row.get_mut(column))
+                    else {
+                        continue;
+                    };
+                    if *held == pressed {
+                        continue;
+                    }
+
+                    *held = pressed;
+                    emit(self.event(row, column, pressed));
+                }
+            }
+        }
+    }
+
+    // Everything that is held as released, e.g. when the keyboard is unplugged
+    pub fn release_all(&mut self, emit: impl FnMut(SyntheticKeyEvent<'static>)) {
+        self.scan(&|_, _| false, emit);
+    }
+
+    fn event(&mut self, row: usize, column: usize, pressed: bool) -> SyntheticKeyEvent<'static> {
+        let key = self.keymap.key(row, column);
+        if let Some(modifier) = modifier_bit(key) {
+            self.modifiers.set(modifier, pressed);
+        }
+
+        let key_with_modifiers = if self.modifiers.intersects(KeyboardModifiers::SHIFT) {
+            self.keymap.shifted_key(row, column)
+        } else {
+            key
+        };
+
+        SyntheticKeyEvent {
+            key_with_modifiers,
+            key_without_modifiers: key,
+            key_base_layout: key,
+            modifiers: self.modifiers,
+            event_type: if pressed {
+                EventType::Press
+            } else {
+                EventType::Release
+            },
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_sequence, key::FunctionalKey, KeyInfo, ReportingMode};
+
+    extern crate std;
+    use std::{format, string::String, vec::Vec};
+
+    const UNKNOWN: (KeyType, KeyType) = (KeyType::Unknown, KeyType::Unknown);
+
+    fn char_key(base: char, shifted: char) -> (KeyType, KeyType) {
+        (KeyType::Unicode(base), KeyType::Unicode(shifted))
+    }
+
+    fn keymap() -> [[(KeyType, KeyType); 3]; 2] {
+        let shift = KeyType::Functional(FunctionalKey::LeftShift);
+        let ctrl = KeyType::Functional(FunctionalKey::LeftControl);
+        [
+            [char_key('a', 'A'), char_key('1', '!'), UNKNOWN],
+            [(shift, shift), (ctrl, ctrl), UNKNOWN],
+        ]
+    }
+
+    fn scan(
+        matrix: &mut Matrix<impl MatrixKeymap, 2, 3>,
+        state: [[bool; 3]; 2],
+    ) -> Vec<SyntheticKeyEvent<'static>> {
+        let mut events = Vec::new();
+        matrix.scan(&state, |event| events.push(event));
+        events
+    }
+
+    #[test]
+    fn presses_and_releases() {
+        let mut matrix = Matrix::new(keymap());
+        let events = scan(&mut matrix, [[true, false, true], [true, false, false]]);
+
+        // Shift comes first even though it is scanned last, the unused switch is ignored
+        let keys: Vec<_> = events.iter().map(|e| e.key_with_modifiers()).collect();
+        assert_eq!(
+            keys,
+            [
+                KeyType::Functional(FunctionalKey::LeftShift),
+                KeyType::Unicode('A')
+            ]
+        );
+        assert_eq!(events[1].key_without_modifiers, KeyType::Unicode('a'));
+        assert_eq!(events[1].modifiers, KeyboardModifiers::SHIFT);
+
+        assert!(scan(&mut matrix, [[true, false, true], [true, false, false]]).is_empty());
+
+        let events = scan(&mut matrix, [[false, false, false], [true, false, false]]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, EventType::Release);
+
+        let mut released = Vec::new();
+        matrix.release_all(|event| released.push(event));
+        assert_eq!(released.len(), 1);
+        assert_eq!(matrix.modifiers(), KeyboardModifiers::empty());
+    }
+
+    #[test]
+    fn encoded_ctrl_chord() {
+        let mut matrix = Matrix::new(keymap());
+        let out: String = scan(&mut matrix, [[true, false, false], [false, true, false]])
+            .iter()
+            .map(|event| format!("{}", generate_sequence(ReportingMode::empty(), event)))
+            .collect();
+        assert_eq!(out, "\x01");
+
+        // The event keeps the letter for the kitty encodings
+        let mut matrix = Matrix::new(keymap());
+        let events = scan(&mut matrix, [[true, false, false], [false, true, false]]);
+        let ctrl_a = events.last().unwrap();
+        assert_eq!(ctrl_a.key_with_modifiers, KeyType::Unicode('a'));
+        assert_eq!(
+            format!(
+                "{}",
+                generate_sequence(ReportingMode::DISAMBIGUATE_ESC_CODES, ctrl_a)
+            ),
+            "\x1b[97;5u"
+        );
+    }
+}
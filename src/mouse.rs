@@ -0,0 +1,584 @@
+use core::{fmt::Write, iter::Chain, option, time::Duration};
+
+use crate::{filter::Clock, sequence::KeyboardModifiers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+    Back,
+    Forward,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MouseEventKind {
+    #[default]
+    Press,
+    Release,
+    Motion,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MouseEvent {
+    pub kind: MouseEventKind,
+    // The button pressed or released, or held during motion
+    pub button: Option<MouseButton>,
+    // Zero based cell position
+    pub column: u16,
+    pub row: u16,
+    // Position in pixels, if the frontend knows it
+    pub pixel: Option<(u16, u16)>,
+    pub modifiers: KeyboardModifiers,
+}
+
+impl MouseEvent {
+    // Decodes the button code and one based position of an xterm report, the
+    // legacy encodings do not say which button was released
+    pub fn from_report(code: u32, x: u32, y: u32) -> Option<Self> {
+        let button = match code & !(4 | 8 | 16 | 32) {
+            0 => Some(MouseButton::Left),
+            1 => Some(MouseButton::Middle),
+            2 => Some(MouseButton::Right),
+            3 => None,
+            64 => Some(MouseButton::WheelUp),
+            65 => Some(MouseButton::WheelDown),
+            66 => Some(MouseButton::WheelLeft),
+            67 => Some(MouseButton::WheelRight),
+            128 => Some(MouseButton::Back),
+            129 => Some(MouseButton::Forward),
+            _ => return None,
+        };
+        let kind = match (code & 32 != 0, button) {
+            (true, _) => MouseEventKind::Motion,
+            (false, None) => MouseEventKind::Release,
+            (false, Some(_)) => MouseEventKind::Press,
+        };
+
+        let mut modifiers = KeyboardModifiers::empty();
+        if code & 4 != 0 {
+            modifiers |= KeyboardModifiers::SHIFT;
+        }
+        if code & 8 != 0 {
+            modifiers |= KeyboardModifiers::ALT;
+        }
+        if code & 16 != 0 {
+            modifiers |= KeyboardModifiers::CTRL;
+        }
+
+        Some(Self {
+            kind,
+            button,
+            column: u16::try_from(x.checked_sub(1)?).ok()?,
+            row: u16::try_from(y.checked_sub(1)?).ok()?,
+            pixel: None,
+            modifiers,
+        })
+    }
+}
+
+// The xterm tracking modes, DECSET 9, 1000, 1002 and 1003
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MouseTracking {
+    #[default]
+    None,
+    X10,
+    Normal,
+    ButtonEvent,
+    AnyEvent,
+}
+
+// The report formats, DECSET 1005, 1006, 1015 and 1016
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MouseEncoding {
+    #[default]
+    Default,
+    Utf8,
+    Sgr,
+    Urxvt,
+    SgrPixels,
+}
+
+// Writes the xterm report for `event`, or nothing if `tracking` does not
+// report it or the position cannot be encoded
+pub fn encode_mouse(
+    event: &MouseEvent,
+    tracking: MouseTracking,
+    encoding: MouseEncoding,
+    out: &mut impl Write,
+) -> core::fmt::Result {
+    let reported = match (tracking, event.kind) {
+        (MouseTracking::None, _) => false,
+        (MouseTracking::X10, kind) => kind == MouseEventKind::Press,
+        (MouseTracking::Normal, kind) => kind != MouseEventKind::Motion,
+        (MouseTracking::ButtonEvent, kind) => {
+            kind != MouseEventKind::Motion || event.button.is_some()
+        }
+        (MouseTracking::AnyEvent, _) => true,
+    };
+    let wheel = matches!(
+        event.button,
+        Some(
+            MouseButton::WheelUp
+                | MouseButton::WheelDown
+                | MouseButton::WheelLeft
+                | MouseButton::WheelRight
+        )
+    );
+    // Wheels have no release
+    if !reported || (wheel && event.kind == MouseEventKind::Release) {
+        return Ok(());
+    }
+
+    let sgr = matches!(encoding, MouseEncoding::Sgr | MouseEncoding::SgrPixels);
+    let mut code = match event.button {
+        // The legacy encodings do not say which button was released
+        _ if event.kind == MouseEventKind::Release && !sgr => 3,
+        Some(MouseButton::Left) => 0,
+        Some(MouseButton::Middle) => 1,
+        Some(MouseButton::Right) => 2,
+        Some(MouseButton::WheelUp) => 64,
+        Some(MouseButton::WheelDown) => 65,
+        Some(MouseButton::WheelLeft) => 66,
+        Some(MouseButton::WheelRight) => 67,
+        Some(MouseButton::Back) => 128,
+        Some(MouseButton::Forward) => 129,
+        None => 3,
+    };
+    if event.kind == MouseEventKind::Motion {
+        code += 32;
+    }
+    if tracking != MouseTracking::X10 {
+        let modifiers = event.modifiers;
+        if modifiers.intersects(KeyboardModifiers::SHIFT) {
+            code += 4;
+        }
+        if modifiers.intersects(KeyboardModifiers::ALT | KeyboardModifiers::META) {
+            code += 8;
+        }
+        if modifiers.intersects(KeyboardModifiers::CTRL) {
+            code += 16;
+        }
+    }
+
+    let (x, y) = match (encoding, event.pixel) {
+        (MouseEncoding::SgrPixels, Some(pixel)) => pixel,
+        (MouseEncoding::SgrPixels, None) => return Ok(()),
+        _ => (event.column, event.row),
+    };
+    let (x, y) = (u32::from(x) + 1, u32::from(y) + 1);
+
+    match encoding {
+        MouseEncoding::Sgr | MouseEncoding::SgrPixels => {
+            let end = if event.kind == MouseEventKind::Release {
+                'm'
+            } else {
+                'M'
+            };
+            write!(out, "\x1b[<{code};{x};{y}{end}")
+        }
+        MouseEncoding::Urxvt => write!(out, "\x1b[{};{x};{y}M", code + 32),
+        MouseEncoding::Utf8 | MouseEncoding::Default => {
+            // Values travel as a character each, `Default` would need raw
+            // bytes above 0x7f which a `Write` cannot carry
+            let limit = if encoding == MouseEncoding::Utf8 {
+                0x7ff
+            } else {
+                0x7f
+            };
+            let values = [code + 32, x + 32, y + 32];
+            if values.iter().any(|value| *value > limit) {
+                return Ok(());
+            }
+
+            out.write_str("\x1b[M")?;
+            values
+                .into_iter()
+                .filter_map(char::from_u32)
+                .try_for_each(|ch| out.write_char(ch))
+        }
+    }
+}
+
+pub type Coalesced = Chain<option::IntoIter<MouseEvent>, option::IntoIter<MouseEvent>>;
+
+// Holds back pointer motion so a flood of it (mode 1003) becomes one report
+// per cell, or per `budget` when that is set. Presses and releases pass
+// through at once, after the motion before them.
+#[derive(Debug, Clone)]
+pub struct MotionCoalescer<C> {
+    clock: C,
+    budget: Duration,
+    // The latest motion and when the first one it replaced came in
+    pending: Option<(MouseEvent, Duration)>,
+}
+
+impl<C: Clock> MotionCoalescer<C> {
+    pub fn new(clock: C) -> Self {
+        Self::with_budget(clock, Duration::ZERO)
+    }
+
+    // Motion across cells is merged too while it is less than `budget` old
+    pub fn with_budget(clock: C, budget: Duration) -> Self {
+        Self {
+            clock,
+            budget,
+            pending: None,
+        }
+    }
+
+    pub fn push(&mut self, event: MouseEvent) -> Coalesced {
+        if event.kind != MouseEventKind::Motion {
+            return self
+                .pending
+                .take()
+                .map(|(motion, _)| motion)
+                .into_iter()
+                .chain(Some(event));
+        }
+
+        let now = self.clock.now();
+        match &mut self.pending {
+            Some((pending, since))
+                if same_motion(pending, &event) || now.saturating_sub(*since) < self.budget =>
+            {
+                *pending = event;
+                None.into_iter().chain(None)
+            }
+            pending => pending
+                .replace((event, now))
+                .map(|(motion, _)| motion)
+                .into_iter()
+                .chain(None),
+        }
+    }
+
+    // The held back motion once its budget ran out; call it from a timer
+    pub fn poll(&mut self) -> Option<MouseEvent> {
+        let (_, since) = self.pending?;
+        if self.clock.now().saturating_sub(since) < self.budget {
+            return None;
+        }
+        self.flush()
+    }
+
+    pub fn flush(&mut self) -> Option<MouseEvent> {
+        self.pending.take().map(|(motion, _)| motion)
+    }
+}
+
+// Reports for these look the same unless pixels are reported
+fn same_motion(a: &MouseEvent, b: &MouseEvent) -> bool {
+    a.column == b.column && a.row == b.row && a.button == b.button && a.modifiers == b.modifiers
+}
+
+// Set by DECELR
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LocatorReporting {
+    #[default]
+    Disabled,
+    Enabled,
+    OneShot,
+}
+
+// The DEC locator protocol: reports are requested with DECRQLP or sent for
+// the button transitions selected with DECSLE, as `CSI Pe;Pb;Pr;Pc;Pp &w`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Locator {
+    reporting: LocatorReporting,
+    pixels: bool,
+    report_press: bool,
+    report_release: bool,
+
+    buttons: u8,
+    position: Option<(u16, u16)>,
+    pixel: Option<(u16, u16)>,
+}
+
+impl Locator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reporting(&self) -> LocatorReporting {
+        self.reporting
+    }
+
+    // DECELR, `CSI Ps ; Pu ' z`
+    pub fn set_reporting(&mut self, ps: u16, pu: u16) {
+        self.reporting = match ps {
+            1 => LocatorReporting::Enabled,
+            2 => LocatorReporting::OneShot,
+            _ => LocatorReporting::Disabled,
+        };
+        self.pixels = pu == 1;
+    }
+
+    // DECSLE, `CSI Ps ' {`, once for each parameter
+    pub fn select_events(&mut self, ps: u16) {
+        match ps {
+            0 => {
+                self.report_press = false;
+                self.report_release = false;
+            }
+            1 => self.report_press = true,
+            2 => self.report_press = false,
+            3 => self.report_release = true,
+            4 => self.report_release = false,
+            _ => {}
+        }
+    }
+
+    // DECRQLP, `CSI Ps ' |`
+    pub fn request(&mut self, out: &mut impl Write) -> core::fmt::Result {
+        if self.reporting == LocatorReporting::Disabled {
+            return out.write_str("\x1b[0&w");
+        }
+        self.report(1, out)
+    }
+
+    // Tracks the pointer and reports the selected button transitions
+    pub fn mouse_event(&mut self, event: &MouseEvent, out: &mut impl Write) -> core::fmt::Result {
+        self.position = Some((event.row, event.column));
+        self.pixel = event.pixel;
+
+        let Some(button) = event.button.and_then(locator_button) else {
+            return Ok(());
+        };
+        let (bit, press_event) = button;
+
+        let report = match event.kind {
+            MouseEventKind::Press => {
+                self.buttons |= bit;
+                self.report_press.then_some(press_event)
+            }
+            MouseEventKind::Release => {
+                self.buttons &= !bit;
+                self.report_release.then_some(press_event + 1)
+            }
+            MouseEventKind::Motion => None,
+        };
+
+        match report {
+            Some(pe) if self.reporting != LocatorReporting::Disabled => self.report(pe, out),
+            _ => Ok(()),
+        }
+    }
+
+    fn report(&mut self, pe: u8, out: &mut impl Write) -> core::fmt::Result {
+        let position = match (self.pixels, self.pixel, self.position) {
+            (true, Some((x, y)), _) => Some((u32::from(y) + 1, u32::from(x) + 1)),
+            (false, _, Some((row, column))) => Some((u32::from(row) + 1, u32::from(column) + 1)),
+            _ => None,
+        };
+        if self.reporting == LocatorReporting::OneShot {
+            self.reporting = LocatorReporting::Disabled;
+        }
+
+        match position {
+            Some((row, column)) => write!(out, "\x1b[{pe};{};{row};{column};1&w", self.buttons),
+            // The pointer was never seen, report it as unavailable
+            None => out.write_str("\x1b[0&w"),
+        }
+    }
+}
+
+// The bit in Pb and the Pe of a press, the release is the next code
+fn locator_button(button: MouseButton) -> Option<(u8, u8)> {
+    Some(match button {
+        MouseButton::Right => (1, 6),
+        MouseButton::Middle => (2, 4),
+        MouseButton::Left => (4, 2),
+        MouseButton::Back => (8, 8),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use core::cell::Cell;
+
+    extern crate std;
+    use std::{string::String, vec::Vec};
+
+    fn motion(column: u16) -> MouseEvent {
+        MouseEvent {
+            kind: MouseEventKind::Motion,
+            column,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn motion_coalescing() {
+        let now = Cell::new(Duration::ZERO);
+        let mut coalescer = MotionCoalescer::new(|| now.get());
+
+        assert_eq!(coalescer.push(motion(1)).count(), 0);
+        assert_eq!(coalescer.push(motion(1)).count(), 0);
+        let moved: Vec<_> = coalescer.push(motion(2)).collect();
+        assert_eq!(moved, [motion(1)]);
+
+        let press = click(MouseEventKind::Press, MouseButton::Left);
+        let flushed: Vec<_> = coalescer.push(press).collect();
+        assert_eq!(flushed, [motion(2), press]);
+        assert_eq!(coalescer.flush(), None);
+
+        let mut coalescer = MotionCoalescer::with_budget(|| now.get(), Duration::from_millis(16));
+        assert_eq!(coalescer.push(motion(1)).count(), 0);
+        now.set(Duration::from_millis(10));
+        assert_eq!(coalescer.push(motion(2)).count(), 0);
+        assert_eq!(coalescer.poll(), None);
+        now.set(Duration::from_millis(16));
+        assert_eq!(coalescer.poll(), Some(motion(2)));
+    }
+
+    fn click(kind: MouseEventKind, button: MouseButton) -> MouseEvent {
+        MouseEvent {
+            kind,
+            button: Some(button),
+            column: 9,
+            row: 4,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn locator_requests() {
+        let mut locator = Locator::new();
+        let mut out = String::new();
+
+        locator.request(&mut out).unwrap();
+        assert_eq!(out, "\x1b[0&w");
+
+        out.clear();
+        locator.set_reporting(2, 0);
+        locator
+            .mouse_event(&click(MouseEventKind::Press, MouseButton::Left), &mut out)
+            .unwrap();
+        assert_eq!(out, "");
+        locator.request(&mut out).unwrap();
+        assert_eq!(out, "\x1b[1;4;5;10;1&w");
+
+        // One shot, disabled after the report
+        assert_eq!(locator.reporting(), LocatorReporting::Disabled);
+    }
+
+    #[test]
+    fn locator_button_events() {
+        let mut locator = Locator::new();
+        let mut out = String::new();
+        locator.set_reporting(1, 0);
+        locator.select_events(1);
+        locator.select_events(3);
+
+        locator
+            .mouse_event(&click(MouseEventKind::Press, MouseButton::Right), &mut out)
+            .unwrap();
+        locator
+            .mouse_event(&click(MouseEventKind::Press, MouseButton::Left), &mut out)
+            .unwrap();
+        locator
+            .mouse_event(
+                &click(MouseEventKind::Release, MouseButton::Right),
+                &mut out,
+            )
+            .unwrap();
+        locator
+            .mouse_event(
+                &click(MouseEventKind::Press, MouseButton::WheelUp),
+                &mut out,
+            )
+            .unwrap();
+        assert_eq!(out, "\x1b[6;1;5;10;1&w\x1b[2;5;5;10;1&w\x1b[7;4;5;10;1&w");
+
+        out.clear();
+        locator.select_events(2);
+        locator
+            .mouse_event(&click(MouseEventKind::Release, MouseButton::Left), &mut out)
+            .unwrap();
+        locator
+            .mouse_event(&click(MouseEventKind::Press, MouseButton::Left), &mut out)
+            .unwrap();
+        assert_eq!(out, "\x1b[3;0;5;10;1&w");
+    }
+
+    #[test]
+    fn locator_pixels() {
+        let mut locator = Locator::new();
+        let mut out = String::new();
+        locator.set_reporting(1, 1);
+
+        locator.request(&mut out).unwrap();
+        assert_eq!(out, "\x1b[0&w");
+
+        out.clear();
+        let motion = MouseEvent {
+            kind: MouseEventKind::Motion,
+            pixel: Some((120, 33)),
+            ..Default::default()
+        };
+        locator.mouse_event(&motion, &mut out).unwrap();
+        locator.request(&mut out).unwrap();
+        assert_eq!(out, "\x1b[1;0;34;121;1&w");
+    }
+
+    #[test]
+    fn xterm_reports() {
+        let mut press = click(MouseEventKind::Press, MouseButton::Left);
+        press.modifiers = KeyboardModifiers::CTRL;
+        let release = click(MouseEventKind::Release, MouseButton::Left);
+        let encode = |event: &MouseEvent, tracking, encoding| {
+            let mut out = String::new();
+            encode_mouse(event, tracking, encoding, &mut out).unwrap();
+            out
+        };
+
+        assert_eq!(
+            encode(&press, MouseTracking::Normal, MouseEncoding::Sgr),
+            "\x1b[<16;10;5M"
+        );
+        assert_eq!(
+            encode(&release, MouseTracking::Normal, MouseEncoding::Sgr),
+            "\x1b[<0;10;5m"
+        );
+        assert_eq!(
+            encode(&release, MouseTracking::Normal, MouseEncoding::Default),
+            "\x1b[M#*%"
+        );
+        assert_eq!(
+            encode(&press, MouseTracking::X10, MouseEncoding::Urxvt),
+            "\x1b[32;10;5M"
+        );
+        assert_eq!(encode(&release, MouseTracking::X10, MouseEncoding::Sgr), "");
+
+        let motion = MouseEvent {
+            kind: MouseEventKind::Motion,
+            column: 200,
+            ..Default::default()
+        };
+        assert_eq!(
+            encode(&motion, MouseTracking::ButtonEvent, MouseEncoding::Sgr),
+            ""
+        );
+        assert_eq!(
+            encode(&motion, MouseTracking::AnyEvent, MouseEncoding::Sgr),
+            "\x1b[<35;201;1M"
+        );
+        assert_eq!(
+            encode(&motion, MouseTracking::AnyEvent, MouseEncoding::Default),
+            ""
+        );
+        assert_eq!(
+            encode(&motion, MouseTracking::AnyEvent, MouseEncoding::Utf8),
+            "\x1b[MC\u{e9}!"
+        );
+    }
+}
@@ -0,0 +1,1926 @@
+use core::time::Duration;
+
+use crate::{
+    key::{FunctionalKey, KeyType},
+    legacy::FunctionKeys,
+    mouse::{MouseEvent, MouseEventKind},
+    sequence::{
+        AssociatedText, EventType, KeyCode, KeyboardModifiers, ModifierParam, Sequence,
+        SequenceIntroducer, SequenceTerminator,
+    },
+    transcode::control_key,
+    EventResponse, KeyInfo, ModifierInfo, ReportingMode, TextInfo,
+};
+
+// Associated text beyond this is dropped, kitty rarely sends more than one
+// character
+pub const MAX_TEXT_LEN: usize = 32;
+
+// The longest end marker, `ESC [ 201 ~` or `CSI 201 ~` with an encoded C1 CSI
+const PASTE_END_LEN: usize = 6;
+
+pub const DEFAULT_ESCAPE_TIMEOUT: Duration = Duration::from_millis(50);
+
+// Text and pastes borrow from the parsed buffer, they stay valid until the
+// caller reuses it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TerminalEvent<'a> {
+    Key(ParsedKey),
+    Text(&'a str),
+    // The content of a bracketed paste, not necessarily UTF-8
+    Paste(&'a [u8]),
+    // A piece of a paste too long for the buffer of an `InputParser`, the
+    // last piece comes as a `Paste`
+    PastePart(&'a [u8]),
+    Mouse(MouseEvent),
+    // `CSI I` and `CSI O`, sent while focus reporting (DECSET 1004) is on
+    FocusGained,
+    FocusLost,
+    // In band resize notifications (DECSET 2048) and replies to `CSI 18 t`
+    Resize(WindowSize),
+    // The terminal's current progressive enhancement flags
+    KeyboardFlags(ReportingMode),
+    // DA1, `CSI ? Ps... c`, also the end of the kitty support probe
+    PrimaryAttributes(DeviceAttributes),
+    // DA2, `CSI > Pp;Pv;Pc c`
+    SecondaryAttributes(DeviceAttributes),
+    // A well formed sequence this parser does not know
+    Unknown(&'a [u8]),
+    // Bytes that do not form a valid sequence or UTF-8 text, parsing resumes
+    // after them
+    Malformed(&'a [u8]),
+}
+
+impl<'a> TerminalEvent<'a> {
+    // The bytes of `Unknown` and `Malformed` events, to log interop problems
+    pub fn unparsed(&self) -> Option<&'a [u8]> {
+        match self {
+            TerminalEvent::Unknown(bytes) | TerminalEvent::Malformed(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    // The input ends in the middle of a sequence, feed more
+    Incomplete,
+    // Returned by `ParsedKey::parse` for other events, with their length so
+    // they can be skipped
+    NotAKey(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WindowSize {
+    pub rows: u16,
+    pub columns: u16,
+    // Width and height in pixels, only in resize notifications
+    pub pixels: Option<(u16, u16)>,
+}
+
+impl WindowSize {
+    // `48;rows;columns;height;width` or `8;rows;columns`
+    fn parse(params: &[u8]) -> Option<Self> {
+        let mut fields = params.split(|byte| *byte == b';').map(|field| {
+            number(field)
+                .ok()
+                .and_then(|value| u16::try_from(value).ok())
+        });
+        let kind = fields.next()??;
+        let rows = fields.next()??;
+        let columns = fields.next()??;
+        let pixels = match kind {
+            48 => {
+                let height = fields.next()??;
+                Some((fields.next()??, height))
+            }
+            8 => None,
+            _ => return None,
+        };
+        fields.next().is_none().then_some(Self {
+            rows,
+            columns,
+            pixels,
+        })
+    }
+}
+
+pub const MAX_ATTRIBUTES: usize = 16;
+
+// The parameters of a device attributes reply, those beyond `MAX_ATTRIBUTES`
+// are dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DeviceAttributes {
+    params: [u32; MAX_ATTRIBUTES],
+    len: u8,
+}
+
+impl DeviceAttributes {
+    pub fn params(&self) -> &[u32] {
+        head(&self.params, usize::from(self.len))
+    }
+
+    // For DA1 the conformance level, for DA2 the terminal type
+    pub fn terminal_type(&self) -> Option<u32> {
+        self.params().first().copied()
+    }
+
+    // The firmware version in a DA2 reply
+    pub fn version(&self) -> Option<u32> {
+        self.params().get(1).copied()
+    }
+
+    fn parse(params: &[u8]) -> Option<Self> {
+        let mut attributes = Self::default();
+        if params.is_empty() {
+            return Some(attributes);
+        }
+        for param in params.split(|byte| *byte == b';') {
+            let param = number(param).ok()?;
+            if let Some(slot) = attributes.params.get_mut(usize::from(attributes.len)) {
+                *slot = param;
+                attributes.len += 1;
+            }
+        }
+        Some(attributes)
+    }
+}
+
+// A key report as sent by a terminal, implementing `KeyEvent` so it can be
+// fed into the rest of the crate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedKey {
+    pub introducer: SequenceIntroducer,
+    pub key_code: KeyCode,
+    pub modifier: KeyboardModifiers,
+    pub event_type: EventType,
+    pub terminator: SequenceTerminator,
+    text: [u8; MAX_TEXT_LEN],
+    text_len: u8,
+}
+
+impl ParsedKey {
+    // A single key report, e.g. to read `generate_sequence` output back
+    pub fn parse(input: &[u8]) -> Result<(Self, usize), ParseError> {
+        match parse(input)? {
+            (TerminalEvent::Key(key), len) => Ok((key, len)),
+            (_, len) => Err(ParseError::NotAKey(len)),
+        }
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        let text = head(&self.text, usize::from(self.text_len));
+        // Only whole characters are ever stored
+        (!text.is_empty()).then(|| core::str::from_utf8(text).unwrap_or_default())
+    }
+
+    pub fn sequence(&self) -> Sequence<'_> {
+        Sequence {
+            introducer: self.introducer,
+            key_code: self.key_code.clone(),
+            modifier: self.modifier,
+            event_type: self.event_type,
+            associated_text: self.text().map(AssociatedText),
+            terminator: self.terminator,
+        }
+    }
+
+    fn push_text(&mut self, ch: char) {
+        let len = usize::from(self.text_len);
+        if len + ch.len_utf8() <= MAX_TEXT_LEN {
+            if let Some(buffer) = self.text.get_mut(len..) {
+                ch.encode_utf8(buffer);
+                self.text_len += ch.len_utf8() as u8;
+            }
+        }
+    }
+}
+
+impl KeyInfo for ParsedKey {
+    fn key_with_modifiers(&self) -> KeyType {
+        match (self.key_code.shifted_key_code, self.text()) {
+            (Some(shifted), _) => KeyType::from_kitty_code(shifted, SequenceTerminator::Kitty),
+            (None, Some(text)) if text.chars().count() == 1 => text
+                .chars()
+                .next()
+                .map_or(KeyType::Unknown, KeyType::Unicode),
+            _ => self.key_without_modifiers(),
+        }
+    }
+
+    fn key_without_modifiers(&self) -> KeyType {
+        KeyType::from_kitty_code(self.key_code.key_code, self.terminator)
+    }
+
+    fn key_base_layout(&self) -> KeyType {
+        match self.key_code.base_layout_key_code {
+            Some(base) => KeyType::from_kitty_code(base, SequenceTerminator::Kitty),
+            None => self.key_without_modifiers(),
+        }
+    }
+
+    fn event_type(&self) -> EventType {
+        self.event_type
+    }
+}
+
+impl ModifierInfo for ParsedKey {
+    fn modifiers(&self) -> KeyboardModifiers {
+        self.modifier
+    }
+}
+
+impl TextInfo for ParsedKey {
+    fn associated_text(&self) -> Option<AssociatedText<'_>> {
+        self.text().map(AssociatedText)
+    }
+}
+
+// The dialects the parser accepts, sequences of the others are reported as
+// `Unknown`. Everything but X10 mouse reports is accepted by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserConfig {
+    // `CSI u` and the kitty additions to legacy sequences: alternate keys,
+    // event types and text
+    pub kitty: bool,
+    // xterm's `CSI 1;m A`, `CSI n;m ~` and `SS3 A`
+    pub legacy: bool,
+    // `CSI n $`, `CSI n ^` and `CSI n @` for modified editing keys, `CSI a`
+    // and `SS3 a` for modified arrows
+    pub rxvt: bool,
+    // `CSI b;x;y M`
+    pub urxvt_mouse: bool,
+    // `CSI < b;x;y M` and `m` for releases, also used for pixel positions
+    // (DECSET 1016) which the parser cannot tell apart
+    pub sgr_mouse: bool,
+    // `CSI M` and three bytes, the default encoding. The UTF-8 variant
+    // (DECSET 1005) is not decoded. Off by default as SCO terminals send F1
+    // as `CSI M`, turn it on with mouse reporting.
+    pub x10_mouse: bool,
+    // `CSI 27;m;c ~`
+    pub modify_other_keys: bool,
+    // The 8-bit CSI, SS3 and DCS, raw or UTF-8 encoded. Off by default as
+    // raw C1 bytes are not UTF-8.
+    pub c1: bool,
+    // C0 controls and DEL as keys, e.g. DEL as Backspace and `^A` as Ctrl+a,
+    // rather than as part of the text. Off by default as pasted or echoed
+    // text keeps its tabs and newlines.
+    pub control_keys: bool,
+    // The function key set of the terminal, Sun's `CSI n z` and SCO's
+    // `CSI G` to `CSI X` are read as the keys they stand for. SCO's take
+    // `CSI I` and `CSI O` from focus reports.
+    pub function_keys: FunctionKeys,
+    // How long an ESC waits for the rest of a sequence before it is taken as
+    // the Escape key, see `parse_idle`
+    pub escape_timeout: Duration,
+    pub limits: ParserLimits,
+}
+
+// Bounds for input from an untrusted pty. Sequences past them are reported as
+// `Malformed` and parsing resumes after them, a sequence without an end never
+// keeps the parser waiting for more than `max_sequence_len` bytes. Pastes are
+// only bounded by the buffer, `InputParser` hands long ones out in parts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserLimits {
+    // CSI and DCS sequences, introducer and final byte included
+    pub max_sequence_len: usize,
+    // Parameters and sub-parameters of a CSI sequence
+    pub max_params: usize,
+    pub max_param_value: u32,
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        Self {
+            max_sequence_len: 1024,
+            // Room for the longest text a key report can carry
+            max_params: 2 * MAX_TEXT_LEN,
+            // Enough for any key code
+            max_param_value: u32::from(char::MAX),
+        }
+    }
+}
+
+impl ParserLimits {
+    fn allow(&self, params: &[u8]) -> bool {
+        let count = params
+            .iter()
+            .filter(|byte| matches!(byte, b';' | b':'))
+            .count();
+        count < self.max_params
+            && params
+                .split(|byte| !byte.is_ascii_digit())
+                .filter(|digits| !digits.is_empty())
+                .all(|digits| number(digits).is_ok_and(|value| value <= self.max_param_value))
+    }
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            kitty: true,
+            legacy: true,
+            rxvt: true,
+            urxvt_mouse: true,
+            sgr_mouse: true,
+            x10_mouse: false,
+            modify_other_keys: true,
+            c1: false,
+            control_keys: false,
+            function_keys: FunctionKeys::Vt220,
+            escape_timeout: DEFAULT_ESCAPE_TIMEOUT,
+            limits: ParserLimits::default(),
+        }
+    }
+}
+
+impl ParserConfig {
+    // Only what a terminal speaking the kitty protocol sends
+    pub fn kitty() -> Self {
+        Self {
+            kitty: true,
+            legacy: true,
+            rxvt: false,
+            urxvt_mouse: false,
+            sgr_mouse: true,
+            x10_mouse: true,
+            modify_other_keys: false,
+            c1: false,
+            control_keys: false,
+            function_keys: FunctionKeys::Vt220,
+            escape_timeout: DEFAULT_ESCAPE_TIMEOUT,
+            limits: ParserLimits::default(),
+        }
+    }
+
+    // Parses the event at the start of `input`, returning it and the number
+    // of bytes it took
+    pub fn parse<'a>(&self, input: &'a [u8]) -> Result<(TerminalEvent<'a>, usize), ParseError> {
+        match input {
+            [] => Err(ParseError::Incomplete),
+            [0x1b, b'[', ..] => self.parse_after_csi(input, 2),
+            [0x1b, b'O', ..] => self.parse_ss3(input, 2),
+            // Alt+Escape or rxvt's ESC prefix for Alt
+            [0x1b] | [0x1b, 0x1b] => Err(ParseError::Incomplete),
+            [0x1b, 0x1b, b'[' | b'O', ..] => {
+                self.parse(tail(input, 1)).map(|(event, len)| match event {
+                    TerminalEvent::Key(key) => (TerminalEvent::Key(with_alt(key)), len + 1),
+                    _ => (TerminalEvent::Unknown(head(input, 1)), 1),
+                })
+            }
+            [0x1b, 0x1b, ..] => Ok((
+                TerminalEvent::Key(with_alt(functional(FunctionalKey::Escape))),
+                2,
+            )),
+            // DCS replies of terminals that send 7-bit controls, HP's keys
+            // send ESC P for Delete
+            [0x1b, b'P', ..] if self.function_keys != FunctionKeys::Hp => {
+                parse_dcs(input, 2, self.limits.max_sequence_len)
+            }
+            // ESC followed by a character is how Alt is sent
+            [0x1b, rest @ ..] => match parse_text(rest, self.c1)? {
+                (TerminalEvent::Text(text), _) => {
+                    let ch = text.chars().next().unwrap_or_default();
+                    Ok((
+                        TerminalEvent::Key(with_alt(char_key(ch))),
+                        1 + ch.len_utf8(),
+                    ))
+                }
+                _ => Ok((TerminalEvent::Unknown(head(input, 1)), 1)),
+            },
+            _ => match c1_introducer(input).filter(|_| self.c1) {
+                Some((0x9b, len)) => self.parse_after_csi(input, len),
+                Some((0x8f, len)) => self.parse_ss3(input, len),
+                Some((_, len)) => parse_dcs(input, len, self.limits.max_sequence_len),
+                None if self.control_keys => match input {
+                    [byte @ (0..=0x1f | 0x7f), ..] => {
+                        Ok((TerminalEvent::Key(char_key(char::from(*byte))), 1))
+                    }
+                    _ => parse_text(input, self.c1).map(split_controls),
+                },
+                None => parse_text(input, self.c1),
+            },
+        }
+    }
+
+    // Like `parse`, but settles what is still incomplete once no input came
+    // for `idle`: a lone ESC is the Escape key and `ESC [` is Alt+[ rather
+    // than the start of a sequence
+    pub fn parse_idle<'a>(
+        &self,
+        input: &'a [u8],
+        idle: Duration,
+    ) -> Result<(TerminalEvent<'a>, usize), ParseError> {
+        match self.parse(input) {
+            Err(ParseError::Incomplete) if !input.is_empty() && idle >= self.escape_timeout => {
+                let escape = functional(FunctionalKey::Escape);
+                Ok(match input {
+                    [0x1b] => (TerminalEvent::Key(escape), 1),
+                    [0x1b, 0x1b] => (TerminalEvent::Key(with_alt(escape)), 2),
+                    [0x1b, second @ (b'[' | b'O' | b'P')] => (
+                        TerminalEvent::Key(with_alt(char_key(char::from(*second)))),
+                        2,
+                    ),
+                    _ => (TerminalEvent::Malformed(input), input.len()),
+                })
+            }
+            result => result,
+        }
+    }
+
+    // Pastes and X10 mouse reports only start like CSI sequences, `intro` is
+    // the length of the CSI
+    fn parse_after_csi<'a>(
+        &self,
+        input: &'a [u8],
+        intro: usize,
+    ) -> Result<(TerminalEvent<'a>, usize), ParseError> {
+        match tail(input, intro) {
+            // The whole paste has to be in the buffer
+            [b'2', b'0', b'0', b'~', paste @ ..] => paste_end(paste, self.c1)
+                .map(|(end, marker)| {
+                    (
+                        TerminalEvent::Paste(head(paste, end)),
+                        intro + 4 + end + marker,
+                    )
+                })
+                .ok_or(ParseError::Incomplete),
+            [b'M', report @ ..] if self.x10_mouse => match report {
+                [code, x, y, ..] => Ok((
+                    x10_mouse(*code, *x, *y).map_or(
+                        TerminalEvent::Malformed(head(input, intro + 4)),
+                        TerminalEvent::Mouse,
+                    ),
+                    intro + 4,
+                )),
+                _ => Err(ParseError::Incomplete),
+            },
+            _ => self.parse_csi(input, intro),
+        }
+    }
+
+    // `intro` is the length of the SS3
+    fn parse_ss3<'a>(
+        &self,
+        input: &'a [u8],
+        intro: usize,
+    ) -> Result<(TerminalEvent<'a>, usize), ParseError> {
+        match input.get(intro) {
+            None => Err(ParseError::Incomplete),
+            Some(final_byte @ 0x40..=0x7e) => {
+                Ok((self.ss3(head(input, intro + 1), *final_byte), intro + 1))
+            }
+            Some(_) => Ok((TerminalEvent::Malformed(head(input, intro)), intro)),
+        }
+    }
+
+    fn ss3<'a>(&self, sequence: &'a [u8], final_byte: u8) -> TerminalEvent<'a> {
+        let (final_byte, modifier) = match final_byte {
+            b'a'..=b'd' if self.rxvt => (final_byte - 0x20, KeyboardModifiers::CTRL),
+            _ if self.legacy => (final_byte, KeyboardModifiers::empty()),
+            _ => return TerminalEvent::Unknown(sequence),
+        };
+        let terminator = SequenceTerminator::Other(char::from(final_byte));
+        match KeyType::from_kitty_code(1, terminator) {
+            KeyType::Functional(_) => TerminalEvent::Key(ParsedKey {
+                introducer: SequenceIntroducer::SS3,
+                key_code: KeyCode {
+                    key_code: 1,
+                    ..Default::default()
+                },
+                modifier,
+                ..key(terminator)
+            }),
+            _ => TerminalEvent::Unknown(sequence),
+        }
+    }
+
+    // `intro` is the length of the CSI
+    fn parse_csi<'a>(
+        &self,
+        input: &'a [u8],
+        intro: usize,
+    ) -> Result<(TerminalEvent<'a>, usize), ParseError> {
+        let rest = tail(input, intro);
+        let max_len = self.limits.max_sequence_len;
+        let mut params_end = 0;
+        for (index, byte) in rest.iter().enumerate() {
+            if intro + index >= max_len {
+                return Ok((TerminalEvent::Malformed(head(input, max_len)), max_len));
+            }
+            let params = head(rest, params_end);
+            match byte {
+                0x30..=0x3f if params_end == index => params_end += 1,
+                // rxvt's shifted editing keys end in an intermediate byte
+                b'$' if self.rxvt && params_end == index && is_number(params) => {
+                    let event = self.csi_event(head(input, intro + index + 1), params, *byte);
+                    return Ok((event, intro + index + 1));
+                }
+                // Intermediate bytes
+                0x20..=0x2f => {}
+                0x40..=0x7e => {
+                    let sequence = head(input, intro + index + 1);
+                    // Intermediates belong to other protocols
+                    let event = if params_end == index {
+                        self.csi_event(sequence, params, *byte)
+                    } else {
+                        TerminalEvent::Unknown(sequence)
+                    };
+                    return Ok((event, intro + index + 1));
+                }
+                // Anything else cannot be part of a sequence, resynchronize on it
+                _ => {
+                    let len = intro + index;
+                    return Ok((TerminalEvent::Malformed(head(input, len)), len));
+                }
+            }
+        }
+
+        Err(ParseError::Incomplete)
+    }
+
+    fn csi_event<'a>(
+        &self,
+        sequence: &'a [u8],
+        params: &[u8],
+        final_byte: u8,
+    ) -> TerminalEvent<'a> {
+        if !self.limits.allow(params) {
+            return TerminalEvent::Malformed(sequence);
+        }
+
+        match (params, final_byte) {
+            // The reply to `CSI ? u`, flags this crate does not know are kept
+            // as the terminal may support newer ones
+            ([b'?', flags @ ..], b'u') => {
+                return match number(flags) {
+                    Ok(flags) => {
+                        TerminalEvent::KeyboardFlags(ReportingMode::from_bits_retain(flags))
+                    }
+                    Err(()) => TerminalEvent::Malformed(sequence),
+                };
+            }
+            ([marker @ (b'?' | b'>'), attributes @ ..], b'c') => {
+                return match DeviceAttributes::parse(attributes) {
+                    Some(attributes) if *marker == b'?' => {
+                        TerminalEvent::PrimaryAttributes(attributes)
+                    }
+                    Some(attributes) => TerminalEvent::SecondaryAttributes(attributes),
+                    None => TerminalEvent::Malformed(sequence),
+                };
+            }
+            ([b'<', report @ ..], b'M' | b'm') if self.sgr_mouse => {
+                return sgr_mouse(report, final_byte == b'm')
+                    .map_or(TerminalEvent::Malformed(sequence), TerminalEvent::Mouse);
+            }
+            // Private markers belong to other protocols
+            ([b'<'..=b'?', ..], _) => return TerminalEvent::Unknown(sequence),
+            _ => {}
+        }
+        let fields = params.iter().filter(|byte| **byte == b';').count() + 1;
+
+        let report = match final_byte {
+            // SCO's PageUp and F3
+            b'I' | b'O' if self.function_keys == FunctionKeys::Sco => {
+                key_report(params, SequenceTerminator::Other(char::from(final_byte)))
+            }
+            b'I' if params.is_empty() => return TerminalEvent::FocusGained,
+            b'O' if params.is_empty() => return TerminalEvent::FocusLost,
+            b't' => {
+                return WindowSize::parse(params)
+                    .map_or(TerminalEvent::Unknown(sequence), TerminalEvent::Resize);
+            }
+            b'M' if self.urxvt_mouse && fields == 3 => {
+                return urxvt_mouse(params)
+                    .map_or(TerminalEvent::Malformed(sequence), TerminalEvent::Mouse);
+            }
+            b'~' if self.modify_other_keys && params.starts_with(b"27;") => {
+                modify_other_keys(params)
+            }
+            b'u' if self.kitty => key_report(params, SequenceTerminator::Kitty),
+            b'$' | b'^' | b'@' if self.rxvt && is_number(params) => {
+                let modifier = match final_byte {
+                    b'$' => KeyboardModifiers::SHIFT,
+                    b'^' => KeyboardModifiers::CTRL,
+                    _ => KeyboardModifiers::CTRL | KeyboardModifiers::SHIFT,
+                };
+                key_report(params, SequenceTerminator::Other('~'))
+                    .map(|key| ParsedKey { modifier, ..key })
+            }
+            b'a'..=b'd' if self.rxvt && params.is_empty() => {
+                let terminator = SequenceTerminator::Other(char::from(final_byte - 0x20));
+                key_report(params, terminator).map(|key| ParsedKey {
+                    modifier: KeyboardModifiers::SHIFT,
+                    ..key
+                })
+            }
+            // Shift+Tab, xterm adds the other modifiers as a parameter
+            b'Z' if self.legacy && fields <= 2 => {
+                key_report(params, SequenceTerminator::Other('Z')).map(|key| ParsedKey {
+                    modifier: key.modifier | KeyboardModifiers::SHIFT,
+                    event_type: key.event_type,
+                    ..functional(FunctionalKey::Tab)
+                })
+            }
+            // Alternate keys, event types and text are kitty's
+            b'A'..=b'Z' | b'~' if !self.kitty && (fields > 2 || params.contains(&b':')) => {
+                return TerminalEvent::Unknown(sequence);
+            }
+            b'A'..=b'Z' | b'~' if self.legacy => {
+                key_report(params, SequenceTerminator::Other(char::from(final_byte)))
+            }
+            b'z' if self.function_keys == FunctionKeys::Sun => {
+                key_report(params, SequenceTerminator::Other('z'))
+            }
+            _ => return TerminalEvent::Unknown(sequence),
+        };
+
+        match report.map(|key| self.function_key(key)) {
+            Ok(key) if key.key_without_modifiers() == KeyType::Unknown => {
+                TerminalEvent::Unknown(sequence)
+            }
+            Ok(key) => TerminalEvent::Key(key),
+            Err(()) => TerminalEvent::Malformed(sequence),
+        }
+    }
+
+    // The key a sequence of the configured function key set stands for
+    fn function_key(&self, key: ParsedKey) -> ParsedKey {
+        let func = FunctionalKey::ALL.iter().find(|func| {
+            matches!(
+                self.function_keys.response(**func, false),
+                Some(EventResponse::Sequence(sequence))
+                    if sequence.key_code.key_code == key.key_code.key_code
+                        && sequence.terminator == key.terminator
+            )
+        });
+        match func {
+            Some(func) => ParsedKey {
+                modifier: key.modifier,
+                event_type: key.event_type,
+                ..functional(*func)
+            },
+            None => key,
+        }
+    }
+}
+
+// Parses with the default, permissive, configuration
+pub fn parse(input: &[u8]) -> Result<(TerminalEvent<'_>, usize), ParseError> {
+    ParserConfig::default().parse(input)
+}
+
+// Reads the reply to a flags query, `CSI ? flags u`, for applications doing
+// their own handshake
+pub fn flags_reply(input: &[u8]) -> Option<(ReportingMode, usize)> {
+    match ParserConfig::kitty().parse(input) {
+        Ok((TerminalEvent::KeyboardFlags(flags), len)) => Some((flags, len)),
+        _ => None,
+    }
+}
+
+// Iterates over the complete events in a buffer
+#[derive(Debug, Clone)]
+pub struct Events<'a> {
+    input: &'a [u8],
+    config: ParserConfig,
+}
+
+impl<'a> Events<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Self::with_config(input, ParserConfig::default())
+    }
+
+    pub fn with_config(input: &'a [u8], config: ParserConfig) -> Self {
+        Self { input, config }
+    }
+
+    // What is left once the iterator is done, the start of an incomplete
+    // sequence to carry over to the next read
+    pub fn remainder(&self) -> &'a [u8] {
+        self.input
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = TerminalEvent<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (event, len) = self.config.parse(self.input).ok()?;
+        self.input = tail(self.input, len);
+        Some(event)
+    }
+}
+
+// Parses input as it comes in, in chunks of any size, keeping the start of an
+// incomplete sequence until the rest arrives. Pastes that do not fit in `N`
+// bytes are handed out in parts, other sequences come out as `Malformed`.
+#[derive(Debug, Clone)]
+pub struct InputParser<const N: usize = 4096> {
+    config: ParserConfig,
+    buffer: [u8; N],
+    len: usize,
+    // The length of the event last handed out, dropped on the next call
+    taken: usize,
+    // Between the parts of a long paste
+    in_paste: bool,
+}
+
+impl<const N: usize> Default for InputParser<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> InputParser<N> {
+    pub fn new() -> Self {
+        Self::with_config(ParserConfig::default())
+    }
+
+    pub fn with_config(config: ParserConfig) -> Self {
+        Self {
+            config,
+            buffer: [0; N],
+            len: 0,
+            taken: 0,
+            in_paste: false,
+        }
+    }
+
+    // The bytes buffered and not yet returned as events
+    pub fn pending(&self) -> &[u8] {
+        tail(head(&self.buffer, self.len), self.taken)
+    }
+
+    // Buffers as much of `bytes` as fits and returns how much that was
+    pub fn push(&mut self, bytes: &[u8]) -> usize {
+        self.discard_taken();
+        let free = self.buffer.get_mut(self.len..).unwrap_or_default();
+        let len = free.len().min(bytes.len());
+        if let Some(free) = free.get_mut(..len) {
+            free.copy_from_slice(head(bytes, len));
+        }
+        self.len += len;
+        len
+    }
+
+    // The next complete event, valid until the parser is used again
+    pub fn next_event(&mut self) -> Option<TerminalEvent<'_>> {
+        self.take(None)
+    }
+
+    // Settles an incomplete ESC once no input came for `idle`, see
+    // `ParserConfig::parse_idle`
+    pub fn next_event_idle(&mut self, idle: Duration) -> Option<TerminalEvent<'_>> {
+        self.take(Some(idle))
+    }
+
+    // Whether the input ends in what is either the start of a sequence or the
+    // Escape key, Alt+Escape, Alt+[, Alt+O or Alt+P, for callers that time it
+    // out themselves with `flush_timeout`
+    pub fn is_escape_pending(&self) -> bool {
+        matches!(
+            self.pending(),
+            [0x1b] | [0x1b, 0x1b] | [0x1b, b'[' | b'O' | b'P']
+        )
+    }
+
+    // Settles the pending input once the caller's timer ran out, a lone ESC
+    // is the Escape key. Anything else still incomplete is `Malformed`.
+    pub fn flush_timeout(&mut self) -> Option<TerminalEvent<'_>> {
+        self.take(Some(Duration::MAX))
+    }
+
+    // Pushes a whole read and hands every complete event to `emit`
+    pub fn feed(&mut self, mut bytes: &[u8], mut emit: impl FnMut(TerminalEvent<'_>)) {
+        loop {
+            let len = self.push(bytes);
+            bytes = tail(bytes, len);
+            while let Some(event) = self.next_event() {
+                emit(event);
+            }
+            if bytes.is_empty() || len == 0 {
+                return;
+            }
+        }
+    }
+
+    fn discard_taken(&mut self) {
+        let taken = core::mem::take(&mut self.taken).min(self.len);
+        self.buffer.copy_within(taken..self.len, 0);
+        self.len -= taken;
+    }
+
+    fn take(&mut self, idle: Option<Duration>) -> Option<TerminalEvent<'_>> {
+        self.discard_taken();
+        let input = head(&self.buffer, self.len);
+        let long_paste = match paste_start(input, self.config.c1) {
+            Some(start) if N > 0 && self.len == N && self.config.parse(input).is_err() => {
+                Some(start)
+            }
+            _ => None,
+        };
+        if let Some(start) = long_paste.filter(|_| !self.in_paste) {
+            self.in_paste = true;
+            self.taken = start;
+            self.discard_taken();
+        }
+        if self.in_paste {
+            return self.take_paste();
+        }
+
+        let input = head(&self.buffer, self.len);
+        let parsed = match idle {
+            Some(idle) => self.config.parse_idle(input, idle),
+            None => self.config.parse(input),
+        };
+
+        let (event, len) = match parsed {
+            Ok(parsed) => parsed,
+            // No more input can complete it
+            Err(_) if N > 0 && self.len == N => (TerminalEvent::Malformed(input), N),
+            Err(_) => return None,
+        };
+        self.taken = len;
+        Some(event)
+    }
+
+    // Everything but what may be the start of the end marker
+    fn take_paste(&mut self) -> Option<TerminalEvent<'_>> {
+        let input = head(&self.buffer, self.len);
+
+        let (event, len) = match paste_end(input, self.config.c1) {
+            Some((end, marker)) => {
+                self.in_paste = false;
+                (TerminalEvent::Paste(head(input, end)), end + marker)
+            }
+            None if input.len() >= PASTE_END_LEN => {
+                let len = input.len() + 1 - PASTE_END_LEN;
+                (TerminalEvent::PastePart(head(input, len)), len)
+            }
+            None => return None,
+        };
+        self.taken = len;
+        Some(event)
+    }
+}
+
+// The length of the paste start marker `input` begins with, if it does
+fn paste_start(input: &[u8], c1: bool) -> Option<usize> {
+    let intro = csi_introducer(input, c1)?;
+    tail(input, intro).starts_with(b"200~").then_some(intro + 4)
+}
+
+// Where the paste ends and the length of the end marker
+fn paste_end(paste: &[u8], c1: bool) -> Option<(usize, usize)> {
+    (0..paste.len()).find_map(|start| {
+        let rest = tail(paste, start);
+        let intro = csi_introducer(rest, c1)?;
+        tail(rest, intro)
+            .starts_with(b"201~")
+            .then_some((start, intro + 4))
+    })
+}
+
+fn csi_introducer(input: &[u8], c1: bool) -> Option<usize> {
+    match input {
+        [0x1b, b'[', ..] => Some(2),
+        _ if c1 => match c1_introducer(input)? {
+            (0x9b, len) => Some(len),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn parse_text(input: &[u8], c1: bool) -> Result<(TerminalEvent<'_>, usize), ParseError> {
+    let end = find_escape(input).unwrap_or(input.len());
+    match core::str::from_utf8(head(input, end)) {
+        Ok(text) => Ok(text_run(text, c1)),
+        Err(error) if error.valid_up_to() > 0 => {
+            let text = core::str::from_utf8(head(input, error.valid_up_to())).unwrap_or_default();
+            Ok(text_run(text, c1))
+        }
+        // A character split across reads, the rest is in the next one
+        Err(error) if error.error_len().is_none() && end == input.len() => {
+            Err(ParseError::Incomplete)
+        }
+        Err(error) => {
+            let len = error.error_len().unwrap_or(end);
+            Ok((TerminalEvent::Malformed(head(input, len)), len))
+        }
+    }
+}
+
+// Ends a text run before its first control character, which is a key
+fn split_controls((event, len): (TerminalEvent<'_>, usize)) -> (TerminalEvent<'_>, usize) {
+    match event {
+        TerminalEvent::Text(text) => {
+            let end = text
+                .find(|ch: char| ch.is_ascii_control())
+                .unwrap_or(text.len());
+            (TerminalEvent::Text(text.get(..end).unwrap_or(text)), end)
+        }
+        event => (event, len),
+    }
+}
+
+// Encoded C1 introducers end the text, raw ones are not UTF-8 anyway
+fn text_run(text: &str, c1: bool) -> (TerminalEvent<'_>, usize) {
+    let end = text
+        .char_indices()
+        .map(|(index, _)| index)
+        .find(|index| c1 && *index > 0 && c1_introducer(tail(text.as_bytes(), *index)).is_some())
+        .unwrap_or(text.len());
+    (TerminalEvent::Text(text.get(..end).unwrap_or(text)), end)
+}
+
+// The C1 control and the length of its encoding
+fn c1_introducer(input: &[u8]) -> Option<(u8, usize)> {
+    match input {
+        [byte @ (0x8f | 0x90 | 0x9b), ..] => Some((*byte, 1)),
+        [0xc2, byte @ (0x8f | 0x90 | 0x9b), ..] => Some((*byte, 2)),
+        _ => None,
+    }
+}
+
+// Device control strings only come as replies, they are skipped whole
+fn parse_dcs(
+    input: &[u8],
+    intro: usize,
+    max_len: usize,
+) -> Result<(TerminalEvent<'_>, usize), ParseError> {
+    let terminator = tail(input, intro)
+        .windows(2)
+        .position(|window| window == b"\x1b\\" || window == b"\xc2\x9c")
+        .map(|position| intro + position + 2);
+    let terminator = terminator.or_else(|| {
+        tail(input, intro)
+            .iter()
+            .position(|byte| *byte == 0x9c)
+            .map(|position| intro + position + 1)
+    });
+
+    match terminator {
+        Some(len) if len <= max_len => Ok((TerminalEvent::Unknown(head(input, len)), len)),
+        _ if input.len() >= max_len => {
+            Ok((TerminalEvent::Malformed(head(input, max_len)), max_len))
+        }
+        _ => Err(ParseError::Incomplete),
+    }
+}
+
+// Slicing that cannot panic, the parser only ever asks for what it has seen
+fn head<T>(slice: &[T], len: usize) -> &[T] {
+    slice.get(..len).unwrap_or(slice)
+}
+
+fn tail<T>(slice: &[T], start: usize) -> &[T] {
+    slice.get(start..).unwrap_or_default()
+}
+
+// Text is most of the input, look for ESC a word at a time
+fn find_escape(input: &[u8]) -> Option<usize> {
+    const LANES: usize = core::mem::size_of::<usize>();
+    const ONES: usize = usize::MAX / 0xff;
+    const ESCAPES: usize = ONES * 0x1b;
+
+    let mut chunks = input.chunks_exact(LANES);
+    let mut offset = 0;
+    for chunk in chunks.by_ref() {
+        let word = usize::from_ne_bytes(chunk.try_into().unwrap_or_default()) ^ ESCAPES;
+        // Sets the high bit of the first byte that was an ESC
+        if word.wrapping_sub(ONES) & !word & (ONES << 7) != 0 {
+            break;
+        }
+        offset += LANES;
+    }
+
+    tail(input, offset)
+        .iter()
+        .position(|byte| *byte == 0x1b)
+        .map(|position| offset + position)
+}
+
+fn key(terminator: SequenceTerminator) -> ParsedKey {
+    ParsedKey {
+        introducer: SequenceIntroducer::CSI,
+        key_code: KeyCode::default(),
+        modifier: KeyboardModifiers::empty(),
+        event_type: EventType::Press,
+        terminator,
+        text: [0; MAX_TEXT_LEN],
+        text_len: 0,
+    }
+}
+
+fn functional(func: FunctionalKey) -> ParsedKey {
+    let sequence = func.to_sequence();
+    ParsedKey {
+        introducer: sequence.introducer,
+        key_code: sequence.key_code,
+        ..key(sequence.terminator)
+    }
+}
+
+// The key that types `ch`, control characters are Ctrl and a letter
+fn char_key(ch: char) -> ParsedKey {
+    let mut key = key(SequenceTerminator::Kitty);
+    match control_key(ch) {
+        Some((KeyType::Functional(func), _)) => return functional(func),
+        Some((KeyType::Unicode(base), modifier)) => {
+            key.key_code.key_code = u32::from(base);
+            key.modifier = modifier;
+        }
+        _ => {
+            let mut lower = ch.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some(lower), None) if lower != ch => {
+                    key.key_code.key_code = u32::from(lower);
+                    key.key_code.shifted_key_code = Some(u32::from(ch));
+                    key.modifier = KeyboardModifiers::SHIFT;
+                }
+                _ => key.key_code.key_code = u32::from(ch),
+            }
+        }
+    }
+    key
+}
+
+fn with_alt(key: ParsedKey) -> ParsedKey {
+    ParsedKey {
+        modifier: key.modifier | KeyboardModifiers::ALT,
+        ..key
+    }
+}
+
+// `27;m;c`, the key is reported like kitty does
+fn modify_other_keys(params: &[u8]) -> Result<ParsedKey, ()> {
+    let mut fields = params.split(|byte| *byte == b';').skip(1);
+    let (Some(modifier), Some(code), None) = (fields.next(), fields.next(), fields.next()) else {
+        return Err(());
+    };
+    let modifier = parse_modifiers(modifier)?;
+
+    // Shifted letters come as the shifted character and Ctrl+Tab as a tab,
+    // kitty reports the unshifted key and the functional one
+    let mut key = char_key(char::from_u32(number(code)?).ok_or(())?);
+    if !modifier.contains(KeyboardModifiers::SHIFT) {
+        key.key_code.shifted_key_code = None;
+    }
+    key.modifier = modifier;
+    Ok(key)
+}
+
+fn urxvt_mouse(params: &[u8]) -> Result<MouseEvent, ()> {
+    let mut fields = params.split(|byte| *byte == b';').map(number);
+    let (Some(code), Some(x), Some(y)) = (fields.next(), fields.next(), fields.next()) else {
+        return Err(());
+    };
+    let code = code?.checked_sub(32).ok_or(())?;
+    MouseEvent::from_report(code, x?, y?).ok_or(())
+}
+
+// Releases are reported with their button, unlike in the other encodings
+fn sgr_mouse(params: &[u8], release: bool) -> Result<MouseEvent, ()> {
+    let mut fields = params.split(|byte| *byte == b';').map(number);
+    let (Some(code), Some(x), Some(y), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return Err(());
+    };
+    let mut event = MouseEvent::from_report(code?, x?, y?).ok_or(())?;
+    if release {
+        event.kind = MouseEventKind::Release;
+    }
+    Ok(event)
+}
+
+// Every value is offset by 32 to keep it printable
+fn x10_mouse(code: u8, x: u8, y: u8) -> Option<MouseEvent> {
+    MouseEvent::from_report(
+        u32::from(code.checked_sub(32)?),
+        u32::from(x.checked_sub(32)?),
+        u32::from(y.checked_sub(32)?),
+    )
+}
+
+fn parse_modifiers(field: &[u8]) -> Result<KeyboardModifiers, ()> {
+    let param = ModifierParam::try_from(number(field)?).map_err(drop)?;
+    Ok(param.modifiers())
+}
+
+fn is_number(params: &[u8]) -> bool {
+    !params.is_empty() && params.iter().all(u8::is_ascii_digit)
+}
+
+// Fails on bogus parameters
+fn key_report(params: &[u8], terminator: SequenceTerminator) -> Result<ParsedKey, ()> {
+    let mut fields = params.split(|byte| *byte == b';');
+    let mut key = key(terminator);
+
+    let mut codes = fields
+        .next()
+        .unwrap_or_default()
+        .split(|byte| *byte == b':');
+    key.key_code.key_code = match codes.next() {
+        Some([]) | None => 1,
+        Some(code) => number(code)?,
+    };
+    for (index, code) in codes.enumerate() {
+        let code = match code {
+            [] => None,
+            code => Some(number(code)?),
+        };
+        match index {
+            0 => key.key_code.shifted_key_code = code,
+            1 => key.key_code.base_layout_key_code = code,
+            _ => return Err(()),
+        }
+    }
+
+    let mut modifiers = fields
+        .next()
+        .unwrap_or_default()
+        .split(|byte| *byte == b':');
+    if let Some(modifier) = modifiers.next().filter(|modifier| !modifier.is_empty()) {
+        key.modifier = parse_modifiers(modifier)?;
+    }
+    key.event_type = match modifiers.next() {
+        None | Some([]) | Some(b"1") => EventType::Press,
+        Some(b"2") => EventType::Repeat,
+        Some(b"3") => EventType::Release,
+        Some(_) => return Err(()),
+    };
+
+    if let Some(text) = fields.next() {
+        for code in text.split(|byte| *byte == b':') {
+            key.push_text(char::from_u32(number(code)?).ok_or(())?);
+        }
+    }
+    if fields.next().is_some() || modifiers.next().is_some() {
+        return Err(());
+    }
+
+    Ok(key)
+}
+
+fn number(digits: &[u8]) -> Result<u32, ()> {
+    if digits.is_empty() {
+        return Err(());
+    }
+    digits.iter().try_fold(0u32, |value, byte| match byte {
+        b'0'..=b'9' => value
+            .checked_mul(10)
+            .and_then(|value| value.checked_add(u32::from(byte - b'0')))
+            .ok_or(()),
+        _ => Err(()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{
+        encoder::InputEncoder,
+        generate_sequence,
+        key::FunctionalKey,
+        legacy::Personality,
+        mouse::{encode_mouse, MouseButton, MouseEncoding, MouseTracking},
+        synthesize::SyntheticKeyEvent,
+    };
+
+    extern crate std;
+    use std::{format, vec::Vec};
+
+    #[test]
+    fn kitty_keys() {
+        let Ok((TerminalEvent::Key(key), 13)) = parse(b"\x1b[97:65;2;65u") else {
+            panic!("not a key");
+        };
+        assert_eq!(key.key_code.key_code, 97);
+        assert_eq!(key.key_code.shifted_key_code, Some(65));
+        assert_eq!(key.modifiers(), KeyboardModifiers::SHIFT);
+        assert_eq!(key.key_with_modifiers(), KeyType::Unicode('A'));
+        assert_eq!(key.text(), Some("A"));
+
+        let Ok((TerminalEvent::Key(key), 8)) = parse(b"\x1b[1;5:3A") else {
+            panic!("not a key");
+        };
+        assert_eq!(key.event_type, EventType::Release);
+        assert_eq!(key.modifier, KeyboardModifiers::CTRL);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mode = ReportingMode::all();
+        let event = SyntheticKeyEvent {
+            modifiers: KeyboardModifiers::SHIFT | KeyboardModifiers::ALT,
+            event_type: EventType::Repeat,
+            ..SyntheticKeyEvent::from_char('A', "A")
+        };
+        let sent = format!("{}", generate_sequence(mode, &event));
+
+        let (key, len) = ParsedKey::parse(sent.as_bytes()).unwrap();
+        assert_eq!(len, sent.len());
+        assert_eq!(format!("{}", key.sequence()), sent);
+        assert_eq!(key.key_with_modifiers(), KeyType::Unicode('A'));
+        assert_eq!(key.key_base_layout(), KeyType::Unicode('a'));
+        assert_eq!(key.event_type(), EventType::Repeat);
+        assert_eq!(key.text(), Some("A"));
+
+        assert_eq!(ParsedKey::parse(b"ab"), Err(ParseError::NotAKey(2)));
+        assert_eq!(ParsedKey::parse(b"\x1b[97"), Err(ParseError::Incomplete));
+    }
+
+    #[test]
+    fn resynchronization() {
+        let input = b"a\x1b[1;\x1b[Ab\x1b[97;0u\xffc\x1b[12";
+        let mut events = Events::new(input);
+        let events_seen: Vec<_> = events.by_ref().collect();
+
+        assert_eq!(events_seen.len(), 7);
+        assert_eq!(events_seen[0], TerminalEvent::Text("a"));
+        assert_eq!(events_seen[1], TerminalEvent::Malformed(b"\x1b[1;"));
+        assert!(matches!(events_seen[2], TerminalEvent::Key(_)));
+        assert_eq!(events_seen[3], TerminalEvent::Text("b"));
+        assert_eq!(events_seen[4], TerminalEvent::Malformed(b"\x1b[97;0u"));
+        assert_eq!(events_seen[5], TerminalEvent::Malformed(b"\xff"));
+        assert_eq!(events_seen[6], TerminalEvent::Text("c"));
+        assert_eq!(events.remainder(), b"\x1b[12");
+
+        assert_eq!(
+            parse(b"\x1b[?1;2$y"),
+            Ok((TerminalEvent::Unknown(b"\x1b[?1;2$y"), 8))
+        );
+        assert_eq!(parse(b"\xc3"), Err(ParseError::Incomplete));
+    }
+
+    #[test]
+    fn dialects() {
+        let kitty = ParserConfig::kitty();
+        let key = |config: ParserConfig, input: &[u8]| match config.parse(input) {
+            Ok((TerminalEvent::Key(key), _)) => Some((key.key_without_modifiers(), key.modifier)),
+            _ => None,
+        };
+        let up = KeyType::Functional(FunctionalKey::Up);
+        let delete = KeyType::Functional(FunctionalKey::Delete);
+
+        assert_eq!(
+            key(ParserConfig::default(), b"\x1b[3$"),
+            Some((delete, KeyboardModifiers::SHIFT))
+        );
+        assert_eq!(
+            key(ParserConfig::default(), b"\x1bOa"),
+            Some((up, KeyboardModifiers::CTRL))
+        );
+        assert_eq!(
+            key(ParserConfig::default(), b"\x1b[27;5;97~"),
+            Some((KeyType::Unicode('a'), KeyboardModifiers::CTRL))
+        );
+        assert_eq!(key(kitty, b"\x1b[3$"), None);
+        assert_eq!(key(kitty, b"\x1b[27;5;97~"), None);
+        assert_eq!(
+            key(kitty, b"\x1b[1;5:2A"),
+            Some((up, KeyboardModifiers::CTRL))
+        );
+
+        let xterm = ParserConfig {
+            kitty: false,
+            ..kitty
+        };
+        assert_eq!(
+            xterm.parse(b"\x1b[1;5:2A"),
+            Ok((TerminalEvent::Unknown(b"\x1b[1;5:2A"), 8))
+        );
+        assert_eq!(key(xterm, b"\x1b[97u"), None);
+        assert_eq!(key(xterm, b"\x1b[1;3A"), Some((up, KeyboardModifiers::ALT)));
+
+        let Ok((TerminalEvent::Mouse(mouse), 10)) = parse(b"\x1b[48;10;5M") else {
+            panic!("not a mouse report");
+        };
+        assert_eq!(mouse.button, Some(crate::mouse::MouseButton::Left));
+        assert_eq!((mouse.column, mouse.row), (9, 4));
+        assert_eq!(mouse.modifiers, KeyboardModifiers::CTRL);
+        assert!(matches!(
+            kitty.parse(b"\x1b[48;10;5M"),
+            Ok((TerminalEvent::Unknown(_), 10))
+        ));
+    }
+
+    #[test]
+    fn borrowed_paste() {
+        let input = b"\x1b[200~a\x1b[Ab\xff\x1b[201~c";
+        let events: Vec<_> = Events::new(input).collect();
+        assert_eq!(
+            events,
+            [
+                TerminalEvent::Paste(b"a\x1b[Ab\xff"),
+                TerminalEvent::Text("c")
+            ]
+        );
+        let TerminalEvent::Paste(paste) = events[0] else {
+            unreachable!()
+        };
+        assert_eq!(paste.as_ptr(), input[6..].as_ptr());
+
+        assert_eq!(parse(b"\x1b[200~abc\x1b[20"), Err(ParseError::Incomplete));
+    }
+
+    #[test]
+    fn escape_scan() {
+        let mut input = [b'a'; 40];
+        assert_eq!(find_escape(&input), None);
+        for position in 0..input.len() {
+            input[position] = 0x1b;
+            assert_eq!(find_escape(&input), Some(position));
+            assert_eq!(find_escape(&input[position + 1..]), None);
+            input[position] = 0x9b;
+        }
+    }
+
+    #[test]
+    fn text_across_reads() {
+        let mut buffer = Vec::new();
+        let mut text = std::string::String::new();
+        for read in [
+            &b"gr\xc3"[..],
+            b"\xbc\xc3\x9f",
+            b"e \xf0\x9f",
+            b"\x91\x8b\x1b[A",
+        ] {
+            buffer.extend_from_slice(read);
+            let mut events = Events::new(&buffer);
+            for event in events.by_ref() {
+                if let TerminalEvent::Text(run) = event {
+                    text.push_str(run);
+                }
+            }
+            let consumed = buffer.len() - events.remainder().len();
+            buffer.drain(..consumed);
+        }
+
+        assert_eq!(text, "grüße 👋");
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn streaming() {
+        let input = "\x1b[97;5ugrüße\x1b[200~hi\x1b[201~\x1b[A\x1b";
+        let mut parser = InputParser::<64>::new();
+        let mut text = std::string::String::new();
+        let mut events = Vec::new();
+        for byte in input.as_bytes() {
+            parser.feed(core::slice::from_ref(byte), |event| match event {
+                TerminalEvent::Text(run) => text.push_str(run),
+                TerminalEvent::Key(key) => events.push(Ok(key.key_without_modifiers())),
+                other => events.push(Err(format!("{other:?}"))),
+            });
+        }
+
+        assert_eq!(text, "grüße");
+        assert_eq!(
+            events,
+            [
+                Ok(KeyType::Unicode('a')),
+                Err(format!("{:?}", TerminalEvent::Paste(b"hi"))),
+                Ok(KeyType::Functional(FunctionalKey::Up)),
+            ]
+        );
+        assert_eq!(parser.pending(), b"\x1b");
+
+        let Some(TerminalEvent::Key(escape)) = parser.next_event_idle(Duration::from_secs(1))
+        else {
+            panic!("not a key");
+        };
+        assert_eq!(
+            escape.key_without_modifiers(),
+            KeyType::Functional(FunctionalKey::Escape)
+        );
+        assert_eq!(parser.next_event(), None);
+        assert!(parser.pending().is_empty());
+    }
+
+    #[test]
+    fn pending_escape() {
+        let mut parser = InputParser::<16>::new();
+        parser.push(b"a\x1b");
+        assert_eq!(parser.next_event(), Some(TerminalEvent::Text("a")));
+        assert_eq!(parser.next_event(), None);
+        assert!(parser.is_escape_pending());
+
+        let Some(TerminalEvent::Key(escape)) = parser.flush_timeout() else {
+            panic!("not a key");
+        };
+        assert_eq!(
+            escape.key_without_modifiers(),
+            KeyType::Functional(FunctionalKey::Escape)
+        );
+        assert!(!parser.is_escape_pending());
+        assert_eq!(parser.flush_timeout(), None);
+
+        // The rest of the sequence arrived in time
+        parser.push(b"\x1b[");
+        assert!(parser.is_escape_pending());
+        parser.push(b"A");
+        assert!(!parser.is_escape_pending());
+        assert!(matches!(parser.next_event(), Some(TerminalEvent::Key(_))));
+    }
+
+    #[test]
+    fn streaming_overflow() {
+        let mut parser = InputParser::<8>::new();
+        let mut paste = Vec::new();
+        let mut events = Vec::new();
+        parser.feed(
+            b"\x1b[200~too \x1b[201 long\x1b[201~a\x1b[1234567",
+            |event| match event {
+                TerminalEvent::PastePart(part) => paste.extend_from_slice(part),
+                TerminalEvent::Paste(rest) => {
+                    paste.extend_from_slice(rest);
+                    events.push(format!("paste {:?}", std::str::from_utf8(&paste).unwrap()));
+                }
+                other => events.push(format!("{other:?}")),
+            },
+        );
+
+        assert_eq!(
+            events,
+            [
+                "paste \"too \\u{1b}[201 long\"".into(),
+                format!("{:?}", TerminalEvent::Text("a")),
+                format!("{:?}", TerminalEvent::Malformed(b"\x1b[123456")),
+                format!("{:?}", TerminalEvent::Text("7")),
+            ]
+        );
+        assert!(parser.pending().is_empty());
+    }
+
+    #[test]
+    fn escape_timing() {
+        let config = ParserConfig::default();
+        let soon = Duration::from_millis(5);
+        let later = Duration::from_millis(100);
+        let key = |result: Result<(TerminalEvent, usize), ParseError>| match result {
+            Ok((TerminalEvent::Key(key), len)) => {
+                Some((key.key_with_modifiers(), key.modifier, len))
+            }
+            _ => None,
+        };
+        let escape = KeyType::Functional(FunctionalKey::Escape);
+
+        assert_eq!(
+            config.parse_idle(b"\x1b", soon),
+            Err(ParseError::Incomplete)
+        );
+        assert_eq!(
+            key(config.parse_idle(b"\x1b", later)),
+            Some((escape, KeyboardModifiers::empty(), 1))
+        );
+        assert_eq!(
+            key(config.parse_idle(b"\x1b[", later)),
+            Some((KeyType::Unicode('['), KeyboardModifiers::ALT, 2))
+        );
+        assert_eq!(
+            key(config.parse_idle(b"\x1b\x1b", later)),
+            Some((escape, KeyboardModifiers::ALT, 2))
+        );
+        assert_eq!(
+            key(config.parse_idle(b"\x1bP", later)),
+            Some((
+                KeyType::Unicode('P'),
+                KeyboardModifiers::ALT | KeyboardModifiers::SHIFT,
+                2
+            ))
+        );
+
+        assert_eq!(
+            key(parse(b"\x1bx")),
+            Some((KeyType::Unicode('x'), KeyboardModifiers::ALT, 2))
+        );
+        assert_eq!(
+            key(parse(b"\x1bX")),
+            Some((
+                KeyType::Unicode('X'),
+                KeyboardModifiers::ALT | KeyboardModifiers::SHIFT,
+                2
+            ))
+        );
+        assert_eq!(
+            key(parse(b"\x1b\x01")),
+            Some((
+                KeyType::Unicode('a'),
+                KeyboardModifiers::ALT | KeyboardModifiers::CTRL,
+                2
+            ))
+        );
+        assert_eq!(
+            key(parse(b"\x1b\x1b[A")),
+            Some((
+                KeyType::Functional(FunctionalKey::Up),
+                KeyboardModifiers::ALT,
+                4
+            ))
+        );
+    }
+
+    #[test]
+    fn legacy_keys() {
+        let config = ParserConfig {
+            control_keys: true,
+            ..ParserConfig::kitty()
+        };
+        let keys: Vec<_> = Events::with_config(
+            b"\x1b[Z\x1b[1;5Z\x1bOS\x1b[15;5~ab\x7f\x08\rc\x01\x1bx",
+            config,
+        )
+        .map(|event| match event {
+            TerminalEvent::Key(key) => Ok((key.key_with_modifiers(), key.modifiers())),
+            other => Err(other),
+        })
+        .collect();
+
+        let key = |func| KeyType::Functional(func);
+        let none = KeyboardModifiers::empty();
+        assert_eq!(
+            keys,
+            [
+                Ok((key(FunctionalKey::Tab), KeyboardModifiers::SHIFT)),
+                Ok((
+                    key(FunctionalKey::Tab),
+                    KeyboardModifiers::SHIFT | KeyboardModifiers::CTRL
+                )),
+                Ok((key(FunctionalKey::F4), none)),
+                Ok((key(FunctionalKey::F5), KeyboardModifiers::CTRL)),
+                Err(TerminalEvent::Text("ab")),
+                Ok((key(FunctionalKey::Backspace), none)),
+                Ok((key(FunctionalKey::Backspace), none)),
+                Ok((key(FunctionalKey::Enter), none)),
+                Err(TerminalEvent::Text("c")),
+                Ok((KeyType::Unicode('a'), KeyboardModifiers::CTRL)),
+                Ok((KeyType::Unicode('x'), KeyboardModifiers::ALT)),
+            ]
+        );
+
+        // Without `control_keys` they stay part of the text
+        assert_eq!(
+            ParserConfig::kitty().parse(b"ab\x7f"),
+            Ok((TerminalEvent::Text("ab\x7f"), 3))
+        );
+    }
+
+    #[test]
+    fn focus_and_resize() {
+        let events: Vec<_> =
+            Events::new(b"\x1b[I\x1b[48;24;80;480;800t\x1b[8;50;132t\x1b[3;1t\x1b[O").collect();
+
+        assert_eq!(
+            events,
+            [
+                TerminalEvent::FocusGained,
+                TerminalEvent::Resize(WindowSize {
+                    rows: 24,
+                    columns: 80,
+                    pixels: Some((800, 480)),
+                }),
+                TerminalEvent::Resize(WindowSize {
+                    rows: 50,
+                    columns: 132,
+                    pixels: None,
+                }),
+                TerminalEvent::Unknown(b"\x1b[3;1t"),
+                TerminalEvent::FocusLost,
+            ]
+        );
+    }
+
+    #[test]
+    fn mouse_reports() {
+        let press = MouseEvent {
+            kind: MouseEventKind::Press,
+            button: Some(MouseButton::Right),
+            column: 9,
+            row: 4,
+            pixel: None,
+            modifiers: KeyboardModifiers::CTRL,
+        };
+        let release = MouseEvent {
+            kind: MouseEventKind::Release,
+            ..press
+        };
+        let motion = MouseEvent {
+            kind: MouseEventKind::Motion,
+            button: Some(MouseButton::Left),
+            ..press
+        };
+
+        let config = ParserConfig {
+            x10_mouse: true,
+            ..Default::default()
+        };
+        for encoding in [MouseEncoding::Sgr, MouseEncoding::Default] {
+            let mut sent = std::string::String::new();
+            for event in [&press, &motion, &release] {
+                encode_mouse(event, MouseTracking::AnyEvent, encoding, &mut sent).unwrap();
+            }
+            let events: Vec<_> = Events::with_config(sent.as_bytes(), config).collect();
+
+            let release = match encoding {
+                MouseEncoding::Sgr => release,
+                _ => MouseEvent {
+                    button: None,
+                    ..release
+                },
+            };
+            assert_eq!(
+                events,
+                [press, motion, release].map(TerminalEvent::Mouse),
+                "{encoding:?}"
+            );
+        }
+
+        assert_eq!(config.parse(b"\x1b[M !"), Err(ParseError::Incomplete));
+        assert_eq!(
+            parse(b"\x1b[<0;1;1;1M"),
+            Ok((TerminalEvent::Malformed(b"\x1b[<0;1;1;1M"), 11))
+        );
+    }
+
+    #[test]
+    fn function_key_sets() {
+        for personality in [Personality::Sco, Personality::Sun] {
+            let mut encoder = InputEncoder::new();
+            encoder.set_legacy_options(personality);
+            let config = ParserConfig {
+                function_keys: personality.options().function_keys,
+                ..Default::default()
+            };
+
+            for func in [
+                FunctionalKey::F1,
+                FunctionalKey::F4,
+                FunctionalKey::F12,
+                FunctionalKey::Insert,
+                FunctionalKey::PageUp,
+            ] {
+                for modifiers in [KeyboardModifiers::empty(), KeyboardModifiers::CTRL] {
+                    let event = SyntheticKeyEvent {
+                        modifiers,
+                        ..SyntheticKeyEvent::from(KeyType::Functional(func))
+                    };
+                    let mut sent = std::string::String::new();
+                    encoder.encode_key(&event, &mut sent).unwrap();
+
+                    let Ok((TerminalEvent::Key(key), len)) = config.parse(sent.as_bytes()) else {
+                        panic!("{personality:?} {func:?} {sent:?} is not a key");
+                    };
+                    assert_eq!(len, sent.len());
+                    assert_eq!(key.key_with_modifiers(), KeyType::Functional(func));
+                    assert_eq!(key.modifiers(), modifiers, "{personality:?} {func:?}");
+                }
+            }
+        }
+
+        // SCO's F1 is not taken for the start of a mouse report
+        assert_eq!(parse(b"\x1b[M"), Ok((TerminalEvent::Unknown(b"\x1b[M"), 3)));
+    }
+
+    #[test]
+    fn modify_other_keys_as_kitty() {
+        let key = |input: &[u8]| match parse(input) {
+            Ok((TerminalEvent::Key(key), _)) => Some((
+                key.key_without_modifiers(),
+                key.key_with_modifiers(),
+                key.modifiers(),
+            )),
+            _ => None,
+        };
+        let ctrl_shift = KeyboardModifiers::CTRL | KeyboardModifiers::SHIFT;
+        let tab = KeyType::Functional(FunctionalKey::Tab);
+
+        assert_eq!(
+            key(b"\x1b[27;6;65~"),
+            Some((KeyType::Unicode('a'), KeyType::Unicode('A'), ctrl_shift))
+        );
+        assert_eq!(
+            key(b"\x1b[27;5;9~"),
+            Some((tab, tab, KeyboardModifiers::CTRL))
+        );
+        // Caps Lock, the key is still the unshifted one
+        assert_eq!(
+            key(b"\x1b[27;69;65~"),
+            Some((
+                KeyType::Unicode('a'),
+                KeyType::Unicode('a'),
+                KeyboardModifiers::CTRL | KeyboardModifiers::CAPS_LOCK
+            ))
+        );
+        assert_eq!(key(b"\x1b[27;6;65~"), key(b"\x1b[97:65;6u"));
+    }
+
+    #[test]
+    fn limits() {
+        let config = ParserConfig {
+            limits: ParserLimits {
+                max_sequence_len: 16,
+                max_params: 4,
+                max_param_value: 0xffff,
+            },
+            ..ParserConfig::default()
+        };
+
+        let endless = [b"\x1b[".as_slice(), &[b'1'; 100]].concat();
+        assert_eq!(
+            config.parse(&endless),
+            Ok((TerminalEvent::Malformed(&endless[..16]), 16))
+        );
+        assert_eq!(config.parse(&endless[..15]), Err(ParseError::Incomplete));
+
+        assert_eq!(
+            config.parse(b"\x1b[1;2;3;4;5u"),
+            Ok((TerminalEvent::Malformed(b"\x1b[1;2;3;4;5u"), 12))
+        );
+        assert_eq!(
+            config.parse(b"\x1b[65536u"),
+            Ok((TerminalEvent::Malformed(b"\x1b[65536u"), 8))
+        );
+        assert_eq!(
+            config.parse(b"\x1b[99999999999u"),
+            Ok((TerminalEvent::Malformed(b"\x1b[99999999999u"), 14))
+        );
+        assert!(matches!(
+            config.parse(b"\x1b[97:65;2u"),
+            Ok((TerminalEvent::Key(_), 10))
+        ));
+
+        let dcs = ParserConfig { c1: true, ..config };
+        let unterminated = [b"\x90$r".as_slice(), &[b'x'; 30]].concat();
+        assert_eq!(
+            dcs.parse(&unterminated),
+            Ok((TerminalEvent::Malformed(&unterminated[..16]), 16))
+        );
+    }
+
+    #[test]
+    fn nothing_discarded() {
+        let input: &[u8] = b"\x1b[1;2;3$x\x1b\xff\x1b[?1049h\x1b[12\x01z\x1b[5;0u\x1b[Aok";
+        let mut consumed = 0;
+        let mut unparsed = Vec::new();
+        while let Ok((event, len)) = parse(&input[consumed..]) {
+            assert_eq!(event.unparsed().map_or(len, <[u8]>::len), len);
+            unparsed.extend(event.unparsed());
+            consumed += len;
+        }
+
+        assert_eq!(consumed, input.len());
+        assert_eq!(
+            unparsed,
+            [
+                &b"\x1b[1;2;3$x"[..],
+                b"\x1b",
+                b"\xff",
+                b"\x1b[?1049h",
+                b"\x1b[12",
+                b"\x1b[5;0u",
+            ]
+        );
+    }
+
+    #[test]
+    fn c1_pastes_and_mouse() {
+        let config = ParserConfig {
+            c1: true,
+            x10_mouse: true,
+            ..Default::default()
+        };
+        let input = b"\x9b200~a\x9bAb\x9b201~\xc2\x9b200~c\x1b[201~\x9bM !!\x9b<0;1;1m";
+        let events: Vec<_> = Events::with_config(input, config).collect();
+
+        let press = MouseEvent::from_report(0, 1, 1).unwrap();
+        assert_eq!(
+            events,
+            [
+                TerminalEvent::Paste(b"a\x9bAb"),
+                TerminalEvent::Paste(b"c"),
+                TerminalEvent::Mouse(press),
+                TerminalEvent::Mouse(MouseEvent {
+                    kind: MouseEventKind::Release,
+                    ..press
+                }),
+            ]
+        );
+
+        // Without `c1` the paste only ends at the 7-bit CSI
+        assert_eq!(
+            parse(b"\x1b[200~a\x9b201~\x1b[201~"),
+            Ok((TerminalEvent::Paste(b"a\x9b201~"), 18))
+        );
+    }
+
+    #[test]
+    fn dcs_replies() {
+        let events: Vec<_> = Events::new(b"\x1bP>|xterm(380)\x1b\\\x1bP1$r0m\x1b\\a").collect();
+        assert_eq!(
+            events,
+            [
+                TerminalEvent::Unknown(b"\x1bP>|xterm(380)\x1b\\"),
+                TerminalEvent::Unknown(b"\x1bP1$r0m\x1b\\"),
+                TerminalEvent::Text("a"),
+            ]
+        );
+        assert_eq!(parse(b"\x1bP1$r0"), Err(ParseError::Incomplete));
+
+        // HP's Delete
+        let hp = ParserConfig {
+            function_keys: FunctionKeys::Hp,
+            ..Default::default()
+        };
+        assert!(matches!(hp.parse(b"\x1bP"), Ok((TerminalEvent::Key(_), 2))));
+    }
+
+    #[test]
+    fn c1_controls() {
+        let config = ParserConfig {
+            c1: true,
+            ..Default::default()
+        };
+        let events: Vec<_> =
+            Events::with_config(b"a\x9bAb\xc2\x8fPc\x90$r1m\x1b\\\xc2\x9b97;5u", config).collect();
+        let keys: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                TerminalEvent::Key(key) => Some(key.key_without_modifiers()),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(events.len(), 7);
+        assert_eq!(events[0], TerminalEvent::Text("a"));
+        assert_eq!(events[2], TerminalEvent::Text("b"));
+        assert_eq!(events[5], TerminalEvent::Unknown(b"\x90$r1m\x1b\\"));
+        assert_eq!(
+            keys,
+            [
+                KeyType::Functional(FunctionalKey::Up),
+                KeyType::Functional(FunctionalKey::F1),
+                KeyType::Unicode('a')
+            ]
+        );
+
+        // Without it, encoded C1 controls are text
+        assert_eq!(
+            parse("\u{9b}A".as_bytes()),
+            Ok((TerminalEvent::Text("\u{9b}A"), 3))
+        );
+    }
+
+    #[test]
+    fn flags_replies() {
+        assert_eq!(
+            flags_reply(b"\x1b[?13u"),
+            Some((
+                ReportingMode::DISAMBIGUATE_ESC_CODES
+                    | ReportingMode::REPORT_ALTERNATE_KEYS
+                    | ReportingMode::REPORT_ALL_KEYS_AS_ESC,
+                6
+            ))
+        );
+        assert_eq!(flags_reply(b"\x1b[?0u"), Some((ReportingMode::empty(), 5)));
+        assert_eq!(flags_reply(b"\x1b[13u"), None);
+        let (newer, _) = flags_reply(b"\x1b[?33u").unwrap();
+        assert_eq!(newer.bits(), 33);
+        assert_eq!(
+            newer & ReportingMode::all(),
+            ReportingMode::DISAMBIGUATE_ESC_CODES
+        );
+        assert_eq!(
+            parse(b"\x1b[?99999999999u"),
+            Ok((TerminalEvent::Malformed(b"\x1b[?99999999999u"), 15))
+        );
+    }
+
+    #[test]
+    fn device_attributes() {
+        // The kitty probe, flags then DA1
+        let events: Vec<_> = Events::new(b"\x1b[?1u\x1b[?62;22c\x1b[>1;4000;15c").collect();
+        assert_eq!(
+            events[0],
+            TerminalEvent::KeyboardFlags(ReportingMode::DISAMBIGUATE_ESC_CODES)
+        );
+        let TerminalEvent::PrimaryAttributes(primary) = events[1] else {
+            panic!("not DA1");
+        };
+        assert_eq!(primary.params(), [62, 22]);
+        let TerminalEvent::SecondaryAttributes(secondary) = events[2] else {
+            panic!("not DA2");
+        };
+        assert_eq!(secondary.terminal_type(), Some(1));
+        assert_eq!(secondary.version(), Some(4000));
+
+        assert!(matches!(
+            parse(b"\x1b[?6:2c"),
+            Ok((TerminalEvent::Malformed(_), 7))
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+use core::{fmt::Write, time::Duration};
+
+use crate::filter::Clock;
+
+pub const BRACKETED_PASTE_START: &str = "\x1b[200~";
+pub const BRACKETED_PASTE_END: &str = "\x1b[201~";
+
+// Control characters that would let pasted text end the paste early or inject
+// sequences of their own
+pub fn is_unsafe_in_paste(ch: char) -> bool {
+    matches!(ch, '\x1b' | '\u{9b}')
+}
+
+// Detects text typed faster than a human can (an OS-level paste delivered as
+// key presses) and wraps it in a bracketed paste block. Only characters the
+// encoder would send verbatim should be fed through it.
+#[derive(Debug, Clone)]
+pub struct BurstDetector<C, const N: usize = 64> {
+    clock: C,
+    max_interval: Duration,
+    min_len: usize,
+    bracketed_paste: bool,
+
+    last: Option<Duration>,
+    pasting: bool,
+    buffer: [u8; N],
+    buffered_bytes: usize,
+    buffered_chars: usize,
+}
+
+impl<C: Clock, const N: usize> BurstDetector<C, N> {
+    pub const DEFAULT_MAX_INTERVAL: Duration = Duration::from_millis(2);
+    pub const DEFAULT_MIN_LEN: usize = 16;
+
+    pub fn new(clock: C) -> Self {
+        Self {
+            clock,
+            max_interval: Self::DEFAULT_MAX_INTERVAL,
+            min_len: Self::DEFAULT_MIN_LEN,
+            bracketed_paste: false,
+            last: None,
+            pasting: false,
+            buffer: [0; N],
+            buffered_bytes: 0,
+            buffered_chars: 0,
+        }
+    }
+
+    pub fn with_thresholds(mut self, max_interval: Duration, min_len: usize) -> Self {
+        self.max_interval = max_interval;
+        self.min_len = min_len;
+        self
+    }
+
+    // Mirrors DECSET 2004, bursts are only converted while the application
+    // asked for bracketed paste
+    pub fn set_bracketed_paste(&mut self, enabled: bool) {
+        self.bracketed_paste = enabled;
+    }
+
+    pub fn is_pasting(&self) -> bool {
+        self.pasting
+    }
+
+    pub fn feed(&mut self, ch: char, out: &mut impl Write) -> core::fmt::Result {
+        self.poll(out)?;
+
+        if !self.bracketed_paste {
+            return out.write_char(ch);
+        }
+
+        self.last = Some(self.clock.now());
+
+        if self.pasting {
+            return write_paste_char(ch, out);
+        }
+
+        // Running out of room is a burst by any measure
+        let fits = self.buffered_bytes + ch.len_utf8() <= N;
+        if fits {
+            ch.encode_utf8(&mut self.buffer[self.buffered_bytes..]);
+            self.buffered_bytes += ch.len_utf8();
+            self.buffered_chars += 1;
+
+            if self.buffered_chars < self.min_len {
+                return Ok(());
+            }
+        }
+
+        self.pasting = true;
+        out.write_str(BRACKETED_PASTE_START)?;
+        for buffered in self.take_buffer().chars() {
+            write_paste_char(buffered, out)?;
+        }
+        if !fits {
+            write_paste_char(ch, out)?;
+        }
+
+        Ok(())
+    }
+
+    // Flushes buffered text or ends the paste once input paused long enough;
+    // call it from a timer while `has_pending` is true
+    pub fn poll(&mut self, out: &mut impl Write) -> core::fmt::Result {
+        let Some(last) = self.last else {
+            return Ok(());
+        };
+        if self.clock.now().saturating_sub(last) <= self.max_interval {
+            return Ok(());
+        }
+
+        self.finish(out)
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.pasting || self.buffered_bytes > 0
+    }
+
+    // Flushes unconditionally, e.g. when bracketed paste gets disabled
+    pub fn finish(&mut self, out: &mut impl Write) -> core::fmt::Result {
+        self.last = None;
+
+        if self.pasting {
+            self.pasting = false;
+            out.write_str(BRACKETED_PASTE_END)
+        } else {
+            out.write_str(self.take_buffer())
+        }
+    }
+
+    fn take_buffer(&mut self) -> &str {
+        let len = core::mem::take(&mut self.buffered_bytes);
+        self.buffered_chars = 0;
+        // Only whole characters are ever buffered
+        core::str::from_utf8(&self.buffer[..len]).unwrap_or_default()
+    }
+}
+
+// Wraps `text` in a bracketed paste block, leaving out the characters that
+// could end it early
+pub fn write_bracketed_paste(text: &str, out: &mut impl Write) -> core::fmt::Result {
+    out.write_str(BRACKETED_PASTE_START)?;
+    for ch in text.chars() {
+        write_paste_char(ch, out)?;
+    }
+    out.write_str(BRACKETED_PASTE_END)
+}
+
+fn write_paste_char(ch: char, out: &mut impl Write) -> core::fmt::Result {
+    if is_unsafe_in_paste(ch) {
+        Ok(())
+    } else {
+        out.write_char(ch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use core::cell::Cell;
+
+    use super::*;
+
+    extern crate std;
+    use std::string::String;
+
+    fn type_at(
+        detector: &mut BurstDetector<impl Clock, 8>,
+        now: &Cell<Duration>,
+        text: &str,
+        interval: Duration,
+        out: &mut String,
+    ) {
+        for ch in text.chars() {
+            now.set(now.get() + interval);
+            detector.feed(ch, out).unwrap();
+        }
+    }
+
+    #[test]
+    fn human_typing_stays_text() {
+        let now = Cell::new(Duration::ZERO);
+        let mut detector: BurstDetector<_, 8> =
+            BurstDetector::new(|| now.get()).with_thresholds(Duration::from_millis(2), 4);
+        detector.set_bracketed_paste(true);
+
+        let mut out = String::new();
+        type_at(
+            &mut detector,
+            &now,
+            "hello",
+            Duration::from_millis(80),
+            &mut out,
+        );
+        assert_eq!(out, "hell");
+
+        now.set(now.get() + Duration::from_millis(80));
+        detector.poll(&mut out).unwrap();
+        assert_eq!(out, "hello");
+        assert!(!detector.has_pending());
+    }
+
+    #[test]
+    fn burst_becomes_paste() {
+        let now = Cell::new(Duration::ZERO);
+        let mut detector: BurstDetector<_, 8> =
+            BurstDetector::new(|| now.get()).with_thresholds(Duration::from_millis(2), 4);
+        detector.set_bracketed_paste(true);
+
+        let mut out = String::new();
+        type_at(
+            &mut detector,
+            &now,
+            "ab",
+            Duration::from_millis(80),
+            &mut out,
+        );
+        type_at(
+            &mut detector,
+            &now,
+            "cd\x1b[201~e",
+            Duration::from_micros(100),
+            &mut out,
+        );
+        assert!(detector.is_pasting());
+
+        now.set(now.get() + Duration::from_millis(10));
+        detector.poll(&mut out).unwrap();
+        assert_eq!(out, "a\x1b[200~bcd[201~e\x1b[201~");
+    }
+
+    #[test]
+    fn buffer_overflow_starts_paste() {
+        let now = Cell::new(Duration::ZERO);
+        let mut detector: BurstDetector<_, 8> =
+            BurstDetector::new(|| now.get()).with_thresholds(Duration::from_millis(2), 100);
+        detector.set_bracketed_paste(true);
+
+        let mut out = String::new();
+        type_at(
+            &mut detector,
+            &now,
+            "ääääx",
+            Duration::from_micros(100),
+            &mut out,
+        );
+        assert_eq!(out, "\x1b[200~ääääx");
+        detector.finish(&mut out).unwrap();
+        assert_eq!(out, "\x1b[200~ääääx\x1b[201~");
+    }
+
+    #[test]
+    fn disabled_passes_through() {
+        let mut detector: BurstDetector<_, 8> = BurstDetector::new(|| Duration::ZERO);
+
+        let mut out = String::new();
+        for ch in "abcdefghijk".chars() {
+            detector.feed(ch, &mut out).unwrap();
+        }
+        assert_eq!(out, "abcdefghijk");
+    }
+}
@@ -0,0 +1,274 @@
+use core::{
+    fmt::{self, Write},
+    time::Duration,
+};
+
+use crate::parse::{InputParser, ParserConfig, TerminalEvent};
+
+// The callbacks of vte's `Perform` that carry input, with the same arguments
+// so an emulator already running vte's parser forwards them from its own
+// `Perform` impl, `&Params` iterates as the parameters taken here. OSC and
+// DCS strings are not input and have no callbacks.
+pub trait Perform {
+    fn print(&mut self, _c: char) {}
+
+    fn execute(&mut self, _byte: u8) {}
+
+    fn csi_dispatch<'p>(
+        &mut self,
+        _params: impl IntoIterator<Item = &'p [u16]>,
+        _intermediates: &[u8],
+        _ignore: bool,
+        _action: char,
+    ) {
+    }
+
+    fn esc_dispatch(&mut self, _intermediates: &[u8], _ignore: bool, _byte: u8) {}
+}
+
+// Turns the callbacks back into the bytes vte took apart and hands the events
+// `InputParser` finds in them to `emit`. vte splits what it does not know as
+// one sequence, e.g. SS3 keys and X10 mouse reports come as an ESC or CSI
+// sequence followed by printed characters, the parser joins them again.
+#[derive(Debug, Clone)]
+pub struct InputPerformer<F, const N: usize = 4096> {
+    parser: InputParser<N>,
+    emit: F,
+}
+
+impl<F: FnMut(TerminalEvent<'_>), const N: usize> InputPerformer<F, N> {
+    pub fn new(emit: F) -> Self {
+        Self::with_config(ParserConfig::default(), emit)
+    }
+
+    pub fn with_config(config: ParserConfig, emit: F) -> Self {
+        Self {
+            parser: InputParser::with_config(config),
+            emit,
+        }
+    }
+
+    // vte keeps what may be SS3 or Alt+O to itself too, settle it once no
+    // input came for `idle`
+    pub fn idle(&mut self, idle: Duration) {
+        if let Some(event) = self.parser.next_event_idle(idle) {
+            (self.emit)(event);
+        }
+    }
+
+    // Or once the caller's own timer ran out
+    pub fn flush_timeout(&mut self) {
+        if let Some(event) = self.parser.flush_timeout() {
+            (self.emit)(event);
+        }
+    }
+
+    pub fn is_escape_pending(&self) -> bool {
+        self.parser.is_escape_pending()
+    }
+
+    fn feed(&mut self) -> Feed<'_, F, N> {
+        Feed {
+            parser: &mut self.parser,
+            emit: &mut self.emit,
+        }
+    }
+}
+
+impl<F: FnMut(TerminalEvent<'_>), const N: usize> Perform for InputPerformer<F, N> {
+    fn print(&mut self, c: char) {
+        self.feed().write_char(c).ok();
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.feed().bytes(&[byte]);
+    }
+
+    fn csi_dispatch<'p>(
+        &mut self,
+        params: impl IntoIterator<Item = &'p [u16]>,
+        intermediates: &[u8],
+        ignore: bool,
+        action: char,
+    ) {
+        // vte dropped the parameters that did not fit, what is left would
+        // read as a different sequence
+        if ignore {
+            return;
+        }
+
+        // vte collects private markers with the intermediates
+        let (markers, intermediates): (&[u8], &[u8]) = match intermediates {
+            [marker @ b'<'..=b'?', rest @ ..] => (core::slice::from_ref(marker), rest),
+            _ => (&[], intermediates),
+        };
+
+        let mut feed = self.feed();
+        feed.bytes(b"\x1b[");
+        feed.bytes(markers);
+        for (index, param) in params.into_iter().enumerate() {
+            if index > 0 {
+                feed.bytes(b";");
+            }
+            for (index, value) in param.iter().enumerate() {
+                if index > 0 {
+                    feed.bytes(b":");
+                }
+                write!(feed, "{value}").ok();
+            }
+        }
+        feed.bytes(intermediates);
+        feed.write_char(action).ok();
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], ignore: bool, byte: u8) {
+        if ignore {
+            return;
+        }
+
+        let mut feed = self.feed();
+        feed.bytes(b"\x1b");
+        feed.bytes(intermediates);
+        feed.bytes(&[byte]);
+    }
+}
+
+struct Feed<'p, F, const N: usize> {
+    parser: &'p mut InputParser<N>,
+    emit: &'p mut F,
+}
+
+impl<F: FnMut(TerminalEvent<'_>), const N: usize> Feed<'_, F, N> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.parser.feed(bytes, &mut *self.emit);
+    }
+}
+
+impl<F: FnMut(TerminalEvent<'_>), const N: usize> Write for Feed<'_, F, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        key::{FunctionalKey, KeyType},
+        mouse::MouseEvent,
+        sequence::KeyboardModifiers,
+        KeyInfo, ModifierInfo, ReportingMode,
+    };
+
+    extern crate std;
+    use std::{format, string::String, vec::Vec};
+
+    type Performer<'e> = InputPerformer<&'e mut dyn FnMut(TerminalEvent<'_>), 16>;
+
+    // Keys by their key and modifiers, pastes put together from their parts
+    fn events(calls: impl FnOnce(&mut Performer<'_>)) -> Vec<String> {
+        let mut events = Vec::new();
+        let mut paste = Vec::new();
+        let mut emit = |event: TerminalEvent<'_>| match event {
+            TerminalEvent::Key(key) => events.push(format!(
+                "{:?} {:?}",
+                key.key_with_modifiers(),
+                key.modifiers()
+            )),
+            TerminalEvent::PastePart(part) => paste.extend_from_slice(part),
+            TerminalEvent::Paste(part) => {
+                paste.extend_from_slice(part);
+                events.push(format!("{:?}", TerminalEvent::Paste(&paste)));
+                paste.clear();
+            }
+            other => events.push(format!("{other:?}")),
+        };
+        calls(&mut InputPerformer::new(&mut emit));
+        events
+    }
+
+    fn key(key: KeyType, modifiers: KeyboardModifiers) -> String {
+        format!("{key:?} {modifiers:?}")
+    }
+
+    #[test]
+    fn keys() {
+        let events = events(|vte| {
+            // `CSI 97:65;2u`
+            vte.csi_dispatch([&[97, 65][..], &[2]], &[], false, 'u');
+            // `SS3 P`, vte ends the escape sequence at the O
+            vte.esc_dispatch(&[], false, b'O');
+            vte.print('P');
+            vte.esc_dispatch(&[], false, b'x');
+            vte.print('ü');
+            vte.execute(b'\r');
+            vte.csi_dispatch([&[1][..], &[5]], &[], false, 'A');
+            vte.csi_dispatch([&[1][..]], b"?", false, 'u');
+            vte.csi_dispatch([&[1][..], &[2]], &[], true, 'A');
+            vte.esc_dispatch(&[], false, b'O');
+            vte.idle(Duration::from_secs(1));
+            vte.esc_dispatch(&[], false, b'O');
+            assert!(vte.is_escape_pending());
+            vte.flush_timeout();
+        });
+
+        let none = KeyboardModifiers::empty();
+        assert_eq!(
+            events,
+            [
+                key(KeyType::Unicode('A'), KeyboardModifiers::SHIFT),
+                key(KeyType::Functional(FunctionalKey::F1), none),
+                key(KeyType::Unicode('x'), KeyboardModifiers::ALT),
+                format!("{:?}", TerminalEvent::Text("ü")),
+                format!("{:?}", TerminalEvent::Text("\r")),
+                key(
+                    KeyType::Functional(FunctionalKey::Up),
+                    KeyboardModifiers::CTRL
+                ),
+                format!(
+                    "{:?}",
+                    TerminalEvent::KeyboardFlags(ReportingMode::DISAMBIGUATE_ESC_CODES)
+                ),
+                key(
+                    KeyType::Unicode('O'),
+                    KeyboardModifiers::ALT | KeyboardModifiers::SHIFT
+                ),
+                key(
+                    KeyType::Unicode('O'),
+                    KeyboardModifiers::ALT | KeyboardModifiers::SHIFT
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn mouse_focus_and_paste() {
+        let events = events(|vte| {
+            vte.csi_dispatch([&[32][..], &[10], &[4]], &[], false, 'M');
+            vte.csi_dispatch([], &[], false, 'I');
+            // Longer than the buffer, with a sequence vte parsed inside it
+            vte.csi_dispatch([&[200][..]], &[], false, '~');
+            for c in "pasted text".chars() {
+                vte.print(c);
+            }
+            vte.csi_dispatch([&[1][..], &[5]], &[], false, 'A');
+            vte.execute(b'\n');
+            vte.csi_dispatch([&[201][..]], &[], false, '~');
+            vte.csi_dispatch([], &[], false, 'O');
+        });
+
+        assert_eq!(
+            events,
+            [
+                format!(
+                    "{:?}",
+                    TerminalEvent::Mouse(MouseEvent::from_report(0, 10, 4).unwrap())
+                ),
+                format!("{:?}", TerminalEvent::FocusGained),
+                format!("{:?}", TerminalEvent::Paste(b"pasted text\x1b[1;5A\n")),
+                format!("{:?}", TerminalEvent::FocusLost),
+            ]
+        );
+    }
+}
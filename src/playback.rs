@@ -0,0 +1,136 @@
+use core::{fmt::Write, iter::Peekable, time::Duration};
+
+use crate::{encoder::InputEncoder, filter::Clock, KeyEvent, KeyInfo};
+
+// Replays recorded events at their original pace, scaled by `speed`. The first
+// poll lines the first event up with the current time. Events without a
+// timestamp are sent right after the one before.
+pub struct Playback<I: Iterator, C> {
+    events: Peekable<I>,
+    clock: C,
+    speed: f64,
+
+    // Recorded time and clock time of the first event
+    anchor: Option<(Duration, Duration)>,
+    last: Duration,
+}
+
+impl<I, C> Playback<I, C>
+where
+    I: Iterator,
+    I::Item: KeyEvent,
+    C: Clock,
+{
+    pub fn new(events: impl IntoIterator<IntoIter = I>, clock: C) -> Self {
+        Self {
+            events: events.into_iter().peekable(),
+            clock,
+            speed: 1.0,
+            anchor: None,
+            last: Duration::ZERO,
+        }
+    }
+
+    // 2.0 plays twice as fast, 0.5 at half speed. Speeds that are not
+    // positive play at the original pace.
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = if speed > 0.0 { speed } else { 1.0 };
+        self
+    }
+
+    pub fn is_finished(&mut self) -> bool {
+        self.events.peek().is_none()
+    }
+
+    // Writes every event that is due and returns how long to wait for the next
+    // one, or `None` once the recording is over
+    pub fn poll(
+        &mut self,
+        encoder: &InputEncoder,
+        out: &mut impl Write,
+    ) -> Result<Option<Duration>, core::fmt::Error> {
+        let now = self.clock.now();
+
+        while let Some(event) = self.events.peek() {
+            let recorded = event.timestamp().unwrap_or(self.last);
+            let (recorded_start, start) = *self.anchor.get_or_insert((recorded, now));
+
+            let offset = recorded.saturating_sub(recorded_start).as_secs_f64() / self.speed;
+            let offset = Duration::try_from_secs_f64(offset).unwrap_or(Duration::MAX);
+            let due = start.saturating_add(offset);
+            if due > now {
+                return Ok(Some(due - now));
+            }
+
+            encoder.encode_key(event, out)?;
+            self.last = recorded;
+            self.events.next();
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        key::KeyType,
+        synthesize::{SyntheticKeyEvent, TimedEvent},
+    };
+    use core::cell::Cell;
+
+    extern crate std;
+    use std::string::String;
+
+    fn typed(ms: u64, ch: char) -> TimedEvent<SyntheticKeyEvent<'static>> {
+        TimedEvent {
+            timestamp: Duration::from_millis(ms),
+            event: SyntheticKeyEvent::from(KeyType::Unicode(ch)),
+        }
+    }
+
+    #[test]
+    fn original_timing() {
+        let now = Cell::new(Duration::from_secs(100));
+        let recording = [typed(5000, 'a'), typed(5000, 'b'), typed(5200, 'c')];
+        let mut playback = Playback::new(recording, || now.get());
+        let encoder = InputEncoder::new();
+        let mut out = String::new();
+
+        assert_eq!(
+            playback.poll(&encoder, &mut out),
+            Ok(Some(Duration::from_millis(200)))
+        );
+        assert_eq!(out, "ab");
+
+        now.set(now.get() + Duration::from_millis(150));
+        assert_eq!(
+            playback.poll(&encoder, &mut out),
+            Ok(Some(Duration::from_millis(50)))
+        );
+
+        now.set(now.get() + Duration::from_millis(60));
+        assert_eq!(playback.poll(&encoder, &mut out), Ok(None));
+        assert_eq!(out, "abc");
+        assert!(playback.is_finished());
+    }
+
+    #[test]
+    fn scaled_timing() {
+        let now = Cell::new(Duration::ZERO);
+        let recording = [typed(0, 'a'), typed(1000, 'b')];
+        let mut playback = Playback::new(recording, || now.get()).with_speed(4.0);
+        let encoder = InputEncoder::new();
+        let mut out = String::new();
+
+        assert_eq!(
+            playback.poll(&encoder, &mut out),
+            Ok(Some(Duration::from_millis(250)))
+        );
+
+        now.set(Duration::from_millis(250));
+        assert_eq!(playback.poll(&encoder, &mut out), Ok(None));
+        assert_eq!(out, "ab");
+    }
+}
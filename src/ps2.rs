@@ -0,0 +1,366 @@
+use crate::{
+    filter::modifier_bit,
+    key::{FunctionalKey, KeyType},
+    layouts::{Layout, PhysicalKey, US},
+    sequence::{EventType, KeyboardModifiers},
+    synthesize::SyntheticKeyEvent,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    Extended,
+    Break,
+    ExtendedBreak,
+    // Bytes of `E1 14 77 E1 F0 14 F0 77` still to come
+    Pause(u8),
+}
+
+// Decodes the bytes of a PS/2 keyboard in scan code set 2. Character keys are
+// resolved through `layout`, modifiers and lock keys are tracked here.
+#[derive(Debug, Clone)]
+pub struct Ps2Decoder {
+    layout: Layout,
+    state: State,
+    modifiers: KeyboardModifiers,
+    // The keyboard only repeats the key pressed last
+    last_make: Option<(bool, u8)>,
+}
+
+impl Ps2Decoder {
+    pub fn new() -> Self {
+        Self::with_layout(US)
+    }
+
+    pub fn with_layout(layout: Layout) -> Self {
+        Self {
+            layout,
+            state: State::Idle,
+            modifiers: KeyboardModifiers::empty(),
+            last_make: None,
+        }
+    }
+
+    pub fn modifiers(&self) -> KeyboardModifiers {
+        self.modifiers
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<SyntheticKeyEvent<'static>> {
+        match (self.state, byte) {
+            (State::Pause(1), _) => {
+                // Pause has no break code
+                self.state = State::Idle;
+                Some(self.event(KeyType::Functional(FunctionalKey::Pause), None, true))
+            }
+            (State::Pause(left), _) => {
+                self.state = State::Pause(left - 1);
+                None
+            }
+            (State::Idle, 0xe0) => {
+                self.state = State::Extended;
+                None
+            }
+            (State::Idle, 0xe1) => {
+                self.state = State::Pause(7);
+                None
+            }
+            (State::Idle, 0xf0) => {
+                self.state = State::Break;
+                None
+            }
+            (State::Extended, 0xf0) => {
+                self.state = State::ExtendedBreak;
+                None
+            }
+            (state, code) => {
+                self.state = State::Idle;
+                let extended = matches!(state, State::Extended | State::ExtendedBreak);
+                let pressed = matches!(state, State::Idle | State::Extended);
+                self.key(extended, code, pressed)
+            }
+        }
+    }
+
+    fn key(
+        &mut self,
+        extended: bool,
+        code: u8,
+        pressed: bool,
+    ) -> Option<SyntheticKeyEvent<'static>> {
+        let (key, physical) = match (extended, physical_key(code)) {
+            (false, Some(physical)) => (
+                KeyType::Unicode(self.layout.base(physical)?),
+                Some(physical),
+            ),
+            _ => (KeyType::Functional(functional_key(extended, code)?), None),
+        };
+
+        let repeat = pressed && self.last_make == Some((extended, code));
+        if pressed {
+            self.last_make = Some((extended, code));
+        } else if self.last_make == Some((extended, code)) {
+            self.last_make = None;
+        }
+
+        if let Some(modifier) = modifier_bit(key) {
+            self.modifiers.set(modifier, pressed);
+        }
+        let lock = match key {
+            KeyType::Functional(FunctionalKey::CapsLock) => KeyboardModifiers::CAPS_LOCK,
+            KeyType::Functional(FunctionalKey::NumLock) => KeyboardModifiers::NUM_LOCK,
+            _ => KeyboardModifiers::empty(),
+        };
+        if pressed && !repeat {
+            self.modifiers.toggle(lock);
+        }
+
+        let mut event = self.event(key, physical, pressed);
+        if repeat {
+            event.event_type = EventType::Repeat;
+        }
+        Some(event)
+    }
+
+    fn event(
+        &self,
+        key: KeyType,
+        physical: Option<PhysicalKey>,
+        pressed: bool,
+    ) -> SyntheticKeyEvent<'static> {
+        SyntheticKeyEvent {
+            key_with_modifiers: physical.map_or(key, |physical| {
+                self.layout.key_with_modifiers(physical, self.modifiers)
+            }),
+            key_without_modifiers: key,
+            key_base_layout: physical.map_or(key, PhysicalKey::base_layout_key),
+            modifiers: self.modifiers,
+            event_type: if pressed {
+                EventType::Press
+            } else {
+                EventType::Release
+            },
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Ps2Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn physical_key(code: u8) -> Option<PhysicalKey> {
+    Some(match code {
+        0x0e => PhysicalKey::Backquote,
+        0x16 => PhysicalKey::Digit1,
+        0x1e => PhysicalKey::Digit2,
+        0x26 => PhysicalKey::Digit3,
+        0x25 => PhysicalKey::Digit4,
+        0x2e => PhysicalKey::Digit5,
+        0x36 => PhysicalKey::Digit6,
+        0x3d => PhysicalKey::Digit7,
+        0x3e => PhysicalKey::Digit8,
+        0x46 => PhysicalKey::Digit9,
+        0x45 => PhysicalKey::Digit0,
+        0x4e => PhysicalKey::Minus,
+        0x55 => PhysicalKey::Equal,
+        0x15 => PhysicalKey::KeyQ,
+        0x1d => PhysicalKey::KeyW,
+        0x24 => PhysicalKey::KeyE,
+        0x2d => PhysicalKey::KeyR,
+        0x2c => PhysicalKey::KeyT,
+        0x35 => PhysicalKey::KeyY,
+        0x3c => PhysicalKey::KeyU,
+        0x43 => PhysicalKey::KeyI,
+        0x44 => PhysicalKey::KeyO,
+        0x4d => PhysicalKey::KeyP,
+        0x54 => PhysicalKey::BracketLeft,
+        0x5b => PhysicalKey::BracketRight,
+        0x5d => PhysicalKey::Backslash,
+        0x1c => PhysicalKey::KeyA,
+        0x1b => PhysicalKey::KeyS,
+        0x23 => PhysicalKey::KeyD,
+        0x2b => PhysicalKey::KeyF,
+        0x34 => PhysicalKey::KeyG,
+        0x33 => PhysicalKey::KeyH,
+        0x3b => PhysicalKey::KeyJ,
+        0x42 => PhysicalKey::KeyK,
+        0x4b => PhysicalKey::KeyL,
+        0x4c => PhysicalKey::Semicolon,
+        0x52 => PhysicalKey::Quote,
+        0x61 => PhysicalKey::IntlBackslash,
+        0x1a => PhysicalKey::KeyZ,
+        0x22 => PhysicalKey::KeyX,
+        0x21 => PhysicalKey::KeyC,
+        0x2a => PhysicalKey::KeyV,
+        0x32 => PhysicalKey::KeyB,
+        0x31 => PhysicalKey::KeyN,
+        0x3a => PhysicalKey::KeyM,
+        0x41 => PhysicalKey::Comma,
+        0x49 => PhysicalKey::Period,
+        0x4a => PhysicalKey::Slash,
+        0x51 => PhysicalKey::IntlRo,
+        0x6a => PhysicalKey::IntlYen,
+        0x29 => PhysicalKey::Space,
+        _ => return None,
+    })
+}
+
+// Everything that is not a character key, `extended` for codes after E0
+pub fn functional_key(extended: bool, code: u8) -> Option<FunctionalKey> {
+    Some(match (extended, code) {
+        (false, 0x76) => FunctionalKey::Escape,
+        (false, 0x05) => FunctionalKey::F1,
+        (false, 0x06) => FunctionalKey::F2,
+        (false, 0x04) => FunctionalKey::F3,
+        (false, 0x0c) => FunctionalKey::F4,
+        (false, 0x03) => FunctionalKey::F5,
+        (false, 0x0b) => FunctionalKey::F6,
+        (false, 0x83) => FunctionalKey::F7,
+        (false, 0x0a) => FunctionalKey::F8,
+        (false, 0x01) => FunctionalKey::F9,
+        (false, 0x09) => FunctionalKey::F10,
+        (false, 0x78) => FunctionalKey::F11,
+        (false, 0x07) => FunctionalKey::F12,
+        (false, 0x66) => FunctionalKey::Backspace,
+        (false, 0x0d) => FunctionalKey::Tab,
+        (false, 0x58) => FunctionalKey::CapsLock,
+        (false, 0x5a) => FunctionalKey::Enter,
+        (false, 0x12) => FunctionalKey::LeftShift,
+        (false, 0x59) => FunctionalKey::RightShift,
+        (false, 0x14) => FunctionalKey::LeftControl,
+        (false, 0x11) => FunctionalKey::LeftAlt,
+        (false, 0x7e) => FunctionalKey::ScrollLock,
+        (false, 0x77) => FunctionalKey::NumLock,
+        (false, 0x7c) => FunctionalKey::NumPadMultply,
+        (false, 0x7b) => FunctionalKey::NumPadSubtract,
+        (false, 0x79) => FunctionalKey::NumPadAdd,
+        (false, 0x71) => FunctionalKey::NumPadDecimal,
+        (false, 0x70) => FunctionalKey::NumPad0,
+        (false, 0x69) => FunctionalKey::NumPad1,
+        (false, 0x72) => FunctionalKey::NumPad2,
+        (false, 0x7a) => FunctionalKey::NumPad3,
+        (false, 0x6b) => FunctionalKey::NumPad4,
+        (false, 0x73) => FunctionalKey::NumPad5,
+        (false, 0x74) => FunctionalKey::NumPad6,
+        (false, 0x6c) => FunctionalKey::NumPad7,
+        (false, 0x75) => FunctionalKey::NumPad8,
+        (false, 0x7d) => FunctionalKey::NumPad9,
+        (false, 0x13) => FunctionalKey::KatakanaHiragana,
+        (false, 0x64) => FunctionalKey::Henkan,
+        (false, 0x67) => FunctionalKey::Muhenkan,
+        (true, 0x11) => FunctionalKey::RightAlt,
+        (true, 0x14) => FunctionalKey::RightControl,
+        (true, 0x1f) => FunctionalKey::LeftSuper,
+        (true, 0x27) => FunctionalKey::RightSuper,
+        (true, 0x2f) => FunctionalKey::Menu,
+        (true, 0x4a) => FunctionalKey::NumPadDivide,
+        (true, 0x5a) => FunctionalKey::NumPadEnter,
+        (true, 0x69) => FunctionalKey::End,
+        (true, 0x6b) => FunctionalKey::Left,
+        (true, 0x6c) => FunctionalKey::Home,
+        (true, 0x70) => FunctionalKey::Insert,
+        (true, 0x71) => FunctionalKey::Delete,
+        (true, 0x72) => FunctionalKey::Down,
+        (true, 0x74) => FunctionalKey::Right,
+        (true, 0x75) => FunctionalKey::Up,
+        (true, 0x7a) => FunctionalKey::PageDown,
+        (true, 0x7d) => FunctionalKey::PageUp,
+        // Print Screen is sent as a fake Left Shift (E0 12) followed by E0 7C,
+        // the fake shift is dropped
+        (true, 0x7c) => FunctionalKey::PrintScreen,
+        (true, 0x34) => FunctionalKey::MediaPlayPause,
+        (true, 0x3b) => FunctionalKey::MediaStop,
+        (true, 0x15) => FunctionalKey::MediaTrackPrevious,
+        (true, 0x4d) => FunctionalKey::MediaTrackNext,
+        (true, 0x23) => FunctionalKey::MuteVolume,
+        (true, 0x32) => FunctionalKey::RaiseVolume,
+        (true, 0x21) => FunctionalKey::LowerVolume,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyInfo;
+
+    extern crate std;
+    use std::vec::Vec;
+
+    fn decode(decoder: &mut Ps2Decoder, bytes: &[u8]) -> Vec<(KeyType, EventType)> {
+        bytes
+            .iter()
+            .filter_map(|byte| decoder.feed(*byte))
+            .map(|event| (event.key_with_modifiers(), event.event_type()))
+            .collect()
+    }
+
+    #[test]
+    fn make_and_break() {
+        let mut decoder = Ps2Decoder::new();
+        let shift_a = [0x12, 0x1c, 0x1c, 0xf0, 0x1c, 0xf0, 0x12, 0x1c];
+        assert_eq!(
+            decode(&mut decoder, &shift_a),
+            [
+                (
+                    KeyType::Functional(FunctionalKey::LeftShift),
+                    EventType::Press
+                ),
+                (KeyType::Unicode('A'), EventType::Press),
+                (KeyType::Unicode('A'), EventType::Repeat),
+                (KeyType::Unicode('A'), EventType::Release),
+                (
+                    KeyType::Functional(FunctionalKey::LeftShift),
+                    EventType::Release
+                ),
+                (KeyType::Unicode('a'), EventType::Press),
+            ]
+        );
+        assert_eq!(decoder.modifiers(), KeyboardModifiers::empty());
+    }
+
+    #[test]
+    fn extended_codes() {
+        let mut decoder = Ps2Decoder::new();
+        let up = KeyType::Functional(FunctionalKey::Up);
+        let print = KeyType::Functional(FunctionalKey::PrintScreen);
+        assert_eq!(
+            decode(&mut decoder, &[0xe0, 0x75, 0xe0, 0xf0, 0x75]),
+            [(up, EventType::Press), (up, EventType::Release)]
+        );
+        assert_eq!(
+            decode(&mut decoder, &[0xe0, 0x12, 0xe0, 0x7c]),
+            [(print, EventType::Press)]
+        );
+        assert_eq!(
+            decode(
+                &mut decoder,
+                &[0xe1, 0x14, 0x77, 0xe1, 0xf0, 0x14, 0xf0, 0x77]
+            ),
+            [(KeyType::Functional(FunctionalKey::Pause), EventType::Press)]
+        );
+        // Right Alt is extended, Left Alt is not
+        decode(&mut decoder, &[0xe0, 0x11]);
+        assert_eq!(decoder.modifiers(), KeyboardModifiers::ALT);
+    }
+
+    #[test]
+    fn lock_keys() {
+        let mut decoder = Ps2Decoder::new();
+        decode(&mut decoder, &[0x58, 0x58, 0xf0, 0x58]);
+        assert_eq!(decoder.modifiers(), KeyboardModifiers::CAPS_LOCK);
+
+        let caps = decode(&mut decoder, &[0x1c, 0xf0, 0x1c, 0x16]);
+        assert_eq!(caps[0].0, KeyType::Unicode('A'));
+        assert_eq!(caps[2].0, KeyType::Unicode('1'));
+
+        let event = decoder.feed(0x70).unwrap();
+        assert_eq!(
+            event.key_with_modifiers,
+            KeyType::Functional(FunctionalKey::NumPad0)
+        );
+        assert_eq!(event.key_base_layout, event.key_without_modifiers);
+    }
+}
@@ -0,0 +1,253 @@
+use core::fmt::Write;
+
+use crate::{
+    encoder::InputEncoder,
+    mouse::{MouseEvent, MouseEventKind},
+    sequence::EventType,
+    synthesize::SyntheticKeyEvent,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuedEvent<'a> {
+    Key(SyntheticKeyEvent<'a>),
+    Mouse(MouseEvent),
+    Focus(bool),
+    Paste(&'a str),
+}
+
+impl QueuedEvent<'_> {
+    fn is_repeat(&self) -> bool {
+        matches!(self, QueuedEvent::Key(key) if key.event_type == EventType::Repeat)
+    }
+
+    fn is_motion(&self) -> bool {
+        matches!(self, QueuedEvent::Mouse(mouse) if mouse.kind == MouseEventKind::Motion)
+    }
+
+    pub fn encode(&self, encoder: &mut InputEncoder, out: &mut impl Write) -> core::fmt::Result {
+        match self {
+            QueuedEvent::Key(key) => encoder.encode_key(key, out),
+            QueuedEvent::Mouse(mouse) => encoder.encode_mouse(mouse, out),
+            QueuedEvent::Focus(focused) => encoder.encode_focus(*focused, out),
+            QueuedEvent::Paste(text) => encoder.encode_paste(text, out),
+        }
+    }
+}
+
+// What happens to events while the queue is full. Every policy pushes back
+// once nothing is left to drop or merge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backpressure {
+    // The event is handed back, the caller waits for the application
+    #[default]
+    Block,
+    // Key repeats are dropped, the oldest first and the new event last
+    DropRepeats,
+    // Consecutive motion collapses into the latest position
+    MergeMotion,
+}
+
+// Holds events between the generator and the pty while the application is not
+// reading, so a stalled application costs at most `N` events
+#[derive(Debug, Clone)]
+pub struct OutputQueue<'a, const N: usize = 64> {
+    events: [Option<QueuedEvent<'a>>; N],
+    len: usize,
+    policy: Backpressure,
+    dropped: usize,
+}
+
+impl<const N: usize> Default for OutputQueue<'_, N> {
+    fn default() -> Self {
+        Self::new(Backpressure::default())
+    }
+}
+
+impl<'a, const N: usize> OutputQueue<'a, N> {
+    pub fn new(policy: Backpressure) -> Self {
+        Self {
+            events: [None; N],
+            len: 0,
+            policy,
+            dropped: 0,
+        }
+    }
+
+    pub fn policy(&self) -> Backpressure {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: Backpressure) {
+        self.policy = policy;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len >= N
+    }
+
+    // Events dropped or merged away so far
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    pub fn push(&mut self, event: QueuedEvent<'a>) -> Result<(), QueuedEvent<'a>> {
+        if self.is_full() && !self.make_room(&event) {
+            return Err(event);
+        }
+        if self.len >= N {
+            // The new event was dropped or merged
+            return Ok(());
+        }
+
+        if let Some(slot) = self.events.get_mut(self.len) {
+            *slot = Some(event);
+            self.len += 1;
+        }
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<QueuedEvent<'a>> {
+        let event = self.events.first_mut()?.take()?;
+        if let Some(queued) = self.events.get_mut(..self.len) {
+            queued.rotate_left(1);
+        }
+        self.len -= 1;
+        Some(event)
+    }
+
+    // Writes everything queued, e.g. once the pty is writable again
+    pub fn flush(&mut self, encoder: &mut InputEncoder, out: &mut impl Write) -> core::fmt::Result {
+        while let Some(event) = self.pop() {
+            event.encode(encoder, out)?;
+        }
+        Ok(())
+    }
+
+    // Returns false if the policy found nothing to give up for `event`
+    fn make_room(&mut self, event: &QueuedEvent<'a>) -> bool {
+        let queued = self.events.get_mut(..self.len).unwrap_or_default();
+        match self.policy {
+            Backpressure::Block => false,
+            Backpressure::DropRepeats => {
+                match queued.iter().position(|e| e.is_some_and(|e| e.is_repeat())) {
+                    Some(i) => {
+                        if let Some(rest) = queued.get_mut(i..) {
+                            rest.rotate_left(1);
+                        }
+                        if let Some(last) = queued.last_mut() {
+                            *last = None;
+                        }
+                        self.len -= 1;
+                    }
+                    None if !event.is_repeat() => return false,
+                    None => {}
+                }
+                self.dropped += 1;
+                true
+            }
+            Backpressure::MergeMotion => {
+                let Some(last) = queued.last_mut() else {
+                    return false;
+                };
+                if !event.is_motion() || !last.is_some_and(|e| e.is_motion()) {
+                    return false;
+                }
+                *last = Some(*event);
+                self.dropped += 1;
+                // Taking the place of the last event leaves the queue full
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::KeyType;
+
+    extern crate std;
+    use std::{string::String, vec::Vec};
+
+    fn key(ch: char, event_type: EventType) -> QueuedEvent<'static> {
+        QueuedEvent::Key(SyntheticKeyEvent {
+            event_type,
+            ..SyntheticKeyEvent::from(KeyType::Unicode(ch))
+        })
+    }
+
+    fn motion(column: u16) -> QueuedEvent<'static> {
+        QueuedEvent::Mouse(MouseEvent {
+            kind: MouseEventKind::Motion,
+            column,
+            ..MouseEvent::default()
+        })
+    }
+
+    fn drain(queue: &mut OutputQueue<'static, 3>) -> Vec<QueuedEvent<'static>> {
+        core::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    #[test]
+    fn block() {
+        let mut queue = OutputQueue::<'_, 2>::new(Backpressure::Block);
+        assert_eq!(queue.push(key('a', EventType::Press)), Ok(()));
+        assert_eq!(queue.push(key('a', EventType::Repeat)), Ok(()));
+        let b = key('b', EventType::Press);
+        assert_eq!(queue.push(b), Err(b));
+
+        let mut out = String::new();
+        queue.flush(&mut InputEncoder::new(), &mut out).unwrap();
+        assert_eq!(out, "aa");
+        assert!(queue.is_empty());
+        assert_eq!(queue.push(b), Ok(()));
+    }
+
+    #[test]
+    fn drop_repeats() {
+        let mut queue = OutputQueue::<'_, 3>::new(Backpressure::DropRepeats);
+        for event in [
+            key('a', EventType::Press),
+            key('a', EventType::Repeat),
+            key('a', EventType::Repeat),
+            key('b', EventType::Press),
+            key('b', EventType::Repeat),
+            key('b', EventType::Repeat),
+            key('c', EventType::Press),
+        ] {
+            assert_eq!(queue.push(event), Ok(()));
+        }
+        assert_eq!(queue.dropped(), 4);
+        let d = key('d', EventType::Press);
+        assert_eq!(queue.push(d), Err(d));
+
+        assert_eq!(
+            drain(&mut queue),
+            [
+                key('a', EventType::Press),
+                key('b', EventType::Press),
+                key('c', EventType::Press),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_motion() {
+        let mut queue = OutputQueue::<'_, 3>::new(Backpressure::MergeMotion);
+        for column in 0..10 {
+            assert_eq!(queue.push(motion(column)), Ok(()));
+        }
+        assert_eq!(queue.dropped(), 7);
+        let a = key('a', EventType::Press);
+        assert_eq!(queue.push(a), Err(a));
+        assert_eq!(drain(&mut queue), [motion(0), motion(1), motion(9)]);
+    }
+}
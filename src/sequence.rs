@@ -1,7 +1,12 @@
-use core::fmt::{Display, Write};
+use core::{
+    fmt::{Display, Write},
+    str::FromStr,
+};
 
 use bitflags::bitflags;
 
+use crate::ReportingMode;
+
 #[derive(Debug, Clone, Default)]
 pub struct Sequence<'a> {
     pub introducer: SequenceIntroducer,
@@ -12,44 +17,306 @@ pub struct Sequence<'a> {
     pub terminator: SequenceTerminator,
 }
 
+impl<'a> Sequence<'a> {
+    pub fn validate(&self) -> Result<(), SequenceError> {
+        if let SequenceIntroducer::Private(_) = self.introducer {
+            let key_fields = self.key_code.shifted_key_code.is_some()
+                || self.key_code.base_layout_key_code.is_some()
+                || self.event_type != EventType::Press
+                || self.associated_text.is_some()
+                || self.terminator != SequenceTerminator::Kitty;
+            if key_fields {
+                return Err(SequenceError::KeyFieldsInCommand);
+            }
+            return Ok(());
+        }
+
+        if !self.modifier.intersects(KeyboardModifiers::SHIFT)
+            && self.key_code.shifted_key_code.is_some()
+        {
+            return Err(SequenceError::ShiftedKeyWithoutShift);
+        }
+        match &self.associated_text {
+            Some(AssociatedText("")) => return Err(SequenceError::EmptyAssociatedText),
+            Some(_) if self.event_type != EventType::Press => {
+                return Err(SequenceError::AssociatedTextNotOnPress)
+            }
+            _ => {}
+        }
+        if self.introducer == SequenceIntroducer::SS3
+            && (self.key_code
+                != KeyCode {
+                    key_code: 1,
+                    ..Default::default()
+                }
+                || !self.modifier.is_empty()
+                || self.event_type != EventType::Press
+                || self.associated_text.is_some())
+        {
+            return Err(SequenceError::ParametersWithSs3);
+        }
+
+        Ok(())
+    }
+
+    // Rewrites into the minimal form the spec's encoders produce
+    pub fn normalize(&mut self) {
+        let key_code = &mut self.key_code;
+        if !self.modifier.intersects(KeyboardModifiers::SHIFT)
+            || key_code.shifted_key_code == Some(key_code.key_code)
+        {
+            key_code.shifted_key_code = None;
+        }
+        if key_code.base_layout_key_code == Some(key_code.key_code) {
+            key_code.base_layout_key_code = None;
+        }
+
+        if matches!(self.associated_text, Some(AssociatedText(""))) {
+            self.associated_text = None;
+        }
+
+        // Older tilde forms of keys that are letter terminated in the spec
+        if self.terminator == SequenceTerminator::Other('~') {
+            let letter = match key_code.key_code {
+                1 | 7 => Some('H'),
+                4 | 8 => Some('F'),
+                11 => Some('P'),
+                12 => Some('Q'),
+                14 => Some('S'),
+                _ => None,
+            };
+            if let Some(letter) = letter {
+                key_code.key_code = 1;
+                self.terminator = SequenceTerminator::Other(letter);
+            }
+        }
+        if let SequenceTerminator::Other('A'..='Z') = self.terminator {
+            key_code.key_code = 1;
+        }
+
+        let has_parameters = *key_code
+            != KeyCode {
+                key_code: 1,
+                ..Default::default()
+            }
+            || !self.modifier.is_empty()
+            || self.event_type != EventType::Press
+            || self.associated_text.is_some();
+        if self.introducer == SequenceIntroducer::SS3 && has_parameters {
+            self.introducer = SequenceIntroducer::CSI;
+        }
+    }
+
+    // Also checks that every field was allowed by the flags in `mode`
+    pub fn validate_for(&self, mode: ReportingMode) -> Result<(), SequenceError> {
+        self.validate()?;
+        // Commands are sent by the application, not reported
+        if let SequenceIntroducer::Private(_) = self.introducer {
+            return Ok(());
+        }
+
+        if self.terminator == SequenceTerminator::Kitty
+            && !mode.intersects(
+                ReportingMode::DISAMBIGUATE_ESC_CODES | ReportingMode::REPORT_ALL_KEYS_AS_ESC,
+            )
+        {
+            return Err(SequenceError::NotReported(
+                ReportingMode::DISAMBIGUATE_ESC_CODES,
+            ));
+        }
+
+        // Without alternate keys the terminal leaves the shifted key out
+        let printable = self.terminator == SequenceTerminator::Kitty
+            && char::from_u32(self.key_code.key_code)
+                .is_some_and(|ch| !ch.is_control() && !is_private_use(ch));
+        if mode.contains(ReportingMode::REPORT_ALTERNATE_KEYS)
+            && printable
+            && self.modifier.intersects(KeyboardModifiers::SHIFT)
+            && self.key_code.shifted_key_code.is_none()
+        {
+            return Err(SequenceError::MissingShiftedKey);
+        }
+
+        let required = [
+            (
+                self.event_type != EventType::Press,
+                ReportingMode::REPORT_EVENT_TYPES,
+            ),
+            (
+                self.key_code.shifted_key_code.is_some()
+                    || self.key_code.base_layout_key_code.is_some(),
+                ReportingMode::REPORT_ALTERNATE_KEYS,
+            ),
+            (
+                self.associated_text.is_some(),
+                ReportingMode::REPORT_ASSOCIATED_TEXT,
+            ),
+        ];
+
+        match required
+            .into_iter()
+            .find(|(present, flag)| *present && !mode.contains(*flag))
+        {
+            Some((_, flag)) => Err(SequenceError::NotReported(flag)),
+            None => Ok(()),
+        }
+    }
+}
+
 impl<'a> Display for Sequence<'a> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        debug_assert!(
-            !self.modifier.intersects(KeyboardModifiers::SHIFT)
-                || self.key_code.shifted_key_code.is_some(),
-            "KeyCode: {:?}, Modifier: {:?}",
-            self.key_code,
-            self.modifier
-        );
+        write!(f, "{}", self.introducer)?;
+        let mut params = CsiParams::new(&mut *f);
+        match self.introducer {
+            // The parameter is not a key code, 1 is not its default
+            SequenceIntroducer::Private(_) => {
+                params.param(Some(self.key_code.key_code).filter(|&param| param != 0))?
+            }
+            _ => self.key_code.write_params(&mut params)?,
+        }
 
-        write!(f, "{}{}", self.introducer, self.key_code)?;
+        let event_type = self.event_type != EventType::Press;
+        let modifier = !self.modifier.is_empty() || event_type;
+        params.param(modifier.then(|| u32::from(self.modifier_param().get())))?;
+        params.sub_param(event_type.then(|| self.event_type.param()))?;
+        if let Some(associated) = &self.associated_text {
+            associated.write_params(&mut params)?;
+        }
+
+        write!(f, "{}", self.terminator)
+    }
+}
 
-        match (
-            self.modifier.is_empty(),
-            self.event_type,
-            &self.associated_text,
+// The commands an application sends to manage the flags, with the flags or
+// count as the key code and the mode of `CSI = flags ; mode u` as the
+// modifier parameter
+impl Sequence<'static> {
+    // `CSI ? u`
+    pub fn query_flags() -> Self {
+        Self::private(PrivateMarker::Question, 0)
+    }
+
+    // `CSI > flags u`
+    pub fn push_flags(mode: ReportingMode) -> Self {
+        Self::private(PrivateMarker::Greater, mode.bits())
+    }
+
+    // `CSI < count u`
+    pub fn pop_flags(count: u32) -> Self {
+        Self::private(PrivateMarker::Less, count)
+    }
+
+    // `CSI = flags ; mode u`
+    pub fn set_flags(mode: ReportingMode, update: FlagsUpdate) -> Self {
+        Self {
+            modifier: KeyboardModifiers::from_bits_retain(update as u8 - 1),
+            ..Self::private(PrivateMarker::Equals, mode.bits())
+        }
+    }
+
+    fn private(marker: PrivateMarker, parameter: u32) -> Self {
+        Self {
+            introducer: SequenceIntroducer::Private(marker),
+            key_code: KeyCode {
+                key_code: parameter,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+impl<'a> Sequence<'a> {
+    pub fn modifier_param(&self) -> ModifierParam {
+        self.modifier.into()
+    }
+
+    // Displays every field, defaults included, for parsers that mishandle
+    // omitted ones. SS3 sequences and the flag commands stay as they are.
+    pub fn verbose(&self) -> Verbose<'_, 'a> {
+        Verbose(self)
+    }
+}
+
+pub struct Verbose<'s, 'a>(&'s Sequence<'a>);
+
+impl<'s, 'a> Display for Verbose<'s, 'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let seq = self.0;
+        if matches!(
+            seq.introducer,
+            SequenceIntroducer::SS3 | SequenceIntroducer::Private(_)
         ) {
-            (true, EventType::Press, None) => Ok(()),
-            (false, EventType::Press, None) => write!(f, ";{}", self.modifier),
-            (true, EventType::Press, Some(associated)) => write!(f, ";;{associated}"),
-            (_, _, None) => write!(f, ";{}:{}", self.modifier, self.event_type),
-            (false, EventType::Press, Some(associated)) => {
-                write!(f, ";{};{associated}", self.modifier)
+            return seq.fmt(f);
+        }
+
+        write!(f, "{}", seq.introducer)?;
+        let mut params = CsiParams::new(&mut *f);
+        params.param(Some(seq.key_code.key_code))?;
+        params.sub_param(seq.key_code.shifted_key_code)?;
+        params.sub_param(seq.key_code.base_layout_key_code)?;
+        params.param(Some(u32::from(seq.modifier_param().get())))?;
+        params.sub_param(Some(seq.event_type.param()))?;
+        if let Some(associated) = &seq.associated_text {
+            associated.write_params(&mut params)?;
+        }
+
+        write!(f, "{}", seq.terminator)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceError {
+    MissingShiftedKey,
+    ShiftedKeyWithoutShift,
+    EmptyAssociatedText,
+    AssociatedTextNotOnPress,
+    ParametersWithSs3,
+    NotReported(ReportingMode),
+    ShiftedKeyCode,
+    KeyFieldsInCommand,
+}
+
+impl Display for SequenceError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SequenceError::MissingShiftedKey => {
+                f.write_str("shift is pressed but no shifted key is given")
             }
-            (_, _, Some(associated)) => {
-                write!(f, ";{}:{};{associated}", self.modifier, self.event_type)
+            SequenceError::ShiftedKeyWithoutShift => {
+                f.write_str("shifted key is given but shift is not pressed")
             }
-        }?;
-
-        write!(f, "{}", self.terminator)
+            SequenceError::EmptyAssociatedText => f.write_str("associated text is empty"),
+            SequenceError::AssociatedTextNotOnPress => {
+                f.write_str("associated text is only sent with press events")
+            }
+            SequenceError::ParametersWithSs3 => f.write_str("SS3 sequences take no parameters"),
+            SequenceError::NotReported(flag) => {
+                write!(f, "field requires reporting mode {flag:?}")
+            }
+            SequenceError::ShiftedKeyCode => {
+                f.write_str("key without modifiers is not the lowercase, unshifted key")
+            }
+            SequenceError::KeyFieldsInCommand => {
+                f.write_str("flag commands only take the flags and the mode")
+            }
+        }
     }
 }
 
+// Kitty assigns its functional key codes from the first range, the keys of
+// `EXTENSION_KEY_BASE` are in the second
+fn is_private_use(ch: char) -> bool {
+    ('\u{e000}'..='\u{f8ff}').contains(&ch) || ('\u{f0000}'..='\u{10fffd}').contains(&ch)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum SequenceIntroducer {
     #[default]
     CSI,
     SS3,
+    // `CSI ?`, `CSI >` and so on, for the commands that manage the flags
+    Private(PrivateMarker),
 }
 
 impl Display for SequenceIntroducer {
@@ -57,10 +324,46 @@ impl Display for SequenceIntroducer {
         match self {
             Self::CSI => write!(f, "\x1b["),
             Self::SS3 => write!(f, "\x1bO"),
+            Self::Private(marker) => write!(f, "\x1b[{marker}"),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivateMarker {
+    // Queries and their replies
+    Question,
+    // Pushes the flags
+    Greater,
+    // Pops the flags
+    Less,
+    // Sets the flags
+    Equals,
+}
+
+impl Display for PrivateMarker {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_char(match self {
+            Self::Question => '?',
+            Self::Greater => '>',
+            Self::Less => '<',
+            Self::Equals => '=',
+        })
+    }
+}
+
+// The second parameter of `CSI = flags ; mode u`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlagsUpdate {
+    // Set bits are set and unset bits reset
+    #[default]
+    Assign = 1,
+    // Only set bits are set
+    Set = 2,
+    // Only set bits are reset
+    Reset = 3,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct KeyCode {
     pub key_code: u32,
@@ -68,21 +371,19 @@ pub struct KeyCode {
     pub base_layout_key_code: Option<u32>,
 }
 
+impl KeyCode {
+    // The key code is left out when it is 1 and there are no alternates
+    fn write_params<W: Write>(&self, params: &mut CsiParams<W>) -> core::fmt::Result {
+        let alternates = self.shifted_key_code.is_some() || self.base_layout_key_code.is_some();
+        params.param(Some(self.key_code).filter(|&key_code| key_code != 1 || alternates))?;
+        params.sub_param(self.shifted_key_code)?;
+        params.sub_param(self.base_layout_key_code)
+    }
+}
+
 impl Display for KeyCode {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        match (
-            self.key_code,
-            self.shifted_key_code,
-            self.base_layout_key_code,
-        ) {
-            (1, None, None) => Ok(()),
-            (_, None, None) => write!(f, "{}", self.key_code),
-            (_, Some(alternate), None) => write!(f, "{}:{}", self.key_code, alternate),
-            (_, None, Some(base)) => write!(f, "{}::{}", self.key_code, base),
-            (_, Some(alternate), Some(base)) => {
-                write!(f, "{}:{}:{}", self.key_code, alternate, base)
-            }
-        }
+        self.write_params(&mut CsiParams::new(f))
     }
 }
 
@@ -102,7 +403,87 @@ bitflags! {
 
 impl Display for KeyboardModifiers {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "{}", self.bits() as u16 + 1)
+        ModifierParam::from(*self).fmt(f)
+    }
+}
+
+// The modifier field as sent, the modifier bits plus one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModifierParam(u16);
+
+impl ModifierParam {
+    pub const NONE: Self = Self(1);
+
+    pub fn get(self) -> u16 {
+        self.0
+    }
+
+    pub fn modifiers(self) -> KeyboardModifiers {
+        KeyboardModifiers::from_bits_retain((self.0 - 1) as u8)
+    }
+}
+
+impl Default for ModifierParam {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+impl From<KeyboardModifiers> for ModifierParam {
+    fn from(modifiers: KeyboardModifiers) -> Self {
+        Self(u16::from(modifiers.bits()) + 1)
+    }
+}
+
+impl From<ModifierParam> for KeyboardModifiers {
+    fn from(param: ModifierParam) -> Self {
+        param.modifiers()
+    }
+}
+
+impl TryFrom<u32> for ModifierParam {
+    type Error = ModifierParamError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match u16::try_from(value) {
+            Ok(value @ 1..=256) => Ok(Self(value)),
+            _ => Err(ModifierParamError::OutOfRange),
+        }
+    }
+}
+
+impl FromStr for ModifierParam {
+    type Err = ModifierParamError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || !s.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(ModifierParamError::NotANumber);
+        }
+        // Anything too long for a u32 is out of range as well
+        s.parse::<u32>()
+            .map_or(Err(ModifierParamError::OutOfRange), Self::try_from)
+    }
+}
+
+impl Display for ModifierParam {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModifierParamError {
+    NotANumber,
+    // Zero or above 256
+    OutOfRange,
+}
+
+impl Display for ModifierParamError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ModifierParamError::NotANumber => f.write_str("modifier field is not a number"),
+            ModifierParamError::OutOfRange => f.write_str("modifier field is not within 1 to 256"),
+        }
     }
 }
 
@@ -114,33 +495,102 @@ pub enum EventType {
     Release,
 }
 
-impl Display for EventType {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+impl EventType {
+    fn param(self) -> u32 {
         match self {
-            EventType::Press => f.write_char('1'),
-            EventType::Repeat => f.write_char('2'),
-            EventType::Release => f.write_char('3'),
+            EventType::Press => 1,
+            EventType::Repeat => 2,
+            EventType::Release => 3,
         }
     }
 }
 
+impl Display for EventType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.param())
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct AssociatedText<'a>(pub &'a str);
 
-impl<'a> Display for AssociatedText<'a> {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+impl<'a> AssociatedText<'a> {
+    fn write_params<W: Write>(&self, params: &mut CsiParams<W>) -> core::fmt::Result {
         let mut chars = self.0.chars();
-        if let Some(ch) = chars.next() {
-            write!(f, "{}", u32::from(ch))?;
-        }
+        params.param(chars.next().map(u32::from))?;
         for ch in chars {
-            write!(f, ":{}", u32::from(ch))?;
+            params.sub_param(Some(u32::from(ch)))?;
         }
 
         Ok(())
     }
 }
 
+impl<'a> Display for AssociatedText<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.write_params(&mut CsiParams::new(f))
+    }
+}
+
+// Writes the parameters of a CSI sequence, `97:65;2` or `;;97`. Omitted
+// parameters and sub-parameters only leave their separators behind when
+// something follows them, so trailing defaults are left out entirely.
+pub struct CsiParams<W> {
+    out: W,
+    started: bool,
+    // Separators waiting for the next value
+    params: usize,
+    sub_params: usize,
+}
+
+impl<W: Write> CsiParams<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            started: false,
+            params: 0,
+            sub_params: 0,
+        }
+    }
+
+    // Starts the next parameter, `None` for one that is omitted
+    pub fn param(&mut self, value: Option<u32>) -> core::fmt::Result {
+        if self.started {
+            self.params += 1;
+        }
+        self.started = true;
+        self.sub_params = 0;
+        self.value(value)
+    }
+
+    // Adds a sub-parameter to the current parameter
+    pub fn sub_param(&mut self, value: Option<u32>) -> core::fmt::Result {
+        self.started = true;
+        self.sub_params += 1;
+        self.value(value)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn value(&mut self, value: Option<u32>) -> core::fmt::Result {
+        let Some(value) = value else {
+            return Ok(());
+        };
+
+        for _ in 0..self.params {
+            self.out.write_char(';')?;
+        }
+        for _ in 0..self.sub_params {
+            self.out.write_char(':')?;
+        }
+        self.params = 0;
+        self.sub_params = 0;
+        write!(self.out, "{value}")
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum SequenceTerminator {
     #[default]
@@ -163,7 +613,7 @@ mod tests {
     use super::*;
 
     extern crate std;
-    use std::format;
+    use std::{format, string::String};
 
     #[test]
     fn introducer_display() {
@@ -171,6 +621,62 @@ mod tests {
         assert_eq!(format!("{}", SequenceIntroducer::SS3), "\x1bO");
     }
 
+    #[test]
+    fn csi_params() {
+        let mut params = CsiParams::new(String::new());
+        params.param(None).unwrap();
+        params.sub_param(Some(2)).unwrap();
+        params.param(Some(0)).unwrap();
+        params.sub_param(None).unwrap();
+        params.sub_param(Some(7)).unwrap();
+        params.param(None).unwrap();
+        params.param(Some(1)).unwrap();
+        params.sub_param(None).unwrap();
+        params.param(None).unwrap();
+        assert_eq!(params.into_inner(), ":2;0::7;;1");
+
+        let mut params = CsiParams::new(String::new());
+        params.param(None).unwrap();
+        params.sub_param(None).unwrap();
+        assert_eq!(params.into_inner(), "");
+    }
+
+    #[test]
+    fn flag_commands() {
+        let mode = ReportingMode::DISAMBIGUATE_ESC_CODES | ReportingMode::REPORT_EVENT_TYPES;
+        assert_eq!(format!("{}", Sequence::query_flags()), "\x1b[?u");
+        assert_eq!(format!("{}", Sequence::push_flags(mode)), "\x1b[>3u");
+        assert_eq!(
+            format!(
+                "{}",
+                Sequence::push_flags(ReportingMode::DISAMBIGUATE_ESC_CODES)
+            ),
+            "\x1b[>1u"
+        );
+        assert_eq!(format!("{}", Sequence::pop_flags(2)), "\x1b[<2u");
+        assert_eq!(
+            format!("{}", Sequence::set_flags(mode, FlagsUpdate::Assign)),
+            "\x1b[=3u"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                Sequence::set_flags(mode, FlagsUpdate::Reset).verbose()
+            ),
+            "\x1b[=3;3u"
+        );
+
+        assert_eq!(
+            Sequence::set_flags(mode, FlagsUpdate::Set).validate(),
+            Ok(())
+        );
+        let release = Sequence {
+            event_type: EventType::Release,
+            ..Sequence::pop_flags(1)
+        };
+        assert_eq!(release.validate(), Err(SequenceError::KeyFieldsInCommand));
+    }
+
     #[test]
     fn key_code_display() {
         assert_eq!(
@@ -241,6 +747,38 @@ mod tests {
         assert_eq!(format!("{}", KeyboardModifiers::all()), "256");
     }
 
+    #[test]
+    fn modifier_params() {
+        let ctrl_shift = KeyboardModifiers::CTRL | KeyboardModifiers::SHIFT;
+        let param = ModifierParam::from(ctrl_shift);
+        assert_eq!(param.get(), 6);
+        assert_eq!(format!("{param}"), "6");
+        assert_eq!("6".parse(), Ok(param));
+        assert_eq!(KeyboardModifiers::from(param), ctrl_shift);
+        assert_eq!(ModifierParam::from(KeyboardModifiers::all()).get(), 256);
+
+        assert_eq!(
+            "0".parse::<ModifierParam>(),
+            Err(ModifierParamError::OutOfRange)
+        );
+        assert_eq!(
+            "257".parse::<ModifierParam>(),
+            Err(ModifierParamError::OutOfRange)
+        );
+        assert_eq!(
+            "99999999999".parse::<ModifierParam>(),
+            Err(ModifierParamError::OutOfRange)
+        );
+        assert_eq!(
+            "-1".parse::<ModifierParam>(),
+            Err(ModifierParamError::NotANumber)
+        );
+        assert_eq!(
+            ModifierParam::default().modifiers(),
+            KeyboardModifiers::empty()
+        );
+    }
+
     #[test]
     fn event_type_display() {
         assert_eq!(format!("{}", EventType::Press), "1");
@@ -261,6 +799,38 @@ mod tests {
         assert_eq!(format!("{}", SequenceTerminator::Other('~')), "~");
     }
 
+    #[test]
+    fn verbose_display() {
+        let up = Sequence {
+            terminator: SequenceTerminator::Other('A'),
+            key_code: KeyCode {
+                key_code: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(format!("{}", up.verbose()), "\x1b[1;1:1A");
+
+        let shift_a = Sequence {
+            key_code: KeyCode {
+                key_code: 97,
+                shifted_key_code: Some(65),
+                base_layout_key_code: None,
+            },
+            modifier: KeyboardModifiers::SHIFT,
+            event_type: EventType::Release,
+            associated_text: Some(AssociatedText("A")),
+            ..Default::default()
+        };
+        assert_eq!(format!("{}", shift_a.verbose()), "\x1b[97:65;2:3;65u");
+
+        let ss3 = Sequence {
+            introducer: SequenceIntroducer::SS3,
+            ..up
+        };
+        assert_eq!(format!("{}", ss3.verbose()), "\x1bOA");
+    }
+
     #[test]
     fn sequence_display() {
         let sequence = Sequence {
@@ -301,8 +871,178 @@ mod tests {
     }
 
     #[test]
-    #[cfg_attr(debug_assertions, should_panic)]
-    fn invalid_sequence_display_no_alternate() {
+    fn validate() {
+        let a = KeyCode {
+            key_code: 'a'.into(),
+            ..Default::default()
+        };
+
+        let shifted = Sequence {
+            key_code: KeyCode {
+                shifted_key_code: Some('A'.into()),
+                ..a.clone()
+            },
+            modifier: KeyboardModifiers::SHIFT,
+            ..Default::default()
+        };
+        assert_eq!(shifted.validate(), Ok(()));
+        assert_eq!(
+            Sequence {
+                modifier: KeyboardModifiers::empty(),
+                ..shifted.clone()
+            }
+            .validate(),
+            Err(SequenceError::ShiftedKeyWithoutShift)
+        );
+        let no_alternate = Sequence {
+            key_code: a.clone(),
+            ..shifted.clone()
+        };
+        assert_eq!(no_alternate.validate(), Ok(()));
+        assert_eq!(
+            no_alternate.validate_for(ReportingMode::DISAMBIGUATE_ESC_CODES),
+            Ok(())
+        );
+        assert_eq!(
+            no_alternate.validate_for(ReportingMode::all()),
+            Err(SequenceError::MissingShiftedKey)
+        );
+
+        // Functional keys have no shifted form
+        let shift_up = Sequence {
+            modifier: KeyboardModifiers::SHIFT,
+            ..crate::key::FunctionalKey::Up.to_sequence()
+        };
+        assert_eq!(shift_up.validate(), Ok(()));
+
+        let text = |event_type, text| Sequence {
+            key_code: a.clone(),
+            event_type,
+            associated_text: Some(AssociatedText(text)),
+            ..Default::default()
+        };
+        assert_eq!(text(EventType::Press, "a").validate(), Ok(()));
+        assert_eq!(
+            text(EventType::Release, "a").validate(),
+            Err(SequenceError::AssociatedTextNotOnPress)
+        );
+        assert_eq!(
+            text(EventType::Press, "").validate(),
+            Err(SequenceError::EmptyAssociatedText)
+        );
+
+        let ss3 = Sequence {
+            introducer: SequenceIntroducer::SS3,
+            key_code: KeyCode {
+                key_code: 1,
+                ..Default::default()
+            },
+            terminator: SequenceTerminator::Other('P'),
+            ..Default::default()
+        };
+        assert_eq!(ss3.validate(), Ok(()));
+        assert_eq!(
+            Sequence {
+                modifier: KeyboardModifiers::CTRL,
+                ..ss3
+            }
+            .validate(),
+            Err(SequenceError::ParametersWithSs3)
+        );
+    }
+
+    #[test]
+    fn normalize() {
+        let mut redundant = Sequence {
+            key_code: KeyCode {
+                key_code: 'a'.into(),
+                shifted_key_code: Some('A'.into()),
+                base_layout_key_code: Some('a'.into()),
+            },
+            associated_text: Some(AssociatedText("")),
+            ..Default::default()
+        };
+        redundant.normalize();
+        assert_eq!(format!("{redundant}"), "\x1b[97u");
+        assert_eq!(redundant.validate(), Ok(()));
+
+        let mut rxvt_home = Sequence {
+            key_code: KeyCode {
+                key_code: 7,
+                ..Default::default()
+            },
+            modifier: KeyboardModifiers::CTRL,
+            terminator: SequenceTerminator::Other('~'),
+            ..Default::default()
+        };
+        rxvt_home.normalize();
+        assert_eq!(format!("{rxvt_home}"), "\x1b[;5H");
+
+        let mut modified_ss3 = Sequence {
+            introducer: SequenceIntroducer::SS3,
+            key_code: KeyCode {
+                key_code: 1,
+                ..Default::default()
+            },
+            modifier: KeyboardModifiers::ALT,
+            terminator: SequenceTerminator::Other('P'),
+            ..Default::default()
+        };
+        modified_ss3.normalize();
+        assert_eq!(format!("{modified_ss3}"), "\x1b[;3P");
+
+        let mut f3 = Sequence {
+            key_code: KeyCode {
+                key_code: 13,
+                ..Default::default()
+            },
+            terminator: SequenceTerminator::Other('~'),
+            ..Default::default()
+        };
+        f3.normalize();
+        assert_eq!(format!("{f3}"), "\x1b[13~");
+    }
+
+    #[test]
+    fn validate_for_mode() {
+        let release = Sequence {
+            key_code: KeyCode {
+                key_code: 'a'.into(),
+                base_layout_key_code: Some('q'.into()),
+                ..Default::default()
+            },
+            event_type: EventType::Release,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            release.validate_for(ReportingMode::empty()),
+            Err(SequenceError::NotReported(
+                ReportingMode::DISAMBIGUATE_ESC_CODES
+            ))
+        );
+        assert_eq!(
+            release.validate_for(ReportingMode::REPORT_ALL_KEYS_AS_ESC),
+            Err(SequenceError::NotReported(
+                ReportingMode::REPORT_EVENT_TYPES
+            ))
+        );
+        assert_eq!(
+            release.validate_for(
+                ReportingMode::REPORT_ALL_KEYS_AS_ESC | ReportingMode::REPORT_EVENT_TYPES
+            ),
+            Err(SequenceError::NotReported(
+                ReportingMode::REPORT_ALTERNATE_KEYS
+            ))
+        );
+        assert_eq!(release.validate_for(ReportingMode::all()), Ok(()));
+
+        let legacy = crate::key::FunctionalKey::Up.to_sequence();
+        assert_eq!(legacy.validate_for(ReportingMode::empty()), Ok(()));
+    }
+
+    #[test]
+    fn sequence_display_no_alternate() {
         let no_alternate_sequence = Sequence {
             key_code: KeyCode {
                 key_code: 'a'.into(),
@@ -312,6 +1052,6 @@ mod tests {
             ..Default::default()
         };
 
-        let _ = format!("{no_alternate_sequence}");
+        assert_eq!(format!("{no_alternate_sequence}"), "\x1b[97;2u");
     }
 }
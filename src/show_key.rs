@@ -0,0 +1,207 @@
+use core::fmt::{self, Write};
+
+use crate::{
+    binding::key_name,
+    generate_sequence,
+    key::KeyType,
+    parse::{ParserConfig, TerminalEvent},
+    sequence::{EventType, KeyboardModifiers},
+    KeyEvent, ReportingMode,
+};
+
+// In the order kitty prints them
+const MODIFIER_NAMES: &[(KeyboardModifiers, &str)] = &[
+    (KeyboardModifiers::SHIFT, "shift"),
+    (KeyboardModifiers::ALT, "alt"),
+    (KeyboardModifiers::CTRL, "ctrl"),
+    (KeyboardModifiers::SUPER, "super"),
+    (KeyboardModifiers::HYPER, "hyper"),
+    (KeyboardModifiers::META, "meta"),
+    (KeyboardModifiers::CAPS_LOCK, "caps_lock"),
+    (KeyboardModifiers::NUM_LOCK, "num_lock"),
+];
+
+// One entry per key in the style of `kitten show-key -m kitty`: the bytes
+// sent, then the key with its modifiers and event type
+pub fn show_event(mode: ReportingMode, event: &impl KeyEvent, out: &mut impl Write) -> fmt::Result {
+    let mut bytes = Bytes(out);
+    write!(bytes, "{}", generate_sequence(mode, event))?;
+    out.write_char('\n')?;
+    describe(event, out)?;
+    out.write_char('\n')
+}
+
+// The same for what a terminal sent, returning the number of bytes consumed.
+// Text is shown as it is, other events are only named.
+pub fn show_bytes(input: &[u8], out: &mut impl Write) -> Result<usize, fmt::Error> {
+    let config = ParserConfig::kitty();
+    let mut consumed = 0;
+
+    while let Ok((event, len)) = config.parse(input.get(consumed..).unwrap_or_default()) {
+        let raw = input.get(consumed..consumed + len).unwrap_or_default();
+        consumed += len;
+
+        for chunk in raw.utf8_chunks() {
+            Bytes(out).write_str(chunk.valid())?;
+            for byte in chunk.invalid() {
+                write!(out, "\\x{byte:02x}")?;
+            }
+        }
+        out.write_char('\n')?;
+
+        match event {
+            TerminalEvent::Key(key) => describe(&key, out)?,
+            TerminalEvent::Text(text) => write!(out, "text {text:?}")?,
+            _ => out.write_str("not a key")?,
+        }
+        out.write_char('\n')?;
+    }
+
+    Ok(consumed)
+}
+
+// `ctrl+shift+a PRESS`, followed by the shifted and base layout keys when they
+// differ and the text
+pub fn describe(event: &impl KeyEvent, out: &mut impl Write) -> fmt::Result {
+    let modifiers = event.modifiers();
+    for (modifier, name) in MODIFIER_NAMES {
+        if modifiers.contains(*modifier) {
+            write!(out, "{name}+")?;
+        }
+    }
+
+    let key = event.key_without_modifiers();
+    write_key(key, out)?;
+    out.write_str(match event.event_type() {
+        EventType::Press => " PRESS",
+        EventType::Repeat => " REPEAT",
+        EventType::Release => " RELEASE",
+    })?;
+
+    let shifted = event.key_with_modifiers();
+    if shifted != key && shifted != KeyType::Unknown {
+        out.write_str(" shifted: ")?;
+        write_key(shifted, out)?;
+    }
+    let base = event.key_base_layout();
+    if base != key && base != KeyType::Unknown {
+        out.write_str(" base: ")?;
+        write_key(base, out)?;
+    }
+    if let Some(text) = event.associated_text() {
+        write!(out, " text: {:?}", text.0)?;
+    }
+
+    Ok(())
+}
+
+// Kitty's names, `page_up` and `kp_0` for the chord names `PageUp` and
+// `NumPad0`
+fn write_key(key: KeyType, out: &mut impl Write) -> fmt::Result {
+    let name = match key {
+        KeyType::Unicode(' ') => return out.write_str("space"),
+        KeyType::Unicode(ch) => return out.write_char(ch),
+        KeyType::Functional(func) => key_name(func),
+        KeyType::Unknown => return out.write_str("unknown"),
+    };
+
+    let name = match name.strip_prefix("NumPad") {
+        Some(rest) => {
+            out.write_str("kp_")?;
+            rest
+        }
+        None => name,
+    };
+    for (i, ch) in name.char_indices() {
+        if ch.is_ascii_uppercase() && i > 0 {
+            out.write_char('_')?;
+        }
+        out.write_char(ch.to_ascii_lowercase())?;
+    }
+
+    Ok(())
+}
+
+// Sequences with their introducer spelled out, `CSI 97;5u`, and other control
+// characters in caret notation
+struct Bytes<'a, W>(&'a mut W);
+
+impl<W: Write> Write for Bytes<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut rest = s;
+        while let Some(ch) = rest.chars().next() {
+            let introducer = [("\x1b[", "CSI "), ("\x1bO", "SS3 ")]
+                .into_iter()
+                .find(|(prefix, _)| rest.starts_with(prefix));
+            if let Some((prefix, name)) = introducer {
+                self.0.write_str(name)?;
+                rest = rest.get(prefix.len()..).unwrap_or_default();
+                continue;
+            }
+
+            match ch {
+                '\x1b' => self.0.write_str("ESC ")?,
+                '\x7f' => self.0.write_str("^?")?,
+                '\0'..='\x1f' => {
+                    self.0.write_char('^')?;
+                    self.0.write_char(char::from(ch as u8 + b'@'))?;
+                }
+                _ => self.0.write_char(ch)?,
+            }
+            rest = rest.get(ch.len_utf8()..).unwrap_or_default();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{key::FunctionalKey, synthesize::SyntheticKeyEvent};
+
+    extern crate std;
+    use std::string::String;
+
+    #[test]
+    fn events() {
+        let mode = ReportingMode::all();
+        let mut out = String::new();
+
+        let shift_a = SyntheticKeyEvent::from_char('A', "A");
+        show_event(mode, &shift_a, &mut out).unwrap();
+        let keypad = SyntheticKeyEvent {
+            modifiers: KeyboardModifiers::CTRL,
+            event_type: EventType::Release,
+            ..SyntheticKeyEvent::from(KeyType::Functional(FunctionalKey::NumPadPageUp))
+        };
+        show_event(mode, &keypad, &mut out).unwrap();
+
+        assert_eq!(
+            out,
+            concat!(
+                "CSI 97:65;2;65u\n",
+                "shift+a PRESS shifted: A text: \"A\"\n",
+                "CSI 57421;5:3u\n",
+                "ctrl+kp_page_up RELEASE\n",
+            )
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        let mut out = String::new();
+        let consumed = show_bytes(b"\x1b[1;5A\x01ab\x1b[", &mut out).unwrap();
+
+        assert_eq!(consumed, 9);
+        assert_eq!(
+            out,
+            concat!(
+                "CSI 1;5A\n",
+                "ctrl+up PRESS\n",
+                "^Aab\n",
+                "text \"\\u{1}ab\"\n",
+            )
+        );
+    }
+}
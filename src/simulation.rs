@@ -0,0 +1,212 @@
+use core::fmt::{self, Write};
+
+use std::{env, fs, io, path::Path, string::String};
+
+use crate::{
+    encoder::InputEncoder,
+    mouse::MouseEvent,
+    synthesize::{type_text, Script, ScriptEvent, Step},
+    test_utils::{disassemble, escape},
+    KeyEvent, ReportingMode,
+};
+
+// Golden files are rewritten instead of compared while this is set
+pub const UPDATE_GOLDEN_VAR: &str = "VTE_INPUT_UPDATE_GOLDEN";
+
+// A terminal without a screen: OS level events go in, the bytes an
+// application would read come out and are compared to the expected output
+#[derive(Debug)]
+pub struct Simulation {
+    encoder: InputEncoder,
+    output: String,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Self::with_encoder(InputEncoder::new())
+    }
+
+    pub fn with_encoder(encoder: InputEncoder) -> Self {
+        Self {
+            encoder,
+            output: String::new(),
+        }
+    }
+
+    pub fn encoder(&mut self) -> &mut InputEncoder {
+        &mut self.encoder
+    }
+
+    // What an application would do with `CSI = flags u`
+    pub fn set_mode(&mut self, mode: ReportingMode) -> &mut Self {
+        self.encoder.set_mode(mode);
+        self
+    }
+
+    pub fn set_private_mode(&mut self, mode: u16, enabled: bool) -> &mut Self {
+        self.encoder.set_private_mode(mode, enabled);
+        self
+    }
+
+    pub fn key(&mut self, event: &impl KeyEvent) -> &mut Self {
+        let _ = self.encoder.encode_key(event, &mut self.output);
+        self
+    }
+
+    // Typed key by key on a US layout, in the encoder's current mode
+    pub fn type_text(&mut self, text: &str) -> &mut Self {
+        for event in type_text(self.encoder.mode(), text) {
+            let _ = self.encoder.encode_key(&event, &mut self.output);
+        }
+        self
+    }
+
+    // Markers are left out of the output, waits only move the timestamps
+    pub fn script(&mut self, steps: &[Step<'_>]) -> &mut Self {
+        for timed in Script::new(self.encoder.mode(), steps).events() {
+            if let ScriptEvent::Key(event) = timed.event {
+                let _ = self.encoder.encode_key(&event, &mut self.output);
+            }
+        }
+        self
+    }
+
+    pub fn paste(&mut self, text: &str) -> &mut Self {
+        let _ = self.encoder.encode_paste(text, &mut self.output);
+        self
+    }
+
+    pub fn mouse(&mut self, event: &MouseEvent) -> &mut Self {
+        let _ = self.encoder.encode_mouse(event, &mut self.output);
+        self
+    }
+
+    pub fn focus(&mut self, focused: bool) -> &mut Self {
+        let _ = self.encoder.encode_focus(focused, &mut self.output);
+        self
+    }
+
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn take_output(&mut self) -> String {
+        core::mem::take(&mut self.output)
+    }
+
+    #[track_caller]
+    #[allow(clippy::panic)]
+    pub fn assert_output(&self, expected: &str) {
+        if self.output != expected {
+            panic!("{}", mismatch(expected, &self.output));
+        }
+    }
+
+    // Compares the output to the contents of `path`. The file is written
+    // instead when it does not exist yet or `UPDATE_GOLDEN_VAR` is set.
+    #[track_caller]
+    #[allow(clippy::panic)]
+    pub fn assert_golden(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let update = env::var_os(UPDATE_GOLDEN_VAR).is_some();
+
+        let expected = match fs::read(path) {
+            Ok(expected) if !update => expected,
+            Err(err) if !update && err.kind() != io::ErrorKind::NotFound => {
+                panic!("cannot read golden file {}: {err}", path.display())
+            }
+            _ => {
+                if let Err(err) = fs::write(path, &self.output) {
+                    panic!("cannot write golden file {}: {err}", path.display());
+                }
+                return;
+            }
+        };
+
+        if expected != self.output.as_bytes() {
+            panic!(
+                "output differs from golden file {}, rerun with {UPDATE_GOLDEN_VAR}=1 to accept it\n{}",
+                path.display(),
+                mismatch(&expected, &self.output)
+            );
+        }
+    }
+}
+
+fn mismatch(expected: impl AsRef<[u8]>, actual: &str) -> String {
+    let mut message = String::new();
+    let _ = write_side(&mut message, "expected", expected.as_ref());
+    let _ = write_side(&mut message, "  actual", actual.as_bytes());
+    message
+}
+
+fn write_side(out: &mut impl Write, name: &str, bytes: &[u8]) -> fmt::Result {
+    write!(out, "{name}: \"")?;
+    escape(bytes, out)?;
+    write!(out, "\"\n{:>name$}  ", "", name = name.len())?;
+    disassemble(bytes, out)?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        key::{FunctionalKey, KeyType},
+        sequence::KeyboardModifiers,
+    };
+
+    use std::{format, panic, process};
+
+    #[test]
+    fn end_to_end() {
+        let mut sim = Simulation::new();
+        sim.type_text("ls")
+            .script(&[Step::Press("enter".parse().unwrap())])
+            .set_private_mode(2004, true)
+            .paste("a\nb");
+        sim.assert_output("ls\r\x1b[200~a\nb\x1b[201~");
+
+        sim.take_output();
+        sim.set_mode(ReportingMode::DISAMBIGUATE_ESC_CODES)
+            .set_private_mode(1004, true)
+            .focus(false)
+            .key(&(
+                KeyType::Functional(FunctionalKey::Escape),
+                KeyboardModifiers::empty(),
+            ));
+        sim.assert_output("\x1b[O\x1b[27u");
+
+        let message = panic::catch_unwind(|| sim.assert_output("\x1b"))
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert!(
+            message.contains("actual: \"\\x1b[O\\x1b[27u\""),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn golden_files() {
+        let path = env::temp_dir().join(format!("vte-input-golden-{}", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut sim = Simulation::new();
+        sim.type_text("hi");
+        sim.assert_golden(&path);
+        assert_eq!(fs::read(&path).unwrap(), b"hi");
+        sim.assert_golden(&path);
+
+        sim.type_text("!");
+        let result = panic::catch_unwind(|| sim.assert_golden(&path));
+        let _ = fs::remove_file(&path);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{
+    key::{FunctionalKey, KeyType},
+    sequence::{EventType, KeyboardModifiers},
+    synthesize::SyntheticKeyEvent,
+};
+
+// Slint's `Key::Backtab`, sent for Shift+Tab
+const BACKTAB: char = '\u{19}';
+
+// Slint sends special keys as the text of the key event: control characters
+// for the editing and modifier keys, and the private use characters of the
+// macOS function keys for the rest
+pub fn special_key(ch: char) -> Option<FunctionalKey> {
+    let func = match ch {
+        '\u{8}' => FunctionalKey::Backspace,
+        '\t' | BACKTAB => FunctionalKey::Tab,
+        '\n' | '\r' => FunctionalKey::Enter,
+        '\u{1b}' => FunctionalKey::Escape,
+        '\u{7f}' => FunctionalKey::Delete,
+        '\u{10}' => FunctionalKey::LeftShift,
+        '\u{11}' => FunctionalKey::LeftControl,
+        '\u{12}' => FunctionalKey::LeftAlt,
+        '\u{13}' => FunctionalKey::IsoLevel3Shift,
+        '\u{14}' => FunctionalKey::CapsLock,
+        '\u{15}' => FunctionalKey::RightShift,
+        '\u{16}' => FunctionalKey::RightControl,
+        '\u{17}' => FunctionalKey::LeftSuper,
+        '\u{18}' => FunctionalKey::RightSuper,
+        '\u{f700}' => FunctionalKey::Up,
+        '\u{f701}' => FunctionalKey::Down,
+        '\u{f702}' => FunctionalKey::Left,
+        '\u{f703}' => FunctionalKey::Right,
+        // F1 to F24
+        '\u{f704}'..='\u{f71b}' => {
+            let index = FunctionalKey::F1 as usize + (ch as usize - 0xf704);
+            return FunctionalKey::ALL.get(index).copied();
+        }
+        '\u{f727}' => FunctionalKey::Insert,
+        '\u{f729}' => FunctionalKey::Home,
+        '\u{f72b}' => FunctionalKey::End,
+        '\u{f72c}' => FunctionalKey::PageUp,
+        '\u{f72d}' => FunctionalKey::PageDown,
+        '\u{f72f}' => FunctionalKey::ScrollLock,
+        '\u{f730}' => FunctionalKey::Pause,
+        '\u{f731}' => FunctionalKey::PrintScreen,
+        '\u{f735}' => FunctionalKey::Menu,
+        _ => return None,
+    };
+
+    Some(func)
+}
+
+// Slint's `KeyboardModifiers`, `meta` is the Windows or Command key
+pub fn modifiers(alt: bool, control: bool, meta: bool, shift: bool) -> KeyboardModifiers {
+    let mut modifiers = KeyboardModifiers::empty();
+    modifiers.set(KeyboardModifiers::ALT, alt);
+    modifiers.set(KeyboardModifiers::CTRL, control);
+    modifiers.set(KeyboardModifiers::SUPER, meta);
+    modifiers.set(KeyboardModifiers::SHIFT, shift);
+    modifiers
+}
+
+// A `KeyPressed` or `KeyReleased` event. Slint has no layout information, so
+// the unshifted key of a character is its lowercase form and the base layout
+// key is left unknown.
+pub fn key_event(
+    text: &str,
+    mut modifiers: KeyboardModifiers,
+    event_type: EventType,
+) -> SyntheticKeyEvent<'_> {
+    let Some(ch) = text.chars().next() else {
+        return SyntheticKeyEvent::default();
+    };
+
+    if ch == BACKTAB {
+        modifiers |= KeyboardModifiers::SHIFT;
+    }
+    if let Some(func) = special_key(ch) {
+        return SyntheticKeyEvent {
+            modifiers,
+            event_type,
+            ..SyntheticKeyEvent::from(KeyType::Functional(func))
+        };
+    }
+
+    let key = KeyType::Unicode(ch);
+    let typed = event_type != EventType::Release
+        && !modifiers.intersects(
+            KeyboardModifiers::CTRL | KeyboardModifiers::ALT | KeyboardModifiers::SUPER,
+        );
+    SyntheticKeyEvent {
+        key_with_modifiers: key,
+        key_without_modifiers: key.lowercase(),
+        key_base_layout: KeyType::Unknown,
+        modifiers,
+        event_type,
+        associated_text: typed.then_some(text),
+        timestamp: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_sequence, ReportingMode};
+
+    extern crate std;
+    use std::format;
+
+    fn encode(
+        mode: ReportingMode,
+        text: &str,
+        modifiers: KeyboardModifiers,
+    ) -> std::string::String {
+        format!(
+            "{}",
+            generate_sequence(mode, &key_event(text, modifiers, EventType::Press))
+        )
+    }
+
+    #[test]
+    fn special_keys() {
+        assert_eq!(special_key('\u{f700}'), Some(FunctionalKey::Up));
+        assert_eq!(special_key('\u{f706}'), Some(FunctionalKey::F3));
+        assert_eq!(special_key('\u{f71b}'), Some(FunctionalKey::F24));
+        assert_eq!(special_key('a'), None);
+
+        let none = KeyboardModifiers::empty();
+        assert_eq!(encode(ReportingMode::empty(), "\u{f72c}", none), "\x1b[5~");
+        let all_keys = ReportingMode::REPORT_ALL_KEYS_AS_ESC;
+        assert_eq!(encode(all_keys, "\u{19}", none), "\x1b[9;2u");
+        #[cfg(feature = "modifier-keys")]
+        assert_eq!(encode(all_keys, "\u{17}", none), "\x1b[57444u");
+    }
+
+    #[test]
+    fn characters() {
+        let shift = modifiers(false, false, false, true);
+        let event = key_event("A", shift, EventType::Press);
+        assert_eq!(event.key_with_modifiers, KeyType::Unicode('A'));
+        assert_eq!(event.key_without_modifiers, KeyType::Unicode('a'));
+        assert_eq!(event.associated_text, Some("A"));
+
+        let ctrl = modifiers(false, true, false, false);
+        assert_eq!(
+            encode(ReportingMode::DISAMBIGUATE_ESC_CODES, "c", ctrl),
+            "\x1b[99;5u"
+        );
+        assert_eq!(key_event("c", ctrl, EventType::Press).associated_text, None);
+        assert_eq!(
+            key_event("c", KeyboardModifiers::empty(), EventType::Release).associated_text,
+            None
+        );
+    }
+}
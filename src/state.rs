@@ -0,0 +1,167 @@
+use core::time::Duration;
+
+use crate::{
+    filter::Clock,
+    key::KeyType,
+    parse::TerminalEvent,
+    sequence::{EventType, KeyboardModifiers},
+    KeyEvent,
+};
+
+// Tracks which keys are held, keyed by their unshifted key. Only meaningful
+// when release events are reported.
+#[derive(Debug, Clone)]
+pub struct KeyStateMap<C, const N: usize = 16> {
+    clock: C,
+    pressed: [(KeyType, Duration); N],
+    len: usize,
+    modifiers: KeyboardModifiers,
+}
+
+impl<C: Clock, const N: usize> KeyStateMap<C, N> {
+    pub fn new(clock: C) -> Self {
+        Self {
+            clock,
+            pressed: [(KeyType::Unknown, Duration::ZERO); N],
+            len: 0,
+            modifiers: KeyboardModifiers::empty(),
+        }
+    }
+
+    pub fn feed(&mut self, event: &impl KeyEvent) {
+        let key = event.key_without_modifiers();
+        self.modifiers = event.modifiers();
+
+        if key == KeyType::Unknown {
+            return;
+        }
+
+        match (event.event_type(), self.position(key)) {
+            (EventType::Release, Some(index)) => {
+                self.pressed.copy_within(index + 1..self.len, index);
+                self.len -= 1;
+            }
+            (EventType::Release, None) | (_, Some(_)) => {}
+            // A repeat for an unknown key means we missed its press, count from now
+            (EventType::Press | EventType::Repeat, None) => {
+                if let Some(slot) = self.pressed.get_mut(self.len) {
+                    *slot = (key, self.clock.now());
+                    self.len += 1;
+                }
+            }
+        }
+    }
+
+    pub fn is_pressed(&self, key: KeyType) -> bool {
+        self.position(key).is_some()
+    }
+
+    pub fn held_duration(&self, key: KeyType) -> Option<Duration> {
+        let (_, pressed_at) = *self.pressed.get(self.position(key)?)?;
+        Some(self.clock.now().saturating_sub(pressed_at))
+    }
+
+    pub fn pressed(&self) -> impl Iterator<Item = KeyType> + '_ {
+        self.pressed[..self.len].iter().map(|(key, _)| *key)
+    }
+
+    // What the application read from the terminal, losing focus clears the
+    // state as the releases go to the other window
+    pub fn feed_terminal_event(&mut self, event: &TerminalEvent<'_>) {
+        match event {
+            TerminalEvent::Key(key) => self.feed(key),
+            TerminalEvent::FocusLost => self.clear(),
+            _ => {}
+        }
+    }
+
+    // Modifiers of the most recent event
+    pub fn modifiers(&self) -> KeyboardModifiers {
+        self.modifiers
+    }
+
+    // Releases are not delivered while unfocused, forget everything
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.modifiers = KeyboardModifiers::empty();
+    }
+
+    fn position(&self, key: KeyType) -> Option<usize> {
+        self.pressed[..self.len]
+            .iter()
+            .position(|(pressed, _)| *pressed == key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use core::cell::Cell;
+
+    use super::*;
+    use crate::{key::FunctionalKey, parse::Events, synthesize::SyntheticKeyEvent};
+
+    extern crate std;
+    use std::vec::Vec;
+
+    fn key(key: KeyType, event_type: EventType) -> SyntheticKeyEvent<'static> {
+        SyntheticKeyEvent {
+            event_type,
+            ..SyntheticKeyEvent::from(key)
+        }
+    }
+
+    #[test]
+    fn press_and_release() {
+        let now = Cell::new(Duration::ZERO);
+        let mut state: KeyStateMap<_> = KeyStateMap::new(|| now.get());
+        let w = KeyType::Unicode('w');
+        let space = KeyType::Unicode(' ');
+
+        state.feed(&key(w, EventType::Press));
+        now.set(Duration::from_millis(50));
+        state.feed(&key(space, EventType::Press));
+        now.set(Duration::from_millis(120));
+        state.feed(&key(w, EventType::Repeat));
+
+        assert!(state.is_pressed(w));
+        assert_eq!(state.held_duration(w), Some(Duration::from_millis(120)));
+        assert_eq!(state.held_duration(space), Some(Duration::from_millis(70)));
+        assert_eq!(state.pressed().collect::<Vec<_>>(), [w, space]);
+
+        state.feed(&key(w, EventType::Release));
+        assert!(!state.is_pressed(w));
+        assert_eq!(state.held_duration(w), None);
+        assert_eq!(state.pressed().collect::<Vec<_>>(), [space]);
+    }
+
+    #[test]
+    fn missed_press_and_capacity() {
+        let mut state: KeyStateMap<_, 2> = KeyStateMap::new(|| Duration::ZERO);
+        let left = KeyType::Functional(FunctionalKey::Left);
+
+        state.feed(&key(left, EventType::Repeat));
+        assert!(state.is_pressed(left));
+
+        state.feed(&key(KeyType::Unicode('a'), EventType::Press));
+        state.feed(&key(KeyType::Unicode('b'), EventType::Press));
+        assert!(!state.is_pressed(KeyType::Unicode('b')));
+
+        state.clear();
+        assert_eq!(state.pressed().count(), 0);
+    }
+
+    #[test]
+    fn focus_lost() {
+        let mut state: KeyStateMap<_> = KeyStateMap::new(|| Duration::ZERO);
+        for event in Events::new(b"\x1b[119;1:1u\x1b[I\x1b[119;1:2u") {
+            state.feed_terminal_event(&event);
+        }
+        assert!(state.is_pressed(KeyType::Unicode('w')));
+
+        for event in Events::new(b"\x1b[O\x1b[97u") {
+            state.feed_terminal_event(&event);
+        }
+        assert_eq!(state.pressed().collect::<Vec<_>>(), [KeyType::Unicode('a')]);
+    }
+}
@@ -0,0 +1,690 @@
+use core::{fmt::Write, slice, str::CharIndices, time::Duration};
+
+use crate::{
+    binding::Chord,
+    generate_sequence,
+    key::{FunctionalKey, KeyType},
+    layouts::US,
+    sequence::{AssociatedText, EventType, KeyboardModifiers},
+    KeyEvent, KeyInfo, ModifierInfo, ReportingMode, TextInfo,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SyntheticKeyEvent<'a> {
+    pub key_with_modifiers: KeyType,
+    pub key_without_modifiers: KeyType,
+    pub key_base_layout: KeyType,
+
+    pub modifiers: KeyboardModifiers,
+    pub event_type: EventType,
+    pub associated_text: Option<&'a str>,
+    pub timestamp: Option<Duration>,
+}
+
+impl<'a> SyntheticKeyEvent<'a> {
+    // `text` is the slice holding `ch`, reported as associated text
+    pub fn from_char(ch: char, text: &'a str) -> Self {
+        let key = match ch {
+            '\r' | '\n' => Some(FunctionalKey::Enter),
+            '\t' => Some(FunctionalKey::Tab),
+            '\x08' | '\x7f' => Some(FunctionalKey::Backspace),
+            '\x1b' => Some(FunctionalKey::Escape),
+            _ => None,
+        };
+
+        if let Some(key) = key {
+            return Self::from(KeyType::Functional(key));
+        }
+
+        match us_layout(ch) {
+            Some((base, shifted)) => Self {
+                key_with_modifiers: KeyType::Unicode(ch),
+                key_without_modifiers: KeyType::Unicode(base),
+                key_base_layout: KeyType::Unicode(base),
+                modifiers: if shifted {
+                    KeyboardModifiers::SHIFT
+                } else {
+                    KeyboardModifiers::empty()
+                },
+                associated_text: Some(text),
+                ..Default::default()
+            },
+            // Not on the layout, pretend a key produced it directly
+            None => Self {
+                associated_text: Some(text),
+                ..Self::from(KeyType::Unicode(ch))
+            },
+        }
+    }
+
+    // `ch` typed with `modifiers` held, Shift is added if the US layout needs
+    // it for `ch`. There is no associated text, `ch` could be anywhere.
+    pub fn with_modifiers(ch: char, modifiers: KeyboardModifiers) -> Self {
+        match us_layout(ch) {
+            Some((base, shifted)) => {
+                let shift = if shifted {
+                    KeyboardModifiers::SHIFT
+                } else {
+                    KeyboardModifiers::empty()
+                };
+                Self::from_chord(Chord::new(KeyType::Unicode(base), modifiers | shift))
+            }
+            None => Self {
+                modifiers,
+                associated_text: None,
+                ..Self::from_char(ch, "")
+            },
+        }
+    }
+
+    pub fn from_chord(chord: Chord) -> Self {
+        let modifiers = chord.modifiers;
+        let KeyType::Unicode(base) = chord.key else {
+            return Self {
+                modifiers,
+                ..Self::from(chord.key)
+            };
+        };
+
+        let mut ch = base;
+        if modifiers.intersects(KeyboardModifiers::SHIFT) {
+            ch = us_shifted(base).unwrap_or(base);
+        }
+
+        Self {
+            key_with_modifiers: KeyType::Unicode(ch),
+            modifiers,
+            ..Self::from(chord.key)
+        }
+    }
+
+    pub fn released(self) -> Self {
+        Self {
+            event_type: EventType::Release,
+            associated_text: None,
+            ..self
+        }
+    }
+}
+
+impl From<KeyType> for SyntheticKeyEvent<'_> {
+    fn from(key: KeyType) -> Self {
+        Self {
+            key_with_modifiers: key,
+            key_without_modifiers: key,
+            key_base_layout: key,
+            ..Default::default()
+        }
+    }
+}
+
+impl KeyInfo for SyntheticKeyEvent<'_> {
+    fn key_with_modifiers(&self) -> KeyType {
+        self.key_with_modifiers
+    }
+
+    fn key_without_modifiers(&self) -> KeyType {
+        self.key_without_modifiers
+    }
+
+    fn key_base_layout(&self) -> KeyType {
+        self.key_base_layout
+    }
+
+    fn event_type(&self) -> EventType {
+        self.event_type
+    }
+
+    fn timestamp(&self) -> Option<Duration> {
+        self.timestamp
+    }
+}
+
+impl ModifierInfo for SyntheticKeyEvent<'_> {
+    fn modifiers(&self) -> KeyboardModifiers {
+        self.modifiers
+    }
+}
+
+impl TextInfo for SyntheticKeyEvent<'_> {
+    fn associated_text(&self) -> Option<AssociatedText<'_>> {
+        self.associated_text.map(AssociatedText)
+    }
+}
+
+pub fn type_text(mode: ReportingMode, text: &str) -> TypeText<'_> {
+    TypeText {
+        mode,
+        text,
+        chars: text.char_indices(),
+        release: None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeText<'a> {
+    mode: ReportingMode,
+    text: &'a str,
+    chars: CharIndices<'a>,
+    release: Option<SyntheticKeyEvent<'a>>,
+}
+
+impl TypeText<'_> {
+    pub fn write_to(self, out: &mut impl Write) -> core::fmt::Result {
+        let mode = self.mode;
+        for event in self {
+            write!(out, "{}", generate_sequence(mode, &event))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for TypeText<'a> {
+    type Item = SyntheticKeyEvent<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(release) = self.release.take() {
+            return Some(release);
+        }
+
+        let (start, ch) = self.chars.next()?;
+        let event = SyntheticKeyEvent::from_char(
+            ch,
+            self.text
+                .get(start..start + ch.len_utf8())
+                .unwrap_or_default(),
+        );
+
+        // Releases would be dropped by the generator anyway
+        if self.mode.intersects(ReportingMode::REPORT_EVENT_TYPES) {
+            self.release = Some(event.released());
+        }
+
+        Some(event)
+    }
+}
+
+// The key reported for each modifier bit, in the order the bits are diffed
+const MODIFIER_KEYS: [(KeyboardModifiers, FunctionalKey); 6] = [
+    (KeyboardModifiers::SHIFT, FunctionalKey::LeftShift),
+    (KeyboardModifiers::ALT, FunctionalKey::LeftAlt),
+    (KeyboardModifiers::CTRL, FunctionalKey::LeftControl),
+    (KeyboardModifiers::SUPER, FunctionalKey::LeftSuper),
+    (KeyboardModifiers::HYPER, FunctionalKey::LeftHyper),
+    (KeyboardModifiers::META, FunctionalKey::LeftMeta),
+];
+
+// For platforms that only report the current modifiers, not the modifier
+// keys: turns changes between snapshots into presses and releases of the left
+// modifier keys. Like kitty, a press already carries its own bit and a release
+// no longer does. Lock modifiers are states and never produce events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ModifierDiff {
+    modifiers: KeyboardModifiers,
+}
+
+impl ModifierDiff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn modifiers(&self) -> KeyboardModifiers {
+        self.modifiers
+    }
+
+    // Call with the modifiers of every platform event before handling it
+    pub fn update(
+        &mut self,
+        modifiers: KeyboardModifiers,
+        mut emit: impl FnMut(SyntheticKeyEvent<'static>),
+    ) {
+        let locks = KeyboardModifiers::CAPS_LOCK | KeyboardModifiers::NUM_LOCK;
+        self.modifiers = (self.modifiers - locks) | (modifiers & locks);
+
+        // Releases first, so a switch from Ctrl to Alt never has both held
+        for pressed in [false, true] {
+            for (bit, key) in MODIFIER_KEYS {
+                let changed = self.modifiers.contains(bit) != modifiers.contains(bit);
+                if !changed || modifiers.contains(bit) != pressed {
+                    continue;
+                }
+                self.modifiers.set(bit, pressed);
+                emit(SyntheticKeyEvent {
+                    modifiers: self.modifiers,
+                    event_type: if pressed {
+                        EventType::Press
+                    } else {
+                        EventType::Release
+                    },
+                    ..SyntheticKeyEvent::from(KeyType::Functional(key))
+                });
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TimedEvent<E> {
+    pub timestamp: Duration,
+    pub event: E,
+}
+
+impl<E: KeyEvent> KeyInfo for TimedEvent<E> {
+    fn key_with_modifiers(&self) -> KeyType {
+        self.event.key_with_modifiers()
+    }
+
+    fn key_without_modifiers(&self) -> KeyType {
+        self.event.key_without_modifiers()
+    }
+
+    fn key_base_layout(&self) -> KeyType {
+        self.event.key_base_layout()
+    }
+
+    fn event_type(&self) -> EventType {
+        self.event.event_type()
+    }
+
+    fn timestamp(&self) -> Option<Duration> {
+        Some(self.timestamp)
+    }
+}
+
+impl<E: KeyEvent> ModifierInfo for TimedEvent<E> {
+    fn modifiers(&self) -> KeyboardModifiers {
+        self.event.modifiers()
+    }
+}
+
+impl<E: KeyEvent> TextInfo for TimedEvent<E> {
+    fn associated_text(&self) -> Option<AssociatedText<'_>> {
+        self.event.associated_text()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step<'a> {
+    Type(&'a str),
+    Press(Chord),
+    Wait(Duration),
+    // Reported in the stream so harnesses can synchronize, e.g. wait for output
+    Marker(&'a str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptEvent<'a> {
+    Key(SyntheticKeyEvent<'a>),
+    Marker(&'a str),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Script<'a> {
+    mode: ReportingMode,
+    steps: &'a [Step<'a>],
+    key_interval: Duration,
+}
+
+impl<'a> Script<'a> {
+    pub const DEFAULT_KEY_INTERVAL: Duration = Duration::from_millis(10);
+
+    pub fn new(mode: ReportingMode, steps: &'a [Step<'a>]) -> Self {
+        Self {
+            mode,
+            steps,
+            key_interval: Self::DEFAULT_KEY_INTERVAL,
+        }
+    }
+
+    pub fn with_key_interval(mut self, key_interval: Duration) -> Self {
+        self.key_interval = key_interval;
+        self
+    }
+
+    pub fn events(&self) -> ScriptEvents<'a> {
+        ScriptEvents {
+            mode: self.mode,
+            steps: self.steps.iter(),
+            key_interval: self.key_interval,
+            typing: None,
+            release: None,
+            now: Duration::ZERO,
+        }
+    }
+
+    pub fn write_to(&self, out: &mut impl Write) -> core::fmt::Result {
+        for timed in self.events() {
+            if let ScriptEvent::Key(event) = timed.event {
+                write!(out, "{}", generate_sequence(self.mode, &event))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptEvents<'a> {
+    mode: ReportingMode,
+    steps: slice::Iter<'a, Step<'a>>,
+    key_interval: Duration,
+    typing: Option<TypeText<'a>>,
+    release: Option<SyntheticKeyEvent<'a>>,
+    now: Duration,
+}
+
+impl<'a> ScriptEvents<'a> {
+    fn key(&mut self, event: SyntheticKeyEvent<'a>) -> TimedEvent<ScriptEvent<'a>> {
+        let timestamp = self.now;
+        self.now = self.now.saturating_add(self.key_interval);
+
+        TimedEvent {
+            timestamp,
+            event: ScriptEvent::Key(SyntheticKeyEvent {
+                timestamp: Some(timestamp),
+                ..event
+            }),
+        }
+    }
+}
+
+impl<'a> Iterator for ScriptEvents<'a> {
+    type Item = TimedEvent<ScriptEvent<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(release) = self.release.take() {
+            return Some(self.key(release));
+        }
+
+        loop {
+            if let Some(event) = self.typing.as_mut().and_then(Iterator::next) {
+                return Some(self.key(event));
+            }
+            self.typing = None;
+
+            match *self.steps.next()? {
+                Step::Type(text) => self.typing = Some(type_text(self.mode, text)),
+                Step::Press(chord) => {
+                    let event = SyntheticKeyEvent::from_chord(chord);
+                    if self.mode.intersects(ReportingMode::REPORT_EVENT_TYPES) {
+                        self.release = Some(event.released());
+                    }
+                    return Some(self.key(event));
+                }
+                Step::Wait(duration) => self.now = self.now.saturating_add(duration),
+                Step::Marker(name) => {
+                    return Some(TimedEvent {
+                        timestamp: self.now,
+                        event: ScriptEvent::Marker(name),
+                    })
+                }
+            }
+        }
+    }
+}
+
+// Maps a character to the unshifted key producing it on a US layout, and
+// whether shift is needed
+fn us_layout(ch: char) -> Option<(char, bool)> {
+    let (key, shift) = US.find(ch)?;
+    Some((US.base(key)?, shift))
+}
+
+fn us_shifted(base: char) -> Option<char> {
+    match US.find(base)? {
+        (key, false) => US.shifted(key),
+        (_, true) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    extern crate std;
+    use std::format;
+    use std::string::String;
+    use std::vec::Vec;
+
+    fn encode(mode: ReportingMode, event: &SyntheticKeyEvent<'_>) -> String {
+        format!("{}", generate_sequence(mode, event))
+    }
+
+    #[test]
+    fn modifier_snapshots() {
+        let mut diff = ModifierDiff::new();
+        let mut events = Vec::new();
+
+        diff.update(
+            KeyboardModifiers::CTRL | KeyboardModifiers::SHIFT,
+            |event| events.push(event),
+        );
+        diff.update(
+            KeyboardModifiers::ALT | KeyboardModifiers::SHIFT | KeyboardModifiers::CAPS_LOCK,
+            |event| events.push(event),
+        );
+        diff.update(
+            KeyboardModifiers::ALT | KeyboardModifiers::SHIFT | KeyboardModifiers::CAPS_LOCK,
+            |event| events.push(event),
+        );
+
+        let summary: Vec<_> = events
+            .iter()
+            .map(|event| (event.key_with_modifiers, event.event_type, event.modifiers))
+            .collect();
+        let key = |func| KeyType::Functional(func);
+        let shift = KeyboardModifiers::SHIFT;
+        assert_eq!(
+            summary,
+            [
+                (key(FunctionalKey::LeftShift), EventType::Press, shift),
+                (
+                    key(FunctionalKey::LeftControl),
+                    EventType::Press,
+                    shift | KeyboardModifiers::CTRL
+                ),
+                (
+                    key(FunctionalKey::LeftControl),
+                    EventType::Release,
+                    shift | KeyboardModifiers::CAPS_LOCK
+                ),
+                (
+                    key(FunctionalKey::LeftAlt),
+                    EventType::Press,
+                    shift | KeyboardModifiers::CAPS_LOCK | KeyboardModifiers::ALT
+                ),
+            ]
+        );
+        assert_eq!(
+            diff.modifiers(),
+            KeyboardModifiers::ALT | KeyboardModifiers::SHIFT | KeyboardModifiers::CAPS_LOCK
+        );
+    }
+
+    #[test]
+    fn shifted_characters() {
+        let events: Vec<_> = type_text(ReportingMode::empty(), "a?é").collect();
+
+        assert_eq!(
+            events,
+            [
+                SyntheticKeyEvent {
+                    associated_text: Some("a"),
+                    ..SyntheticKeyEvent::from(KeyType::Unicode('a'))
+                },
+                SyntheticKeyEvent {
+                    key_with_modifiers: KeyType::Unicode('?'),
+                    key_without_modifiers: KeyType::Unicode('/'),
+                    key_base_layout: KeyType::Unicode('/'),
+                    modifiers: KeyboardModifiers::SHIFT,
+                    associated_text: Some("?"),
+                    ..Default::default()
+                },
+                SyntheticKeyEvent {
+                    associated_text: Some("é"),
+                    ..SyntheticKeyEvent::from(KeyType::Unicode('é'))
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn releases_with_event_types() {
+        let events: Vec<_> = type_text(ReportingMode::REPORT_EVENT_TYPES, "\n")
+            .map(|event| (event.key_with_modifiers, event.event_type))
+            .collect();
+
+        assert_eq!(
+            events,
+            [
+                (KeyType::Functional(FunctionalKey::Enter), EventType::Press),
+                (
+                    KeyType::Functional(FunctionalKey::Enter),
+                    EventType::Release
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn chord_events() {
+        let ctrl_c = SyntheticKeyEvent::from_chord("ctrl+c".parse().unwrap());
+        assert_eq!(ctrl_c.key_with_modifiers, KeyType::Unicode('c'));
+        assert_eq!(ctrl_c.key_without_modifiers, KeyType::Unicode('c'));
+
+        // The C0 control is the legacy encoding's, the shifted key stays
+        let ctrl_shift_a = SyntheticKeyEvent::from_chord("ctrl+shift+a".parse().unwrap());
+        assert_eq!(ctrl_shift_a.key_with_modifiers, KeyType::Unicode('A'));
+        assert_eq!(encode(ReportingMode::empty(), &ctrl_shift_a), "\x01");
+        assert_eq!(
+            encode(ReportingMode::DISAMBIGUATE_ESC_CODES, &ctrl_shift_a),
+            "\x1b[97;6u"
+        );
+        assert_eq!(encode(ReportingMode::all(), &ctrl_shift_a), "\x1b[97:65;6u");
+
+        let shift_1 = SyntheticKeyEvent::from_chord("shift+1".parse().unwrap());
+        assert_eq!(shift_1.key_with_modifiers, KeyType::Unicode('!'));
+        assert_eq!(shift_1.modifiers, KeyboardModifiers::SHIFT);
+
+        let ctrl_up = SyntheticKeyEvent::from_chord("ctrl+up".parse().unwrap());
+        assert_eq!(
+            ctrl_up.key_with_modifiers,
+            KeyType::Functional(FunctionalKey::Up)
+        );
+        assert_eq!(ctrl_up.modifiers, KeyboardModifiers::CTRL);
+    }
+
+    #[test]
+    fn char_with_modifiers() {
+        let ctrl_shift_a = SyntheticKeyEvent::with_modifiers('A', KeyboardModifiers::CTRL);
+        assert_eq!(
+            ctrl_shift_a.modifiers,
+            KeyboardModifiers::CTRL | KeyboardModifiers::SHIFT
+        );
+        assert_eq!(encode(ReportingMode::empty(), &ctrl_shift_a), "\x01");
+        assert_eq!(
+            encode(ReportingMode::DISAMBIGUATE_ESC_CODES, &ctrl_shift_a),
+            "\x1b[97;6u"
+        );
+        assert_eq!(encode(ReportingMode::all(), &ctrl_shift_a), "\x1b[97:65;6u");
+
+        let alt_question = SyntheticKeyEvent::with_modifiers('?', KeyboardModifiers::ALT);
+        assert_eq!(alt_question.key_with_modifiers, KeyType::Unicode('?'));
+        assert_eq!(alt_question.key_base_layout, KeyType::Unicode('/'));
+
+        let alt_enter = SyntheticKeyEvent::with_modifiers('\r', KeyboardModifiers::ALT);
+        assert_eq!(
+            alt_enter.key_without_modifiers,
+            KeyType::Functional(FunctionalKey::Enter)
+        );
+        assert_eq!(alt_enter.modifiers, KeyboardModifiers::ALT);
+        assert_eq!(
+            SyntheticKeyEvent::with_modifiers('é', KeyboardModifiers::empty()).associated_text,
+            None
+        );
+    }
+
+    #[test]
+    fn script_timestamps() {
+        let steps = [
+            Step::Type("ls"),
+            Step::Press("enter".parse().unwrap()),
+            Step::Wait(Duration::from_millis(500)),
+            Step::Marker("prompt"),
+            Step::Press("ctrl+d".parse().unwrap()),
+        ];
+        let script = Script::new(ReportingMode::REPORT_EVENT_TYPES, &steps)
+            .with_key_interval(Duration::from_millis(5));
+
+        let events: Vec<_> = script
+            .events()
+            .map(|timed| {
+                let event = match timed.event {
+                    ScriptEvent::Key(key) => {
+                        assert_eq!(key.timestamp, Some(timed.timestamp));
+                        Ok((key.key_without_modifiers, key.event_type))
+                    }
+                    ScriptEvent::Marker(name) => Err(name),
+                };
+                (timed.timestamp.as_millis(), event)
+            })
+            .collect();
+
+        let enter = KeyType::Functional(FunctionalKey::Enter);
+        assert_eq!(
+            events,
+            [
+                (0, Ok((KeyType::Unicode('l'), EventType::Press))),
+                (5, Ok((KeyType::Unicode('l'), EventType::Release))),
+                (10, Ok((KeyType::Unicode('s'), EventType::Press))),
+                (15, Ok((KeyType::Unicode('s'), EventType::Release))),
+                (20, Ok((enter, EventType::Press))),
+                (25, Ok((enter, EventType::Release))),
+                (530, Err("prompt")),
+                (530, Ok((KeyType::Unicode('d'), EventType::Press))),
+                (535, Ok((KeyType::Unicode('d'), EventType::Release))),
+            ]
+        );
+    }
+
+    #[test]
+    fn script_legacy_output() {
+        let steps = [
+            Step::Type("echo hi"),
+            Step::Press("enter".parse().unwrap()),
+            Step::Marker("done"),
+            Step::Press("ctrl+c".parse().unwrap()),
+        ];
+        let mut out = String::new();
+        Script::new(ReportingMode::empty(), &steps)
+            .write_to(&mut out)
+            .unwrap();
+
+        assert_eq!(out, "echo hi\r\x03");
+    }
+
+    #[test]
+    fn write_legacy() {
+        let mut out = String::new();
+        type_text(ReportingMode::empty(), "Hi!\n")
+            .write_to(&mut out)
+            .unwrap();
+
+        assert_eq!(out, "Hi!\r");
+    }
+
+    #[test]
+    fn write_all_keys_as_esc() {
+        let mut out = String::new();
+        type_text(
+            ReportingMode::REPORT_ALL_KEYS_AS_ESC | ReportingMode::REPORT_EVENT_TYPES,
+            "a\t",
+        )
+        .write_to(&mut out)
+        .unwrap();
+
+        assert_eq!(out, "\x1b[97u\x1b[97;1:3u\x1b[9u\x1b[9;1:3u");
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{
+    key::{FunctionalKey, KeyType},
+    mouse::{self, MouseEventKind},
+    parse::{ParsedKey, TerminalEvent},
+    sequence::{EventType, KeyboardModifiers},
+    KeyInfo, ModifierInfo,
+};
+
+// termion's `event::Key`, an application written against termion's events
+// keeps its key handling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    BackTab,
+    Delete,
+    Insert,
+    F(u8),
+    Char(char),
+    Alt(char),
+    Ctrl(char),
+    Null,
+    Esc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+}
+
+// termion's positions are one based
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEvent {
+    Press(MouseButton, u16, u16),
+    Release(u16, u16),
+    Hold(u16, u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    Key(Key),
+    Mouse(MouseEvent),
+    Unsupported(&'a [u8]),
+}
+
+// A character the way termion reads it from a legacy terminal
+pub fn char_key(ch: char) -> Key {
+    match ch {
+        '\x7f' => Key::Backspace,
+        '\x1b' => Key::Esc,
+        '\r' | '\n' => Key::Char('\n'),
+        '\t' => Key::Char('\t'),
+        '\0' => Key::Null,
+        '\x01'..='\x1a' => Key::Ctrl((ch as u8 - 1 + b'a') as char),
+        '\x1c'..='\x1f' => Key::Ctrl((ch as u8 - 0x1c + b'4') as char),
+        _ => Key::Char(ch),
+    }
+}
+
+// termion has no releases and knows only some of the modifier combinations,
+// the rest have no `Key`
+pub fn key(key: &ParsedKey) -> Option<Key> {
+    if key.event_type() == EventType::Release {
+        return None;
+    }
+
+    let modifiers = key.modifiers() & !(KeyboardModifiers::CAPS_LOCK | KeyboardModifiers::NUM_LOCK);
+    let func = match key.key_with_modifiers() {
+        KeyType::Unicode(ch) => {
+            return match modifiers {
+                KeyboardModifiers::CTRL => match key.key_without_modifiers() {
+                    KeyType::Unicode(ch) => Some(Key::Ctrl(ch)),
+                    _ => None,
+                },
+                KeyboardModifiers::ALT => Some(Key::Alt(ch)),
+                modifiers if modifiers.difference(KeyboardModifiers::SHIFT).is_empty() => {
+                    Some(char_key(ch))
+                }
+                _ => None,
+            };
+        }
+        KeyType::Functional(func) => func,
+        KeyType::Unknown => return None,
+    };
+
+    let key = match (func, modifiers) {
+        (FunctionalKey::Tab, KeyboardModifiers::SHIFT) => Key::BackTab,
+        (_, modifiers) if !modifiers.is_empty() => return None,
+        (FunctionalKey::Escape, _) => Key::Esc,
+        (FunctionalKey::Enter, _) => Key::Char('\n'),
+        (FunctionalKey::Tab, _) => Key::Char('\t'),
+        (FunctionalKey::Backspace, _) => Key::Backspace,
+        (FunctionalKey::Insert, _) => Key::Insert,
+        (FunctionalKey::Delete, _) => Key::Delete,
+        (FunctionalKey::Left, _) => Key::Left,
+        (FunctionalKey::Right, _) => Key::Right,
+        (FunctionalKey::Up, _) => Key::Up,
+        (FunctionalKey::Down, _) => Key::Down,
+        (FunctionalKey::PageUp, _) => Key::PageUp,
+        (FunctionalKey::PageDown, _) => Key::PageDown,
+        (FunctionalKey::Home, _) => Key::Home,
+        (FunctionalKey::End, _) => Key::End,
+        (func, _)
+            if (FunctionalKey::F1 as u8..=FunctionalKey::F35 as u8).contains(&(func as u8)) =>
+        {
+            Key::F(func as u8 - FunctionalKey::F1 as u8 + 1)
+        }
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+// termion has no motion without a button held, nor the back and forward
+// buttons
+pub fn mouse(event: &mouse::MouseEvent) -> Option<MouseEvent> {
+    let (column, row) = (event.column.checked_add(1)?, event.row.checked_add(1)?);
+    let event = match (event.kind, event.button) {
+        (MouseEventKind::Release, _) => MouseEvent::Release(column, row),
+        (MouseEventKind::Motion, Some(_)) => MouseEvent::Hold(column, row),
+        (MouseEventKind::Press, Some(button)) => {
+            let button = match button {
+                mouse::MouseButton::Left => MouseButton::Left,
+                mouse::MouseButton::Middle => MouseButton::Middle,
+                mouse::MouseButton::Right => MouseButton::Right,
+                mouse::MouseButton::WheelUp => MouseButton::WheelUp,
+                mouse::MouseButton::WheelDown => MouseButton::WheelDown,
+                mouse::MouseButton::WheelLeft => MouseButton::WheelLeft,
+                mouse::MouseButton::WheelRight => MouseButton::WheelRight,
+                mouse::MouseButton::Back | mouse::MouseButton::Forward => return None,
+            };
+            MouseEvent::Press(button, column, row)
+        }
+        _ => return None,
+    };
+
+    Some(event)
+}
+
+// The termion events of a parsed event: text is a key per character, the
+// bytes termion would not understand either are `Unsupported`. The replies
+// and reports of the terminal itself are not termion events.
+pub fn for_each_event<'a>(event: &TerminalEvent<'a>, mut emit: impl FnMut(Event<'a>)) {
+    match *event {
+        TerminalEvent::Key(ref parsed) => {
+            if let Some(key) = key(parsed) {
+                emit(Event::Key(key));
+            }
+        }
+        TerminalEvent::Text(text) => text.chars().for_each(|ch| emit(Event::Key(char_key(ch)))),
+        TerminalEvent::Mouse(ref event) => {
+            if let Some(event) = mouse(event) {
+                emit(Event::Mouse(event));
+            }
+        }
+        // termion does not do bracketed paste
+        TerminalEvent::Paste(bytes)
+        | TerminalEvent::PastePart(bytes)
+        | TerminalEvent::Unknown(bytes)
+        | TerminalEvent::Malformed(bytes) => emit(Event::Unsupported(bytes)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Events;
+
+    extern crate std;
+    use std::vec::Vec;
+
+    fn events(input: &'static [u8]) -> Vec<Event<'static>> {
+        let mut events = Vec::new();
+        for event in Events::new(input) {
+            for_each_event(&event, |event| events.push(event));
+        }
+        events
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(
+            events(b"a\x01\x7f\r\x1bx\x1b[A\x1b[9;2u\x1b[3~\x1bOP\x1b[24~"),
+            [
+                Event::Key(Key::Char('a')),
+                Event::Key(Key::Ctrl('a')),
+                Event::Key(Key::Backspace),
+                Event::Key(Key::Char('\n')),
+                Event::Key(Key::Alt('x')),
+                Event::Key(Key::Up),
+                Event::Key(Key::BackTab),
+                Event::Key(Key::Delete),
+                Event::Key(Key::F(1)),
+                Event::Key(Key::F(12)),
+            ]
+        );
+
+        // Kitty's reports, the release is dropped
+        assert_eq!(
+            events(b"\x1b[97;5u\x1b[97;2u\x1b[97:65;2u\x1b[97;1:3u\x1b[1;5A\x1b[57399u"),
+            [
+                Event::Key(Key::Ctrl('a')),
+                Event::Key(Key::Char('a')),
+                Event::Key(Key::Char('A')),
+            ]
+        );
+    }
+
+    #[test]
+    fn mouse_and_unsupported() {
+        assert_eq!(
+            events(b"\x1b[32;3;4M\x1b[64;5;4M\x1b[35;5;4M\x1b[97;1;1M\x1b[67;1;1M"),
+            [
+                Event::Mouse(MouseEvent::Press(MouseButton::Left, 3, 4)),
+                Event::Mouse(MouseEvent::Hold(5, 4)),
+                Event::Mouse(MouseEvent::Release(5, 4)),
+                Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, 1, 1)),
+            ]
+        );
+        assert_eq!(
+            events(b"\x1b[200~hi\x1b[201~\x1b[99x"),
+            [Event::Unsupported(b"hi"), Event::Unsupported(b"\x1b[99x")]
+        );
+    }
+}
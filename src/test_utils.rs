@@ -0,0 +1,164 @@
+use core::fmt::{self, Display, Write};
+
+use alloc::{format, string::String};
+
+use crate::{
+    parse::{Events, TerminalEvent},
+    KeyInfo, ModifierInfo, TextInfo,
+};
+
+// Compares the output of two encoders, e.g. `EventResponse`s, sequences or
+// plain strings. On mismatch both sides are shown escaped and decoded.
+#[macro_export]
+macro_rules! assert_seq_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::test_utils::assert_seq_eq(&$left, &$right)
+    };
+}
+
+#[track_caller]
+#[allow(clippy::panic)]
+pub fn assert_seq_eq(left: &impl Display, right: &impl Display) {
+    let (left, right) = (format!("{left}"), format!("{right}"));
+    if left == right {
+        return;
+    }
+
+    let mut message = String::new();
+    let _ = write_side(&mut message, " left", left.as_bytes());
+    let _ = write_side(&mut message, "right", right.as_bytes());
+    panic!("sequences are not equal\n{message}");
+}
+
+fn write_side(out: &mut impl Write, name: &str, bytes: &[u8]) -> fmt::Result {
+    write!(out, "{name}: \"")?;
+    escape(bytes, out)?;
+    write!(out, "\"\n{:>name$}  ", "", name = name.len())?;
+    disassemble(bytes, out)?;
+    writeln!(out)
+}
+
+// Control bytes as `\xNN`, everything else as it is
+pub fn escape(bytes: &[u8], out: &mut impl Write) -> fmt::Result {
+    for chunk in bytes.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            match ch {
+                '"' | '\\' => write!(out, "\\{ch}")?,
+                '\0'..='\x1f' | '\x7f' => write!(out, "\\x{:02x}", ch as u32)?,
+                _ => out.write_char(ch)?,
+            }
+        }
+        for byte in chunk.invalid() {
+            write!(out, "\\x{byte:02x}")?;
+        }
+    }
+
+    Ok(())
+}
+
+// One line description of what a terminal application would read
+pub fn disassemble(bytes: &[u8], out: &mut impl Write) -> fmt::Result {
+    let mut events = Events::new(bytes);
+    let mut first = true;
+
+    for event in events.by_ref() {
+        if !first {
+            out.write_str(", ")?;
+        }
+        first = false;
+
+        match event {
+            TerminalEvent::Key(key) => {
+                write!(out, "key {:?}", key.key_with_modifiers())?;
+                for (name, _) in key.modifiers().iter_names() {
+                    write!(out, "+{name}")?;
+                }
+                write!(out, " {:?}", key.event_type())?;
+                if let Some(text) = key.associated_text() {
+                    write!(out, " text {:?}", text.0)?;
+                }
+            }
+            TerminalEvent::Text(text) => write!(out, "text {text:?}")?,
+            event => write!(out, "{event:?}")?,
+        }
+    }
+
+    if !events.remainder().is_empty() {
+        if !first {
+            out.write_str(", ")?;
+        }
+        out.write_str("incomplete \"")?;
+        escape(events.remainder(), out)?;
+        out.write_str("\"")?;
+    } else if first {
+        out.write_str("nothing")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generate_sequence,
+        key::{FunctionalKey, KeyType},
+        sequence::KeyboardModifiers,
+        ReportingMode,
+    };
+
+    extern crate std;
+    use std::{panic, string::ToString};
+
+    fn disassembled(bytes: &[u8]) -> String {
+        let mut out = String::new();
+        disassemble(bytes, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn disassembly() {
+        assert_eq!(
+            disassembled(b"\x1b[97;5uab\x1b[5;2:3~"),
+            "key Unicode('a')+CTRL Press, text \"ab\", key Functional(PageUp)+SHIFT Release"
+        );
+        assert_eq!(disassembled(b"a\x1b["), "text \"a\", incomplete \"\\x1b[\"");
+        assert_eq!(disassembled(b""), "nothing");
+    }
+
+    #[test]
+    fn matching_sequences() {
+        let ctrl_a = (KeyType::Unicode('a'), KeyboardModifiers::CTRL);
+        assert_seq_eq!(
+            generate_sequence(ReportingMode::DISAMBIGUATE_ESC_CODES, &ctrl_a),
+            "\x1b[97;5u"
+        );
+    }
+
+    #[test]
+    fn mismatch_message() {
+        let up = (
+            KeyType::Functional(FunctionalKey::PageUp),
+            KeyboardModifiers::ALT,
+        );
+        let result = panic::catch_unwind(|| {
+            assert_seq_eq!(generate_sequence(ReportingMode::empty(), &up), "\x1b[5~");
+        });
+        let payload = result.unwrap_err();
+        let message = payload
+            .downcast_ref::<String>()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+
+        assert_eq!(
+            message,
+            concat!(
+                "sequences are not equal\n",
+                " left: \"\\x1b[5;3~\"\n",
+                "       key Functional(PageUp)+ALT Press\n",
+                "right: \"\\x1b[5~\"\n",
+                "       key Functional(PageUp) Press\n",
+            )
+        );
+    }
+}
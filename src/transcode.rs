@@ -0,0 +1,449 @@
+use core::fmt::Write;
+
+use crate::{
+    generate_sequence,
+    key::{FunctionalKey, KeyType},
+    sequence::{KeyboardModifiers, Sequence, SequenceTerminator},
+    synthesize::SyntheticKeyEvent,
+    ReportingMode,
+};
+
+// Recovers the key event behind a sequence, as far as the sequence tells it
+pub fn to_key_event<'a>(sequence: &Sequence<'a>) -> SyntheticKeyEvent<'a> {
+    let terminator = sequence.terminator;
+    let key_code = &sequence.key_code;
+    let modifiers = sequence.modifier;
+    let text = sequence.associated_text.as_ref().map(|text| text.0);
+
+    let key = KeyType::from_kitty_code(key_code.key_code, terminator);
+    let mut key_with_modifiers = key;
+    if let KeyType::Unicode(ch) = key {
+        let ch = match (
+            text.map(|text| only_char(text.chars())),
+            key_code.shifted_key_code,
+        ) {
+            (Some(Some(text)), _) => text,
+            (_, Some(shifted)) if modifiers.intersects(KeyboardModifiers::SHIFT) => {
+                char::from_u32(shifted).unwrap_or(ch)
+            }
+            // Without layout knowledge, shifting letters is the best we can do
+            _ if modifiers.intersects(KeyboardModifiers::SHIFT) => {
+                only_char(ch.to_uppercase()).unwrap_or(ch)
+            }
+            _ => ch,
+        };
+        key_with_modifiers = KeyType::Unicode(ch);
+    }
+
+    SyntheticKeyEvent {
+        key_with_modifiers,
+        key_without_modifiers: key,
+        key_base_layout: key_code
+            .base_layout_key_code
+            .map_or(KeyType::Unknown, |code| {
+                KeyType::from_kitty_code(code, SequenceTerminator::Kitty)
+            }),
+        modifiers,
+        event_type: sequence.event_type,
+        associated_text: text,
+        timestamp: None,
+    }
+}
+
+// Re-encodes a sequence for a client that enabled `mode`, which can be less
+// than what the sequence was created with. Fields the client did not ask for
+// are dropped, releases disappear without event types and keys fall back to
+// their legacy encoding.
+pub fn transcode(
+    sequence: &Sequence<'_>,
+    mode: ReportingMode,
+    out: &mut impl Write,
+) -> core::fmt::Result {
+    let event = to_key_event(sequence);
+    write!(out, "{}", generate_sequence(mode, &event))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LegacyKey<'a> {
+    pub event: SyntheticKeyEvent<'a>,
+    // Bytes of the input the key was parsed from
+    pub len: usize,
+    // Flags whose information the legacy encoding does not carry, the event
+    // holds a guess for them
+    pub unrecoverable: ReportingMode,
+}
+
+// Parses the next key from legacy terminal input. Returns `None` when the input
+// is empty or ends in the middle of a sequence.
+pub fn parse_legacy(input: &str) -> Option<LegacyKey<'_>> {
+    let mut chars = input.chars();
+    let first = chars.next()?;
+
+    let key = |key: KeyType, modifiers: KeyboardModifiers, len: usize| LegacyKey {
+        event: SyntheticKeyEvent {
+            key_with_modifiers: key,
+            key_without_modifiers: key,
+            modifiers,
+            ..Default::default()
+        },
+        len,
+        unrecoverable: ReportingMode::REPORT_EVENT_TYPES | ReportingMode::REPORT_ALTERNATE_KEYS,
+    };
+
+    if first != '\x1b' {
+        return Some(match control_key(first) {
+            Some((KeyType::Functional(func), _)) => {
+                key(KeyType::Functional(func), KeyboardModifiers::empty(), 1)
+            }
+            Some((base, modifiers)) => {
+                let mut control = key(base, modifiers, 1);
+                control.event.key_with_modifiers = KeyType::Unicode(first);
+                control
+            }
+            None => {
+                // Without layout knowledge, only letters can be unshifted
+                let (base, modifiers) = match only_char(first.to_lowercase()) {
+                    Some(lower) if lower != first => (lower, KeyboardModifiers::SHIFT),
+                    _ => (first, KeyboardModifiers::empty()),
+                };
+                let mut typed = key(KeyType::Unicode(base), modifiers, first.len_utf8());
+                typed.event.key_with_modifiers = KeyType::Unicode(first);
+                typed.event.associated_text = input.get(..first.len_utf8());
+                typed
+            }
+        });
+    }
+
+    // A lone ESC is taken as the Escape key, callers that read in chunks have
+    // to wait a moment before passing it in
+    match chars.next() {
+        None => Some(key(
+            KeyType::Functional(FunctionalKey::Escape),
+            KeyboardModifiers::empty(),
+            1,
+        )),
+        Some('[') => {
+            let (func, modifiers, len) = parse_csi(input.get(2..)?)?;
+            Some(key(func, modifiers, 2 + len))
+        }
+        Some('O') => {
+            let letter = chars.next()?;
+            let func = FunctionalKey::from_kitty_code(1, SequenceTerminator::Other(letter));
+            Some(key(
+                func.map_or(KeyType::Unknown, KeyType::Functional),
+                KeyboardModifiers::empty(),
+                2 + letter.len_utf8(),
+            ))
+        }
+        // ESC prefix for Alt
+        Some(_) => {
+            let mut alt = parse_legacy(input.get(1..)?)?;
+            alt.event.modifiers |= KeyboardModifiers::ALT;
+            alt.event.associated_text = None;
+            alt.len += 1;
+            Some(alt)
+        }
+    }
+}
+
+// Converts all complete keys at the start of `input` into their encoding
+// under `mode`, returning the number of bytes consumed
+pub fn upgrade(
+    input: &str,
+    mode: ReportingMode,
+    out: &mut impl Write,
+) -> Result<usize, core::fmt::Error> {
+    let mut consumed = 0;
+    while let Some(key) = input.get(consumed..).and_then(parse_legacy) {
+        write!(out, "{}", generate_sequence(mode, &key.event))?;
+        consumed += key.len;
+    }
+
+    Ok(consumed)
+}
+
+// C0 bytes and DEL, with the key that produced them. Ctrl+H, Ctrl+I, Ctrl+M
+// and Ctrl+[ are indistinguishable from the functional keys.
+pub fn control_key(ch: char) -> Option<(KeyType, KeyboardModifiers)> {
+    let functional = match ch {
+        '\r' => Some(FunctionalKey::Enter),
+        '\t' => Some(FunctionalKey::Tab),
+        '\x7f' | '\x08' => Some(FunctionalKey::Backspace),
+        _ => None,
+    };
+    if let Some(func) = functional {
+        return Some((KeyType::Functional(func), KeyboardModifiers::empty()));
+    }
+
+    let base = match ch {
+        '\0' => ' ',
+        '\x01'..='\x1a' => (ch as u8 - 1 + b'a') as char,
+        '\x1c'..='\x1f' => (ch as u8 + b'@') as char,
+        _ => return None,
+    };
+    Some((KeyType::Unicode(base), KeyboardModifiers::CTRL))
+}
+
+// `input` starts after the CSI, returns the key, its modifiers and the length
+// of the parameters and final byte. Unknown sequences are consumed whole with
+// an unknown key rather than leaking into the text.
+fn parse_csi(input: &str) -> Option<(KeyType, KeyboardModifiers, usize)> {
+    let end = input.find(|ch: char| ('@'..='~').contains(&ch))?;
+    let terminator = input.get(end..)?.chars().next()?;
+    let len = end + terminator.len_utf8();
+
+    let mut params = input.get(..end)?.split(';');
+    let code = match params.next() {
+        Some("") | None => Some(1),
+        Some(code) => code.parse().ok(),
+    };
+    let modifiers = match params.next() {
+        Some(modifiers) => modifiers
+            .parse::<u8>()
+            .ok()
+            .and_then(|modifiers| modifiers.checked_sub(1))
+            .map(KeyboardModifiers::from_bits_retain),
+        None => Some(KeyboardModifiers::empty()),
+    };
+    let (Some(code), Some(modifiers)) = (code, modifiers) else {
+        return Some((KeyType::Unknown, KeyboardModifiers::empty(), len));
+    };
+
+    let func = match terminator {
+        // Backtab
+        'Z' => {
+            return Some((
+                KeyType::Functional(FunctionalKey::Tab),
+                modifiers | KeyboardModifiers::SHIFT,
+                len,
+            ))
+        }
+        'A'..='Y' | '~' => {
+            FunctionalKey::from_kitty_code(code, SequenceTerminator::Other(terminator))
+        }
+        _ => None,
+    };
+
+    Some((
+        func.map_or(KeyType::Unknown, KeyType::Functional),
+        modifiers,
+        len,
+    ))
+}
+
+fn only_char(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Some(ch),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sequence::{AssociatedText, EventType, KeyCode};
+
+    extern crate std;
+    use std::string::String;
+
+    fn downgrade(sequence: &Sequence, mode: ReportingMode) -> String {
+        let mut out = String::new();
+        transcode(sequence, mode, &mut out).unwrap();
+        out
+    }
+
+    fn kitty(
+        key_code: KeyCode,
+        modifier: KeyboardModifiers,
+        event_type: EventType,
+    ) -> Sequence<'static> {
+        Sequence {
+            key_code,
+            modifier,
+            event_type,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn drops_unrequested_fields() {
+        let full = ReportingMode::all();
+        let mut shift_a = kitty(
+            KeyCode {
+                key_code: 'a'.into(),
+                shifted_key_code: Some('A'.into()),
+                base_layout_key_code: Some('q'.into()),
+            },
+            KeyboardModifiers::SHIFT,
+            EventType::Press,
+        );
+        shift_a.associated_text = Some(AssociatedText("A"));
+
+        assert_eq!(downgrade(&shift_a, full), "\x1b[97:65:113;2;65u");
+        assert_eq!(
+            downgrade(
+                &shift_a,
+                ReportingMode::REPORT_ALL_KEYS_AS_ESC | ReportingMode::REPORT_ALTERNATE_KEYS
+            ),
+            "\x1b[97:65:113;2u"
+        );
+        assert_eq!(
+            downgrade(&shift_a, ReportingMode::DISAMBIGUATE_ESC_CODES),
+            "A"
+        );
+        assert_eq!(downgrade(&shift_a, ReportingMode::empty()), "A");
+    }
+
+    #[test]
+    fn ctrl_shift_round_trip() {
+        let ctrl_shift_a = kitty(
+            KeyCode {
+                key_code: 'a'.into(),
+                shifted_key_code: Some('A'.into()),
+                base_layout_key_code: None,
+            },
+            KeyboardModifiers::CTRL | KeyboardModifiers::SHIFT,
+            EventType::Press,
+        );
+
+        let alternate =
+            ReportingMode::DISAMBIGUATE_ESC_CODES | ReportingMode::REPORT_ALTERNATE_KEYS;
+        assert_eq!(downgrade(&ctrl_shift_a, alternate), "\x1b[97:65;6u");
+        assert_eq!(
+            downgrade(&ctrl_shift_a, ReportingMode::DISAMBIGUATE_ESC_CODES),
+            "\x1b[97;6u"
+        );
+        assert_eq!(downgrade(&ctrl_shift_a, ReportingMode::empty()), "\x01");
+    }
+
+    #[test]
+    fn event_types() {
+        let enter = |event_type| {
+            kitty(
+                KeyCode {
+                    key_code: 13,
+                    ..Default::default()
+                },
+                KeyboardModifiers::empty(),
+                event_type,
+            )
+        };
+
+        let mode = ReportingMode::REPORT_ALL_KEYS_AS_ESC;
+        assert_eq!(
+            downgrade(
+                &enter(EventType::Release),
+                mode | ReportingMode::REPORT_EVENT_TYPES
+            ),
+            "\x1b[13;1:3u"
+        );
+        assert_eq!(downgrade(&enter(EventType::Release), mode), "");
+        assert_eq!(downgrade(&enter(EventType::Repeat), mode), "\x1b[13u");
+        assert_eq!(
+            downgrade(&enter(EventType::Repeat), ReportingMode::empty()),
+            "\r"
+        );
+    }
+
+    #[test]
+    fn legacy_fallback() {
+        let ctrl_c = kitty(
+            KeyCode {
+                key_code: 'c'.into(),
+                ..Default::default()
+            },
+            KeyboardModifiers::CTRL,
+            EventType::Press,
+        );
+        assert_eq!(
+            downgrade(&ctrl_c, ReportingMode::DISAMBIGUATE_ESC_CODES),
+            "\x1b[99;5u"
+        );
+        assert_eq!(downgrade(&ctrl_c, ReportingMode::empty()), "\x03");
+
+        let alt_x = kitty(
+            KeyCode {
+                key_code: 'x'.into(),
+                ..Default::default()
+            },
+            KeyboardModifiers::ALT,
+            EventType::Press,
+        );
+        assert_eq!(downgrade(&alt_x, ReportingMode::empty()), "\x1bx");
+
+        let f3 = Sequence {
+            terminator: SequenceTerminator::Other('~'),
+            ..kitty(
+                KeyCode {
+                    key_code: 13,
+                    ..Default::default()
+                },
+                KeyboardModifiers::empty(),
+                EventType::Press,
+            )
+        };
+        assert_eq!(downgrade(&f3, ReportingMode::empty()), "\x1b[13~");
+
+        let caps_lock = kitty(
+            KeyCode {
+                key_code: 57358,
+                ..Default::default()
+            },
+            KeyboardModifiers::empty(),
+            EventType::Press,
+        );
+        assert_eq!(downgrade(&caps_lock, ReportingMode::empty()), "");
+    }
+
+    fn upgraded(input: &str, mode: ReportingMode) -> (String, usize) {
+        let mut out = String::new();
+        let consumed = upgrade(input, mode, &mut out).unwrap();
+        (out, consumed)
+    }
+
+    #[test]
+    fn upgrade_legacy() {
+        let mode = ReportingMode::DISAMBIGUATE_ESC_CODES;
+        assert_eq!(
+            upgraded("\x1b[A\x1bOP\x1b[3;5~\x1b[Z", mode),
+            ("\x1b[A\x1b[P\x1b[3;5~\t".into(), 15)
+        );
+        assert_eq!(
+            upgraded("\x03\x1bx\x1b\x1b[B\r", mode),
+            ("\x1b[99;5u\x1b[120;3u\x1b[;3B\r".into(), 8)
+        );
+
+        let mode =
+            mode | ReportingMode::REPORT_ALL_KEYS_AS_ESC | ReportingMode::REPORT_ASSOCIATED_TEXT;
+        assert_eq!(
+            upgraded("a\x7f\x1b", mode),
+            ("\x1b[97;;97u\x1b[127u\x1b[27u".into(), 3)
+        );
+    }
+
+    #[test]
+    fn upgrade_partial_input() {
+        let mode = ReportingMode::DISAMBIGUATE_ESC_CODES;
+        assert_eq!(upgraded("x\x1b[1;5", mode), ("x".into(), 1));
+        // Unknown sequences are swallowed
+        assert_eq!(upgraded("\x1b[?1uy", mode), ("y".into(), 6));
+    }
+
+    #[test]
+    fn parse_legacy_fields() {
+        let shift_q = parse_legacy("Q").unwrap();
+        assert_eq!(shift_q.event.key_without_modifiers, KeyType::Unicode('q'));
+        assert_eq!(shift_q.event.modifiers, KeyboardModifiers::SHIFT);
+        assert!(shift_q
+            .unrecoverable
+            .contains(ReportingMode::REPORT_EVENT_TYPES | ReportingMode::REPORT_ALTERNATE_KEYS));
+
+        let ctrl_space = parse_legacy("\0").unwrap();
+        assert_eq!(
+            ctrl_space.event.key_without_modifiers,
+            KeyType::Unicode(' ')
+        );
+        assert_eq!(ctrl_space.event.modifiers, KeyboardModifiers::CTRL);
+    }
+}
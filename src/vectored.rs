@@ -0,0 +1,322 @@
+use core::{
+    fmt::{self, Write},
+    ops::Range,
+};
+
+use std::{
+    io::{self, IoSlice},
+    vec::Vec,
+};
+
+use crate::{
+    encoder::InputEncoder,
+    mouse::MouseEvent,
+    paste::{is_unsafe_in_paste, BRACKETED_PASTE_END, BRACKETED_PASTE_START},
+    queue::QueuedEvent,
+    KeyEvent,
+};
+
+// Linux' IOV_MAX, the rest goes out with the next write
+pub const MAX_SLICES: usize = 1024;
+
+// Responses repeated often enough, by key repeat or focus changes, to point
+// at instead of copying them for every event
+const FIXED: &[&str] = &[
+    "\x1b[A", "\x1b[B", "\x1b[C", "\x1b[D", "\x1bOA", "\x1bOB", "\x1bOC", "\x1bOD", "\x1b[H",
+    "\x1b[F", "\x1b[3~", "\x1b[5~", "\x1b[6~", "\x1b[I", "\x1b[O", "\x1b", "\x7f", "\r", "\t",
+];
+
+#[derive(Debug, Clone)]
+enum Part<'a> {
+    // Static tables and pasted text
+    Borrowed(&'a [u8]),
+    // Everything else, encoded into the shared buffer
+    Dynamic(Range<usize>),
+}
+
+// Encoded events waiting for the pty, written with one `write_vectored` call
+// per batch instead of being copied into a single buffer first
+#[derive(Debug, Clone, Default)]
+pub struct VectoredOutput<'a> {
+    dynamic: Vec<u8>,
+    parts: Vec<Part<'a>>,
+    // How far the pty has read into the parts
+    head: usize,
+    offset: usize,
+}
+
+impl<'a> VectoredOutput<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The bytes not written yet
+    pub fn len(&self) -> usize {
+        self.remaining().map(<[u8]>::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head >= self.parts.len()
+    }
+
+    pub fn key(&mut self, encoder: &InputEncoder, event: &impl KeyEvent) -> fmt::Result {
+        let start = self.dynamic.len();
+        encoder.encode_key(event, &mut Bytes(&mut self.dynamic))?;
+        self.encoded(start);
+        Ok(())
+    }
+
+    pub fn mouse(&mut self, encoder: &mut InputEncoder, event: &MouseEvent) -> fmt::Result {
+        let start = self.dynamic.len();
+        encoder.encode_mouse(event, &mut Bytes(&mut self.dynamic))?;
+        self.encoded(start);
+        Ok(())
+    }
+
+    pub fn focus(&mut self, encoder: &mut InputEncoder, focused: bool) -> fmt::Result {
+        let start = self.dynamic.len();
+        encoder.encode_focus(focused, &mut Bytes(&mut self.dynamic))?;
+        self.encoded(start);
+        Ok(())
+    }
+
+    // Bracketed pastes point at `text` unless it has to be sanitized
+    pub fn paste(&mut self, encoder: &InputEncoder, text: &'a str) -> fmt::Result {
+        if !encoder.is_bracketed_paste() || text.chars().any(is_unsafe_in_paste) {
+            let start = self.dynamic.len();
+            encoder.encode_paste(text, &mut Bytes(&mut self.dynamic))?;
+            self.encoded(start);
+            return Ok(());
+        }
+
+        self.borrowed(BRACKETED_PASTE_START.as_bytes());
+        self.borrowed(text.as_bytes());
+        self.borrowed(BRACKETED_PASTE_END.as_bytes());
+        Ok(())
+    }
+
+    pub fn queued(&mut self, encoder: &mut InputEncoder, event: &QueuedEvent<'a>) -> fmt::Result {
+        match event {
+            QueuedEvent::Key(key) => self.key(encoder, key),
+            QueuedEvent::Mouse(mouse) => self.mouse(encoder, mouse),
+            QueuedEvent::Focus(focused) => self.focus(encoder, *focused),
+            QueuedEvent::Paste(text) => self.paste(encoder, text),
+        }
+    }
+
+    // At most `MAX_SLICES` of them
+    pub fn io_slices(&self) -> Vec<IoSlice<'_>> {
+        self.remaining()
+            .take(MAX_SLICES)
+            .map(IoSlice::new)
+            .collect()
+    }
+
+    // A single write, e.g. once a non-blocking pty is writable. Whatever it
+    // did not take is kept for the next one.
+    pub fn write_to(&mut self, out: &mut impl io::Write) -> io::Result<usize> {
+        let written = out.write_vectored(&self.io_slices())?;
+        self.consume(written);
+        Ok(written)
+    }
+
+    pub fn write_all_to(&mut self, out: &mut impl io::Write) -> io::Result<()> {
+        while !self.is_empty() {
+            match self.write_to(out) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    fn remaining(&self) -> impl Iterator<Item = &[u8]> {
+        let parts = self.parts.get(self.head..).unwrap_or_default();
+        parts.iter().enumerate().map(|(i, part)| {
+            let bytes = self.bytes(part);
+            match i {
+                0 => bytes.get(self.offset..).unwrap_or_default(),
+                _ => bytes,
+            }
+        })
+    }
+
+    fn bytes<'s>(&'s self, part: &'s Part<'a>) -> &'s [u8] {
+        match part {
+            Part::Borrowed(bytes) => bytes,
+            Part::Dynamic(range) => self.dynamic.get(range.clone()).unwrap_or_default(),
+        }
+    }
+
+    fn consume(&mut self, mut written: usize) {
+        while let Some(part) = self.parts.get(self.head) {
+            let left = self.bytes(part).len().saturating_sub(self.offset);
+            if written < left {
+                self.offset += written;
+                return;
+            }
+            written -= left;
+            self.head += 1;
+            self.offset = 0;
+        }
+
+        // Everything is out, start over
+        self.dynamic.clear();
+        self.parts.clear();
+        self.head = 0;
+    }
+
+    fn borrowed(&mut self, bytes: &'a [u8]) {
+        if !bytes.is_empty() {
+            self.parts.push(Part::Borrowed(bytes));
+        }
+    }
+
+    // Turns the bytes an event left in the buffer into a part
+    fn encoded(&mut self, start: usize) {
+        let bytes = self.dynamic.get(start..).unwrap_or_default();
+        if let Some(fixed) = FIXED.iter().find(|fixed| fixed.as_bytes() == bytes) {
+            self.dynamic.truncate(start);
+            self.borrowed(fixed.as_bytes());
+            return;
+        }
+
+        let end = self.dynamic.len();
+        match self.parts.last_mut() {
+            _ if start == end => {}
+            Some(Part::Dynamic(range)) if range.end == start => range.end = end,
+            _ => self.parts.push(Part::Dynamic(start..end)),
+        }
+    }
+}
+
+impl Write for VectoredOutput<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let start = self.dynamic.len();
+        self.dynamic.extend_from_slice(s.as_bytes());
+        self.encoded(start);
+        Ok(())
+    }
+}
+
+struct Bytes<'v>(&'v mut Vec<u8>);
+
+impl Write for Bytes<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        key::{FunctionalKey, KeyType},
+        synthesize::SyntheticKeyEvent,
+    };
+
+    use std::vec;
+
+    fn key(key: KeyType) -> SyntheticKeyEvent<'static> {
+        SyntheticKeyEvent::from(key)
+    }
+
+    // Takes at most `limit` bytes per write, failing every other call
+    struct Pty {
+        written: Vec<u8>,
+        limit: usize,
+        calls: usize,
+    }
+
+    impl io::Write for Pty {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.write_vectored(&[IoSlice::new(buf)])
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls.is_multiple_of(2) {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let before = self.written.len();
+            for buf in bufs {
+                let take = buf.len().min(self.limit - (self.written.len() - before));
+                self.written.extend_from_slice(&buf[..take]);
+            }
+            Ok(self.written.len() - before)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn parts() {
+        let mut encoder = InputEncoder::new();
+        encoder.set_private_mode(2004, true);
+        let text = "pasted text";
+
+        let mut out = VectoredOutput::new();
+        out.key(&encoder, &key(KeyType::Unicode('a'))).unwrap();
+        out.key(&encoder, &key(KeyType::Unicode('b'))).unwrap();
+        let up = key(KeyType::Functional(FunctionalKey::Up));
+        out.key(&encoder, &up).unwrap();
+        out.key(&encoder, &up).unwrap();
+        out.paste(&encoder, text).unwrap();
+        out.paste(&encoder, "\x1b[201~").unwrap();
+        write!(out, "!").unwrap();
+
+        let slices = out.io_slices();
+        let slices: Vec<&[u8]> = slices.iter().map(|slice| &**slice).collect();
+        assert_eq!(
+            slices,
+            [
+                &b"ab"[..],
+                b"\x1b[A",
+                b"\x1b[A",
+                b"\x1b[200~",
+                b"pasted text",
+                b"\x1b[201~",
+                b"\x1b[200~[201~\x1b[201~!",
+            ]
+        );
+        assert_eq!(slices[4].as_ptr(), text.as_ptr());
+        assert_eq!(slices[1].as_ptr(), slices[2].as_ptr());
+        assert_eq!(out.len(), 49);
+    }
+
+    #[test]
+    fn partial_writes() {
+        let mut encoder = InputEncoder::new();
+        encoder.set_private_mode(1004, true);
+        let mut out = VectoredOutput::new();
+        for ch in "hello".chars() {
+            out.key(&encoder, &key(KeyType::Unicode(ch))).unwrap();
+        }
+        out.focus(&mut encoder, false).unwrap();
+        out.key(&encoder, &key(KeyType::Functional(FunctionalKey::Enter)))
+            .unwrap();
+
+        let mut pty = Pty {
+            written: vec![],
+            limit: 3,
+            calls: 0,
+        };
+        assert_eq!(out.write_to(&mut pty).unwrap(), 3);
+        assert_eq!(out.len(), 6);
+        assert!(out.write_to(&mut pty).is_err());
+
+        out.write_all_to(&mut pty).unwrap();
+        assert_eq!(pty.written, b"hello\x1b[O\r");
+        assert!(out.is_empty());
+        assert!(out.io_slices().is_empty());
+
+        out.key(&encoder, &key(KeyType::Unicode('x'))).unwrap();
+        out.write_all_to(&mut pty).unwrap();
+        assert_eq!(pty.written, b"hello\x1b[O\rx");
+    }
+}
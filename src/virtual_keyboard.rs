@@ -0,0 +1,153 @@
+use crate::{
+    binding::Chord,
+    filter::modifier_bit,
+    key::{FunctionalKey, KeyType},
+    sequence::KeyboardModifiers,
+    synthesize::{ModifierDiff, SyntheticKeyEvent},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    // The unshifted character on the key, 'a' or '1'. Shift, when latched,
+    // picks the shifted one from the US layout.
+    Character(char),
+    // Modifier keys latch instead of being sent right away
+    Key(FunctionalKey),
+}
+
+// An on-screen keyboard, where every button is tapped rather than held. A
+// modifier tapped once is held for the next key, tapped twice it stays held
+// until it is tapped again. The events come out paired like those of a
+// physical keyboard, the modifier keys included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VirtualKeyboard {
+    latched: KeyboardModifiers,
+    locked: KeyboardModifiers,
+    // Holds the locked modifiers between taps
+    held: ModifierDiff,
+}
+
+impl VirtualKeyboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn latched(&self) -> KeyboardModifiers {
+        self.latched
+    }
+
+    pub fn locked(&self) -> KeyboardModifiers {
+        self.locked
+    }
+
+    pub fn tap(&mut self, button: Button, mut emit: impl FnMut(SyntheticKeyEvent<'_>)) {
+        let key = match button {
+            Button::Character(ch) => KeyType::Unicode(ch),
+            Button::Key(func) => KeyType::Functional(func),
+        };
+
+        if let Some(modifier) = modifier_bit(key) {
+            if self.locked.intersects(modifier) {
+                self.locked.remove(modifier);
+            } else if self.latched.intersects(modifier) {
+                self.latched.remove(modifier);
+                self.locked.insert(modifier);
+            } else {
+                self.latched.insert(modifier);
+            }
+            self.held.update(self.locked, &mut emit);
+            return;
+        }
+
+        let modifiers = self.latched | self.locked;
+        self.held.update(modifiers, &mut emit);
+
+        let press = SyntheticKeyEvent::from_chord(Chord::new(key, modifiers));
+        let mut buf = [0; 4];
+        let typed = !modifiers.intersects(
+            KeyboardModifiers::CTRL | KeyboardModifiers::ALT | KeyboardModifiers::SUPER,
+        );
+        let text = match press.key_with_modifiers {
+            KeyType::Unicode(ch) if typed => Some(&*ch.encode_utf8(&mut buf)),
+            _ => None,
+        };
+        let press = SyntheticKeyEvent {
+            associated_text: text,
+            ..press
+        };
+        emit(press);
+        emit(press.released());
+
+        self.latched = KeyboardModifiers::empty();
+        self.held.update(self.locked, &mut emit);
+    }
+
+    // Releases every modifier, e.g. when the keyboard is hidden
+    pub fn clear(&mut self, emit: impl FnMut(SyntheticKeyEvent<'_>)) {
+        self.latched = KeyboardModifiers::empty();
+        self.locked = KeyboardModifiers::empty();
+        self.held.update(KeyboardModifiers::empty(), emit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_sequence, ReportingMode};
+
+    extern crate std;
+    use std::{format, string::String};
+
+    fn tap(keyboard: &mut VirtualKeyboard, mode: ReportingMode, button: Button) -> String {
+        let mut out = String::new();
+        keyboard.tap(button, |event| {
+            out += &format!("{}", generate_sequence(mode, &event));
+        });
+        out
+    }
+
+    #[test]
+    fn latched_shift() {
+        let mut keyboard = VirtualKeyboard::new();
+        let mode = ReportingMode::empty();
+        let shift = Button::Key(FunctionalKey::LeftShift);
+
+        assert_eq!(tap(&mut keyboard, mode, shift), "");
+        assert_eq!(keyboard.latched(), KeyboardModifiers::SHIFT);
+        assert_eq!(tap(&mut keyboard, mode, Button::Character('a')), "A");
+        assert_eq!(tap(&mut keyboard, mode, Button::Character('a')), "a");
+        tap(&mut keyboard, mode, shift);
+        assert_eq!(tap(&mut keyboard, mode, Button::Character('1')), "!");
+    }
+
+    // The modifier keys are Unknown without their table
+    #[test]
+    #[cfg(feature = "modifier-keys")]
+    fn paired_events() {
+        let mut keyboard = VirtualKeyboard::new();
+        let mode = ReportingMode::REPORT_ALL_KEYS_AS_ESC | ReportingMode::REPORT_EVENT_TYPES;
+        let ctrl = Button::Key(FunctionalKey::LeftControl);
+
+        tap(&mut keyboard, mode, ctrl);
+        assert_eq!(
+            tap(&mut keyboard, mode, Button::Character('c')),
+            "\x1b[57442;5u\x1b[99;5u\x1b[99;5:3u\x1b[57442;1:3u"
+        );
+
+        // Locked, held until it is tapped again
+        tap(&mut keyboard, mode, ctrl);
+        assert_eq!(tap(&mut keyboard, mode, ctrl), "\x1b[57442;5u");
+        assert_eq!(keyboard.locked(), KeyboardModifiers::CTRL);
+        assert_eq!(
+            tap(&mut keyboard, mode, Button::Key(FunctionalKey::Up)),
+            "\x1b[;5A\x1b[;5:3A"
+        );
+        assert_eq!(tap(&mut keyboard, mode, ctrl), "\x1b[57442;1:3u");
+
+        tap(&mut keyboard, mode, ctrl);
+        tap(&mut keyboard, mode, ctrl);
+        let mut out = String::new();
+        keyboard.clear(|event| out += &format!("{}", generate_sequence(mode, &event)));
+        assert_eq!(out, "\x1b[57442;1:3u");
+    }
+}
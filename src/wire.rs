@@ -0,0 +1,519 @@
+use core::time::Duration;
+
+use crate::{
+    encoder::InputEncoder,
+    key::{FunctionalKey, KeyType},
+    mouse::{MouseButton, MouseEvent, MouseEventKind},
+    sequence::{EventType, KeyboardModifiers},
+    synthesize::SyntheticKeyEvent,
+    KeyEvent, ReportingMode,
+};
+
+// A compact binary format for emulators whose frontend and pty process are
+// separate processes. Messages start with a tag byte and numbers are LEB128
+// varints, so a plain key press takes five bytes. Functional keys are sent by
+// discriminant, both ends need the same version of the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireMessage<'a> {
+    Key(SyntheticKeyEvent<'a>),
+    Mouse(MouseEvent),
+    // DECSET (`enabled`) and DECRST, replayed with `set_private_mode`
+    PrivateMode { mode: u16, enabled: bool },
+    // The kitty flags in effect after a push, pop or set
+    KeyboardMode(ReportingMode),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireError {
+    // The input ends in the middle of a message, read more
+    Incomplete,
+    // An unknown tag or a value out of range, the stream cannot be resynced
+    Malformed,
+    BufferTooSmall,
+}
+
+const KEY: u8 = 0;
+const MOUSE: u8 = 1;
+const PRIVATE_MODE: u8 = 2;
+const KEYBOARD_MODE: u8 = 3;
+
+// Flags after the key tag, the event type takes the low two bits. The
+// unshifted and base layout keys are left out when they are the same as the
+// key before them.
+const HAS_TEXT: u8 = 1 << 2;
+const HAS_TIMESTAMP: u8 = 1 << 3;
+const SAME_WITHOUT_MODIFIERS: u8 = 1 << 4;
+const SAME_BASE_LAYOUT: u8 = 1 << 5;
+
+// Mouse events pack the kind in the low two bits and the button, one based
+// with zero for none, above it
+const HAS_PIXEL: u8 = 1 << 6;
+
+const EVENT_TYPES: [EventType; 3] = [EventType::Press, EventType::Repeat, EventType::Release];
+const MOUSE_KINDS: [MouseEventKind; 3] = [
+    MouseEventKind::Press,
+    MouseEventKind::Release,
+    MouseEventKind::Motion,
+];
+const BUTTONS: [MouseButton; 9] = [
+    MouseButton::Left,
+    MouseButton::Middle,
+    MouseButton::Right,
+    MouseButton::WheelUp,
+    MouseButton::WheelDown,
+    MouseButton::WheelLeft,
+    MouseButton::WheelRight,
+    MouseButton::Back,
+    MouseButton::Forward,
+];
+
+impl<'a> WireMessage<'a> {
+    pub fn key(event: &'a impl KeyEvent) -> Self {
+        WireMessage::Key(SyntheticKeyEvent {
+            key_with_modifiers: event.key_with_modifiers(),
+            key_without_modifiers: event.key_without_modifiers(),
+            key_base_layout: event.key_base_layout(),
+            modifiers: event.modifiers(),
+            event_type: event.event_type(),
+            associated_text: event.associated_text().map(|text| text.0),
+            timestamp: event.timestamp(),
+        })
+    }
+
+    // Returns the length of the message written to the start of `out`
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, WireError> {
+        let mut writer = Writer { out, len: 0 };
+        match self {
+            WireMessage::Key(event) => writer.key_event(event)?,
+            WireMessage::Mouse(event) => writer.mouse_event(event)?,
+            WireMessage::PrivateMode { mode, enabled } => {
+                writer.byte(PRIVATE_MODE)?;
+                writer.varint((*mode).into())?;
+                writer.byte((*enabled).into())?;
+            }
+            WireMessage::KeyboardMode(mode) => {
+                writer.byte(KEYBOARD_MODE)?;
+                writer.varint(mode.bits().into())?;
+            }
+        }
+        Ok(writer.len)
+    }
+
+    // The message at the start of `input` and its length, key text borrows
+    // from the input
+    pub fn decode(input: &'a [u8]) -> Result<(Self, usize), WireError> {
+        let mut reader = Reader { input, pos: 0 };
+        let message = match reader.byte()? {
+            KEY => WireMessage::Key(reader.key_event()?),
+            MOUSE => WireMessage::Mouse(reader.mouse_event()?),
+            PRIVATE_MODE => WireMessage::PrivateMode {
+                mode: reader.small()?,
+                enabled: match reader.byte()? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(WireError::Malformed),
+                },
+            },
+            KEYBOARD_MODE => {
+                WireMessage::KeyboardMode(ReportingMode::from_bits_retain(reader.small()?))
+            }
+            _ => return Err(WireError::Malformed),
+        };
+        Ok((message, reader.pos))
+    }
+
+    // Brings the pty side's encoder to the frontend's modes, returns whether
+    // the message changed one
+    pub fn apply(&self, encoder: &mut InputEncoder) -> bool {
+        match self {
+            WireMessage::PrivateMode { mode, enabled } => encoder.set_private_mode(*mode, *enabled),
+            WireMessage::KeyboardMode(mode) => {
+                encoder.set_mode(*mode);
+                true
+            }
+            WireMessage::Key(_) | WireMessage::Mouse(_) => false,
+        }
+    }
+}
+
+struct Writer<'o> {
+    out: &'o mut [u8],
+    len: usize,
+}
+
+impl Writer<'_> {
+    fn byte(&mut self, byte: u8) -> Result<(), WireError> {
+        let slot = self
+            .out
+            .get_mut(self.len)
+            .ok_or(WireError::BufferTooSmall)?;
+        *slot = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn varint(&mut self, mut value: u64) -> Result<(), WireError> {
+        while value >= 0x80 {
+            self.byte(value as u8 | 0x80)?;
+            value >>= 7;
+        }
+        self.byte(value as u8)
+    }
+
+    fn key(&mut self, key: KeyType) -> Result<(), WireError> {
+        // Zero is Unknown, characters and discriminants are told apart by the
+        // low bit
+        self.varint(match key {
+            KeyType::Unknown => 0,
+            KeyType::Unicode(ch) => u64::from(ch) << 1 | 1,
+            KeyType::Functional(func) => (func as u64 + 1) << 1,
+        })
+    }
+
+    fn key_event(&mut self, event: &SyntheticKeyEvent<'_>) -> Result<(), WireError> {
+        let mut flags = match event.event_type {
+            EventType::Press => 0,
+            EventType::Repeat => 1,
+            EventType::Release => 2,
+        };
+        if event.associated_text.is_some() {
+            flags |= HAS_TEXT;
+        }
+        if event.timestamp.is_some() {
+            flags |= HAS_TIMESTAMP;
+        }
+        if event.key_without_modifiers == event.key_with_modifiers {
+            flags |= SAME_WITHOUT_MODIFIERS;
+        }
+        if event.key_base_layout == event.key_without_modifiers {
+            flags |= SAME_BASE_LAYOUT;
+        }
+
+        self.byte(KEY)?;
+        self.byte(flags)?;
+        self.byte(event.modifiers.bits())?;
+        self.key(event.key_with_modifiers)?;
+        if flags & SAME_WITHOUT_MODIFIERS == 0 {
+            self.key(event.key_without_modifiers)?;
+        }
+        if flags & SAME_BASE_LAYOUT == 0 {
+            self.key(event.key_base_layout)?;
+        }
+        if let Some(text) = event.associated_text {
+            self.varint(text.len() as u64)?;
+            for byte in text.bytes() {
+                self.byte(byte)?;
+            }
+        }
+        if let Some(timestamp) = event.timestamp {
+            self.varint(timestamp.as_secs())?;
+            self.varint(timestamp.subsec_nanos().into())?;
+        }
+        Ok(())
+    }
+
+    fn mouse_event(&mut self, event: &MouseEvent) -> Result<(), WireError> {
+        let kind = match event.kind {
+            MouseEventKind::Press => 0,
+            MouseEventKind::Release => 1,
+            MouseEventKind::Motion => 2,
+        };
+        let button = event
+            .button
+            .and_then(|button| BUTTONS.iter().position(|known| *known == button))
+            .map_or(0, |index| index as u8 + 1);
+        let pixel = if event.pixel.is_some() { HAS_PIXEL } else { 0 };
+
+        self.byte(MOUSE)?;
+        self.byte(kind | button << 2 | pixel)?;
+        self.byte(event.modifiers.bits())?;
+        self.varint(event.column.into())?;
+        self.varint(event.row.into())?;
+        if let Some((x, y)) = event.pixel {
+            self.varint(x.into())?;
+            self.varint(y.into())?;
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, WireError> {
+        let byte = *self.input.get(self.pos).ok_or(WireError::Incomplete)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, WireError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let bits = u64::from(byte & 0x7f);
+            if bits << shift >> shift != bits {
+                return Err(WireError::Malformed);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(WireError::Malformed)
+    }
+
+    fn small<T: TryFrom<u64>>(&mut self) -> Result<T, WireError> {
+        self.varint()?.try_into().or(Err(WireError::Malformed))
+    }
+
+    fn key(&mut self) -> Result<KeyType, WireError> {
+        let value: u32 = self.small()?;
+        Ok(match value {
+            0 => KeyType::Unknown,
+            _ if value & 1 == 1 => {
+                KeyType::Unicode(char::from_u32(value >> 1).ok_or(WireError::Malformed)?)
+            }
+            _ => {
+                let index = (value >> 1) as usize - 1;
+                let func = FunctionalKey::ALL.get(index);
+                KeyType::Functional(*func.ok_or(WireError::Malformed)?)
+            }
+        })
+    }
+
+    fn key_event(&mut self) -> Result<SyntheticKeyEvent<'a>, WireError> {
+        let flags = self.byte()?;
+        let event_type = *EVENT_TYPES
+            .get(usize::from(flags & 3))
+            .ok_or(WireError::Malformed)?;
+        let modifiers = KeyboardModifiers::from_bits_retain(self.byte()?);
+
+        let key_with_modifiers = self.key()?;
+        let key_without_modifiers = match flags & SAME_WITHOUT_MODIFIERS {
+            0 => self.key()?,
+            _ => key_with_modifiers,
+        };
+        let key_base_layout = match flags & SAME_BASE_LAYOUT {
+            0 => self.key()?,
+            _ => key_without_modifiers,
+        };
+
+        let associated_text = match flags & HAS_TEXT {
+            0 => None,
+            _ => {
+                let len: usize = self.small()?;
+                let end = self.pos.checked_add(len).ok_or(WireError::Malformed)?;
+                let bytes = self.input.get(self.pos..end).ok_or(WireError::Incomplete)?;
+                self.pos = end;
+                Some(core::str::from_utf8(bytes).or(Err(WireError::Malformed))?)
+            }
+        };
+        let timestamp = match flags & HAS_TIMESTAMP {
+            0 => None,
+            _ => {
+                let secs = self.varint()?;
+                let nanos: u32 = self.small()?;
+                if nanos >= 1_000_000_000 {
+                    return Err(WireError::Malformed);
+                }
+                Some(Duration::new(secs, nanos))
+            }
+        };
+
+        Ok(SyntheticKeyEvent {
+            key_with_modifiers,
+            key_without_modifiers,
+            key_base_layout,
+            modifiers,
+            event_type,
+            associated_text,
+            timestamp,
+        })
+    }
+
+    fn mouse_event(&mut self) -> Result<MouseEvent, WireError> {
+        let packed = self.byte()?;
+        let kind = *MOUSE_KINDS
+            .get(usize::from(packed & 3))
+            .ok_or(WireError::Malformed)?;
+        let button = match usize::from(packed >> 2 & 0xf) {
+            0 => None,
+            index => Some(*BUTTONS.get(index - 1).ok_or(WireError::Malformed)?),
+        };
+        let modifiers = KeyboardModifiers::from_bits_retain(self.byte()?);
+        let column = self.small()?;
+        let row = self.small()?;
+        let pixel = match packed & HAS_PIXEL {
+            0 => None,
+            _ => Some((self.small()?, self.small()?)),
+        };
+
+        Ok(MouseEvent {
+            kind,
+            button,
+            column,
+            row,
+            pixel,
+            modifiers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mouse::MouseTracking,
+        parse::{parse, TerminalEvent},
+    };
+
+    extern crate std;
+    use std::vec::Vec;
+
+    #[test]
+    fn round_trip() {
+        let Ok((TerminalEvent::Key(parsed), _)) = parse(b"\x1b[97:65;2:2;65u") else {
+            panic!("not a key");
+        };
+        let messages = [
+            WireMessage::Key(SyntheticKeyEvent::from(KeyType::Unicode('a'))),
+            WireMessage::key(&parsed),
+            WireMessage::Key(SyntheticKeyEvent {
+                key_base_layout: KeyType::Functional(FunctionalKey::F12),
+                event_type: EventType::Release,
+                timestamp: Some(Duration::new(5, 999_999_999)),
+                ..SyntheticKeyEvent::from(KeyType::Functional(FunctionalKey::Escape))
+            }),
+            WireMessage::Mouse(MouseEvent {
+                kind: MouseEventKind::Motion,
+                button: Some(MouseButton::Forward),
+                column: 300,
+                row: 2,
+                pixel: Some((u16::MAX, 0)),
+                modifiers: KeyboardModifiers::CTRL,
+            }),
+            WireMessage::Mouse(MouseEvent::default()),
+            WireMessage::PrivateMode {
+                mode: 1006,
+                enabled: true,
+            },
+            WireMessage::KeyboardMode(ReportingMode::all()),
+        ];
+
+        let mut stream = Vec::new();
+        for message in &messages {
+            let mut buffer = [0; 64];
+            let len = message.encode(&mut buffer).unwrap();
+            stream.extend_from_slice(&buffer[..len]);
+        }
+        // A plain press is the tag, flags, modifiers and a character
+        assert_eq!(
+            &stream[..5],
+            [
+                KEY,
+                SAME_WITHOUT_MODIFIERS | SAME_BASE_LAYOUT,
+                0,
+                0xc3,
+                0x01
+            ]
+        );
+
+        let mut input = &stream[..];
+        let mut decoded = Vec::new();
+        while !input.is_empty() {
+            let (message, len) = WireMessage::decode(input).unwrap();
+            decoded.push(message);
+            input = &input[len..];
+        }
+        assert_eq!(decoded, messages);
+        let WireMessage::Key(event) = decoded[1] else {
+            panic!("not a key");
+        };
+        assert_eq!(event.associated_text, Some("A"));
+        assert_eq!(event.event_type, EventType::Repeat);
+    }
+
+    #[test]
+    fn errors() {
+        let message = WireMessage::Key(SyntheticKeyEvent::from_char('x', "x"));
+        let mut buffer = [0; 8];
+        let len = message.encode(&mut buffer).unwrap();
+        assert_eq!(
+            message.encode(&mut buffer[..len - 1]),
+            Err(WireError::BufferTooSmall)
+        );
+        for end in 0..len {
+            assert_eq!(
+                WireMessage::decode(&buffer[..end]),
+                Err(WireError::Incomplete)
+            );
+        }
+
+        assert_eq!(WireMessage::decode(&[9]), Err(WireError::Malformed));
+        assert_eq!(
+            WireMessage::decode(&[KEY, 3, 0, 0]),
+            Err(WireError::Malformed)
+        );
+        // A surrogate and a discriminant past the last key
+        assert_eq!(
+            WireMessage::decode(&[KEY, 0, 0, 0x81, 0xe0, 0x06]),
+            Err(WireError::Malformed)
+        );
+        assert_eq!(
+            WireMessage::decode(&[KEY, 0, 0, 0xfe, 0x7f]),
+            Err(WireError::Malformed)
+        );
+        assert_eq!(
+            WireMessage::decode(&[PRIVATE_MODE, 0xff, 0xff, 0x04, 1]),
+            Err(WireError::Malformed)
+        );
+        assert_eq!(
+            WireMessage::decode(&[MOUSE, 10 << 2, 0, 0, 0]),
+            Err(WireError::Malformed)
+        );
+        assert_eq!(
+            WireMessage::decode(&[
+                KEYBOARD_MODE,
+                0xff,
+                0xff,
+                0xff,
+                0xff,
+                0xff,
+                0xff,
+                0xff,
+                0xff,
+                0xff,
+                0xff,
+                0x01
+            ]),
+            Err(WireError::Malformed)
+        );
+    }
+
+    #[test]
+    fn modes() {
+        let mut encoder = InputEncoder::new();
+        let messages = [
+            WireMessage::PrivateMode {
+                mode: 1003,
+                enabled: true,
+            },
+            WireMessage::KeyboardMode(ReportingMode::DISAMBIGUATE_ESC_CODES),
+        ];
+        for message in messages {
+            assert!(message.apply(&mut encoder));
+        }
+        assert_eq!(encoder.mouse_tracking(), MouseTracking::AnyEvent);
+        assert_eq!(encoder.mode(), ReportingMode::DISAMBIGUATE_ESC_CODES);
+
+        let unknown = WireMessage::PrivateMode {
+            mode: 12345,
+            enabled: true,
+        };
+        assert!(!unknown.apply(&mut encoder));
+        assert!(!WireMessage::Mouse(MouseEvent::default()).apply(&mut encoder));
+    }
+}
@@ -0,0 +1,477 @@
+use crate::{
+    key::{FunctionalKey, KeyType},
+    layouts::PhysicalKey,
+    sequence::KeyboardModifiers,
+};
+
+// The parts of `xkb_state`/`xkb_keymap` the resolution needs, so any
+// xkbcommon binding can be plugged in. Keycodes are XKB keycodes (evdev + 8).
+pub trait XkbState {
+    // xkb_state_key_get_one_sym
+    fn key_get_one_sym(&self, keycode: u32) -> u32;
+
+    // xkb_state_key_get_layout
+    fn key_get_layout(&self, keycode: u32) -> u32;
+
+    // First symbol of xkb_keymap_key_get_syms_by_level
+    fn key_get_sym_by_level(&self, keycode: u32, layout: u32, level: u32) -> Option<u32>;
+
+    // xkb_keysym_to_utf32, the default only knows Latin-1 and Unicode keysyms
+    fn keysym_to_utf32(&self, keysym: u32) -> u32 {
+        keysym_to_char(keysym).map_or(0, u32::from)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KeyViews {
+    // What the key produces with all modifiers applied, including Caps Lock.
+    // Ctrl does not change it, the encoder sends the control character.
+    pub with_modifiers: KeyType,
+    // The key at shift level 0 of the active layout
+    pub without_modifiers: KeyType,
+    // The key at shift level 1, to be reported as the shifted key while
+    // SHIFT is held (Caps Lock does not cancel it out here)
+    pub shifted: KeyType,
+    pub base_layout: KeyType,
+}
+
+pub fn resolve(state: &impl XkbState, keycode: u32) -> KeyViews {
+    let layout = state.key_get_layout(keycode);
+    let level = |level| {
+        state
+            .key_get_sym_by_level(keycode, layout, level)
+            .map_or(KeyType::Unknown, |sym| keysym_to_key(state, sym))
+    };
+
+    let with_modifiers = keysym_to_key(state, state.key_get_one_sym(keycode));
+
+    let without_modifiers = level(0);
+    let shifted = match level(1) {
+        KeyType::Unknown => without_modifiers,
+        shifted => shifted,
+    };
+
+    KeyViews {
+        with_modifiers,
+        without_modifiers,
+        shifted,
+        base_layout: physical_key(keycode).map_or(KeyType::Unknown, PhysicalKey::base_layout_key),
+    }
+}
+
+// Which ISO level shifts (AltGr and friends) are active. XKB selects the level
+// through them, but kitty never reports them as modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LevelShifts {
+    pub level3: bool,
+    pub level5: bool,
+}
+
+impl LevelShifts {
+    // The shift level a standard four/eight level key type picks
+    pub fn level(self, shift: bool) -> u32 {
+        u32::from(shift) + 2 * u32::from(self.level3) + 4 * u32::from(self.level5)
+    }
+
+    // Platforms without a separate AltGr modifier deliver it as Ctrl + Alt,
+    // those bits belong to the level shift and are removed
+    pub fn strip_modifiers(self, modifiers: KeyboardModifiers) -> KeyboardModifiers {
+        let altgr = KeyboardModifiers::CTRL | KeyboardModifiers::ALT;
+        if self.level3 && modifiers.contains(altgr) {
+            modifiers - altgr
+        } else {
+            modifiers
+        }
+    }
+}
+
+// Like `resolve`, with the level shifts folded into `with_modifiers` only. The
+// unshifted and shifted views stay at levels 0 and 1 as kitty expects, and the
+// returned modifiers are the ones to report.
+pub fn resolve_with_level_shifts(
+    state: &impl XkbState,
+    keycode: u32,
+    modifiers: KeyboardModifiers,
+    shifts: LevelShifts,
+) -> (KeyViews, KeyboardModifiers) {
+    let modifiers = shifts.strip_modifiers(modifiers);
+    let mut views = resolve(state, keycode);
+
+    if shifts != LevelShifts::default() {
+        let layout = state.key_get_layout(keycode);
+        let shift = modifiers.intersects(KeyboardModifiers::SHIFT);
+        let level = shifts.level(shift);
+        // Fall back to the level without shift like XKB does for key types
+        // that do not distinguish it
+        let sym = state
+            .key_get_sym_by_level(keycode, layout, level)
+            .or_else(|| state.key_get_sym_by_level(keycode, layout, level & !1));
+        if let Some(sym) = sym {
+            views.with_modifiers = keysym_to_key(state, sym);
+        }
+    }
+
+    (views, modifiers)
+}
+
+pub fn keysym_to_key(state: &impl XkbState, keysym: u32) -> KeyType {
+    if let Some(key) = keysym_to_functional(keysym) {
+        return KeyType::Functional(key);
+    }
+
+    match char::from_u32(state.keysym_to_utf32(keysym)) {
+        Some(ch) if ch != '\0' && !ch.is_control() => KeyType::Unicode(ch),
+        _ => KeyType::Unknown,
+    }
+}
+
+pub fn keysym_to_char(keysym: u32) -> Option<char> {
+    match keysym {
+        0x20..=0x7e | 0xa0..=0xff => char::from_u32(keysym),
+        0x0100_0100..=0x0110_ffff => char::from_u32(keysym - 0x0100_0000),
+        _ => None,
+    }
+}
+
+pub fn keysym_to_functional(keysym: u32) -> Option<FunctionalKey> {
+    Some(match keysym {
+        0xff1b => FunctionalKey::Escape,
+        0xff0d => FunctionalKey::Enter,
+        0xff09 | 0xfe20 => FunctionalKey::Tab,
+        0xff08 => FunctionalKey::Backspace,
+        0xff63 => FunctionalKey::Insert,
+        0xffff => FunctionalKey::Delete,
+        0xff51 => FunctionalKey::Left,
+        0xff53 => FunctionalKey::Right,
+        0xff52 => FunctionalKey::Up,
+        0xff54 => FunctionalKey::Down,
+        0xff55 => FunctionalKey::PageUp,
+        0xff56 => FunctionalKey::PageDown,
+        0xff50 => FunctionalKey::Home,
+        0xff57 => FunctionalKey::End,
+        0xffe5 => FunctionalKey::CapsLock,
+        0xff14 => FunctionalKey::ScrollLock,
+        0xff7f => FunctionalKey::NumLock,
+        0xff61 => FunctionalKey::PrintScreen,
+        0xff13 => FunctionalKey::Pause,
+        0xff67 => FunctionalKey::Menu,
+
+        0xffbe..=0xffe0 => return FUNCTION_KEYS.get((keysym - 0xffbe) as usize).copied(),
+
+        0xffb0..=0xffb9 => return NUMPAD_DIGITS.get((keysym - 0xffb0) as usize).copied(),
+        0xffae => FunctionalKey::NumPadDecimal,
+        0xffaf => FunctionalKey::NumPadDivide,
+        0xffaa => FunctionalKey::NumPadMultply,
+        0xffad => FunctionalKey::NumPadSubtract,
+        0xffab => FunctionalKey::NumPadAdd,
+        0xff8d => FunctionalKey::NumPadEnter,
+        0xffbd => FunctionalKey::NumPadEqual,
+        0xffac => FunctionalKey::NumPadSeparator,
+        0xff96 => FunctionalKey::NumPadLeft,
+        0xff98 => FunctionalKey::NumPadRight,
+        0xff97 => FunctionalKey::NumPadUp,
+        0xff99 => FunctionalKey::NumPadDown,
+        0xff9a => FunctionalKey::NumPadPageUp,
+        0xff9b => FunctionalKey::NumPadPageDown,
+        0xff95 => FunctionalKey::NumPadHome,
+        0xff9c => FunctionalKey::NumPadEnd,
+        0xff9e => FunctionalKey::NumPadInsert,
+        0xff9f => FunctionalKey::NumPadDelete,
+        0xff9d => FunctionalKey::NumPadBegin,
+
+        0x1008_ff14 => FunctionalKey::MediaPlay,
+        0x1008_ff31 => FunctionalKey::MediaPause,
+        0x1008_ff15 => FunctionalKey::MediaStop,
+        0x1008_ff97 => FunctionalKey::MediaFastForward,
+        0x1008_ff3e => FunctionalKey::MediaRewind,
+        0x1008_ff17 => FunctionalKey::MediaTrackNext,
+        0x1008_ff16 => FunctionalKey::MediaTrackPrevious,
+        0x1008_ff1c => FunctionalKey::MediaRecord,
+        0x1008_ff11 => FunctionalKey::LowerVolume,
+        0x1008_ff13 => FunctionalKey::RaiseVolume,
+        0x1008_ff12 => FunctionalKey::MuteVolume,
+
+        0xffe1 => FunctionalKey::LeftShift,
+        0xffe3 => FunctionalKey::LeftControl,
+        0xffe9 => FunctionalKey::LeftAlt,
+        0xffeb => FunctionalKey::LeftSuper,
+        0xffed => FunctionalKey::LeftHyper,
+        0xffe7 => FunctionalKey::LeftMeta,
+        0xffe2 => FunctionalKey::RightShift,
+        0xffe4 => FunctionalKey::RightControl,
+        0xffea => FunctionalKey::RightAlt,
+        0xffec => FunctionalKey::RightSuper,
+        0xffee => FunctionalKey::RightHyper,
+        0xffe8 => FunctionalKey::RightMeta,
+        0xfe03 => FunctionalKey::IsoLevel3Shift,
+        0xfe11 => FunctionalKey::IsoLevel5Shift,
+
+        0xff22 => FunctionalKey::Muhenkan,
+        0xff23 => FunctionalKey::Henkan,
+        0xff27 => FunctionalKey::KatakanaHiragana,
+        0xff2a => FunctionalKey::ZenkakuHankaku,
+        0xff30 => FunctionalKey::Eisu,
+        0xff31 => FunctionalKey::Hangul,
+        0xff34 => FunctionalKey::Hanja,
+
+        _ => return None,
+    })
+}
+
+const FUNCTION_KEYS: [FunctionalKey; 35] = [
+    FunctionalKey::F1,
+    FunctionalKey::F2,
+    FunctionalKey::F3,
+    FunctionalKey::F4,
+    FunctionalKey::F5,
+    FunctionalKey::F6,
+    FunctionalKey::F7,
+    FunctionalKey::F8,
+    FunctionalKey::F9,
+    FunctionalKey::F10,
+    FunctionalKey::F11,
+    FunctionalKey::F12,
+    FunctionalKey::F13,
+    FunctionalKey::F14,
+    FunctionalKey::F15,
+    FunctionalKey::F16,
+    FunctionalKey::F17,
+    FunctionalKey::F18,
+    FunctionalKey::F19,
+    FunctionalKey::F20,
+    FunctionalKey::F21,
+    FunctionalKey::F22,
+    FunctionalKey::F23,
+    FunctionalKey::F24,
+    FunctionalKey::F25,
+    FunctionalKey::F26,
+    FunctionalKey::F27,
+    FunctionalKey::F28,
+    FunctionalKey::F29,
+    FunctionalKey::F30,
+    FunctionalKey::F31,
+    FunctionalKey::F32,
+    FunctionalKey::F33,
+    FunctionalKey::F34,
+    FunctionalKey::F35,
+];
+
+const NUMPAD_DIGITS: [FunctionalKey; 10] = [
+    FunctionalKey::NumPad0,
+    FunctionalKey::NumPad1,
+    FunctionalKey::NumPad2,
+    FunctionalKey::NumPad3,
+    FunctionalKey::NumPad4,
+    FunctionalKey::NumPad5,
+    FunctionalKey::NumPad6,
+    FunctionalKey::NumPad7,
+    FunctionalKey::NumPad8,
+    FunctionalKey::NumPad9,
+];
+
+// Maps an XKB keycode to its position on the keyboard
+pub fn physical_key(keycode: u32) -> Option<PhysicalKey> {
+    Some(match keycode.checked_sub(8)? {
+        41 => PhysicalKey::Backquote,
+        2 => PhysicalKey::Digit1,
+        3 => PhysicalKey::Digit2,
+        4 => PhysicalKey::Digit3,
+        5 => PhysicalKey::Digit4,
+        6 => PhysicalKey::Digit5,
+        7 => PhysicalKey::Digit6,
+        8 => PhysicalKey::Digit7,
+        9 => PhysicalKey::Digit8,
+        10 => PhysicalKey::Digit9,
+        11 => PhysicalKey::Digit0,
+        12 => PhysicalKey::Minus,
+        13 => PhysicalKey::Equal,
+        16 => PhysicalKey::KeyQ,
+        17 => PhysicalKey::KeyW,
+        18 => PhysicalKey::KeyE,
+        19 => PhysicalKey::KeyR,
+        20 => PhysicalKey::KeyT,
+        21 => PhysicalKey::KeyY,
+        22 => PhysicalKey::KeyU,
+        23 => PhysicalKey::KeyI,
+        24 => PhysicalKey::KeyO,
+        25 => PhysicalKey::KeyP,
+        26 => PhysicalKey::BracketLeft,
+        27 => PhysicalKey::BracketRight,
+        43 => PhysicalKey::Backslash,
+        30 => PhysicalKey::KeyA,
+        31 => PhysicalKey::KeyS,
+        32 => PhysicalKey::KeyD,
+        33 => PhysicalKey::KeyF,
+        34 => PhysicalKey::KeyG,
+        35 => PhysicalKey::KeyH,
+        36 => PhysicalKey::KeyJ,
+        37 => PhysicalKey::KeyK,
+        38 => PhysicalKey::KeyL,
+        39 => PhysicalKey::Semicolon,
+        40 => PhysicalKey::Quote,
+        86 => PhysicalKey::IntlBackslash,
+        44 => PhysicalKey::KeyZ,
+        45 => PhysicalKey::KeyX,
+        46 => PhysicalKey::KeyC,
+        47 => PhysicalKey::KeyV,
+        48 => PhysicalKey::KeyB,
+        49 => PhysicalKey::KeyN,
+        50 => PhysicalKey::KeyM,
+        51 => PhysicalKey::Comma,
+        52 => PhysicalKey::Period,
+        53 => PhysicalKey::Slash,
+        89 => PhysicalKey::IntlRo,
+        124 => PhysicalKey::IntlYen,
+        57 => PhysicalKey::Space,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // A German keymap reduced to the Z key (on the US Y position) and Up,
+    // with shift and caps lock state
+    struct GermanState {
+        shift: bool,
+        caps_lock: bool,
+    }
+
+    const KEY_Y: u32 = 21 + 8;
+    const KEY_UP: u32 = 103 + 8;
+
+    impl XkbState for GermanState {
+        fn key_get_one_sym(&self, keycode: u32) -> u32 {
+            match keycode {
+                KEY_Y if self.shift != self.caps_lock => 'Z'.into(),
+                KEY_Y => 'z'.into(),
+                KEY_UP => 0xff52,
+                _ => 0,
+            }
+        }
+
+        fn key_get_layout(&self, _keycode: u32) -> u32 {
+            0
+        }
+
+        fn key_get_sym_by_level(&self, keycode: u32, _layout: u32, level: u32) -> Option<u32> {
+            match (keycode, level) {
+                (KEY_Y, 0) => Some('z'.into()),
+                (KEY_Y, 1) => Some('Z'.into()),
+                (KEY_UP, 0) => Some(0xff52),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn levels_with_caps_lock() {
+        let state = GermanState {
+            shift: true,
+            caps_lock: true,
+        };
+        let views = resolve(&state, KEY_Y);
+
+        assert_eq!(
+            views,
+            KeyViews {
+                with_modifiers: KeyType::Unicode('z'),
+                without_modifiers: KeyType::Unicode('z'),
+                shifted: KeyType::Unicode('Z'),
+                base_layout: KeyType::Unicode('y'),
+            }
+        );
+
+        let state = GermanState {
+            shift: false,
+            caps_lock: true,
+        };
+        let views = resolve(&state, KEY_Y);
+        assert_eq!(views.with_modifiers, KeyType::Unicode('Z'));
+        assert_eq!(views.without_modifiers, KeyType::Unicode('z'));
+    }
+
+    #[test]
+    fn unshifted_and_functional() {
+        let state = GermanState {
+            shift: false,
+            caps_lock: false,
+        };
+
+        let views = resolve(&state, KEY_Y);
+        assert_eq!(views.with_modifiers, KeyType::Unicode('z'));
+        assert_eq!(views.without_modifiers, KeyType::Unicode('z'));
+
+        let views = resolve(&state, KEY_UP);
+        assert_eq!(views.with_modifiers, KeyType::Functional(FunctionalKey::Up));
+        assert_eq!(views.shifted, KeyType::Functional(FunctionalKey::Up));
+        assert_eq!(views.base_layout, KeyType::Unknown);
+    }
+
+    // The German Q key, with @ on level 3
+    struct AltGrState;
+
+    const KEY_Q: u32 = 16 + 8;
+
+    impl XkbState for AltGrState {
+        fn key_get_one_sym(&self, _keycode: u32) -> u32 {
+            '@'.into()
+        }
+
+        fn key_get_layout(&self, _keycode: u32) -> u32 {
+            0
+        }
+
+        fn key_get_sym_by_level(&self, _keycode: u32, _layout: u32, level: u32) -> Option<u32> {
+            match level {
+                0 => Some('q'.into()),
+                1 => Some('Q'.into()),
+                2 => Some('@'.into()),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn level_shifts() {
+        let altgr = LevelShifts {
+            level3: true,
+            level5: false,
+        };
+        assert_eq!(altgr.level(false), 2);
+        assert_eq!(altgr.level(true), 3);
+
+        let (views, modifiers) = resolve_with_level_shifts(
+            &AltGrState,
+            KEY_Q,
+            KeyboardModifiers::CTRL | KeyboardModifiers::ALT,
+            altgr,
+        );
+        assert_eq!(modifiers, KeyboardModifiers::empty());
+        assert_eq!(views.with_modifiers, KeyType::Unicode('@'));
+        assert_eq!(views.without_modifiers, KeyType::Unicode('q'));
+        assert_eq!(views.shifted, KeyType::Unicode('Q'));
+
+        // Level 4 is missing, level 3 is used
+        let (views, modifiers) =
+            resolve_with_level_shifts(&AltGrState, KEY_Q, KeyboardModifiers::SHIFT, altgr);
+        assert_eq!(modifiers, KeyboardModifiers::SHIFT);
+        assert_eq!(views.with_modifiers, KeyType::Unicode('@'));
+
+        // A lone Alt is a real modifier
+        let (_, modifiers) =
+            resolve_with_level_shifts(&AltGrState, KEY_Q, KeyboardModifiers::ALT, altgr);
+        assert_eq!(modifiers, KeyboardModifiers::ALT);
+    }
+
+    #[test]
+    fn keysyms() {
+        assert_eq!(keysym_to_functional(0xffc8), Some(FunctionalKey::F11));
+        assert_eq!(keysym_to_functional(0xffb5), Some(FunctionalKey::NumPad5));
+        assert_eq!(keysym_to_char(0xe4), Some('ä'));
+        assert_eq!(keysym_to_char(0x0100_20ac), Some('€'));
+        assert_eq!(keysym_to_char(0xff0d), None);
+    }
+}
@@ -0,0 +1,186 @@
+use core::fmt::Display;
+
+use crate::{key::KeyType, layouts::PhysicalKey};
+
+// Keycodes above this are not writing system keys on any keymap in use
+const KEYCODES: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeymapError {
+    MissingKeycodes,
+    Unterminated,
+}
+
+impl Display for KeymapError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            KeymapError::MissingKeycodes => f.write_str("keymap has no xkb_keycodes section"),
+            KeymapError::Unterminated => f.write_str("xkb_keycodes section is not terminated"),
+        }
+    }
+}
+
+// The positions of the keys of a compiled keymap, as sent by Wayland's
+// `wl_keyboard.keymap`. Only the `xkb_keycodes` section is read, which is
+// enough to fill in the base layout key without libxkbcommon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XkbKeymap {
+    keys: [Option<PhysicalKey>; KEYCODES],
+}
+
+impl XkbKeymap {
+    pub fn parse(text: &str) -> Result<Self, KeymapError> {
+        let start = text
+            .find("xkb_keycodes")
+            .ok_or(KeymapError::MissingKeycodes)?;
+        let section = text.get(start..).unwrap_or_default();
+        let body = section
+            .split_once('{')
+            .and_then(|(_, body)| body.split_once('}'))
+            .map(|(body, _)| body)
+            .ok_or(KeymapError::Unterminated)?;
+
+        let mut keys = [None; KEYCODES];
+        for line in body.lines() {
+            let line = line.split_once("//").map_or(line, |(line, _)| line);
+            // Aliases, indicators and the keycode range are not needed
+            for statement in line.split(';') {
+                let Some((name, keycode)) = key_statement(statement) else {
+                    continue;
+                };
+                if let Some(slot) = keys.get_mut(keycode) {
+                    *slot = key_name(name);
+                }
+            }
+        }
+
+        Ok(Self { keys })
+    }
+
+    pub fn physical_key(&self, keycode: u32) -> Option<PhysicalKey> {
+        *self.keys.get(keycode as usize)?
+    }
+
+    // The `key_base_layout` for an XKB keycode
+    pub fn base_layout_key(&self, keycode: u32) -> KeyType {
+        self.physical_key(keycode)
+            .map_or(KeyType::Unknown, PhysicalKey::base_layout_key)
+    }
+}
+
+// `<AC01> = 38`
+fn key_statement(statement: &str) -> Option<(&str, usize)> {
+    let (name, keycode) = statement.split_once('=')?;
+    let name = name.trim().strip_prefix('<')?.strip_suffix('>')?;
+    Some((name, keycode.trim().parse().ok()?))
+}
+
+// The XKB names of the positions, as defined by the evdev and pc keycodes
+fn key_name(name: &str) -> Option<PhysicalKey> {
+    Some(match name {
+        "TLDE" => PhysicalKey::Backquote,
+        "AE01" => PhysicalKey::Digit1,
+        "AE02" => PhysicalKey::Digit2,
+        "AE03" => PhysicalKey::Digit3,
+        "AE04" => PhysicalKey::Digit4,
+        "AE05" => PhysicalKey::Digit5,
+        "AE06" => PhysicalKey::Digit6,
+        "AE07" => PhysicalKey::Digit7,
+        "AE08" => PhysicalKey::Digit8,
+        "AE09" => PhysicalKey::Digit9,
+        "AE10" => PhysicalKey::Digit0,
+        "AE11" => PhysicalKey::Minus,
+        "AE12" => PhysicalKey::Equal,
+        "AE13" => PhysicalKey::IntlYen,
+        "AD01" => PhysicalKey::KeyQ,
+        "AD02" => PhysicalKey::KeyW,
+        "AD03" => PhysicalKey::KeyE,
+        "AD04" => PhysicalKey::KeyR,
+        "AD05" => PhysicalKey::KeyT,
+        "AD06" => PhysicalKey::KeyY,
+        "AD07" => PhysicalKey::KeyU,
+        "AD08" => PhysicalKey::KeyI,
+        "AD09" => PhysicalKey::KeyO,
+        "AD10" => PhysicalKey::KeyP,
+        "AD11" => PhysicalKey::BracketLeft,
+        "AD12" => PhysicalKey::BracketRight,
+        "BKSL" | "AC12" => PhysicalKey::Backslash,
+        "AC01" => PhysicalKey::KeyA,
+        "AC02" => PhysicalKey::KeyS,
+        "AC03" => PhysicalKey::KeyD,
+        "AC04" => PhysicalKey::KeyF,
+        "AC05" => PhysicalKey::KeyG,
+        "AC06" => PhysicalKey::KeyH,
+        "AC07" => PhysicalKey::KeyJ,
+        "AC08" => PhysicalKey::KeyK,
+        "AC09" => PhysicalKey::KeyL,
+        "AC10" => PhysicalKey::Semicolon,
+        "AC11" => PhysicalKey::Quote,
+        "LSGT" => PhysicalKey::IntlBackslash,
+        "AB01" => PhysicalKey::KeyZ,
+        "AB02" => PhysicalKey::KeyX,
+        "AB03" => PhysicalKey::KeyC,
+        "AB04" => PhysicalKey::KeyV,
+        "AB05" => PhysicalKey::KeyB,
+        "AB06" => PhysicalKey::KeyN,
+        "AB07" => PhysicalKey::KeyM,
+        "AB08" => PhysicalKey::Comma,
+        "AB09" => PhysicalKey::Period,
+        "AB10" => PhysicalKey::Slash,
+        "AB11" => PhysicalKey::IntlRo,
+        "SPCE" => PhysicalKey::Space,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xkb;
+
+    // Trimmed from `xkbcomp -xkb`, with the A key moved to an unusual keycode
+    const KEYMAP: &str = r#"xkb_keymap {
+xkb_keycodes "evdev+aliases(qwerty)" {
+	minimum = 8;
+	maximum = 255;
+	<ESC>                = 9;
+	<AE01>               = 10;
+	<AC01>               = 200; // moved
+	<BKSL>               = 51;
+	<SPCE>               = 65;
+	indicator 1 = "Caps Lock";
+	alias <AC12>         = <BKSL>;
+};
+
+xkb_symbols "pc+us" {
+	key <AC01>               {	[               a,               A ] };
+};
+};
+"#;
+
+    #[test]
+    fn keycodes_section() {
+        let keymap = XkbKeymap::parse(KEYMAP).unwrap();
+
+        assert_eq!(keymap.base_layout_key(200), KeyType::Unicode('a'));
+        assert_eq!(keymap.base_layout_key(38), KeyType::Unknown);
+        assert_eq!(keymap.base_layout_key(9), KeyType::Unknown);
+        assert_eq!(keymap.physical_key(51), Some(PhysicalKey::Backslash));
+        for keycode in [10, 65] {
+            assert_eq!(keymap.physical_key(keycode), xkb::physical_key(keycode));
+        }
+        assert_eq!(keymap.physical_key(1000), None);
+    }
+
+    #[test]
+    fn malformed_keymaps() {
+        assert_eq!(
+            XkbKeymap::parse("xkb_symbols { };"),
+            Err(KeymapError::MissingKeycodes)
+        );
+        assert_eq!(
+            XkbKeymap::parse("xkb_keycodes { <AE01> = 10;"),
+            Err(KeymapError::Unterminated)
+        );
+    }
+}